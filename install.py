@@ -321,6 +321,7 @@ def cmd_install(args, source_dir: Path) -> bool:
 
     source_service = source_dir / "abraxas.service"
     source_zipdb = source_dir / "us_zipcodes.bin"
+    source_citydb = source_dir / "cities.bin"
 
     # Determine implementation: --impl flag overrides, otherwise prompt
     if args.impl_choice:
@@ -437,6 +438,14 @@ def cmd_install(args, source_dir: Path) -> bool:
     else:
         log_info("Skipping ZIP database (non-USA build)")
 
+    # City name database (Rust only): the bundled tz database cities; a
+    # GeoNames one from --download-citydb is kept
+    if impl_choice == "rust" and source_citydb.exists():
+        dest_citydb = INSTALL_CONFIG_DIR / "cities.bin"
+        if not dest_citydb.exists():
+            log_info("Installing city database...")
+            shutil.copy2(source_citydb, dest_citydb)
+
     # Copy systemd service
     if source_service.exists():
         log_info("Installing systemd service...")
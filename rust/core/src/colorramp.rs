@@ -2,19 +2,47 @@
 //!
 //! Based on data from Ingo Thies (2013) and redshift project.
 
-use super::Error;
+use core::fmt;
+
+#[cfg(not(feature = "std"))]
+use crate::math::FloatExt;
 
 /// Temperature bounds (Kelvin)
 pub const TEMP_MIN: i32 = 1000;
+/// Upper end of the blackbody table
 pub const TEMP_MAX: i32 = 25000;
 
 /// RGB color (0.0 - 1.0 range)
 pub struct Rgb {
+    /// Red
     pub r: f32,
+    /// Green
     pub g: f32,
+    /// Blue
     pub b: f32,
 }
 
+/// Why a ramp could not be computed
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RampError {
+    /// Outside TEMP_MIN..=TEMP_MAX
+    TempOutOfRange(i32),
+    /// Fewer than two ramp entries
+    RampTooShort(usize),
+}
+
+impl fmt::Display for RampError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RampError::TempOutOfRange(t) => write!(f, "{}K outside {}-{}K", t, TEMP_MIN, TEMP_MAX),
+            RampError::RampTooShort(n) => write!(f, "gamma ramp of {} entries", n),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for RampError {}
+
 /// Whitepoint values for temperatures at 100K intervals (1000K - 25000K).
 /// Each entry is [R, G, B], interpolated for actual temperature.
 static BLACKBODY_COLOR: &[[f32; 3]] = &[
@@ -261,9 +289,9 @@ static BLACKBODY_COLOR: &[[f32; 3]] = &[
 ];
 
 /// Convert color temperature to RGB multipliers
-pub fn temp_to_rgb(temp: i32) -> Result<Rgb, Error> {
+pub fn temp_to_rgb(temp: i32) -> Result<Rgb, RampError> {
     if temp < TEMP_MIN || temp > TEMP_MAX {
-        return Err(Error::InvalidTemp);
+        return Err(RampError::TempOutOfRange(temp));
     }
 
     let alpha = (temp - TEMP_MIN) as f32 / 100.0;
@@ -284,22 +312,156 @@ pub fn temp_to_rgb(temp: i32) -> Result<Rgb, Error> {
     })
 }
 
-/// Fill gamma ramp arrays for the given temperature
-pub fn fill_gamma_ramps(
+/// Closest blackbody temperature (to 100K) for a ramp already on screen,
+/// from the ratio of its top entries, so brightness doesn't matter. Assumes
+/// a D65 panel; None for an all-zero ramp.
+pub fn estimate_temperature(r: &[u16], g: &[u16], b: &[u16]) -> Option<i32> {
+    let top = [*r.last()?, *g.last()?, *b.last()?].map(|v| v as f32);
+    let max = top[0].max(top[1]).max(top[2]);
+    if max <= 0.0 {
+        return None;
+    }
+    let rgb = top.map(|v| v / max);
+
+    let distance = |c: &[f32; 3]| (0..3).map(|i| (c[i] - rgb[i]).powi(2)).sum::<f32>();
+    let (idx, _) = BLACKBODY_COLOR
+        .iter()
+        .enumerate()
+        .min_by(|(_, a), (_, b)| distance(a).total_cmp(&distance(b)))?;
+    Some(TEMP_MIN + idx as i32 * 100)
+}
+
+/// CIE 1931 xy chromaticity of a blackbody at `temp_k`, using the Kang et al.
+/// (2002) cubic spline fit of the Planckian locus. Valid over 1667-25000K;
+/// temperatures outside that range are clamped.
+pub fn temp_to_xy(temp_k: i32) -> (f64, f64) {
+    let t = temp_k.clamp(1667, 25000) as f64;
+    let (t2, t3) = (t * t, t * t * t);
+
+    let x = if t <= 4000.0 {
+        -0.2661239e9 / t3 - 0.2343589e6 / t2 + 0.8776956e3 / t + 0.179910
+    } else {
+        -3.0258469e9 / t3 + 2.1070379e6 / t2 + 0.2226347e3 / t + 0.240390
+    };
+
+    let (x2, x3) = (x * x, x * x * x);
+    let y = if t <= 2222.0 {
+        -1.1063814 * x3 - 1.34811020 * x2 + 2.18555832 * x - 0.20219683
+    } else if t <= 4000.0 {
+        -0.9549476 * x3 - 1.37418593 * x2 + 2.09137015 * x - 0.16748867
+    } else {
+        3.0817580 * x3 - 5.87338670 * x2 + 3.75112997 * x - 0.37001483
+    };
+
+    (x, y)
+}
+
+/// CIE xy of the D65 white point the blackbody table is relative to
+pub const D65_X: f64 = 0.3127;
+/// See D65_X
+pub const D65_Y: f64 = 0.3290;
+
+/// Row-major 3x3 matrix
+pub type Mat3 = [[f64; 3]; 3];
+
+/// Bradford cone response matrix (XYZ to sharpened LMS)
+const BRADFORD: Mat3 = [
+    [0.8951, 0.2664, -0.1614],
+    [-0.7502, 1.7135, 0.0367],
+    [0.0389, -0.0685, 1.0296],
+];
+
+/// Linear sRGB (D65) to CIE XYZ
+const SRGB_TO_XYZ: Mat3 = [
+    [0.4124564, 0.3575761, 0.1804375],
+    [0.2126729, 0.7151522, 0.0721750],
+    [0.0193339, 0.1191920, 0.9503041],
+];
+
+fn mat_mul(a: &Mat3, b: &Mat3) -> Mat3 {
+    let mut out = [[0.0; 3]; 3];
+    for (i, row) in out.iter_mut().enumerate() {
+        for (j, v) in row.iter_mut().enumerate() {
+            *v = (0..3).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    out
+}
+
+fn mat_vec(m: &Mat3, v: [f64; 3]) -> [f64; 3] {
+    m.map(|row| row[0] * v[0] + row[1] * v[1] + row[2] * v[2])
+}
+
+/// Inverse by cofactors (the matrices here are all well conditioned).
+fn mat_inverse(m: &Mat3) -> Mat3 {
+    let cof = |r: usize, c: usize| {
+        let (r1, r2) = ((r + 1) % 3, (r + 2) % 3);
+        let (c1, c2) = ((c + 1) % 3, (c + 2) % 3);
+        m[r1][c1] * m[r2][c2] - m[r1][c2] * m[r2][c1]
+    };
+    let det: f64 = (0..3).map(|c| m[0][c] * cof(0, c)).sum();
+    let mut out = [[0.0; 3]; 3];
+    for (i, row) in out.iter_mut().enumerate() {
+        for (j, v) in row.iter_mut().enumerate() {
+            *v = cof(j, i) / det;
+        }
+    }
+    out
+}
+
+/// XYZ (Y = 1) of a CIE xy chromaticity
+fn xy_to_xyz(x: f64, y: f64) -> [f64; 3] {
+    [x / y, 1.0, (1.0 - x - y) / y]
+}
+
+/// Bradford chromatic adaptation from a monitor's native white point (CIE
+/// xy) to D65, as a linear RGB transform. Identity for a D65 monitor.
+pub fn white_point_matrix(monitor_wp_x: f64, monitor_wp_y: f64) -> Mat3 {
+    let src = mat_vec(&BRADFORD, xy_to_xyz(monitor_wp_x, monitor_wp_y));
+    let dst = mat_vec(&BRADFORD, xy_to_xyz(D65_X, D65_Y));
+    let mut scale = [[0.0; 3]; 3];
+    for i in 0..3 {
+        scale[i][i] = dst[i] / src[i];
+    }
+    let adapt = mat_mul(&mat_inverse(&BRADFORD), &mat_mul(&scale, &BRADFORD));
+    mat_mul(&mat_inverse(&SRGB_TO_XYZ), &mat_mul(&adapt, &SRGB_TO_XYZ))
+}
+
+/// Fill gamma ramp arrays for the given temperature on a monitor whose
+/// native white is (monitor_wp_x, monitor_wp_y) rather than D65: the
+/// blackbody white is adapted to the panel, then rescaled so the strongest
+/// channel keeps full range.
+#[allow(clippy::too_many_arguments)]
+pub fn fill_gamma_ramps_adapted(
     temp: i32,
     gamma_size: usize,
     r: &mut [u16],
     g: &mut [u16],
     b: &mut [u16],
     brightness: f32,
-) -> Result<(), Error> {
+    monitor_wp_x: f64,
+    monitor_wp_y: f64,
+) -> Result<(), RampError> {
     if gamma_size < 2 {
-        return Err(Error::InvalidTemp);
+        return Err(RampError::RampTooShort(gamma_size));
     }
 
     let brightness = brightness.clamp(0.0, 1.0);
 
     let mut rgb = temp_to_rgb(temp)?;
+    // Exactly D65 skips the transform, keeping ramps bit-identical
+    if (monitor_wp_x, monitor_wp_y) != (D65_X, D65_Y) {
+        let m = white_point_matrix(monitor_wp_x, monitor_wp_y);
+        let v = mat_vec(&m, [rgb.r as f64, rgb.g as f64, rgb.b as f64]);
+        let max = v[0].max(v[1]).max(v[2]);
+        if max > 0.0 {
+            rgb = Rgb {
+                r: (v[0] / max).clamp(0.0, 1.0) as f32,
+                g: (v[1] / max).clamp(0.0, 1.0) as f32,
+                b: (v[2] / max).clamp(0.0, 1.0) as f32,
+            };
+        }
+    }
     rgb.r *= brightness;
     rgb.g *= brightness;
     rgb.b *= brightness;
@@ -313,3 +475,94 @@ pub fn fill_gamma_ramps(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn xy_d65() {
+        // D65 sits on the daylight locus, slightly above the Planckian locus:
+        // the blackbody at 6504K is within ~0.001 in x and ~0.0055 in y.
+        let (x, y) = temp_to_xy(6504);
+        assert!((x - 0.3127).abs() < 0.005, "x = {}", x);
+        assert!((y - 0.3290).abs() < 0.006, "y = {}", y);
+    }
+
+    #[test]
+    fn xy_warm_is_redder() {
+        let (x_warm, _) = temp_to_xy(2700);
+        let (x_cool, _) = temp_to_xy(6500);
+        assert!(x_warm > x_cool);
+        // Incandescent (illuminant A, 2856K)
+        let (x, y) = temp_to_xy(2856);
+        assert!((x - 0.4476).abs() < 0.002 && (y - 0.4074).abs() < 0.002, "{} {}", x, y);
+    }
+
+    #[test]
+    fn ramp_temperature_estimated() {
+        let mut r = vec![0u16; 256];
+        let mut g = vec![0u16; 256];
+        let mut b = vec![0u16; 256];
+        for (temp, brightness) in [(6500, 1.0), (2900, 1.0), (4500, 0.6), (1000, 1.0), (10000, 1.0)] {
+            fill_gamma_ramps_adapted(temp, 256, &mut r, &mut g, &mut b, brightness, D65_X, D65_Y).unwrap();
+            assert_eq!(estimate_temperature(&r, &g, &b), Some(temp));
+        }
+        // The far blue end is nearly flat: within a few hundred K
+        fill_gamma_ramps_adapted(25000, 256, &mut r, &mut g, &mut b, 1.0, D65_X, D65_Y).unwrap();
+        assert!(estimate_temperature(&r, &g, &b).unwrap() >= 24500);
+
+        // A linear ramp is 6500K; a blanked one says nothing
+        let linear: Vec<u16> = (0..256).map(|i| (i * 257) as u16).collect();
+        assert_eq!(estimate_temperature(&linear, &linear, &linear), Some(6500));
+        assert_eq!(estimate_temperature(&[0, 0], &[0, 0], &[0, 0]), None);
+        assert_eq!(estimate_temperature(&[], &[], &[]), None);
+    }
+
+    #[test]
+    fn out_of_range_rejected() {
+        assert_eq!(temp_to_rgb(999).err(), Some(RampError::TempOutOfRange(999)));
+        assert_eq!(temp_to_rgb(25001).err(), Some(RampError::TempOutOfRange(25001)));
+        assert!(temp_to_rgb(TEMP_MIN).is_ok() && temp_to_rgb(TEMP_MAX).is_ok());
+
+        let (mut r, mut g, mut b) = ([0u16; 1], [0u16; 1], [0u16; 1]);
+        let short = fill_gamma_ramps_adapted(6500, 1, &mut r, &mut g, &mut b, 1.0, D65_X, D65_Y);
+        assert_eq!(short, Err(RampError::RampTooShort(1)));
+    }
+
+    #[test]
+    fn bradford_identity_at_d65() {
+        let m = white_point_matrix(D65_X, D65_Y);
+        for (i, row) in m.iter().enumerate() {
+            for (j, v) in row.iter().enumerate() {
+                let want = if i == j { 1.0 } else { 0.0 };
+                assert!((v - want).abs() < 1e-9, "m[{}][{}] = {}", i, j, v);
+            }
+        }
+
+        // D65 ramps are the plain blackbody ramps
+        let (mut r, mut g, mut b) = ([0u16; 256], [0u16; 256], [0u16; 256]);
+        fill_gamma_ramps_adapted(3400, 256, &mut r, &mut g, &mut b, 0.8, D65_X, D65_Y).unwrap();
+        let rgb = temp_to_rgb(3400).unwrap();
+        assert_eq!(r[255], (rgb.r * 0.8 * u16::MAX as f32) as u16);
+        assert_eq!(b[128], (128.0 / 255.0 * rgb.b * 0.8 * u16::MAX as f32) as u16);
+    }
+
+    #[test]
+    fn bluish_panel_gets_less_blue() {
+        // Native white near 7500K (bluer than D65): neutral white must pull
+        // blue down and keep red at full range
+        let (x, y) = temp_to_xy(7500);
+        let (mut r, mut g, mut b) = ([0u16; 256], [0u16; 256], [0u16; 256]);
+        fill_gamma_ramps_adapted(6500, 256, &mut r, &mut g, &mut b, 1.0, x, y).unwrap();
+        assert_eq!(r[255], u16::MAX);
+        assert!(b[255] < g[255] && g[255] < r[255], "{} {} {}", r[255], g[255], b[255]);
+        assert!(b[255] > u16::MAX / 2);
+
+        // The matrix maps the panel's white onto D65
+        let m = mat_mul(&SRGB_TO_XYZ, &mat_mul(&white_point_matrix(x, y), &mat_inverse(&SRGB_TO_XYZ)));
+        let xyz = mat_vec(&m, xy_to_xyz(x, y));
+        let sum = xyz[0] + xyz[1] + xyz[2];
+        assert!((xyz[0] / sum - D65_X).abs() < 1e-4 && (xyz[1] / sum - D65_Y).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,54 @@
+//! Solar position, dawn/dusk transition and blackbody color math from
+//! ABRAXAS, without the daemon.
+//!
+//! - `solar`: NOAA sun position, sunrise/sunset, polar day/night, golden
+//!   hour, moon phase
+//! - `sigmoid`: the temperature curve across dawn/dusk, by sun elevation,
+//!   from schedule keyframes, and for manual overrides
+//! - `colorramp`: blackbody color temperature to RGB and gamma ramps
+//! - `model`: the temperature and transition settings the curves take
+//! - `time`: the UTC offsets local-day calculations need
+//!
+//! Nothing here reads the system clock or time zone: times are Unix epoch
+//! seconds, and anything that depends on the local day takes a `TimeZone`.
+//!
+//! Features: `std` (default); without it the crate is `no_std` (it needs
+//! `alloc`) and takes its float functions from `libm`. `serde` derives
+//! Serialize/Deserialize for `model::Easing`.
+//!
+//! ```
+//! use abraxas_core::{model::TempParams, solar, time::FixedOffset};
+//!
+//! // Berlin on the 2024 June solstice, in CEST (UTC+2)
+//! let cest = FixedOffset::hours(2.0);
+//! let times = solar::sunrise_sunset(1718971200, 52.52, 13.40, &cest).unwrap();
+//! assert!(times.sunrise < times.solar_noon && times.solar_noon < times.sunset);
+//!
+//! let temps = TempParams {
+//!     day_clear: 6500,
+//!     day_dark: 4500,
+//!     night: 2900,
+//!     cloud_threshold: 75,
+//!     golden_hour_temp: 0,
+//!     moon_brightness_reduction: false,
+//! };
+//! let elevation = solar::position(times.solar_noon, 52.52, 13.40, &cest).elevation;
+//! assert_eq!(abraxas_core::sigmoid::calculate_elevation_temp(elevation, false, &temps, -6.0, 3.0), 6500);
+//! ```
+
+#![cfg_attr(not(feature = "std"), no_std)]
+#![warn(missing_docs)]
+
+#[cfg(not(any(feature = "std", feature = "libm")))]
+compile_error!("abraxas-core needs the \"std\" or the \"libm\" feature");
+
+#[cfg(not(feature = "std"))]
+extern crate alloc;
+
+pub mod colorramp;
+#[cfg(not(feature = "std"))]
+mod math;
+pub mod model;
+pub mod sigmoid;
+pub mod solar;
+pub mod time;
@@ -0,0 +1,77 @@
+//! The float methods std provides, from libm, for no_std builds. Modules
+//! import the trait only without std, so the code reads the same either way.
+
+pub(crate) trait FloatExt {
+    fn sin(self) -> Self;
+    fn cos(self) -> Self;
+    fn tan(self) -> Self;
+    fn asin(self) -> Self;
+    fn acos(self) -> Self;
+    fn exp(self) -> Self;
+    fn powi(self, n: i32) -> Self;
+    fn rem_euclid(self, rhs: Self) -> Self;
+}
+
+impl FloatExt for f64 {
+    fn sin(self) -> f64 {
+        libm::sin(self)
+    }
+    fn cos(self) -> f64 {
+        libm::cos(self)
+    }
+    fn tan(self) -> f64 {
+        libm::tan(self)
+    }
+    fn asin(self) -> f64 {
+        libm::asin(self)
+    }
+    fn acos(self) -> f64 {
+        libm::acos(self)
+    }
+    fn exp(self) -> f64 {
+        libm::exp(self)
+    }
+    fn powi(self, n: i32) -> f64 {
+        libm::pow(self, n as f64)
+    }
+    fn rem_euclid(self, rhs: f64) -> f64 {
+        let r = self % rhs;
+        if r < 0.0 {
+            r + libm::fabs(rhs)
+        } else {
+            r
+        }
+    }
+}
+
+impl FloatExt for f32 {
+    fn sin(self) -> f32 {
+        libm::sinf(self)
+    }
+    fn cos(self) -> f32 {
+        libm::cosf(self)
+    }
+    fn tan(self) -> f32 {
+        libm::tanf(self)
+    }
+    fn asin(self) -> f32 {
+        libm::asinf(self)
+    }
+    fn acos(self) -> f32 {
+        libm::acosf(self)
+    }
+    fn exp(self) -> f32 {
+        libm::expf(self)
+    }
+    fn powi(self, n: i32) -> f32 {
+        libm::powf(self, n as f32)
+    }
+    fn rem_euclid(self, rhs: f32) -> f32 {
+        let r = self % rhs;
+        if r < 0.0 {
+            r + libm::fabsf(rhs)
+        } else {
+            r
+        }
+    }
+}
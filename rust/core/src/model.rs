@@ -0,0 +1,115 @@
+//! Settings the temperature curves take: targets, transition windows,
+//! schedule keyframes and override easing.
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+/// Temperature targets (`[temperatures]`)
+#[derive(Clone, Copy)]
+pub struct TempParams {
+    /// Daytime target under clear skies (K)
+    pub day_clear: i32,
+    /// Daytime target when overcast (K)
+    pub day_dark: i32,
+    /// Night target (K)
+    pub night: i32,
+    /// Cloud cover percent at which the day target turns dark
+    pub cloud_threshold: i32,
+    /// Replaces the solar curve during golden hour (0 = off)
+    pub golden_hour_temp: i32,
+    /// Lower the night target under a bright (gibbous to full) moon
+    pub moon_brightness_reduction: bool,
+}
+
+/// Transition windows (`[transitions]`)
+#[derive(Clone, Copy)]
+pub struct TransitionParams {
+    /// Minutes
+    pub dawn_duration: f64,
+    /// Minutes
+    pub dusk_duration: f64,
+    /// Dawn midpoint, minutes after sunrise
+    pub dawn_offset_min: f64,
+    /// Dusk midpoint, minutes before sunset
+    pub dusk_offset_min: f64,
+    /// Sigmoid steepness of each window (higher = sharper mid-transition)
+    pub steepness_dawn: f64,
+    /// See steepness_dawn
+    pub steepness_dusk: f64,
+    /// Tick interval inside a transition window (seconds)
+    pub transition_tick_sec: i64,
+    /// Smallest change (K) applied during stable day/night
+    pub temp_change_threshold: i32,
+}
+
+/// One `[schedule]` keyframe: `temp` from `minute` (local time of day) on
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Keyframe {
+    /// Minutes after local midnight
+    pub minute: i32,
+    /// Kelvin
+    pub temp: i32,
+}
+
+/// Fixed daily schedule (`[schedule]`) used instead of the solar model.
+/// Keyframes are sorted by time with unique times.
+#[derive(Clone, Debug)]
+pub struct Schedule {
+    /// At least one
+    pub keyframes: Vec<Keyframe>,
+    /// Minutes each change blends over, centered on its keyframe
+    pub blend_min: f64,
+}
+
+impl Schedule {
+    /// First keyframe after `minute`, wrapping to tomorrow's first.
+    pub fn next_keyframe(&self, minute: f64) -> Keyframe {
+        self.keyframes
+            .iter()
+            .find(|k| k.minute as f64 > minute)
+            .copied()
+            .unwrap_or(self.keyframes[0])
+    }
+}
+
+/// Override transition curve
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum Easing {
+    /// Normalized sigmoid, same shape as dawn/dusk (default)
+    #[default]
+    Sigmoid,
+    /// Constant rate, for screen recordings
+    Linear,
+    /// Cubic: gentle start and finish, fast middle
+    EaseInOut,
+    /// Hold the start temperature, then jump at the end of the duration
+    Step,
+}
+
+impl Easing {
+    /// Every name, for error messages
+    pub const NAMES: &'static str = "sigmoid, linear, ease-in-out, step";
+
+    /// Parse a config/CLI name
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "sigmoid" => Some(Easing::Sigmoid),
+            "linear" => Some(Easing::Linear),
+            "ease-in-out" => Some(Easing::EaseInOut),
+            "step" => Some(Easing::Step),
+            _ => None,
+        }
+    }
+
+    /// The config/CLI name
+    pub fn name(self) -> &'static str {
+        match self {
+            Easing::Sigmoid => "sigmoid",
+            Easing::Linear => "linear",
+            Easing::EaseInOut => "ease-in-out",
+            Easing::Step => "step",
+        }
+    }
+}
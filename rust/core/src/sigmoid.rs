@@ -0,0 +1,671 @@
+//! Sigmoid transition math.
+//!
+//! Dusk is canonical: day -> night over dusk_duration centered on sunset.
+//! Dawn is its inverse: night -> day over dawn_duration centered on sunrise.
+//! Both midpoints are shifted by the configured dawn/dusk offsets.
+//! Across dusk the day target follows the look-ahead cloud forecast.
+//! Elevation mode maps sun elevation between two thresholds instead.
+//! Manual overrides use the same sigmoid over [0, duration].
+
+#[cfg(not(feature = "std"))]
+use crate::math::FloatExt;
+use crate::colorramp::TEMP_MIN;
+use crate::model::{Easing, Schedule, TempParams, TransitionParams};
+use crate::solar;
+use crate::time::TimeZone;
+
+/// Steepness of the elevation, schedule and override curves, and the
+/// default for dawn and dusk
+pub const STEEPNESS: f64 = 8.0;
+
+const SECONDS_PER_DAY: i64 = 86400;
+const MINUTES_PER_DAY: f64 = 1440.0;
+
+fn sigmoid_raw(x: f64, steepness: f64) -> f64 {
+    1.0 / (1.0 + (-steepness * x).exp())
+}
+
+/// Logistic curve over x in [-1, 1], rescaled to run exactly 0..1 there
+pub fn sigmoid_norm(x: f64, steepness: f64) -> f64 {
+    let raw = sigmoid_raw(x, steepness);
+    let low = sigmoid_raw(-1.0, steepness);
+    let high = sigmoid_raw(1.0, steepness);
+    (raw - low) / (high - low)
+}
+
+/// Temperature on the dawn/dusk curve, from the minutes since sunrise and
+/// until sunset (negative outside the day). Overcast (`cloud_cover` at
+/// the threshold) swaps the clear day target for the dark one.
+pub fn calculate_solar_temp(
+    minutes_from_sunrise: f64,
+    minutes_to_sunset: f64,
+    cloud_cover: i32,
+    cloud_cover_3h: i32,
+    temps: &TempParams,
+    params: &TransitionParams,
+) -> i32 {
+    trace_solar_temp(minutes_from_sunrise, minutes_to_sunset, cloud_cover, cloud_cover_3h, temps, params).temp
+}
+
+/// Where on the dawn/dusk curve a time falls
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SolarPhase {
+    /// Night target
+    Night,
+    /// Rising through the dawn window
+    Dawn,
+    /// Day target
+    Day,
+    /// Falling through the dusk window
+    Dusk,
+}
+
+impl SolarPhase {
+    /// Lowercase name, for diagnostics
+    pub fn name(self) -> &'static str {
+        match self {
+            SolarPhase::Night => "night",
+            SolarPhase::Dawn => "dawn",
+            SolarPhase::Day => "day",
+            SolarPhase::Dusk => "dusk",
+        }
+    }
+}
+
+/// The working behind a `calculate_solar_temp` result
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SolarTrace {
+    /// Which part of the curve decided the result
+    pub phase: SolarPhase,
+    /// Sigmoid input in the deciding window, from -1 (night side) to 1 (day
+    /// side); beyond that outside it. Falls through dusk as sunset nears.
+    pub x: f64,
+    /// Day fraction: 0 is the night target, 1 the day target
+    pub factor: f64,
+    /// Day target after weather, blended toward the forecast across dusk (K)
+    pub day_temp: f64,
+    /// The temperature (K)
+    pub temp: i32,
+}
+
+/// `calculate_solar_temp` with its intermediate values, so tools can show
+/// why the temperature is what it is.
+pub fn trace_solar_temp(
+    minutes_from_sunrise: f64,
+    minutes_to_sunset: f64,
+    cloud_cover: i32,
+    cloud_cover_3h: i32,
+    temps: &TempParams,
+    params: &TransitionParams,
+) -> SolarTrace {
+    let day_for = |cover: i32| {
+        if cover >= temps.cloud_threshold {
+            temps.day_dark
+        } else {
+            temps.day_clear
+        }
+    };
+    let night_temp = temps.night;
+
+    // Ramp linearly from the current cover to the forecast one across the
+    // dusk window, so clouds rolling in at sunset don't flip the day
+    // target mid-transition. Before dusk (and all through dawn) this is 0.
+    let dusk_progress = ((params.dusk_duration / 2.0 - (minutes_to_sunset - params.dusk_offset_min))
+        / params.dusk_duration)
+        .clamp(0.0, 1.0);
+    let (now_day, later_day) = (day_for(cloud_cover), day_for(cloud_cover_3h));
+    let day_temp = now_day as f64 + (later_day - now_day) as f64 * dusk_progress;
+
+    // Day fraction from each window, evaluated independently: 0 before dawn
+    // (after dusk), 1 after dawn (before dusk). On days shorter than the
+    // windows they overlap, and the darker of the two wins so the curve
+    // stays continuous and rises then falls.
+    let (dawn_shift, dawn_half) = (minutes_from_sunrise - params.dawn_offset_min, params.dawn_duration / 2.0);
+    let (dusk_shift, dusk_half) = (minutes_to_sunset - params.dusk_offset_min, params.dusk_duration / 2.0);
+    let dawn = window_factor(dawn_shift, dawn_half, params.steepness_dawn);
+    let dusk = window_factor(dusk_shift, dusk_half, params.steepness_dusk);
+    let factor = dawn.min(dusk);
+
+    let (window, x) = if dawn <= dusk {
+        (SolarPhase::Dawn, dawn_shift / dawn_half)
+    } else {
+        (SolarPhase::Dusk, dusk_shift / dusk_half)
+    };
+    let phase = if factor >= 1.0 {
+        SolarPhase::Day
+    } else if factor <= 0.0 {
+        SolarPhase::Night
+    } else {
+        window
+    };
+
+    SolarTrace {
+        phase,
+        x,
+        factor,
+        day_temp,
+        temp: (night_temp as f64 + (day_temp - night_temp as f64) * factor) as i32,
+    }
+}
+
+/// Sigmoid progress through a transition window `shifted` minutes past its
+/// midpoint: 0 before the window, 1 after it.
+fn window_factor(shifted: f64, half: f64, steepness: f64) -> f64 {
+    if shifted <= -half {
+        0.0
+    } else if shifted >= half {
+        1.0
+    } else {
+        sigmoid_norm(shifted / half, steepness)
+    }
+}
+
+/// Temperature from sun elevation (`[daemon] mode = elevation`): day at or
+/// above `elevation_high`, night at or below `elevation_low`, sigmoid between.
+pub fn calculate_elevation_temp(
+    elevation: f64,
+    is_dark_mode: bool,
+    temps: &TempParams,
+    elevation_low: f64,
+    elevation_high: f64,
+) -> i32 {
+    let day_temp = if is_dark_mode {
+        temps.day_dark
+    } else {
+        temps.day_clear
+    };
+    let night_temp = temps.night;
+
+    if elevation >= elevation_high {
+        return day_temp;
+    }
+    if elevation <= elevation_low {
+        return night_temp;
+    }
+
+    let mid = (elevation_high + elevation_low) / 2.0;
+    let half = (elevation_high - elevation_low) / 2.0;
+    let factor = sigmoid_norm((elevation - mid) / half, STEEPNESS);
+    (night_temp as f64 + (day_temp - night_temp) as f64 * factor) as i32
+}
+
+/// True between the elevation-mode endpoints.
+pub fn in_elevation_transition(elevation: f64, elevation_low: f64, elevation_high: f64) -> bool {
+    elevation > elevation_low && elevation < elevation_high
+}
+
+/// The two windows the solar curve moves in
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SolarWindow {
+    /// Night to day, around sunrise
+    Dawn,
+    /// Day to night, around sunset
+    Dusk,
+}
+
+/// The dawn or dusk window containing this time, with the minutes left in it.
+pub fn solar_window(
+    minutes_from_sunrise: f64,
+    minutes_to_sunset: f64,
+    params: &TransitionParams,
+) -> Option<(SolarWindow, f64)> {
+    let dawn = minutes_from_sunrise - params.dawn_offset_min;
+    if dawn.abs() < params.dawn_duration / 2.0 {
+        return Some((SolarWindow::Dawn, params.dawn_duration / 2.0 - dawn));
+    }
+    let dusk = minutes_to_sunset - params.dusk_offset_min;
+    if dusk.abs() < params.dusk_duration / 2.0 {
+        return Some((SolarWindow::Dusk, params.dusk_duration / 2.0 + dusk));
+    }
+    None
+}
+
+/// True inside the dawn or dusk window, where the curve is still moving.
+pub fn in_solar_transition(
+    minutes_from_sunrise: f64,
+    minutes_to_sunset: f64,
+    params: &TransitionParams,
+) -> bool {
+    solar_window(minutes_from_sunrise, minutes_to_sunset, params).is_some()
+}
+
+/// True inside the blend window of any `[schedule]` keyframe.
+pub fn in_schedule_blend(minute_of_day: f64, schedule: &Schedule) -> bool {
+    let half = schedule.blend_min / 2.0;
+    schedule.keyframes.len() > 1
+        && schedule.keyframes.iter().any(|k| {
+            let d = (minute_of_day - k.minute as f64).rem_euclid(MINUTES_PER_DAY);
+            d.min(MINUTES_PER_DAY - d) < half
+        })
+}
+
+/// Temperature from `[schedule]` keyframes at a local time of day (minutes).
+/// Each keyframe holds until the next; changes blend over `blend_min`
+/// centered on the keyframe time, wrapping across midnight.
+pub fn calculate_schedule_temp(minute_of_day: f64, schedule: &Schedule) -> i32 {
+    let kf = &schedule.keyframes;
+    let n = kf.len();
+
+    // Latest keyframe at or before now; before the first, yesterday's last
+    let cur = kf
+        .iter()
+        .rposition(|k| k.minute as f64 <= minute_of_day)
+        .unwrap_or(n - 1);
+
+    let half = schedule.blend_min / 2.0;
+    if n > 1 && half > 0.0 {
+        let blend = |from: i32, to: i32, x: f64| {
+            (from as f64 + (to - from) as f64 * sigmoid_norm(x, STEEPNESS)) as i32
+        };
+
+        // Approaching the next keyframe: first half of its blend
+        let next = (cur + 1) % n;
+        let to_next = (kf[next].minute as f64 - minute_of_day).rem_euclid(MINUTES_PER_DAY);
+        if to_next < half {
+            return blend(kf[cur].temp, kf[next].temp, -to_next / half);
+        }
+
+        // Just past the current keyframe: second half of its blend
+        let prev = (cur + n - 1) % n;
+        let since_cur = (minute_of_day - kf[cur].minute as f64).rem_euclid(MINUTES_PER_DAY);
+        if since_cur < half {
+            return blend(kf[prev].temp, kf[cur].temp, since_cur / half);
+        }
+    }
+
+    kf[cur].temp
+}
+
+/// Progress (0..1) of an override transition at time fraction `t` (0..1).
+pub fn ease(easing: Easing, t: f64) -> f64 {
+    let t = t.clamp(0.0, 1.0);
+    match easing {
+        // Map [0, 1] -> [-1, 1]
+        Easing::Sigmoid => sigmoid_norm(2.0 * t - 1.0, STEEPNESS),
+        Easing::Linear => t,
+        Easing::EaseInOut => {
+            if t < 0.5 {
+                4.0 * t * t * t
+            } else {
+                1.0 - (2.0 - 2.0 * t).powi(3) / 2.0
+            }
+        }
+        Easing::Step => {
+            if t >= 1.0 {
+                1.0
+            } else {
+                0.0
+            }
+        }
+    }
+}
+
+/// Temperature of a manual override `now`: from `start_temp` at
+/// `start_time` to `target_temp` over `duration_sec`, along `easing`.
+pub fn calculate_manual_temp(
+    start_temp: i32,
+    target_temp: i32,
+    start_time: i64,
+    duration_sec: i64,
+    easing: Easing,
+    now: i64,
+) -> i32 {
+    let elapsed = now - start_time;
+    if duration_sec <= 0 || elapsed >= duration_sec {
+        return target_temp;
+    }
+
+    let factor = ease(easing, elapsed as f64 / duration_sec as f64);
+    (start_temp as f64 + (target_temp - start_temp) as f64 * factor) as i32
+}
+
+/// Most a bright moon takes off the night temperature (K)
+pub const MOON_REDUCTION_MAX: i32 = 200;
+/// Moon illumination above which the night temperature drops (gibbous)
+pub const MOON_ILLUMINATION_MIN: f64 = 0.7;
+
+/// Night temperature under a moon `illumination` lit: lowered linearly from
+/// nothing at MOON_ILLUMINATION_MIN to MOON_REDUCTION_MAX at full moon.
+pub fn moonlit_night_temp(night: i32, illumination: f64) -> i32 {
+    let brightness = (illumination - MOON_ILLUMINATION_MIN) / (1.0 - MOON_ILLUMINATION_MIN);
+    let reduction = (brightness.clamp(0.0, 1.0) * MOON_REDUCTION_MAX as f64) as i32;
+    (night - reduction).max(TEMP_MIN)
+}
+
+/// Calculate next time to auto-resume solar control after a manual override.
+/// Returns the epoch time 15 minutes before the next dawn/dusk transition window.
+pub fn next_transition_resume(now: i64, lat: f64, lon: f64, params: &TransitionParams, tz: &(impl TimeZone + ?Sized)) -> i64 {
+    let st = match solar::sunrise_sunset(now, lat, lon, tz) {
+        Some(st) => st,
+        None => return now + SECONDS_PER_DAY, // polar fallback: 24h
+    };
+
+    let dawn_window_start = st.sunrise - ((params.dawn_duration / 2.0 - params.dawn_offset_min) * 60.0) as i64;
+    let dusk_window_start = st.sunset - ((params.dusk_duration / 2.0 + params.dusk_offset_min) * 60.0) as i64;
+
+    let resume_dawn = dawn_window_start - 15 * 60;
+    let resume_dusk = dusk_window_start - 15 * 60;
+
+    // Find earliest future candidate
+    let mut best: i64 = 0;
+    if resume_dawn > now {
+        best = resume_dawn;
+    }
+    if resume_dusk > now && (best == 0 || resume_dusk < best) {
+        best = resume_dusk;
+    }
+
+    if best > 0 {
+        return best;
+    }
+
+    // Both today's transitions passed -- use tomorrow's dawn
+    let tomorrow = now + SECONDS_PER_DAY;
+    match solar::sunrise_sunset(tomorrow, lat, lon, tz) {
+        Some(st2) => st2.sunrise - ((params.dawn_duration / 2.0 - params.dawn_offset_min + 15.0) * 60.0) as i64,
+        None => now + SECONDS_PER_DAY,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Keyframe;
+    use crate::time::FixedOffset;
+
+    /// The daemon's defaults
+    fn temps() -> TempParams {
+        TempParams {
+            day_clear: 6500,
+            day_dark: 4500,
+            night: 2900,
+            cloud_threshold: 75,
+            golden_hour_temp: 0,
+            moon_brightness_reduction: false,
+        }
+    }
+
+    fn transitions() -> TransitionParams {
+        TransitionParams {
+            dawn_duration: 90.0,
+            dusk_duration: 180.0,
+            dawn_offset_min: 30.0,
+            dusk_offset_min: 30.0,
+            steepness_dawn: STEEPNESS,
+            steepness_dusk: STEEPNESS,
+            transition_tick_sec: 10,
+            temp_change_threshold: 5,
+        }
+    }
+
+    fn schedule(blend_min: f64) -> Schedule {
+        Schedule {
+            keyframes: vec![
+                Keyframe { minute: 7 * 60, temp: 6500 },
+                Keyframe { minute: 18 * 60, temp: 4500 },
+                Keyframe { minute: 21 * 60, temp: 2700 },
+            ],
+            blend_min,
+        }
+    }
+
+    #[test]
+    fn schedule_holds_between_keyframes() {
+        let s = schedule(30.0);
+        assert_eq!(calculate_schedule_temp(12.0 * 60.0, &s), 6500);
+        assert_eq!(calculate_schedule_temp(19.0 * 60.0, &s), 4500);
+        assert_eq!(calculate_schedule_temp(23.0 * 60.0, &s), 2700);
+    }
+
+    #[test]
+    fn schedule_wraps_across_midnight() {
+        let s = schedule(30.0);
+        // Before the first keyframe: still yesterday's last
+        assert_eq!(calculate_schedule_temp(3.0 * 60.0, &s), 2700);
+        assert_eq!(calculate_schedule_temp(0.0, &s), 2700);
+
+        // A keyframe at 00:10 blends from the previous evening's value
+        let mut s = schedule(30.0);
+        s.keyframes.insert(0, Keyframe { minute: 10, temp: 2000 });
+        let before = calculate_schedule_temp(1438.0, &s);
+        let after = calculate_schedule_temp(20.0, &s);
+        assert!(before < 2700 && before > 2000, "{before}");
+        assert!(after < before, "{after}");
+    }
+
+    #[test]
+    fn schedule_blends_monotonically_around_keyframe() {
+        let s = schedule(60.0);
+        let at = |m: f64| calculate_schedule_temp(m, &s);
+        assert_eq!(at(17.0 * 60.0 + 29.0), 6500);
+        assert_eq!(at(18.0 * 60.0 + 31.0), 4500);
+        let mid = at(18.0 * 60.0);
+        assert!((5400..=5600).contains(&mid), "{mid}");
+
+        let mut prev = at(17.0 * 60.0 + 30.0);
+        for m in (17 * 60 + 31)..=(18 * 60 + 30) {
+            let t = at(m as f64);
+            assert!(t <= prev, "{m}: {t} > {prev}");
+            prev = t;
+        }
+    }
+
+    #[test]
+    fn schedule_without_blend_steps() {
+        let s = schedule(0.0);
+        assert_eq!(calculate_schedule_temp(17.0 * 60.0 + 59.0, &s), 6500);
+        assert_eq!(calculate_schedule_temp(18.0 * 60.0, &s), 4500);
+    }
+
+    #[test]
+    fn schedule_blend_window_detected() {
+        let s = schedule(30.0);
+        assert!(in_schedule_blend(7.0 * 60.0 + 10.0, &s));
+        assert!(in_schedule_blend(18.0 * 60.0 - 14.0, &s));
+        assert!(!in_schedule_blend(12.0 * 60.0, &s));
+        assert!(!in_schedule_blend(3.0 * 60.0, &s));
+    }
+
+    #[test]
+    fn solar_windows_report_minutes_left() {
+        // Defaults: dawn 90 min centered 30 min after sunrise, dusk 180 min
+        // centered 30 min before sunset
+        let p = transitions();
+        assert_eq!(solar_window(-14.0, 600.0, &p), Some((SolarWindow::Dawn, 89.0)));
+        assert_eq!(solar_window(30.0, 600.0, &p), Some((SolarWindow::Dawn, 45.0)));
+        assert_eq!(solar_window(80.0, 600.0, &p), None);
+        assert_eq!(solar_window(600.0, 119.0, &p), Some((SolarWindow::Dusk, 179.0)));
+        assert_eq!(solar_window(600.0, -50.0, &p), Some((SolarWindow::Dusk, 10.0)));
+        assert_eq!(solar_window(900.0, -70.0, &p), None);
+        assert!(in_solar_transition(600.0, 0.0, &p) && !in_solar_transition(600.0, 300.0, &p));
+    }
+
+    #[test]
+    fn elevation_curve_between_thresholds() {
+        let t = temps();
+        assert_eq!(calculate_elevation_temp(10.0, false, &t, -6.0, 3.0), 6500);
+        assert_eq!(calculate_elevation_temp(10.0, true, &t, -6.0, 3.0), 4500);
+        assert_eq!(calculate_elevation_temp(-6.0, false, &t, -6.0, 3.0), 2900);
+        // Halfway up is halfway between night and day
+        assert_eq!(calculate_elevation_temp(-1.5, false, &t, -6.0, 3.0), 4700);
+        assert!(calculate_elevation_temp(-3.0, false, &t, -6.0, 3.0) < 4700);
+        assert!(in_elevation_transition(0.0, -6.0, 3.0));
+        assert!(!in_elevation_transition(3.0, -6.0, 3.0) && !in_elevation_transition(-6.0, -6.0, 3.0));
+    }
+
+    #[test]
+    fn resume_15_minutes_before_next_window() {
+        // Greenwich on the 2025 equinox; dawn starts 15 min before sunrise,
+        // dusk 2h before sunset
+        let (utc, p) = (FixedOffset::UTC, transitions());
+        let st = solar::sunrise_sunset(1742472000, 51.48, 0.0, &utc).unwrap();
+        let resume = |now: i64| next_transition_resume(now, 51.48, 0.0, &p, &utc);
+        assert_eq!(resume(st.sunrise - 3 * 3600), st.sunrise - 30 * 60);
+        assert_eq!(resume(st.solar_noon), st.sunset - 135 * 60);
+
+        // After dusk: tomorrow's dawn
+        let tomorrow = solar::sunrise_sunset(1742472000 + 86400, 51.48, 0.0, &utc).unwrap();
+        assert_eq!(resume(st.sunset + 3600), tomorrow.sunrise - 30 * 60);
+
+        // Polar night has no window: a day later
+        let now = 1734782400;
+        assert_eq!(next_transition_resume(now, 78.2, 15.6, &p, &utc), now + 86400);
+    }
+
+    // Steepest sigmoid step is ~2.0 per unit x; the 90-min dawn window
+    // moves x by 1/45 a minute, so 3600K of range moves under 170K/minute
+    const MAX_STEP_K: i32 = 170;
+
+    /// Temperatures for each minute of a day with the given sun times
+    /// (minutes after midnight), plus a margin either side.
+    fn sweep(sunrise: f64, sunset: f64) -> Vec<i32> {
+        (-120..1560)
+            .map(|m| {
+                let m = m as f64;
+                calculate_solar_temp(m - sunrise, sunset - m, 0, 0, &temps(), &transitions())
+            })
+            .collect()
+    }
+
+    fn assert_smooth_and_unimodal(temps: &[i32], what: &str) {
+        let peak = temps.iter().position(|&t| t == *temps.iter().max().unwrap()).unwrap();
+        for (i, w) in temps.windows(2).enumerate() {
+            assert!((w[1] - w[0]).abs() <= MAX_STEP_K, "{}: minute {}: {} -> {}", what, i, w[0], w[1]);
+            if i < peak {
+                assert!(w[1] >= w[0], "{}: falls before peak at minute {}", what, i);
+            } else {
+                assert!(w[1] <= w[0], "{}: rises after peak at minute {}", what, i);
+            }
+        }
+    }
+
+    #[test]
+    fn manual_easing_boundaries() {
+        let start = 1_000_000;
+        let at = |easing, elapsed_sec: i64| {
+            calculate_manual_temp(6500, 2500, start, 600, easing, start + elapsed_sec)
+        };
+        for easing in [Easing::Sigmoid, Easing::Linear, Easing::EaseInOut, Easing::Step] {
+            assert_eq!(at(easing, 0), 6500, "{:?} at t=0", easing);
+            assert_eq!(at(easing, 600), 2500, "{:?} at t=duration", easing);
+            assert_eq!(at(easing, 900), 2500, "{:?} past duration", easing);
+        }
+
+        // Symmetric curves cross the midpoint at half time
+        assert_eq!(at(Easing::Sigmoid, 300), 4500);
+        assert_eq!(at(Easing::Linear, 300), 4500);
+        assert_eq!(at(Easing::EaseInOut, 300), 4500);
+        assert_eq!(at(Easing::Step, 300), 6500);
+        assert_eq!(at(Easing::Step, 599), 6500);
+
+        // Linear moves evenly; ease-in-out starts slower than linear
+        assert_eq!(at(Easing::Linear, 150), 5500);
+        assert!(at(Easing::EaseInOut, 60) > at(Easing::Linear, 60));
+    }
+
+    #[test]
+    fn dusk_blends_toward_forecast_cloud_cover() {
+        let (t, tp) = (&temps(), &transitions());
+        let mid = tp.dusk_offset_min;
+        let half = tp.dusk_duration / 2.0;
+
+        // Clear now, overcast in 3h: afternoon and dawn still use the clear target
+        assert_eq!(calculate_solar_temp(600.0, mid + half + 60.0, 0, 100, t, tp), t.day_clear);
+        assert_eq!(
+            calculate_solar_temp(10.0, 600.0, 0, 100, t, tp),
+            calculate_solar_temp(10.0, 600.0, 0, 0, t, tp)
+        );
+
+        // Midway through dusk the day target sits between the two
+        let clear = calculate_solar_temp(600.0, mid, 0, 0, t, tp);
+        let dark = calculate_solar_temp(600.0, mid, 100, 100, t, tp);
+        let blended = calculate_solar_temp(600.0, mid, 0, 100, t, tp);
+        assert!(dark < blended && blended < clear, "{} {} {}", dark, blended, clear);
+
+        // No jumps minute to minute across the window
+        let temps: Vec<i32> = (-200..200)
+            .map(|m| calculate_solar_temp(600.0, mid + m as f64, 0, 100, t, tp))
+            .collect();
+        for w in temps.windows(2) {
+            assert!((w[1] - w[0]).abs() <= MAX_STEP_K, "{} -> {}", w[0], w[1]);
+        }
+    }
+
+    #[test]
+    fn trace_names_the_deciding_phase() {
+        let (t, tp) = (&temps(), &transitions());
+        let trace = |from_sunrise: f64, to_sunset: f64| trace_solar_temp(from_sunrise, to_sunset, 0, 0, t, tp);
+
+        let night = trace(-120.0, 900.0);
+        assert_eq!((night.phase, night.factor, night.temp), (SolarPhase::Night, 0.0, t.night));
+        assert!(night.x < -1.0);
+        let day = trace(300.0, 400.0);
+        assert_eq!((day.phase, day.factor, day.temp), (SolarPhase::Day, 1.0, t.day_clear));
+
+        // Window midpoints sit at x = 0, halfway between the targets
+        let dawn = trace(tp.dawn_offset_min, 600.0);
+        assert_eq!((dawn.phase, dawn.x), (SolarPhase::Dawn, 0.0));
+        assert!((dawn.factor - 0.5).abs() < 1e-9);
+        let dusk = trace(600.0, tp.dusk_offset_min + tp.dusk_duration / 4.0);
+        assert_eq!((dusk.phase, dusk.x), (SolarPhase::Dusk, 0.5));
+        assert!(dusk.factor > 0.5 && dusk.factor < 1.0);
+        assert_eq!(dusk.temp, calculate_solar_temp(600.0, tp.dusk_offset_min + tp.dusk_duration / 4.0, 0, 0, t, tp));
+        assert_eq!(dusk.day_temp, t.day_clear as f64);
+    }
+
+    #[test]
+    fn steepness_shapes_each_window_separately() {
+        let normal = transitions();
+        let mut sharp = normal;
+        sharp.steepness_dusk = 16.0;
+        let temp = |from_sunrise: f64, to_sunset: f64, tp: &TransitionParams| {
+            calculate_solar_temp(from_sunrise, to_sunset, 0, 0, &temps(), tp)
+        };
+
+        // A quarter into dusk (45 min before its midpoint): sharper holds day longer
+        let quarter = normal.dusk_offset_min + 45.0;
+        assert!(temp(600.0, quarter, &sharp) > temp(600.0, quarter, &normal));
+        // Midpoint is unchanged, dawn untouched
+        let mid = normal.dusk_offset_min;
+        assert_eq!(temp(600.0, mid, &sharp), temp(600.0, mid, &normal));
+        assert_eq!(temp(20.0, 600.0, &sharp), temp(20.0, 600.0, &normal));
+    }
+
+    #[test]
+    fn short_days_stay_continuous() {
+        // Day lengths shorter than the (90 + 180) / 2 minutes of windows
+        for day_len in [30.0, 60.0, 120.0, 134.0, 200.0] {
+            let sunrise = 720.0 - day_len / 2.0;
+            let temps = sweep(sunrise, sunrise + day_len);
+            assert_smooth_and_unimodal(&temps, &format!("{} min day", day_len));
+            // Windows end/start at sunrise + 75 and sunset - 120: no full day
+            if day_len < 195.0 {
+                assert!(temps.iter().all(|&t| t < 6500), "{} min day", day_len);
+            }
+        }
+    }
+
+    #[test]
+    fn real_days_stay_continuous() {
+        // Winter solstice 2025, noon UTC, along the Greenwich meridian
+        let noon = 1766318400;
+        let midnight = noon - 43200;
+        for lat in [0.0, 40.0, 55.0, 62.0, 65.0, 66.0, -40.0] {
+            let st = solar::sunrise_sunset(noon, lat, 0.0, &FixedOffset::UTC).unwrap();
+            let temps = sweep(
+                (st.sunrise - midnight) as f64 / 60.0,
+                (st.sunset - midnight) as f64 / 60.0,
+            );
+            assert_smooth_and_unimodal(&temps, &format!("lat {}", lat));
+        }
+    }
+
+    #[test]
+    fn bright_moon_lowers_night() {
+        assert_eq!(moonlit_night_temp(2900, 0.5), 2900);
+        assert_eq!(moonlit_night_temp(2900, MOON_ILLUMINATION_MIN), 2900);
+        assert_eq!(moonlit_night_temp(2900, 0.85), 2800);
+        assert_eq!(moonlit_night_temp(2900, 1.0), 2900 - MOON_REDUCTION_MAX);
+        assert_eq!(moonlit_night_temp(1100, 1.0), TEMP_MIN);
+    }
+}
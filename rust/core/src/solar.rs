@@ -0,0 +1,565 @@
+//! NOAA sun position and sunrise/sunset calculations.
+//!
+//! Port of the C23 NOAA solar equations.
+//! Julian day -> Julian century -> geometric mean longitude/anomaly ->
+//! equation of center -> apparent longitude -> declination -> hour angle.
+//!
+//! Times are Unix epoch seconds. Sunrise and sunset are for the local
+//! calendar day of `when` in `tz`.
+
+use core::f64::consts::PI;
+
+#[cfg(not(feature = "std"))]
+use crate::math::FloatExt;
+use crate::time::{LocalTime, TimeZone};
+
+fn deg2rad(d: f64) -> f64 {
+    d * PI / 180.0
+}
+
+fn rad2deg(r: f64) -> f64 {
+    r * 180.0 / PI
+}
+
+/// Sun position result
+pub struct SunPosition {
+    /// Degrees above the horizon (negative below)
+    pub elevation: f64,
+    /// Degrees clockwise from true north
+    pub azimuth: f64,
+}
+
+/// Sunrise/sunset times
+pub struct SunTimes {
+    /// Epoch seconds
+    pub sunrise: i64,
+    /// Epoch seconds
+    pub sunset: i64,
+    /// Sun crosses the meridian (highest elevation of the day)
+    pub solar_noon: i64,
+}
+
+/// Julian Day from broken-down time
+pub fn julian_day(year: i32, month: i32, day: i32, hour_frac: f64) -> f64 {
+    let (y, m) = if month <= 2 {
+        (year - 1, month + 12)
+    } else {
+        (year, month)
+    };
+
+    let a = y / 100;
+    let b = 2 - a + a / 4;
+
+    let jd = (365.25 * (y + 4716) as f64) as i32 as f64
+        + (30.6001 * (m + 1) as f64) as i32 as f64
+        + day as f64
+        + b as f64
+        - 1524.5;
+    jd + hour_frac / 24.0
+}
+
+/// Shared NOAA solar parameters from Julian century (degrees unless noted)
+pub struct SolarParams {
+    /// Geometric mean longitude
+    pub l0: f64,
+    /// Geometric mean anomaly
+    pub m: f64,
+    /// Eccentricity of Earth's orbit (unitless)
+    pub e: f64,
+    /// Equation of center
+    pub c: f64,
+    /// True longitude
+    pub true_lon: f64,
+    /// Apparent longitude (nutation and aberration corrected)
+    pub apparent_lon: f64,
+    /// Corrected obliquity of the ecliptic
+    pub obliq_corr: f64,
+    /// Declination
+    pub sun_declin: f64,
+    /// Equation of time (minutes)
+    pub eq_time: f64,
+}
+
+/// Solar parameters at a Julian century (from J2000.0)
+pub fn compute_solar_params(jc: f64) -> SolarParams {
+    let l0 = (280.46646 + jc * (36000.76983 + 0.0003032 * jc)) % 360.0;
+    let m = 357.52911 + jc * (35999.05029 - 0.0001537 * jc);
+    let m_rad = deg2rad(m);
+    let e = 0.016708634 - jc * (0.000042037 + 0.0000001267 * jc);
+
+    // Sun's equation of center
+    let c = m_rad.sin() * (1.914602 - jc * (0.004817 + 0.000014 * jc))
+        + (2.0 * m_rad).sin() * (0.019993 - 0.000101 * jc)
+        + (3.0 * m_rad).sin() * 0.000289;
+
+    // Sun's true and apparent longitude
+    let sun_lon = l0 + c;
+    let omega = 125.04 - 1934.136 * jc;
+    let sun_apparent_lon = sun_lon - 0.00569 - 0.00478 * deg2rad(omega).sin();
+
+    // Mean obliquity and correction
+    let obliq_mean = 23.0
+        + (26.0 + (21.448 - jc * (46.815 + jc * (0.00059 - jc * 0.001813))) / 60.0) / 60.0;
+    let obliq_corr = obliq_mean + 0.00256 * deg2rad(omega).cos();
+    let obliq_corr_rad = deg2rad(obliq_corr);
+
+    // Solar declination
+    let sun_declin =
+        rad2deg((obliq_corr_rad.sin() * deg2rad(sun_apparent_lon).sin()).asin());
+
+    // Equation of time
+    let var_y = (obliq_corr_rad / 2.0).tan().powi(2);
+    let eq_time = 4.0
+        * rad2deg(
+            var_y * (2.0 * deg2rad(l0)).sin()
+                - 2.0 * e * m_rad.sin()
+                + 4.0 * e * var_y * m_rad.sin() * (2.0 * deg2rad(l0)).cos()
+                - 0.5 * var_y * var_y * (4.0 * deg2rad(l0)).sin()
+                - 1.25 * e * e * (2.0 * m_rad).sin(),
+        );
+
+    SolarParams {
+        l0,
+        m,
+        e,
+        c,
+        true_lon: sun_lon,
+        apparent_lon: sun_apparent_lon,
+        obliq_corr,
+        sun_declin,
+        eq_time,
+    }
+}
+
+/// Calculate sun position (elevation angle) at a given time and location
+pub fn position(when: i64, lat: f64, lon: f64, tz: &(impl TimeZone + ?Sized)) -> SunPosition {
+    let d = position_detail(when, lat, lon, tz);
+    SunPosition {
+        elevation: 90.0 - d.zenith,
+        azimuth: d.azimuth,
+    }
+}
+
+/// position() with its intermediate values
+pub struct PositionDetail {
+    /// Julian Day of the instant (UT)
+    pub julian_day: f64,
+    /// Julian centuries since J2000.0
+    pub julian_century: f64,
+    /// Parameters at that century
+    pub params: SolarParams,
+    /// Degrees, negative before solar noon
+    pub hour_angle: f64,
+    /// Degrees from straight up (90 - elevation)
+    pub zenith: f64,
+    /// Degrees clockwise from true north
+    pub azimuth: f64,
+}
+
+/// position() with its intermediate values
+pub fn position_detail(when: i64, lat: f64, lon: f64, tz: &(impl TimeZone + ?Sized)) -> PositionDetail {
+    // Local wall clock at `when`, shifted back to UT for the Julian day
+    let lt = LocalTime::at(when, tz);
+    let tz_offset = lt.offset_hours();
+    let hour_frac = lt.hour_frac();
+    let jd = julian_day(lt.year, lt.month, lt.day, hour_frac) - tz_offset / 24.0;
+    let jc = (jd - 2451545.0) / 36525.0;
+
+    let sp = compute_solar_params(jc);
+
+    // True solar time
+    let time_offset = sp.eq_time + 4.0 * lon - 60.0 * tz_offset;
+    let tst = hour_frac * 60.0 + time_offset;
+
+    // Hour angle
+    let mut hour_angle = tst / 4.0 - 180.0;
+    if hour_angle < -180.0 {
+        hour_angle += 360.0;
+    }
+
+    // Zenith and elevation
+    let lat_rad = deg2rad(lat);
+    let declin_rad = deg2rad(sp.sun_declin);
+    let ha_rad = deg2rad(hour_angle);
+
+    let cos_zenith =
+        (lat_rad.sin() * declin_rad.sin() + lat_rad.cos() * declin_rad.cos() * ha_rad.cos())
+            .clamp(-1.0, 1.0);
+
+    let zenith = rad2deg(cos_zenith.acos());
+
+    // Azimuth (NOAA): undefined with the sun at the zenith or at a pole
+    let denom = lat_rad.cos() * deg2rad(zenith).sin();
+    let azimuth = if denom.abs() < 1e-9 {
+        180.0
+    } else {
+        let cos_az = ((lat_rad.sin() * cos_zenith - declin_rad.sin()) / denom).clamp(-1.0, 1.0);
+        let a = rad2deg(cos_az.acos());
+        if hour_angle > 0.0 {
+            (a + 180.0) % 360.0
+        } else {
+            (540.0 - a) % 360.0
+        }
+    };
+
+    PositionDetail {
+        julian_day: jd,
+        julian_century: jc,
+        params: sp,
+        hour_angle,
+        zenith,
+        azimuth,
+    }
+}
+
+/// Hours between sunrise and sunset, or None in polar day/night.
+pub fn day_length(when: i64, lat: f64, lon: f64, tz: &(impl TimeZone + ?Sized)) -> Option<f64> {
+    sunrise_sunset(when, lat, lon, tz).map(|times| (times.sunset - times.sunrise) as f64 / 3600.0)
+}
+
+/// Whether the sun is above the horizon as sunrise/sunset define it
+/// (zenith 90.833: refraction and the solar disc).
+pub fn sun_is_up(elevation: f64) -> bool {
+    elevation > -0.833
+}
+
+/// Whether the sun rises and sets on a day
+pub enum PolarStatus {
+    /// Midnight sun: above the horizon all day
+    Day,
+    /// Below the horizon all day
+    Night,
+    /// Rises and sets at these times
+    Normal(SunTimes),
+}
+
+/// Sunrise and sunset for the day of `when`, or which polar case applies.
+pub fn polar_status(when: i64, lat: f64, lon: f64, tz: &(impl TimeZone + ?Sized)) -> PolarStatus {
+    let d = sunrise_sunset_detail(when, lat, lon, tz);
+    match crossing_times(&d) {
+        Some(times) => PolarStatus::Normal(times),
+        None if d.cos_ha < -1.0 => PolarStatus::Day,
+        None => PolarStatus::Night,
+    }
+}
+
+/// The sun never sets on the day of `when`.
+pub fn is_polar_day(when: i64, lat: f64, lon: f64, tz: &(impl TimeZone + ?Sized)) -> bool {
+    matches!(polar_status(when, lat, lon, tz), PolarStatus::Day)
+}
+
+/// The sun never rises on the day of `when`.
+pub fn is_polar_night(when: i64, lat: f64, lon: f64, tz: &(impl TimeZone + ?Sized)) -> bool {
+    matches!(polar_status(when, lat, lon, tz), PolarStatus::Night)
+}
+
+/// "polar day" or "polar night" for a day sunrise_sunset() has no times for.
+pub fn polar_label(when: i64, lat: f64, lon: f64, tz: &(impl TimeZone + ?Sized)) -> &'static str {
+    if is_polar_day(when, lat, lon, tz) {
+        "polar day"
+    } else if is_polar_night(when, lat, lon, tz) {
+        "polar night"
+    } else {
+        "sun rises and sets"
+    }
+}
+
+/// Calculate sunrise and sunset times for a given day and location
+pub fn sunrise_sunset(when: i64, lat: f64, lon: f64, tz: &(impl TimeZone + ?Sized)) -> Option<SunTimes> {
+    horizon_crossings(when, lat, lon, 90.833, tz)
+}
+
+/// Golden hour: the sun between -4 and 6 degrees elevation (zenith 94..84)
+const GOLDEN_ZENITH_LOW: f64 = 94.0;
+const GOLDEN_ZENITH_HIGH: f64 = 84.0;
+
+/// Morning golden hour on the day of `when`: (sun rises through -4 degrees,
+/// sun rises through 6 degrees). None if the sun never crosses either.
+pub fn golden_hour_morning(when: i64, lat: f64, lon: f64, tz: &(impl TimeZone + ?Sized)) -> Option<(i64, i64)> {
+    let low = horizon_crossings(when, lat, lon, GOLDEN_ZENITH_LOW, tz)?;
+    let high = horizon_crossings(when, lat, lon, GOLDEN_ZENITH_HIGH, tz)?;
+    Some((low.sunrise, high.sunrise))
+}
+
+/// Evening golden hour on the day of `when`: (sun sets through 6 degrees,
+/// sun sets through -4 degrees). None if the sun never crosses either.
+pub fn golden_hour_evening(when: i64, lat: f64, lon: f64, tz: &(impl TimeZone + ?Sized)) -> Option<(i64, i64)> {
+    let low = horizon_crossings(when, lat, lon, GOLDEN_ZENITH_LOW, tz)?;
+    let high = horizon_crossings(when, lat, lon, GOLDEN_ZENITH_HIGH, tz)?;
+    Some((high.sunset, low.sunset))
+}
+
+/// The golden hour window (morning or evening) containing `when`, if any.
+/// Neighbouring days count too: a window can run past local midnight.
+pub fn golden_hour(when: i64, lat: f64, lon: f64, tz: &(impl TimeZone + ?Sized)) -> Option<(i64, i64)> {
+    [when - 86400, when, when + 86400]
+        .into_iter()
+        .flat_map(|day| [golden_hour_morning(day, lat, lon, tz), golden_hour_evening(day, lat, lon, tz)])
+        .flatten()
+        .find(|&(start, end)| (start..end).contains(&when))
+}
+
+/// A new moon: 2000-01-06 18:14 UTC
+const NEW_MOON_EPOCH: i64 = 947182440;
+/// Mean synodic month (days)
+const SYNODIC_MONTH: f64 = 29.530588853;
+
+/// Place in the lunar cycle at `when`: 0.0 new moon, 0.5 full moon, back
+/// to new toward 1.0. Mean lunar age, so the real phases can be most of a
+/// day off.
+pub fn moon_phase(when: i64) -> f64 {
+    let days = (when - NEW_MOON_EPOCH) as f64 / 86400.0;
+    (days / SYNODIC_MONTH).rem_euclid(1.0)
+}
+
+/// Fraction of the lunar disk lit at `when`, 0.0 (new) to 1.0 (full)
+pub fn moon_illumination(when: i64) -> f64 {
+    (1.0 - (moon_phase(when) * 2.0 * PI).cos()) / 2.0
+}
+
+/// Name of a moon_phase(): "new moon", "waxing crescent", "first
+/// quarter", ... Each quarter is named within 1/16 of a cycle of it.
+pub fn moon_phase_name(phase: f64) -> &'static str {
+    const NAMES: [&str; 8] = [
+        "new moon", "waxing crescent", "first quarter", "waxing gibbous",
+        "full moon", "waning gibbous", "last quarter", "waning crescent",
+    ];
+    NAMES[((phase * 8.0 + 0.5) as usize) % 8]
+}
+
+/// Times the sun crosses `zenith` degrees on the day of `when` (rising as
+/// `sunrise`, setting as `sunset`). None if it stays above or below all day.
+fn horizon_crossings(when: i64, lat: f64, lon: f64, zenith: f64, tz: &(impl TimeZone + ?Sized)) -> Option<SunTimes> {
+    crossing_times(&crossing_detail(when, lat, lon, zenith, tz))
+}
+
+fn crossing_times(d: &CrossingDetail) -> Option<SunTimes> {
+    let (rise_min, set_min) = d.rise_set_min?;
+    Some(SunTimes {
+        sunrise: d.midnight + (rise_min * 60.0) as i64,
+        sunset: d.midnight + (set_min * 60.0) as i64,
+        solar_noon: d.midnight + (d.noon_min * 60.0) as i64,
+    })
+}
+
+/// horizon_crossings() with its intermediate values
+pub struct CrossingDetail {
+    /// Julian Day at noon of the day (the crossings use noon's parameters)
+    pub julian_day: f64,
+    /// cos(hour angle) of the crossing; outside [-1, 1] when there is none
+    pub cos_ha: f64,
+    /// Local midnight the minutes count from
+    pub midnight: i64,
+    /// Rising and setting crossing, minutes after midnight
+    pub rise_set_min: Option<(f64, f64)>,
+    /// Solar noon, minutes after midnight
+    pub noon_min: f64,
+}
+
+/// Sunrise/sunset internals at the standard zenith (90.833 degrees)
+pub fn sunrise_sunset_detail(when: i64, lat: f64, lon: f64, tz: &(impl TimeZone + ?Sized)) -> CrossingDetail {
+    crossing_detail(when, lat, lon, 90.833, tz)
+}
+
+fn crossing_detail(when: i64, lat: f64, lon: f64, zenith: f64, tz: &(impl TimeZone + ?Sized)) -> CrossingDetail {
+    // Use noon of the given day
+    let lt = LocalTime::at(when, tz);
+    let jd = julian_day(lt.year, lt.month, lt.day, 12.0);
+    let jc = (jd - 2451545.0) / 36525.0;
+
+    let sp = compute_solar_params(jc);
+
+    // Hour angle for the crossing (zenith 90.833 degrees at sunrise/sunset)
+    let lat_rad = deg2rad(lat);
+    let declin_rad = deg2rad(sp.sun_declin);
+
+    let cos_ha =
+        deg2rad(zenith).cos() / (lat_rad.cos() * declin_rad.cos()) - lat_rad.tan() * declin_rad.tan();
+
+    // Base midnight of the given day
+    let midnight = lt.midnight(tz);
+
+    // Minutes after that midnight: the offset must be midnight's own, not
+    // today's, or a DST change between the two shifts everything an hour
+    let tz_offset = tz.utc_offset(midnight) as f64 / 3600.0;
+    let noon_min = 720.0 - 4.0 * lon - sp.eq_time + tz_offset * 60.0;
+
+    // Polar region check
+    let rise_set_min = if (-1.0..=1.0).contains(&cos_ha) {
+        let ha = rad2deg(cos_ha.acos());
+        Some((noon_min - 4.0 * ha, noon_min + 4.0 * ha))
+    } else {
+        None
+    };
+
+    CrossingDetail {
+        julian_day: jd,
+        cos_ha,
+        midnight,
+        rise_set_min,
+        noon_min,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::tests::{NewYork2025, FALL_BACK, SPRING_FORWARD};
+    use crate::time::FixedOffset;
+
+    const DAY: i64 = 86400;
+    const NYC: (f64, f64) = (40.71, -74.01);
+    const UTC: FixedOffset = FixedOffset::UTC;
+    /// Chicago in summer (CDT)
+    const CDT: FixedOffset = FixedOffset(-5 * 3600);
+
+    #[test]
+    fn sunrise_steps_by_a_day_across_dst_changes() {
+        for change in [SPRING_FORWARD, FALL_BACK] {
+            // Noon local on the days around the change
+            for day in -2..2 {
+                let when = change + day * DAY + 10 * 3600;
+                let a = sunrise_sunset(when, NYC.0, NYC.1, &NewYork2025).unwrap();
+                let b = sunrise_sunset(when + DAY, NYC.0, NYC.1, &NewYork2025).unwrap();
+                // Day-to-day drift is a couple of minutes, never an hour
+                assert!((b.sunrise - a.sunrise - DAY).abs() < 300, "day {}", day);
+                assert!((b.sunset - a.sunset - DAY).abs() < 300, "day {}", day);
+            }
+        }
+    }
+
+    #[test]
+    fn sun_times_independent_of_time_zone() {
+        // 17:00 UTC is the same calendar day in UTC and New York
+        for when in [SPRING_FORWARD - DAY + 36000, FALL_BACK + DAY + 36000] {
+            let utc = sunrise_sunset(when, NYC.0, NYC.1, &UTC).unwrap();
+            let ny = sunrise_sunset(when, NYC.0, NYC.1, &NewYork2025).unwrap();
+            assert!((utc.sunrise - ny.sunrise).abs() < 60);
+            assert!((utc.sunset - ny.sunset).abs() < 60);
+
+            let utc = position(when, NYC.0, NYC.1, &UTC).elevation;
+            let ny = position(when, NYC.0, NYC.1, &NewYork2025).elevation;
+            assert!((utc - ny).abs() < 0.01, "{} vs {}", utc, ny);
+        }
+    }
+
+    /// Seconds after local midnight, for comparing against NOAA's table
+    fn clock_sec(epoch: i64, tz: &(impl TimeZone + ?Sized)) -> i64 {
+        let t = LocalTime::at(epoch, tz);
+        (t.hour * 3600 + t.minute * 60 + t.second) as i64
+    }
+
+    fn hms(h: i64, m: i64) -> i64 {
+        h * 3600 + m * 60
+    }
+
+    #[test]
+    fn matches_noaa_calculator() {
+        // Chicago, 2024-06-21 (CDT): NOAA gives sunrise 05:15, solar noon
+        // 12:52, sunset 20:29, elevation at noon 71.56, sunrise azimuth 56.8
+        let (lat, lon) = (41.8781, -87.6298);
+        let t = sunrise_sunset(1718989200, lat, lon, &CDT).unwrap();
+        assert!((clock_sec(t.sunrise, &CDT) - hms(5, 15)).abs() < 120);
+        assert!((clock_sec(t.solar_noon, &CDT) - hms(12, 52)).abs() < 120);
+        assert!((clock_sec(t.sunset, &CDT) - hms(20, 29)).abs() < 120);
+
+        let noon = position(t.solar_noon, lat, lon, &CDT);
+        assert!((noon.elevation - 71.56).abs() < 0.1);
+        assert!((noon.azimuth - 180.0).abs() < 0.5);
+        assert!((position(t.sunrise, lat, lon, &CDT).azimuth - 56.8).abs() < 1.0);
+        assert!((position(t.sunset, lat, lon, &CDT).azimuth - 303.2).abs() < 1.0);
+
+        // Greenwich, 2025-03-20 equinox: solar noon 12:07 UTC, 12h11m of day
+        let t = sunrise_sunset(1742472000, 51.4779, 0.0, &UTC).unwrap();
+        assert!((clock_sec(t.solar_noon, &UTC) - hms(12, 7)).abs() < 90);
+        assert!((t.sunset - t.sunrise - hms(12, 11)).abs() < 120);
+        let hours = day_length(1742472000, 51.4779, 0.0, &UTC).unwrap();
+        assert!((hours - 12.0 - 11.0 / 60.0).abs() < 0.05);
+        // Tromso midsummer: no sunset, no day length
+        assert!(day_length(1750464000, 69.65, 18.96, &UTC).is_none());
+    }
+
+    #[test]
+    fn polar_status_tells_day_from_night() {
+        // Tromso at both solstices, and McMurdo in December
+        assert!(matches!(polar_status(1750464000, 69.65, 18.96, &UTC), PolarStatus::Day));
+        assert!(matches!(polar_status(1734782400, 69.65, 18.96, &UTC), PolarStatus::Night));
+        assert!(is_polar_day(1734782400, -77.85, 166.67, &UTC));
+        assert!(is_polar_night(1750464000, -77.85, 166.67, &UTC));
+        assert_eq!(polar_label(1750464000, 69.65, 18.96, &UTC), "polar day");
+        assert_eq!(polar_label(1734782400, 69.65, 18.96, &UTC), "polar night");
+
+        // Greenwich rises and sets, with the same times sunrise_sunset gives
+        let (when, lat) = (1742472000, 51.4779);
+        match polar_status(when, lat, 0.0, &UTC) {
+            PolarStatus::Normal(t) => assert_eq!(t.sunrise, sunrise_sunset(when, lat, 0.0, &UTC).unwrap().sunrise),
+            _ => panic!("Greenwich has a sunrise"),
+        }
+        assert!(!is_polar_day(when, lat, 0.0, &UTC) && !is_polar_night(when, lat, 0.0, &UTC));
+    }
+
+    #[test]
+    fn detail_matches_noaa_spreadsheet() {
+        // Chicago solar noon, 2024-06-21: NOAA's spreadsheet gives
+        // declination 23.44, equation of time -2.0 min
+        let (lat, lon) = (41.8781, -87.6298);
+        let t = sunrise_sunset(1718989200, lat, lon, &CDT).unwrap();
+        let d = position_detail(t.solar_noon, lat, lon, &CDT);
+        assert!((d.params.sun_declin - 23.44).abs() < 0.01);
+        assert!((d.params.eq_time + 2.0).abs() < 0.1);
+        assert!(d.hour_angle.abs() < 0.5);
+        assert!((d.julian_century - (d.julian_day - 2451545.0) / 36525.0).abs() < 1e-12);
+        assert_eq!(90.0 - d.zenith, position(t.solar_noon, lat, lon, &CDT).elevation);
+
+        let x = sunrise_sunset_detail(t.solar_noon, lat, lon, &CDT);
+        let (rise, set) = x.rise_set_min.unwrap();
+        assert_eq!(x.midnight + (rise * 60.0) as i64, t.sunrise);
+        assert_eq!(x.midnight + (set * 60.0) as i64, t.sunset);
+        assert!(x.cos_ha.abs() < 1.0);
+    }
+
+    #[test]
+    fn golden_hour_within_civil_day() {
+        // Every ~2 weeks of 2025, latitudes 50S..50N, a spread of longitudes
+        for day in (0..365).step_by(15) {
+            let when = 1735732800 + day * DAY; // 2025-01-01 12:00 UTC
+            for lat in (-50..=50).step_by(10) {
+                for lon in [-120.0, 0.0, 135.0] {
+                    let lat = lat as f64;
+                    let civil = horizon_crossings(when, lat, lon, 96.0, &UTC).unwrap();
+                    let (m0, m1) = golden_hour_morning(when, lat, lon, &UTC).unwrap();
+                    let (e0, e1) = golden_hour_evening(when, lat, lon, &UTC).unwrap();
+                    let at = format!("day {} lat {} lon {}", day, lat, lon);
+                    assert!(civil.sunrise < m0 && m0 < m1 && m1 < e0, "{}", at);
+                    assert!(e0 < e1 && e1 < civil.sunset, "{}", at);
+
+                    // Sunrise falls inside the morning window
+                    let sun = sunrise_sunset(when, lat, lon, &UTC).unwrap();
+                    assert_eq!(golden_hour(sun.sunrise, lat, lon, &UTC), Some((m0, m1)), "{}", at);
+                    assert_eq!(golden_hour(sun.solar_noon, lat, lon, &UTC), None, "{}", at);
+                }
+            }
+        }
+
+        // Tromso midwinter: the sun peaks near -3 degrees, never reaching 6
+        assert!(golden_hour_morning(1734782400, 69.65, 18.96, &UTC).is_none());
+    }
+
+    #[test]
+    fn moon_phases_2024() {
+        // New moon 2024-04-08 18:21 UTC (the eclipse), full 2024-04-23 23:49
+        let (new, full) = (1712600460, 1713916140);
+        let cycle_off = |phase: f64, want: f64| (phase - want + 0.5).rem_euclid(1.0) - 0.5;
+        assert!(cycle_off(moon_phase(new), 0.0).abs() < 1.0 / SYNODIC_MONTH);
+        assert!(cycle_off(moon_phase(full), 0.5).abs() < 1.0 / SYNODIC_MONTH);
+        assert!(moon_illumination(new) < 0.01);
+        assert!(moon_illumination(full) > 0.99);
+
+        assert_eq!(moon_phase_name(moon_phase(new)), "new moon");
+        assert_eq!(moon_phase_name(moon_phase(full)), "full moon");
+        assert_eq!(moon_phase_name(moon_phase(new + 4 * DAY)), "waxing crescent");
+        assert_eq!(moon_phase_name(moon_phase(full - 4 * DAY)), "waxing gibbous");
+        assert_eq!(moon_phase_name(moon_phase(full + 4 * DAY)), "waning gibbous");
+        assert_eq!(moon_phase_name(0.99), "new moon");
+    }
+}
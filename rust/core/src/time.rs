@@ -0,0 +1,197 @@
+//! UTC offsets and local calendar days.
+//!
+//! Sunrise and sunset belong to a local day, so the solar math asks a
+//! `TimeZone` for the offset instead of reading the process time zone.
+
+const SECONDS_PER_DAY: i64 = 86400;
+
+/// Source of UTC offsets. Functions taking one accept `&dyn` zones too.
+pub trait TimeZone {
+    /// Seconds east of UTC in effect at `utc` (epoch seconds), DST included
+    fn utc_offset(&self, utc: i64) -> i64;
+}
+
+/// A zone that never changes its offset (UTC, or a zone outside DST)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FixedOffset(pub i64);
+
+impl FixedOffset {
+    /// Zero offset
+    pub const UTC: FixedOffset = FixedOffset(0);
+
+    /// Offset from hours east of UTC (5.5 for India)
+    pub fn hours(hours: f64) -> FixedOffset {
+        FixedOffset((hours * 3600.0) as i64)
+    }
+}
+
+impl TimeZone for FixedOffset {
+    fn utc_offset(&self, _utc: i64) -> i64 {
+        self.0
+    }
+}
+
+/// Wall clock at an instant in a zone
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LocalTime {
+    /// Gregorian year
+    pub year: i32,
+    /// 1-12
+    pub month: i32,
+    /// 1-31
+    pub day: i32,
+    /// 0-23
+    pub hour: i32,
+    /// 0-59
+    pub minute: i32,
+    /// 0-59
+    pub second: i32,
+    /// Seconds east of UTC
+    pub offset: i64,
+}
+
+impl LocalTime {
+    /// Wall clock at `utc` (epoch seconds)
+    pub fn at(utc: i64, tz: &(impl TimeZone + ?Sized)) -> LocalTime {
+        let offset = tz.utc_offset(utc);
+        let local = utc + offset;
+        let (year, month, day) = civil_from_days(local.div_euclid(SECONDS_PER_DAY));
+        let secs = local.rem_euclid(SECONDS_PER_DAY) as i32;
+        LocalTime {
+            year,
+            month,
+            day,
+            hour: secs / 3600,
+            minute: secs / 60 % 60,
+            second: secs % 60,
+            offset,
+        }
+    }
+
+    /// Hours east of UTC
+    pub fn offset_hours(&self) -> f64 {
+        self.offset as f64 / 3600.0
+    }
+
+    /// Hours since local midnight, with the fraction
+    pub fn hour_frac(&self) -> f64 {
+        self.hour as f64 + self.minute as f64 / 60.0 + self.second as f64 / 3600.0
+    }
+
+    /// Epoch time of this day's local midnight. If midnight falls in a DST
+    /// gap, the first instant after it (as mktime does).
+    pub fn midnight(&self, tz: &(impl TimeZone + ?Sized)) -> i64 {
+        let local = days_from_civil(self.year, self.month, self.day) * SECONDS_PER_DAY;
+        // The offsets a day either side cover any change at midnight
+        let before = tz.utc_offset(local - SECONDS_PER_DAY);
+        let after = tz.utc_offset(local + SECONDS_PER_DAY);
+        [before, after]
+            .into_iter()
+            .map(|offset| local - offset)
+            .find(|&t| t + tz.utc_offset(t) == local)
+            .unwrap_or(local - before)
+    }
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date (Howard Hinnant's
+/// days_from_civil)
+pub fn days_from_civil(year: i32, month: i32, day: i32) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year } as i64;
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// (year, month, day) of a day count since 1970-01-01
+pub fn civil_from_days(days: i64) -> (i32, i32, i32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as i32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as i32;
+    let year = (yoe + era * 400 + (month <= 2) as i64) as i32;
+    (year, month, day)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    // 2025 US DST changes: 2025-03-09 07:00 UTC and 2025-11-02 06:00 UTC
+    pub const SPRING_FORWARD: i64 = 1741503600;
+    pub const FALL_BACK: i64 = 1762063200;
+
+    /// America/New_York through 2025
+    pub struct NewYork2025;
+
+    impl TimeZone for NewYork2025 {
+        fn utc_offset(&self, utc: i64) -> i64 {
+            if (SPRING_FORWARD..FALL_BACK).contains(&utc) {
+                -4 * 3600
+            } else {
+                -5 * 3600
+            }
+        }
+    }
+
+    /// Chile's 2025 spring change: 00:00 local on 2025-09-07 (04:00 UTC)
+    /// skips to 01:00
+    struct Santiago2025;
+
+    const SANTIAGO_CHANGE: i64 = 1757217600;
+
+    impl TimeZone for Santiago2025 {
+        fn utc_offset(&self, utc: i64) -> i64 {
+            if utc < SANTIAGO_CHANGE {
+                -4 * 3600
+            } else {
+                -3 * 3600
+            }
+        }
+    }
+
+    #[test]
+    fn civil_dates_round_trip() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(days_from_civil(2000, 3, 1), 11017);
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        for days in (-800_000..800_000).step_by(997) {
+            let (y, m, d) = civil_from_days(days);
+            assert_eq!(days_from_civil(y, m, d), days, "{}-{}-{}", y, m, d);
+        }
+    }
+
+    #[test]
+    fn local_time_and_midnight() {
+        // 2024-02-29 23:30 UTC is 2024-03-01 05:00 in India
+        let india = FixedOffset::hours(5.5);
+        let t = LocalTime::at(1709249400, &india);
+        assert_eq!((t.year, t.month, t.day, t.hour, t.minute, t.second), (2024, 3, 1, 5, 0, 0));
+        assert_eq!(t.midnight(&india), 1709249400 - 5 * 3600);
+
+        // Both changes happen at 02:00, so that day's midnight is on the old
+        // offset; the day itself is an hour short or long
+        let day = 86400;
+        for (change, hour, length) in [(SPRING_FORWARD, 9, day - 3600), (FALL_BACK, 7, day + 3600)] {
+            let t = LocalTime::at(change + 6 * 3600, &NewYork2025);
+            assert_eq!(t.hour, hour);
+            assert_eq!(t.midnight(&NewYork2025), change - 2 * 3600);
+            let next = LocalTime::at(change + day, &NewYork2025);
+            assert_eq!(next.midnight(&NewYork2025) - t.midnight(&NewYork2025), length);
+        }
+
+        // No midnight on the day Chile springs forward: 01:00 instead
+        let t = LocalTime::at(SANTIAGO_CHANGE + 12 * 3600, &Santiago2025);
+        assert_eq!((t.day, t.hour), (7, 13));
+        assert_eq!(t.midnight(&Santiago2025), SANTIAGO_CHANGE);
+        let before = LocalTime::at(SANTIAGO_CHANGE - 12 * 3600, &Santiago2025);
+        assert_eq!(before.midnight(&Santiago2025), SANTIAGO_CHANGE - 86400);
+    }
+}
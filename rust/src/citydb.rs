@@ -0,0 +1,400 @@
+//! City name database lookup.
+//!
+//! mmap'd binary search on cities.bin (see mmapdb.rs), keyed by the city's
+//! ASCII name in lowercase. Records vary in length, so an offset table
+//! sits between the header and the records:
+//!   u32 count, count x u32 record offset (from the start of the file)
+//!   record: u8 len + key, u8 len + name, u8 len + admin1 code,
+//!           u8 len + admin1 name, 2 bytes country code,
+//!           f32 lat, f32 lon, u32 population
+//! Records are sorted by key, then by population (largest first).
+//!
+//! Built from a GeoNames cities extract by `--build-citydb`. The cities.bin
+//! shipped with ABRAXAS is built the same way from the tz database's
+//! zone.tab (public domain): the few hundred cities time zones are named
+//! after, with no regions or populations.
+
+use crate::mmapdb::{self, MappedFile, HEADER_SIZE};
+use std::path::Path;
+
+/// GeoNames extract of every city over 15,000 people, and the admin1
+/// (state/province) names its codes refer to
+pub const GEONAMES_CITIES_URL: &str = "https://download.geonames.org/export/dump/cities15000.zip";
+pub const GEONAMES_ADMIN1_URL: &str = "https://download.geonames.org/export/dump/admin1CodesASCII.txt";
+
+/// --build-citydb keeps cities at least this big by default, a few thousand
+/// worldwide
+pub const DEFAULT_MIN_POPULATION: u32 = 100_000;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct City {
+    /// Lookup key: GeoNames' ASCII name in lowercase
+    pub key: String,
+    pub name: String,
+    /// Admin1 code: the state for the US ("OR"), often numeric elsewhere
+    pub admin_code: String,
+    pub admin_name: String,
+    /// ISO 3166 alpha-2
+    pub country: String,
+    pub lat: f32,
+    pub lon: f32,
+    pub population: u32,
+}
+
+impl City {
+    /// "Portland, Oregon, US"
+    pub fn label(&self) -> String {
+        if self.admin_name.is_empty() {
+            format!("{}, {}", self.name, self.country)
+        } else {
+            format!("{}, {}, {}", self.name, self.admin_name, self.country)
+        }
+    }
+
+    /// Whether a "City, QUALIFIER" part names this city's region or country.
+    fn matches(&self, qualifier: &str) -> bool {
+        let q = normalize(qualifier);
+        // Common name for GB that isn't its ISO code
+        let q = if q == "uk" { "gb".to_string() } else { q };
+        [&self.admin_code, &self.admin_name, &self.country]
+            .iter()
+            .any(|field| !field.is_empty() && normalize(field) == q)
+    }
+
+    /// Shortest "Name, QUALIFIER" that picks this city out of `others`.
+    pub fn disambiguation(&self, others: &[City]) -> String {
+        let alpha_code = self.admin_code.chars().all(|c| c.is_ascii_alphabetic());
+        let candidates = [
+            alpha_code.then_some(&self.admin_code),
+            Some(&self.admin_name),
+            Some(&self.country),
+        ];
+        for q in candidates.into_iter().flatten().filter(|q| !q.is_empty()) {
+            if others.iter().filter(|c| c.matches(q)).count() == 1 {
+                return format!("{}, {}", self.name, q);
+            }
+        }
+        format!("{}, {}, {}", self.name, self.admin_name, self.country)
+    }
+}
+
+/// A name as a lookup key: lowercase, with common Latin accents dropped
+/// ("Zürich" -> "zurich") to meet GeoNames' ASCII names.
+pub fn normalize(name: &str) -> String {
+    name.trim()
+        .chars()
+        .map(|c| match c {
+            'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'À' | 'Á' | 'Â' | 'Ã' | 'Ä' | 'Å' => 'a',
+            'ç' | 'Ç' => 'c',
+            'è' | 'é' | 'ê' | 'ë' | 'È' | 'É' | 'Ê' | 'Ë' => 'e',
+            'ì' | 'í' | 'î' | 'ï' | 'Ì' | 'Í' | 'Î' | 'Ï' => 'i',
+            'ñ' | 'Ñ' => 'n',
+            'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'Ò' | 'Ó' | 'Ô' | 'Õ' | 'Ö' | 'Ø' => 'o',
+            'ù' | 'ú' | 'û' | 'ü' | 'Ù' | 'Ú' | 'Û' | 'Ü' => 'u',
+            'ý' | 'ÿ' | 'Ý' => 'y',
+            _ => c.to_ascii_lowercase(),
+        })
+        .collect()
+}
+
+/// Parse a GeoNames cities file (tab-separated, no header) plus, optionally,
+/// admin1CodesASCII.txt for region names. Keeps populated places of at
+/// least `min_population`; malformed rows are skipped. Returned in file
+/// order.
+pub fn parse_geonames(cities: &str, admin1: Option<&str>, min_population: u32) -> Result<Vec<City>, String> {
+    // "US.OR" -> "Oregon"
+    let admin_names: std::collections::HashMap<&str, &str> = admin1
+        .unwrap_or("")
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            Some((fields.next()?, fields.next()?))
+        })
+        .collect();
+
+    let mut entries = Vec::new();
+    for line in cities.lines() {
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 15 || fields[6] != "P" {
+            continue;
+        }
+        let (name, ascii, country, admin_code) = (fields[1], fields[2], fields[8], fields[10]);
+        let (lat, lon, population) = match (
+            fields[4].parse::<f32>(),
+            fields[5].parse::<f32>(),
+            fields[14].parse::<u32>(),
+        ) {
+            (Ok(la), Ok(lo), Ok(p)) => (la, lo, p),
+            _ => continue,
+        };
+        if population < min_population || ascii.is_empty() || country.len() != 2 {
+            continue;
+        }
+        let admin_name = admin_names
+            .get(format!("{}.{}", country, admin_code).as_str())
+            .copied()
+            .unwrap_or("");
+        // Each string is stored behind a one-byte length
+        if [ascii, name, admin_code, admin_name].iter().any(|f| f.len() > 255) {
+            continue;
+        }
+        entries.push(City {
+            key: ascii.to_ascii_lowercase(),
+            name: name.to_string(),
+            admin_code: admin_code.to_string(),
+            admin_name: admin_name.to_string(),
+            country: country.to_string(),
+            lat,
+            lon,
+            population,
+        });
+    }
+
+    if entries.is_empty() {
+        return Err(format!("no cities of {}+ people in the GeoNames file", min_population));
+    }
+    Ok(entries)
+}
+
+/// Whether `text` is a tz database zone.tab ("CC<TAB>+DDMM+DDDMM<TAB>Area/City")
+/// rather than a GeoNames file.
+pub fn is_zone_tab(text: &str) -> bool {
+    let first = text.lines().find(|l| !l.trim().is_empty() && !l.starts_with('#'));
+    first.is_some_and(|line| {
+        let fields: Vec<&str> = line.split('\t').collect();
+        fields.len() >= 3 && parse_iso6709(fields[1]).is_some()
+    })
+}
+
+/// Parse a zone.tab: one city per zone, named by the zone's last component
+/// ("America/Argentina/Buenos_Aires" -> "Buenos Aires"). Comments and
+/// malformed rows are skipped.
+pub fn parse_zone_tab(text: &str) -> Result<Vec<City>, String> {
+    let mut entries = Vec::new();
+    for line in text.lines().filter(|l| !l.starts_with('#')) {
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 3 || fields[0].len() != 2 {
+            continue;
+        }
+        let (lat, lon) = match parse_iso6709(fields[1]) {
+            Some(c) => c,
+            None => continue,
+        };
+        let name = fields[2].rsplit('/').next().unwrap_or("").replace('_', " ");
+        if name.is_empty() || name.len() > 255 {
+            continue;
+        }
+        entries.push(City {
+            key: normalize(&name),
+            name,
+            admin_code: String::new(),
+            admin_name: String::new(),
+            country: fields[0].to_string(),
+            lat,
+            lon,
+            population: 0,
+        });
+    }
+
+    if entries.is_empty() {
+        return Err("no zones in the zone.tab file".to_string());
+    }
+    Ok(entries)
+}
+
+/// zone.tab coordinates: ISO 6709 "+DDMM+DDDMM" or "+DDMMSS+DDDMMSS"
+fn parse_iso6709(s: &str) -> Option<(f32, f32)> {
+    if !s.is_ascii() || s.is_empty() {
+        return None;
+    }
+    let split = s[1..].find(['+', '-'])? + 1;
+    let (lat, lon) = s.split_at(split);
+    Some((sexagesimal(lat, 2)?, sexagesimal(lon, 3)?))
+}
+
+/// "+DDMM[SS]" with `degree_digits` of degrees, to decimal degrees
+fn sexagesimal(s: &str, degree_digits: usize) -> Option<f32> {
+    let sign = match s.as_bytes().first()? {
+        b'+' => 1.0,
+        b'-' => -1.0,
+        _ => return None,
+    };
+    let digits = &s[1..];
+    let len = digits.len();
+    if !digits.bytes().all(|b| b.is_ascii_digit()) || (len != degree_digits + 2 && len != degree_digits + 4) {
+        return None;
+    }
+    let part = |range: std::ops::Range<usize>| digits[range].parse::<f64>().unwrap_or(0.0);
+    let degrees = part(0..degree_digits);
+    let minutes = part(degree_digits..degree_digits + 2);
+    let seconds = part(degree_digits + 2..len);
+    Some((sign * (degrees + minutes / 60.0 + seconds / 3600.0)) as f32)
+}
+
+/// Serialize cities in the on-disk format read by `lookup` (sorting them).
+pub fn encode(cities: &[City]) -> Vec<u8> {
+    let mut sorted: Vec<&City> = cities.iter().collect();
+    sorted.sort_by(|a, b| a.key.cmp(&b.key).then(b.population.cmp(&a.population)));
+
+    let mut records = Vec::new();
+    let mut offsets = Vec::with_capacity(sorted.len());
+    let records_start = HEADER_SIZE + sorted.len() * 4;
+    for c in &sorted {
+        offsets.push((records_start + records.len()) as u32);
+        for s in [&c.key, &c.name, &c.admin_code, &c.admin_name] {
+            records.push(s.len() as u8);
+            records.extend_from_slice(s.as_bytes());
+        }
+        records.extend_from_slice(&c.country.as_bytes()[..2]);
+        records.extend_from_slice(&c.lat.to_le_bytes());
+        records.extend_from_slice(&c.lon.to_le_bytes());
+        records.extend_from_slice(&c.population.to_le_bytes());
+    }
+
+    let mut out = Vec::with_capacity(records_start + records.len());
+    out.extend_from_slice(&(sorted.len() as u32).to_le_bytes());
+    for offset in offsets {
+        out.extend_from_slice(&offset.to_le_bytes());
+    }
+    out.extend_from_slice(&records);
+    out
+}
+
+/// Length-prefixed string at `*pos`, advancing past it.
+fn read_str<'a>(data: &'a [u8], pos: &mut usize) -> Option<&'a str> {
+    let len = *data.get(*pos)? as usize;
+    let s = std::str::from_utf8(data.get(*pos + 1..*pos + 1 + len)?).ok()?;
+    *pos += 1 + len;
+    Some(s)
+}
+
+/// Key of record `i` (None for a corrupt offset).
+fn key_at(data: &[u8], i: usize) -> Option<&str> {
+    let mut pos = mmapdb::read_u32(data, HEADER_SIZE + i * 4)? as usize;
+    read_str(data, &mut pos)
+}
+
+fn city_at(data: &[u8], i: usize) -> Option<City> {
+    let mut pos = mmapdb::read_u32(data, HEADER_SIZE + i * 4)? as usize;
+    let key = read_str(data, &mut pos)?.to_string();
+    let name = read_str(data, &mut pos)?.to_string();
+    let admin_code = read_str(data, &mut pos)?.to_string();
+    let admin_name = read_str(data, &mut pos)?.to_string();
+    let country = std::str::from_utf8(data.get(pos..pos + 2)?).ok()?.to_string();
+    Some(City {
+        key,
+        name,
+        admin_code,
+        admin_name,
+        country,
+        lat: mmapdb::read_f32(data, pos + 2)?,
+        lon: mmapdb::read_f32(data, pos + 6)?,
+        population: mmapdb::read_u32(data, pos + 10)?,
+    })
+}
+
+/// Cities matching "Name" or "Name, Region[, Country]", largest first.
+/// Empty if there is none (or no database).
+/// Whether `db_path` holds a city database lookup can read. None ships
+/// with ABRAXAS; --download-citydb or --build-citydb writes it.
+pub fn is_available(db_path: &Path) -> bool {
+    MappedFile::open(db_path).is_some_and(|d| d.count().is_some())
+}
+
+pub fn lookup(db_path: &Path, query: &str) -> Vec<City> {
+    let mut parts = query.split(',').map(str::trim);
+    let key = normalize(parts.next().unwrap_or(""));
+    let qualifiers: Vec<&str> = parts.filter(|q| !q.is_empty()).collect();
+
+    let data = match MappedFile::open(db_path) {
+        Some(d) => d,
+        None => return Vec::new(),
+    };
+    let count = match data.count() {
+        Some(n) => n.min((data.len() - HEADER_SIZE) / 4),
+        None => return Vec::new(),
+    };
+
+    // A corrupt record never counts as before the key: at worst a miss
+    let first = mmapdb::partition_point(count, |i| key_at(&data, i).is_some_and(|k| k < key.as_str()));
+    (first..count)
+        .take_while(|&i| key_at(&data, i) == Some(key.as_str()))
+        .filter_map(|i| city_at(&data, i))
+        .filter(|c| qualifiers.iter().all(|q| c.matches(q)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(name: &str, ascii: &str, class: &str, country: &str, admin: &str, pop: u32) -> String {
+        format!(
+            "1\t{}\t{}\t\t45.5\t-122.6\t{}\tPPL\t{}\t\t{}\t\t\t\t{}\t\t30\tAmerica/Los_Angeles\t2024-01-01",
+            name, ascii, class, country, admin, pop
+        )
+    }
+
+    #[test]
+    fn geonames_round_trip_and_disambiguation() {
+        let cities = [
+            row("Portland", "Portland", "P", "US", "OR", 652_503),
+            row("Portland", "Portland", "P", "US", "ME", 68_408),
+            row("Portland", "Portland", "P", "AU", "07", 150_000),
+            row("Zürich", "Zurich", "P", "CH", "25", 341_730),
+            row("Tiny", "Tiny", "P", "US", "OR", 10),
+            row("Portland Bill", "Portland Bill", "T", "GB", "ENG", 500_000),
+            "not\ta\tcity".to_string(),
+        ]
+        .join("\n");
+        let admin1 = "US.OR\tOregon\tOregon\t5744337\nUS.ME\tMaine\tMaine\t4971068\nAU.07\tVictoria\tVictoria\t2145234\n";
+        let parsed = parse_geonames(&cities, Some(admin1), 50_000).unwrap();
+        assert_eq!(parsed.len(), 4);
+        assert_eq!(parsed[0].label(), "Portland, Oregon, US");
+        assert_eq!(parsed[3].label(), "Zürich, CH");
+
+        let path = std::env::temp_dir().join(format!("abraxas-citydb-{}.bin", std::process::id()));
+        std::fs::write(&path, encode(&parsed)).unwrap();
+        assert!(is_available(&path));
+
+        let all = lookup(&path, "portland");
+        assert_eq!(all.iter().map(|c| c.population).collect::<Vec<_>>(), [652_503, 150_000, 68_408]);
+        assert_eq!(all[0].disambiguation(&all), "Portland, OR");
+        assert_eq!(all[1].disambiguation(&all), "Portland, Victoria");
+        assert_eq!(all[2].disambiguation(&all), "Portland, ME");
+
+        assert_eq!(lookup(&path, "Portland, ME")[0].lat, 45.5);
+        assert_eq!(lookup(&path, " PORTLAND , oregon, us ").len(), 1);
+        assert_eq!(lookup(&path, "Portland, AU").len(), 1);
+        assert!(lookup(&path, "Portland, UK").is_empty());
+        assert_eq!(lookup(&path, "Zürich").len(), 1);
+        assert_eq!(lookup(&path, "zurich, ch")[0].name, "Zürich");
+        assert!(lookup(&path, "Tiny").is_empty());
+        assert!(lookup(&path, "Aaa").is_empty());
+        assert!(lookup(&path, "Zzz").is_empty());
+        let _ = std::fs::remove_file(&path);
+
+        assert!(lookup(&path, "Portland").is_empty()); // no database
+        assert!(!is_available(&path));
+        assert!(parse_geonames("", None, 0).is_err());
+    }
+
+    #[test]
+    fn zone_tab_cities_parsed() {
+        let tab = "# comment\n\
+            DE\t+5230+01322\tEurope/Berlin\tmost of Germany\n\
+            AR\t-3436-05827\tAmerica/Argentina/Buenos_Aires\tBuenos Aires (BA, CF)\n\
+            US\t+404251-0740023\tAmerica/New_York\tEastern (most areas)\n\
+            XX\tnonsense\tEtc/Bad\n";
+        assert!(is_zone_tab(tab));
+        assert!(!is_zone_tab(&row("Portland", "Portland", "P", "US", "OR", 652_503)));
+
+        let cities = parse_zone_tab(tab).unwrap();
+        assert_eq!(cities.len(), 3);
+        assert_eq!(cities[0].label(), "Berlin, DE");
+        assert_eq!((cities[0].lat, cities[0].lon), (52.5, 13.366667));
+        assert_eq!((cities[1].key.as_str(), cities[1].lat, cities[1].lon), ("buenos aires", -34.6, -58.45));
+        assert!((cities[2].lat - 40.714165).abs() < 1e-4 && (cities[2].lon + 74.00639).abs() < 1e-4);
+        assert!(parse_zone_tab("# only comments\n").is_err());
+    }
+}
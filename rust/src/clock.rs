@@ -0,0 +1,76 @@
+//! Where the daemon gets the time from.
+//!
+//! The tick logic asks a `Clock` for the current time and the local zone
+//! instead of calling time(2) and localtime_r itself, so tests can run it
+//! through a whole day on a `FakeClock` in milliseconds.
+
+pub use abraxas_core::time::LocalTime;
+use abraxas_core::time::TimeZone;
+
+/// Current time plus the zone it is read in
+pub trait Clock: TimeZone {
+    /// Unix epoch seconds
+    fn now(&self) -> i64;
+
+    /// Wall clock at `epoch`
+    fn local(&self, epoch: i64) -> LocalTime {
+        LocalTime::at(epoch, self)
+    }
+
+    /// Hours east of UTC at `epoch` (DST-aware)
+    fn tz_offset_hours(&self, epoch: i64) -> f64 {
+        self.utc_offset(epoch) as f64 / 3600.0
+    }
+}
+
+/// The system clock in the process time zone (TZ or /etc/localtime)
+pub struct SystemClock;
+
+impl TimeZone for SystemClock {
+    fn utc_offset(&self, utc: i64) -> i64 {
+        let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+        unsafe { libc::localtime_r(&utc, &mut tm) };
+        tm.tm_gmtoff
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> i64 {
+        crate::now_epoch()
+    }
+}
+
+/// A clock that only moves when told to, in a fixed-offset zone. Clones
+/// share the time, so a test can keep one and hand the other to the daemon.
+#[cfg(test)]
+#[derive(Clone)]
+pub struct FakeClock {
+    now: std::rc::Rc<std::cell::Cell<i64>>,
+    offset: i64,
+}
+
+#[cfg(test)]
+impl FakeClock {
+    /// Stopped at `now`, `offset` seconds east of UTC
+    pub fn new(now: i64, offset: i64) -> Self {
+        FakeClock { now: std::rc::Rc::new(std::cell::Cell::new(now)), offset }
+    }
+
+    pub fn advance(&self, secs: i64) {
+        self.now.set(self.now.get() + secs);
+    }
+}
+
+#[cfg(test)]
+impl TimeZone for FakeClock {
+    fn utc_offset(&self, _utc: i64) -> i64 {
+        self.offset
+    }
+}
+
+#[cfg(test)]
+impl Clock for FakeClock {
+    fn now(&self) -> i64 {
+        self.now.get()
+    }
+}
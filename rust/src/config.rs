@@ -1,52 +1,579 @@
 //! Configuration, override state, and path resolution.
 //!
-//! INI parser for [location] section. JSON override and weather cache via serde.
+//! INI parser for [location] and tunable settings sections. JSON override and
+//! weather cache via serde.
 
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::io;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use crate::{WEATHER_REFRESH_SEC, now_epoch};
+use crate::{
+    CLOUD_THRESHOLD, DAWN_DURATION, ELEVATION_HIGH, ELEVATION_LOW, DAWN_OFFSET, DUSK_DURATION, DUSK_OFFSET, DUSK_OFFSET_RANGE,
+    SCHEDULE_BLEND_MIN, SIGMOID_STEEPNESS, STEEPNESS_RANGE, TEMP_CHANGE_THRESHOLD, TEMP_UPDATE_SEC,
+    TRANSITION_TICK_SEC, TEMP_DAY_CLEAR, TEMP_DAY_DARK, TEMP_MAX,
+    TEMP_MIN, TEMP_NIGHT, CONFIG_VERSION, WEATHER_REFRESH_SEC, WEATHER_REFRESH_MAX_SEC, WEATHER_RETRY_SEC, WEATHER_TIMEOUT_SEC, CONNECT_TIMEOUT_SEC, NETWORK_TIMEOUT_RANGE, HOOK_CHANGE_DELTA, HOOK_MIN_INTERVAL_SEC, RAMP_QUANTIZE_RANGE, RAMP_QUANTIZE_STEP, LAT_RANGE, LON_RANGE, OVERRIDE_CLOCK_SKEW_SEC, OVERRIDE_MAX_BYTES, OVERRIDE_MAX_DURATION_SEC,
+    WHITE_POINT_RANGE, now_epoch, zipdb,
+};
+use crate::gamma::colorramp::{D65_X, D65_Y};
+pub use abraxas_core::model::{Easing, Keyframe, Schedule, TempParams, TransitionParams};
+
+/// Where Paths came from
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PathMode {
+    /// ~/.config and ~/.cache (or their XDG overrides)
+    User,
+    /// /etc/abraxas (read-only) and /var/lib/abraxas, for a system service
+    System,
+    /// One directory from --config-dir / ABRAXAS_CONFIG_DIR
+    Custom,
+}
+
+impl PathMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            PathMode::User => "user",
+            PathMode::System => "system",
+            PathMode::Custom => "custom",
+        }
+    }
+}
 
 /// Resolved filesystem paths
 #[derive(Clone)]
 pub struct Paths {
+    pub mode: PathMode,
+    /// Holds config.ini (read-only in system mode)
+    pub config_dir: PathBuf,
+    /// Daemon state (override.json, daemon.pid, ...); config_dir except in system mode
+    pub state_dir: PathBuf,
+    /// Re-creatable downloads (weather cache, ZIP and city databases)
+    pub cache_dir: PathBuf,
     pub config_file: PathBuf,
     pub cache_file: PathBuf,
     pub override_file: PathBuf,
     pub zipdb_file: PathBuf,
+    pub citydb_file: PathBuf,
     pub pid_file: PathBuf,
+    pub state_file: PathBuf,
+    pub active_profile_file: PathBuf,
+    /// Held exclusively while the daemon loads its startup state; in
+    /// state_dir since the system config dir is read-only
+    pub lock_file: PathBuf,
 }
 
+const CACHE_FILE: &str = "weather_cache.json";
+const ZIPDB_FILE: &str = "us_zipcodes.bin";
+const CITYDB_FILE: &str = "cities.bin";
+const SYSTEM_CONFIG_DIR: &str = "/etc/abraxas";
+const SYSTEM_STATE_DIR: &str = "/var/lib/abraxas";
+
 impl Paths {
     pub fn init() -> Result<Self, io::Error> {
-        let home = std::env::var("HOME").map_err(|_| {
-            io::Error::new(io::ErrorKind::NotFound, "HOME not set")
-        })?;
-
-        let config_dir = PathBuf::from(&home).join(".config").join("abraxas");
-        fs::create_dir_all(&config_dir)?;
+        let uid = unsafe { libc::getuid() };
+        let paths = resolve_paths(|name| std::env::var(name).ok(), uid, passwd_home)?;
+        // The system config dir belongs to root's package/admin, not to us
+        if paths.mode != PathMode::System {
+            fs::create_dir_all(&paths.config_dir)?;
+        }
+        fs::create_dir_all(&paths.state_dir)?;
+        fs::create_dir_all(&paths.cache_dir)?;
+        if paths.mode == PathMode::User {
+            migrate_cache(&paths.config_dir, &paths.cache_dir);
+        }
+        if paths.mode != PathMode::System {
+            migrate_config(&paths);
+        }
+        Ok(paths)
+    }
 
-        Ok(Self {
+    pub(crate) fn in_dirs(mode: PathMode, config_dir: PathBuf, state_dir: PathBuf, cache_dir: PathBuf) -> Self {
+        Self {
+            mode,
             config_file: config_dir.join("config.ini"),
-            cache_file: config_dir.join("weather_cache.json"),
-            override_file: config_dir.join("override.json"),
-            zipdb_file: config_dir.join("us_zipcodes.bin"),
-            pid_file: config_dir.join("daemon.pid"),
-        })
+            cache_file: cache_dir.join(CACHE_FILE),
+            override_file: state_dir.join("override.json"),
+            zipdb_file: cache_dir.join(ZIPDB_FILE),
+            citydb_file: cache_dir.join(CITYDB_FILE),
+            pid_file: state_dir.join("daemon.pid"),
+            state_file: state_dir.join("state.json"),
+            active_profile_file: state_dir.join("active_profile.json"),
+            lock_file: state_dir.join(".lock"),
+            config_dir,
+            state_dir,
+            cache_dir,
+        }
+    }
+}
+
+/// Resolve the directories, from (highest first):
+///   ABRAXAS_CONFIG_DIR (also set by --config-dir): everything in that directory
+///   ABRAXAS_SYSTEM (set by --system), or root without HOME (a systemd
+///     system service): /etc/abraxas/config.ini, state and cache in /var/lib/abraxas
+///   $XDG_CONFIG_HOME/abraxas and $XDG_CACHE_HOME/abraxas
+///   ~/.config/abraxas and ~/.cache/abraxas, with ~ from HOME or else
+///     `passwd_home(uid)` (minimal environments such as some user units)
+/// Relative XDG values are ignored, as the XDG spec requires.
+fn resolve_paths(
+    env: impl Fn(&str) -> Option<String>,
+    uid: u32,
+    passwd_home: impl Fn(u32) -> Option<PathBuf>,
+) -> Result<Paths, io::Error> {
+    let set = |name: &str| env(name).filter(|v| !v.is_empty());
+
+    if let Some(dir) = set("ABRAXAS_CONFIG_DIR") {
+        let dir = std::path::absolute(dir)?;
+        return Ok(Paths::in_dirs(PathMode::Custom, dir.clone(), dir.clone(), dir));
+    }
+
+    let home = set("HOME").map(PathBuf::from);
+    if set("ABRAXAS_SYSTEM").is_some() || (home.is_none() && uid == 0) {
+        let state = PathBuf::from(SYSTEM_STATE_DIR);
+        return Ok(Paths::in_dirs(PathMode::System, PathBuf::from(SYSTEM_CONFIG_DIR), state.clone(), state));
+    }
+    let home = match home.or_else(|| passwd_home(uid)) {
+        Some(h) => h,
+        None => {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("HOME not set and no passwd entry for uid {}", uid),
+            ))
+        }
+    };
+
+    let base = |var: &str, fallback: &str| match set(var).map(PathBuf::from) {
+        Some(p) if p.is_absolute() => p.join("abraxas"),
+        _ => home.join(fallback).join("abraxas"),
+    };
+    let config_dir = base("XDG_CONFIG_HOME", ".config");
+    Ok(Paths::in_dirs(PathMode::User, config_dir.clone(), config_dir, base("XDG_CACHE_HOME", ".cache")))
+}
+
+/// Home directory of `uid` from the passwd database (getpwuid_r), or None
+/// if there is no entry or it has no absolute home.
+fn passwd_home(uid: u32) -> Option<PathBuf> {
+    use std::ffi::{CStr, OsStr};
+    use std::os::unix::ffi::OsStrExt;
+
+    let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut result: *mut libc::passwd = std::ptr::null_mut();
+    // Holds the strings pwd points into; ERANGE (too small) just gives None
+    let mut buf = [0 as libc::c_char; 4096];
+    let rc = unsafe { libc::getpwuid_r(uid, &mut pwd, buf.as_mut_ptr(), buf.len(), &mut result) };
+    if rc != 0 || result.is_null() || pwd.pw_dir.is_null() {
+        return None;
+    }
+
+    let dir = unsafe { CStr::from_ptr(pwd.pw_dir) };
+    let dir = PathBuf::from(OsStr::from_bytes(dir.to_bytes()));
+    if dir.is_absolute() {
+        Some(dir)
+    } else {
+        None
+    }
+}
+
+/// One-time copy of the cache files from the config directory, where
+/// versions before the XDG split (and the C23 build) keep them. Copied,
+/// not moved, so those can still share the config directory. A legacy
+/// format ZIP database in the cache is then upgraded in place.
+fn migrate_cache(config_dir: &Path, cache_dir: &Path) {
+    if config_dir == cache_dir {
+        return;
+    }
+    for name in [CACHE_FILE, ZIPDB_FILE, CITYDB_FILE] {
+        let (old, new) = (config_dir.join(name), cache_dir.join(name));
+        if !old.exists() || new.exists() {
+            continue;
+        }
+        match fs::read(&old).and_then(|data| write_atomic(&new, data)) {
+            Ok(()) => eprintln!("[config] Copied {} to {}", name, cache_dir.display()),
+            Err(e) => eprintln!("[warn] Cannot copy {} to {}: {}", name, cache_dir.display(), e),
+        }
+    }
+
+    // The C23 build only reads the config directory's copy, so the cache's
+    // can move to the current ZIP database format
+    match zipdb::upgrade_file(&cache_dir.join(ZIPDB_FILE)) {
+        Ok(true) => eprintln!("[config] Upgraded {} to format v{}", ZIPDB_FILE, zipdb::FORMAT_VERSION),
+        Ok(false) => {}
+        Err(e) => eprintln!("[warn] Cannot upgrade {}: {}", ZIPDB_FILE, e),
+    }
+}
+
+/// config.ini layout version; a missing file counts as current (nothing
+/// to migrate).
+pub fn detect_config_version(paths: &Paths) -> u32 {
+    match fs::read_to_string(&paths.config_file) {
+        Ok(content) if !ini_sections(&content).contains(&"temperatures") => 1,
+        _ => CONFIG_VERSION,
+    }
+}
+
+/// Section names in INI content, in file order.
+fn ini_sections(content: &str) -> Vec<&str> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|l| l.starts_with('[') && l.ends_with(']'))
+        .map(|l| l[1..l.len() - 1].trim())
+        .collect()
+}
+
+/// Bring a v1 config.ini (only [location]) up to date, so the settings
+/// it has been running on defaults for are visible and editable. The
+/// original is kept as config.ini.bak. Best effort: failures only warn.
+fn migrate_config(paths: &Paths) {
+    let version = detect_config_version(paths);
+    if version >= CONFIG_VERSION {
+        return;
+    }
+    let content = match fs::read_to_string(&paths.config_file) {
+        Ok(c) => c,
+        Err(_) => return,
+    };
+
+    let backup = paths.config_dir.join("config.ini.bak");
+    if let Err(e) = write_atomic(&backup, &content) {
+        eprintln!("[warn] Cannot back up config.ini, not migrating it: {}", e);
+        return;
+    }
+    match write_atomic(&paths.config_file, append_new_sections(&content)) {
+        Ok(()) => eprintln!(
+            "[config] Migrated config from v{} to v{}, backup at {}",
+            version,
+            CONFIG_VERSION,
+            backup.display()
+        ),
+        Err(e) => eprintln!("[warn] Cannot migrate config.ini: {}", e),
     }
 }
 
+/// Append every tuning section missing from `content`, filled with the
+/// defaults and a comment per key.
+pub fn append_new_sections(content: &str) -> String {
+    let d = Settings::default();
+    let (t, tr) = (&d.temperatures, &d.transitions);
+    let sections = [
+        ("temperatures", vec![
+            ("day_clear", "Daytime temperature under clear skies in Kelvin", t.day_clear.to_string()),
+            ("day_dark", "Daytime temperature when overcast in Kelvin", t.day_dark.to_string()),
+            ("night", "Night temperature in Kelvin", t.night.to_string()),
+            ("cloud_threshold", "Cloud cover percent that switches daytime to day_dark", t.cloud_threshold.to_string()),
+        ]),
+        ("transitions", vec![
+            ("dawn_duration", "Dawn transition duration in minutes", tr.dawn_duration.to_string()),
+            ("dusk_duration", "Dusk transition duration in minutes", tr.dusk_duration.to_string()),
+            ("dawn_offset", "Dawn midpoint in minutes after sunrise", tr.dawn_offset_min.to_string()),
+            ("dusk_offset", "Dusk midpoint in minutes before sunset", tr.dusk_offset_min.to_string()),
+        ]),
+        ("display", vec![
+            ("brightness", "Brightness multiplier applied with every temperature, 0.1-1.0", d.display.brightness.to_string()),
+            ("monitor_white_x", "Monitor's native white point, CIE x (D65 = 0.3127)", d.display.monitor_white_x.to_string()),
+            ("monitor_white_y", "Monitor's native white point, CIE y (D65 = 0.3290)", d.display.monitor_white_y.to_string()),
+        ]),
+        ("network", vec![
+            ("weather_refresh_minutes", "Minutes between weather refreshes", (d.network.weather_refresh_sec / 60).to_string()),
+        ]),
+    ];
+
+    let present = ini_sections(content);
+    let mut out = content.trim_end().to_string();
+    let mut first = true;
+    for (section, keys) in &sections {
+        if present.contains(section) {
+            continue;
+        }
+        if !out.is_empty() {
+            out.push_str("\n\n");
+        }
+        if first {
+            out.push_str("# Defaults (abraxas --export-config lists every key)\n");
+            first = false;
+        }
+        out.push_str(&format!("[{}]", section));
+        for (key, comment, value) in keys {
+            out.push_str(&format!("\n# {}\n{} = {}", comment, key, value));
+        }
+    }
+    out.push('\n');
+    out
+}
+
 /// Geographic location
 pub struct Location {
     pub lat: f64,
     pub lon: f64,
 }
 
+/// Output settings ([display])
+#[derive(Clone, Copy)]
+pub struct DisplayParams {
+    pub brightness: f32,
+    /// The monitor's native white (CIE xy), adapted to D65 in the ramps
+    pub monitor_white_x: f64,
+    pub monitor_white_y: f64,
+    /// Kelvin within which a CRTC's ramp is not rewritten
+    pub ramp_quantize_step: i32,
+}
+
+impl DisplayParams {
+    pub fn white_point(&self) -> (f64, f64) {
+        (self.monitor_white_x, self.monitor_white_y)
+    }
+}
+
+/// Where weather comes from ([network] weather_provider)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WeatherProvider {
+    /// api.weather.gov (feature "noaa", US only)
+    Noaa,
+    /// api.met.no (feature "met-norway", worldwide)
+    MetNorway,
+    /// No fetches: always clear skies
+    Disabled,
+}
+
+impl WeatherProvider {
+    pub const ALL: [WeatherProvider; 3] = [WeatherProvider::Noaa, WeatherProvider::MetNorway, WeatherProvider::Disabled];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            WeatherProvider::Noaa => "noaa",
+            WeatherProvider::MetNorway => "met-norway",
+            WeatherProvider::Disabled => "disabled",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|p| p.name() == name)
+    }
+
+    /// Compiled into this build
+    pub fn available(self) -> bool {
+        match self {
+            WeatherProvider::Noaa => cfg!(feature = "noaa"),
+            WeatherProvider::MetNorway => cfg!(feature = "met-norway"),
+            WeatherProvider::Disabled => true,
+        }
+    }
+
+    /// "noaa, met-norway or disabled": the choices this build accepts
+    pub fn choices() -> String {
+        let names: Vec<&str> = Self::ALL.into_iter().filter(|p| p.available()).map(|p| p.name()).collect();
+        match names.split_last() {
+            Some((last, rest)) if !rest.is_empty() => format!("{} or {}", rest.join(", "), last),
+            _ => names.join(""),
+        }
+    }
+
+    /// API host, for user-facing messages
+    pub fn host(self) -> &'static str {
+        match self {
+            WeatherProvider::Noaa => "api.weather.gov",
+            WeatherProvider::MetNorway => "api.met.no",
+            WeatherProvider::Disabled => "the weather API",
+        }
+    }
+}
+
+impl Default for WeatherProvider {
+    /// MET Norway when compiled in (it covers the US too), then NOAA
+    fn default() -> Self {
+        if cfg!(feature = "met-norway") {
+            WeatherProvider::MetNorway
+        } else if cfg!(feature = "noaa") {
+            WeatherProvider::Noaa
+        } else {
+            WeatherProvider::Disabled
+        }
+    }
+}
+
+/// Weather fetch settings ([network])
+#[derive(Clone, Copy)]
+pub struct NetworkParams {
+    pub weather_provider: WeatherProvider,
+    pub weather_refresh_sec: i64,
+    /// curl --max-time for each weather request
+    pub weather_timeout_sec: u32,
+    /// curl --connect-timeout
+    pub connect_timeout_sec: u32,
+}
+
+/// Bounds accepted by --set ([temperature_limits])
+#[derive(Clone, Copy)]
+pub struct TempLimits {
+    pub min: i32,
+    pub max: i32,
+}
+
+/// How the automatic temperature follows the sun ([daemon] mode)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SolarMode {
+    /// Sigmoid windows around sunrise/sunset (default)
+    Time,
+    /// Directly from sun elevation; robust where twilight lasts hours
+    Elevation,
+}
+
+/// Daemon model settings ([daemon])
+#[derive(Clone, Copy)]
+pub struct DaemonParams {
+    pub mode: SolarMode,
+    /// Elevation (degrees) at or above which the day temperature applies
+    pub elevation_high: f64,
+    /// Elevation (degrees) at or below which the night temperature applies
+    pub elevation_low: f64,
+    /// Desktop notifications at dawn/dusk and when an override ends
+    pub notify: bool,
+}
+
+/// Named [schedule] window (NAME_start / NAME_end / NAME_temp): holds
+/// `temp_k` between two local times, over the solar curve or keyframes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScheduleEntry {
+    pub name: String,
+    /// Minutes after local midnight
+    pub start_hhmm: u32,
+    /// Minutes after local midnight (1440 = midnight); earlier than the
+    /// start wraps past midnight
+    pub end_hhmm: u32,
+    pub temp_k: i32,
+}
+
+impl ScheduleEntry {
+    /// Whether a local time of day (minutes) falls inside the window.
+    pub fn contains(&self, minute: u32) -> bool {
+        if self.start_hhmm <= self.end_hhmm {
+            (self.start_hhmm..self.end_hhmm).contains(&minute)
+        } else {
+            minute >= self.start_hhmm || minute < self.end_hhmm
+        }
+    }
+}
+
+/// Parse "HH:MM" into minutes since midnight.
+pub fn parse_hhmm(s: &str) -> Option<i32> {
+    let (h, m) = s.split_once(':')?;
+    let h: i32 = h.parse().ok()?;
+    let m: i32 = m.parse().ok()?;
+    if !(0..24).contains(&h) || !(0..60).contains(&m) {
+        return None;
+    }
+    Some(h * 60 + m)
+}
+
+/// All tunable settings from config.ini. Missing keys use the compiled-in defaults.
+#[derive(Clone)]
+pub struct Settings {
+    pub temperatures: TempParams,
+    pub transitions: TransitionParams,
+    pub display: DisplayParams,
+    pub network: NetworkParams,
+    pub limits: TempLimits,
+    pub daemon: DaemonParams,
+    /// [schedule] keyframes; None = follow the sun
+    pub schedule: Option<Schedule>,
+    /// Named [schedule] windows; the first containing now wins
+    pub schedule_windows: Vec<ScheduleEntry>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            temperatures: TempParams {
+                day_clear: TEMP_DAY_CLEAR,
+                day_dark: TEMP_DAY_DARK,
+                night: TEMP_NIGHT,
+                cloud_threshold: CLOUD_THRESHOLD,
+                golden_hour_temp: 0,
+                moon_brightness_reduction: false,
+            },
+            transitions: TransitionParams {
+                dawn_duration: DAWN_DURATION,
+                dusk_duration: DUSK_DURATION,
+                dawn_offset_min: DAWN_OFFSET,
+                dusk_offset_min: DUSK_OFFSET,
+                steepness_dawn: SIGMOID_STEEPNESS,
+                steepness_dusk: SIGMOID_STEEPNESS,
+                transition_tick_sec: TRANSITION_TICK_SEC,
+                temp_change_threshold: TEMP_CHANGE_THRESHOLD,
+            },
+            display: DisplayParams {
+                brightness: 1.0,
+                monitor_white_x: D65_X,
+                monitor_white_y: D65_Y,
+                ramp_quantize_step: RAMP_QUANTIZE_STEP,
+            },
+            network: NetworkParams {
+                weather_provider: WeatherProvider::default(),
+                weather_refresh_sec: WEATHER_REFRESH_SEC,
+                weather_timeout_sec: WEATHER_TIMEOUT_SEC,
+                connect_timeout_sec: CONNECT_TIMEOUT_SEC,
+            },
+            limits: TempLimits {
+                min: TEMP_MIN,
+                max: TEMP_MAX,
+            },
+            daemon: DaemonParams {
+                mode: SolarMode::Time,
+                elevation_high: ELEVATION_HIGH,
+                elevation_low: ELEVATION_LOW,
+                notify: false,
+            },
+            schedule: None,
+            schedule_windows: Vec::new(),
+        }
+    }
+}
+
+/// Thermal-aware dimming ([thermal], feature = "thermal")
+#[cfg(feature = "thermal")]
+#[derive(Clone)]
+pub struct ThermalParams {
+    pub enabled: bool,
+    pub cpu_temp_file: PathBuf,
+    pub temp_threshold_c: f64,
+    pub brightness_reduction: f32,
+}
+
+#[cfg(feature = "thermal")]
+impl Default for ThermalParams {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            cpu_temp_file: PathBuf::from("/sys/class/thermal/thermal_zone0/temp"),
+            temp_threshold_c: 75.0,
+            brightness_reduction: 0.2,
+        }
+    }
+}
+
+/// Scripts run by the daemon on changes ([hooks])
+#[derive(Clone, Default, PartialEq)]
+pub struct HookParams {
+    /// Run when the applied temperature moves more than change_delta
+    pub on_change: Option<PathBuf>,
+    /// Run when the mode changes (clear, dark, night, manual, paused)
+    pub on_mode_change: Option<PathBuf>,
+    pub change_delta: i32,
+    /// Fewest seconds between two runs of the same hook
+    pub min_interval_sec: i64,
+}
+
+impl HookParams {
+    /// The configured scripts
+    pub fn paths(&self) -> impl Iterator<Item = &PathBuf> {
+        self.on_change.iter().chain(self.on_mode_change.iter())
+    }
+}
+
 /// Cached weather data
 pub struct WeatherData {
     pub cloud_cover: i32,
+    /// Forecast cloud cover a few hours ahead, blended in across dusk
+    pub cloud_cover_3h: i32,
     pub forecast: String,
     pub temperature: f64,
     pub is_day: bool,
@@ -54,23 +581,134 @@ pub struct WeatherData {
     pub has_error: bool,
 }
 
-/// Manual override state
+/// override.json format version written by this build
+pub const OVERRIDE_SCHEMA_VERSION: u32 = 1;
+
+/// Files from before schema_version existed are version 1
+fn default_version() -> u32 {
+    1
+}
+
+/// Manual override state. Every field has a default, so files from older
+/// versions (missing fields) and newer ones (unknown fields) both load.
 #[derive(Serialize, Deserialize)]
+#[serde(default)]
 pub struct OverrideState {
+    /// Format version, for detecting incompatible future layouts
+    #[serde(default = "default_version")]
+    pub schema_version: u32,
     pub active: bool,
     pub target_temp: i32,
-    pub duration_minutes: i32,
+    /// Transition length in seconds
+    pub duration_secs: i64,
     pub issued_at: i64,
     pub start_temp: i32,
+    /// Gamma adjustment paused via --toggle (survives restarts)
+    pub paused: bool,
+    /// Minutes to hold after the transition completes (None = until next dawn/dusk)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hold_minutes: Option<i32>,
+    /// target_temp is a signed delta from the current temperature; the
+    /// daemon resolves it to an absolute value and clears this flag
+    pub is_relative: bool,
+    /// Brightness applied for the duration of the override (--brightness)
+    pub brightness: f32,
+    /// Curve of the transition to target_temp (--easing)
+    pub easing: Easing,
+    /// Epoch the override takes effect (--at); the transition starts then
+    /// rather than at issued_at. Pending until then.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub at: Option<i64>,
+    /// Keep the override through dawn/dusk until --resume (--hold)
+    pub hold: bool,
+    /// Bumped by each --set/--resume; identifies the command that wrote
+    /// the file so daemon write-backs never replace a newer one
+    pub sequence: u64,
 }
 
-/// Load location from INI config
-pub fn load_location(paths: &Paths) -> Option<Location> {
-    let content = fs::read_to_string(&paths.config_file).ok()?;
+/// An inactive, unpaused override at full brightness
+impl Default for OverrideState {
+    fn default() -> Self {
+        OverrideState {
+            schema_version: OVERRIDE_SCHEMA_VERSION,
+            active: false,
+            target_temp: 0,
+            duration_secs: 0,
+            issued_at: 0,
+            start_temp: 0,
+            paused: false,
+            hold_minutes: None,
+            is_relative: false,
+            brightness: 1.0,
+            easing: Easing::Sigmoid,
+            at: None,
+            hold: false,
+            sequence: 0,
+        }
+    }
+}
 
-    let mut lat: Option<f64> = None;
-    let mut lon: Option<f64> = None;
-    let mut in_location = false;
+impl OverrideState {
+    /// When the transition starts: the scheduled time, or when it was issued.
+    pub fn starts_at(&self) -> i64 {
+        self.at.unwrap_or(self.issued_at)
+    }
+
+    /// Active but scheduled for later than `now`.
+    pub fn is_pending(&self, now: i64) -> bool {
+        self.active && self.starts_at() > now
+    }
+
+    /// Repair out-of-range fields in place, logging each correction.
+    /// Err for values that can't be repaired (negative or over-24h
+    /// durations and holds).
+    pub fn validate(&mut self, now: i64) -> Result<(), String> {
+        if !(0..=OVERRIDE_MAX_DURATION_SEC).contains(&self.duration_secs) {
+            return Err(format!("duration_secs {} outside 0-{}", self.duration_secs, OVERRIDE_MAX_DURATION_SEC));
+        }
+        if let Some(hold) = self.hold_minutes {
+            if !(0..=OVERRIDE_MAX_DURATION_SEC as i32 / 60).contains(&hold) {
+                return Err(format!("hold_minutes {} outside 0-{}", hold, OVERRIDE_MAX_DURATION_SEC / 60));
+            }
+        }
+
+        let clamp_temp = |field: &str, temp: &mut i32, range: std::ops::RangeInclusive<i32>| {
+            if !range.contains(temp) {
+                let fixed = (*temp).clamp(*range.start(), *range.end());
+                eprintln!("[override] {} {} outside {}-{}K, using {}K", field, temp, range.start(), range.end(), fixed);
+                *temp = fixed;
+            }
+        };
+        if self.active {
+            if self.is_relative {
+                let span = TEMP_MAX - TEMP_MIN;
+                clamp_temp("target_temp", &mut self.target_temp, -span..=span);
+            } else {
+                clamp_temp("target_temp", &mut self.target_temp, TEMP_MIN..=TEMP_MAX);
+            }
+        }
+        // 0 = not yet filled in by the daemon
+        if self.start_temp != 0 {
+            clamp_temp("start_temp", &mut self.start_temp, TEMP_MIN..=TEMP_MAX);
+        }
+
+        if self.issued_at > now + OVERRIDE_CLOCK_SKEW_SEC {
+            eprintln!("[override] issued_at {} is {} s in the future, using now", self.issued_at, self.issued_at - now);
+            self.issued_at = now;
+        }
+
+        if !(self.brightness.is_finite() && (0.1..=1.0).contains(&self.brightness)) {
+            let fixed = if self.brightness.is_finite() { self.brightness.clamp(0.1, 1.0) } else { 1.0 };
+            eprintln!("[override] brightness {} outside 0.1-1.0, using {}", self.brightness, fixed);
+            self.brightness = fixed;
+        }
+        Ok(())
+    }
+}
+
+/// Walk INI content, calling `f(section, key, value)` for each key = value line.
+fn for_each_ini_entry(content: &str, mut f: impl FnMut(&str, &str, &str)) {
+    let mut section = "";
 
     for line in content.lines() {
         let trimmed = line.trim();
@@ -78,52 +716,919 @@ pub fn load_location(paths: &Paths) -> Option<Location> {
             continue;
         }
 
-        if trimmed.starts_with('[') {
-            in_location = trimmed == "[location]";
-            continue;
+        if trimmed.starts_with('[') && trimmed.ends_with(']') {
+            section = trimmed[1..trimmed.len() - 1].trim();
+            continue;
+        }
+
+        if let Some((key, value)) = trimmed.split_once('=') {
+            f(section, key.trim(), value.trim());
+        }
+    }
+}
+
+/// Rewrite `section` in INI content with the given keys, preserving every
+/// other line. Existing keys are replaced in place, missing keys are appended
+/// to the section, and a missing section is appended to the file.
+fn update_ini_section(content: &str, section: &str, values: &[(&str, String)]) -> String {
+    let header = format!("[{}]", section);
+    let mut out: Vec<String> = Vec::new();
+    let mut written = vec![false; values.len()];
+    let mut in_section = false;
+    let mut found = false;
+
+    let flush_missing = |out: &mut Vec<String>, written: &mut Vec<bool>| {
+        // Insert before trailing blank lines of the section
+        let mut insert_at = out.len();
+        while insert_at > 0 && out[insert_at - 1].trim().is_empty() {
+            insert_at -= 1;
+        }
+        for (i, (key, value)) in values.iter().enumerate() {
+            if !written[i] {
+                out.insert(insert_at, format!("{} = {}", key, value));
+                insert_at += 1;
+                written[i] = true;
+            }
+        }
+    };
+
+    for line in content.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') && trimmed.ends_with(']') {
+            if in_section {
+                flush_missing(&mut out, &mut written);
+            }
+            in_section = same_section(&trimmed[1..trimmed.len() - 1], section);
+            found |= in_section;
+            out.push(line.to_string());
+            continue;
+        }
+
+        if in_section && !trimmed.starts_with('#') && !trimmed.starts_with(';') {
+            if let Some((key, _)) = trimmed.split_once('=') {
+                if let Some(i) = values.iter().position(|(k, _)| *k == key.trim()) {
+                    out.push(format!("{} = {}", values[i].0, values[i].1));
+                    written[i] = true;
+                    continue;
+                }
+            }
+        }
+        out.push(line.to_string());
+    }
+
+    if in_section {
+        flush_missing(&mut out, &mut written);
+    }
+    if !found {
+        if out.last().map(|l| !l.trim().is_empty()).unwrap_or(false) {
+            out.push(String::new());
+        }
+        out.push(header);
+        flush_missing(&mut out, &mut written);
+    }
+
+    let mut result = out.join("\n");
+    result.push('\n');
+    result
+}
+
+/// Drop `key` from `section` in INI content, preserving every other line.
+fn remove_ini_key(content: &str, section: &str, key: &str) -> String {
+    let mut in_section = false;
+    let mut out = String::with_capacity(content.len());
+    for line in content.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') && trimmed.ends_with(']') {
+            in_section = same_section(&trimmed[1..trimmed.len() - 1], section);
+        } else if in_section && !trimmed.starts_with('#') && !trimmed.starts_with(';') {
+            if let Some((k, _)) = trimmed.split_once('=') {
+                if k.trim() == key {
+                    continue;
+                }
+            }
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+    out
+}
+
+/// The NAME of a `[KIND "NAME"]` section header (as passed to
+/// for_each_ini_entry), for named instances of a section.
+fn section_name<'a>(section: &'a str, kind: &str) -> Option<&'a str> {
+    let rest = section.strip_prefix(kind)?.trim_start();
+    rest.strip_prefix('"')?.strip_suffix('"').filter(|name| !name.is_empty())
+}
+
+/// Whether two section headers name the same section, however they are
+/// spaced: `location "home"` matches `location  "home" `.
+fn same_section(a: &str, b: &str) -> bool {
+    fn kind(s: &str) -> &str {
+        s.split(|c: char| c == '"' || c.is_whitespace()).next().unwrap_or("")
+    }
+    let (a, b) = (a.trim(), b.trim());
+    let (ka, kb) = (kind(a), kind(b));
+    ka == kb && match (section_name(a, ka), section_name(b, kb)) {
+        (Some(na), Some(nb)) => na == nb,
+        (None, None) => a == b,
+        _ => false,
+    }
+}
+
+/// A `[location "NAME"]` entry
+pub struct NamedLocation {
+    pub name: String,
+    /// None if its latitude/longitude are missing or out of range
+    pub location: Option<Location>,
+}
+
+/// Every location in config.ini
+pub struct Locations {
+    /// The unnamed [location], used when no named one is active
+    pub default: Option<Location>,
+    pub named: Vec<NamedLocation>,
+    /// [location] active = NAME
+    pub active: Option<String>,
+}
+
+impl Locations {
+    /// The location in effect: the active named one if it is usable, else
+    /// the unnamed default.
+    pub fn current(&self) -> Option<&Location> {
+        if let Some(ref name) = self.active {
+            match self.named.iter().find(|n| &n.name == name) {
+                Some(NamedLocation { location: Some(loc), .. }) => return Some(loc),
+                Some(_) => eprintln!("[config] Active location '{}' has no valid latitude/longitude", name),
+                None => eprintln!("[config] Active location '{}' not found in config.ini", name),
+            }
+        }
+        self.default.as_ref()
+    }
+}
+
+fn parse_locations(content: &str) -> Locations {
+    let mut default = (None, None);
+    let mut named: Vec<(String, Option<f64>, Option<f64>)> = Vec::new();
+    let mut active = None;
+
+    for_each_ini_entry(content, |section, key, value| {
+        let coords = if section == "location" {
+            if key == "active" && !value.is_empty() {
+                active = Some(value.to_string());
+            }
+            &mut default
+        } else if let Some(name) = section_name(section, "location") {
+            let idx = match named.iter().position(|n| n.0 == name) {
+                Some(i) => i,
+                None => {
+                    named.push((name.to_string(), None, None));
+                    named.len() - 1
+                }
+            };
+            let n = &mut named[idx];
+            match key {
+                "latitude" => n.1 = value.parse().ok(),
+                "longitude" => n.2 = value.parse().ok(),
+                _ => {}
+            }
+            return;
+        } else {
+            return;
+        };
+        match key {
+            "latitude" => coords.0 = value.parse().ok(),
+            "longitude" => coords.1 = value.parse().ok(),
+            _ => {}
+        }
+    });
+
+    Locations {
+        default: checked_location("location", default.0, default.1),
+        named: named
+            .into_iter()
+            .map(|(name, lat, lon)| NamedLocation {
+                location: checked_location(&format!("location \"{}\"", name), lat, lon),
+                name,
+            })
+            .collect(),
+        active,
+    }
+}
+
+fn checked_location(section: &str, lat: Option<f64>, lon: Option<f64>) -> Option<Location> {
+    match (lat, lon) {
+        (Some(lat), Some(lon)) if valid_coordinates(lat, lon) => Some(Location { lat, lon }),
+        (Some(lat), Some(lon)) => {
+            eprintln!("[config] [{}] {}, {} out of range (latitude -90 to 90, longitude -180 to 180)", section, lat, lon);
+            None
+        }
+        _ => None,
+    }
+}
+
+/// All locations in config.ini (none if it is missing)
+pub fn load_locations(paths: &Paths) -> Locations {
+    let content = fs::read_to_string(&paths.config_file).unwrap_or_default();
+    parse_locations(&content)
+}
+
+/// Load the location in effect from INI config: the active
+/// `[location "NAME"]`, else the unnamed [location]
+pub fn load_location(paths: &Paths) -> Option<Location> {
+    let content = fs::read_to_string(&paths.config_file).ok()?;
+    parse_locations(&content).current().map(|l| Location { lat: l.lat, lon: l.lon })
+}
+
+/// Select a named location (`[location] active = NAME`), or the unnamed
+/// [location] again with None
+pub fn save_active_location(paths: &Paths, name: Option<&str>) -> Result<(), io::Error> {
+    let existing = fs::read_to_string(&paths.config_file).unwrap_or_default();
+    let content = match name {
+        Some(name) => update_ini_section(&existing, "location", &[("active", name.to_string())]),
+        None => remove_ini_key(&existing, "location", "active"),
+    };
+    write_atomic(&paths.config_file, content)
+}
+
+/// The section --set-location writes to: the active named location if
+/// there is one, so it is the one that changes
+fn active_location_section(content: &str) -> String {
+    let locations = parse_locations(content);
+    match locations.active {
+        Some(name) if locations.named.iter().any(|n| n.name == name) => format!("location \"{}\"", name),
+        _ => "location".to_string(),
+    }
+}
+
+pub fn valid_coordinates(lat: f64, lon: f64) -> bool {
+    LAT_RANGE.contains(&lat) && LON_RANGE.contains(&lon)
+}
+
+/// Parse "LAT,LON" ("41.88,-87.63") or with hemisphere suffixes
+/// ("41.88N, 87.63W"), checking both are in range.
+pub fn parse_coordinates(s: &str) -> Result<(f64, f64), String> {
+    const FORMAT: &str = "Invalid format. Use: LAT,LON (e.g., 41.88,-87.63 or 41.88N,87.63W)";
+
+    let (lat, lon) = s.split_once(',').ok_or(FORMAT)?;
+    let lat = parse_coordinate(lat, 'N', 'S').ok_or(FORMAT)?;
+    let lon = parse_coordinate(lon, 'E', 'W').ok_or(FORMAT)?;
+
+    if !LAT_RANGE.contains(&lat) {
+        return Err(format!("Latitude {} out of range (-90 to 90)", lat));
+    }
+    if !LON_RANGE.contains(&lon) {
+        return Err(format!("Longitude {} out of range (-180 to 180)", lon));
+    }
+    Ok((lat, lon))
+}
+
+/// One signed decimal coordinate, or an unsigned one with a hemisphere
+/// suffix (`neg` negates it).
+fn parse_coordinate(s: &str, pos: char, neg: char) -> Option<f64> {
+    let s = s.trim();
+    let v: f64 = match s.chars().last().map(|c| c.to_ascii_uppercase()) {
+        Some(c) if c == pos || c == neg => {
+            let num = s[..s.len() - 1].trim_end();
+            // The suffix is the sign, so "-41.88N" is ambiguous
+            if num.starts_with(['-', '+']) {
+                return None;
+            }
+            let v: f64 = num.parse().ok()?;
+            if c == neg { -v } else { v }
+        }
+        _ => s.parse().ok()?,
+    };
+    v.is_finite().then_some(v)
+}
+
+/// Save location to INI config, preserving any other sections
+pub fn save_location(paths: &Paths, lat: f64, lon: f64) -> Result<(), io::Error> {
+    // A location set by hand stops GeoClue from moving it again
+    let auto = if location_is_auto(paths) { Some(false) } else { None };
+    write_location(paths, lat, lon, auto)
+}
+
+/// Save a GeoClue fix to [location] and mark it `auto = true`, so the
+/// daemon keeps it current
+#[cfg_attr(not(feature = "geoclue"), allow(dead_code))]
+pub fn save_auto_location(paths: &Paths, lat: f64, lon: f64) -> Result<(), io::Error> {
+    write_location(paths, lat, lon, Some(true))
+}
+
+fn write_location(paths: &Paths, lat: f64, lon: f64, auto: Option<bool>) -> Result<(), io::Error> {
+    let existing = fs::read_to_string(&paths.config_file).unwrap_or_default();
+    let mut values = vec![
+        ("latitude", format!("{:.6}", lat)),
+        ("longitude", format!("{:.6}", lon)),
+    ];
+    if let Some(auto) = auto {
+        values.push(("auto", auto.to_string()));
+    }
+    let content = update_ini_section(&existing, &active_location_section(&existing), &values);
+    // A new file starts out current, rather than as a v1 to migrate next run
+    let content = if existing.trim().is_empty() { append_new_sections(&content) } else { content };
+    write_atomic(&paths.config_file, content)
+}
+
+/// Whether the location in effect came from `--set-location auto`
+pub fn location_is_auto(paths: &Paths) -> bool {
+    let content = fs::read_to_string(&paths.config_file).unwrap_or_default();
+    let target = active_location_section(&content);
+    let mut auto = false;
+    for_each_ini_entry(&content, |section, key, value| {
+        if key == "auto" && same_section(section, &target) {
+            parse_setting(section, key, value, &mut auto);
+        }
+    });
+    auto
+}
+
+/// Save [temperatures] day_clear/night (whichever are given), preserving
+/// every other key and section
+pub fn save_temp_params(paths: &Paths, day_clear: Option<i32>, night: Option<i32>) -> Result<(), io::Error> {
+    let mut values = Vec::new();
+    if let Some(t) = day_clear {
+        values.push(("day_clear", t.to_string()));
+    }
+    if let Some(t) = night {
+        values.push(("night", t.to_string()));
+    }
+    let existing = fs::read_to_string(&paths.config_file).unwrap_or_default();
+    write_atomic(&paths.config_file, update_ini_section(&existing, "temperatures", &values))
+}
+
+/// Save [network] weather_provider, preserving every other key and section
+pub fn save_network_params(paths: &Paths, weather_provider: WeatherProvider) -> Result<(), io::Error> {
+    let existing = fs::read_to_string(&paths.config_file).unwrap_or_default();
+    let values = [("weather_provider", weather_provider.name().to_string())];
+    write_atomic(&paths.config_file, update_ini_section(&existing, "network", &values))
+}
+
+/// Settings read from a Redshift (or Gammastep) config file
+#[derive(Debug, Default, PartialEq)]
+pub struct RedshiftConfig {
+    pub lat: Option<f64>,
+    pub lon: Option<f64>,
+    pub temp_day: Option<i32>,
+    pub temp_night: Option<i32>,
+    /// Fixed dawn/dusk times ("6:00-7:45"), instead of sun position
+    pub dawn_time: Option<String>,
+    pub dusk_time: Option<String>,
+    /// transition = 0 turns fading off
+    pub transition: Option<bool>,
+}
+
+/// Parse redshift.conf: [redshift] (Gammastep: [general]) and [manual].
+pub fn parse_redshift(content: &str) -> RedshiftConfig {
+    let mut rs = RedshiftConfig::default();
+    for_each_ini_entry(content, |section, key, value| match (section, key) {
+        ("redshift" | "general", "temp-day") => rs.temp_day = value.parse().ok(),
+        ("redshift" | "general", "temp-night") => rs.temp_night = value.parse().ok(),
+        ("redshift" | "general", "dawn-time") => rs.dawn_time = Some(value.to_string()),
+        ("redshift" | "general", "dusk-time") => rs.dusk_time = Some(value.to_string()),
+        ("redshift" | "general", "transition") => rs.transition = Some(value != "0"),
+        ("manual", "lat") => rs.lat = value.parse().ok(),
+        ("manual", "lon") => rs.lon = value.parse().ok(),
+        _ => {}
+    });
+    rs
+}
+
+/// Named location/temperature preset ([profile.NAME])
+pub struct Profile {
+    pub name: String,
+    pub location: Option<Location>,
+    pub day_clear: Option<i32>,
+    pub night: Option<i32>,
+}
+
+impl Profile {
+    pub fn apply_location(&self, loc: &mut Location) {
+        if let Some(ref l) = self.location {
+            loc.lat = l.lat;
+            loc.lon = l.lon;
+        }
+    }
+
+    pub fn apply_settings(&self, settings: &mut Settings) {
+        if let Some(t) = self.day_clear {
+            settings.temperatures.day_clear = t.clamp(TEMP_MIN, TEMP_MAX);
+        }
+        if let Some(t) = self.night {
+            settings.temperatures.night = t.clamp(TEMP_MIN, TEMP_MAX);
+        }
+    }
+}
+
+/// Load all [profile.NAME] sections, in config order
+pub fn load_profiles(paths: &Paths) -> Vec<Profile> {
+    let content = match fs::read_to_string(&paths.config_file) {
+        Ok(c) => c,
+        Err(_) => return Vec::new(),
+    };
+
+    struct Raw {
+        name: String,
+        lat: Option<f64>,
+        lon: Option<f64>,
+        day_clear: Option<i32>,
+        night: Option<i32>,
+    }
+    let mut raw: Vec<Raw> = Vec::new();
+
+    for_each_ini_entry(&content, |section, key, value| {
+        let name = match section.strip_prefix("profile.") {
+            Some(n) if !n.is_empty() => n,
+            _ => return,
+        };
+        let idx = match raw.iter().position(|p| p.name == name) {
+            Some(i) => i,
+            None => {
+                raw.push(Raw { name: name.to_string(), lat: None, lon: None, day_clear: None, night: None });
+                raw.len() - 1
+            }
+        };
+        let p = &mut raw[idx];
+        let ok = match key {
+            "latitude" => value.parse().ok().filter(|v| LAT_RANGE.contains(v)).map(|v| p.lat = Some(v)).is_some(),
+            "longitude" => value.parse().ok().filter(|v| LON_RANGE.contains(v)).map(|v| p.lon = Some(v)).is_some(),
+            "day_clear" => value.parse().map(|v| p.day_clear = Some(v)).is_ok(),
+            "night" => value.parse().map(|v| p.night = Some(v)).is_ok(),
+            _ => true,
+        };
+        if !ok {
+            eprintln!("[config] Invalid value for [{}] {}: {}", section, key, value);
+        }
+    });
+
+    raw.into_iter()
+        .map(|r| Profile {
+            location: match (r.lat, r.lon) {
+                (Some(lat), Some(lon)) => Some(Location { lat, lon }),
+                _ => None,
+            },
+            name: r.name,
+            day_clear: r.day_clear,
+            night: r.night,
+        })
+        .collect()
+}
+
+/// Selected profile (active_profile.json)
+#[derive(Serialize, Deserialize)]
+pub struct ActiveProfile {
+    pub name: String,
+}
+
+/// Save the selected profile name
+pub fn save_active_profile(paths: &Paths, name: &str) -> Result<(), io::Error> {
+    let json = serde_json::to_string_pretty(&ActiveProfile { name: name.to_string() })
+        .map_err(io::Error::other)?;
+    write_atomic(&paths.active_profile_file, json)
+}
+
+/// Clear the selected profile
+pub fn clear_active_profile(paths: &Paths) {
+    let _ = fs::remove_file(&paths.active_profile_file);
+}
+
+/// The currently selected profile, if any and still defined in config.ini
+pub fn active_profile(paths: &Paths) -> Option<Profile> {
+    let content = fs::read_to_string(&paths.active_profile_file).ok()?;
+    if content.len() > 4096 {
+        return None;
+    }
+    let active: ActiveProfile = serde_json::from_str(&content).ok()?;
+    let profile = load_profiles(paths).into_iter().find(|p| p.name == active.name);
+    if profile.is_none() {
+        eprintln!("[config] Active profile '{}' not found in config.ini", active.name);
+    }
+    profile
+}
+
+/// Parse a settings value, warning and keeping the default on bad input.
+fn parse_setting<T: std::str::FromStr>(section: &str, key: &str, value: &str, target: &mut T) {
+    match value.parse() {
+        Ok(v) => *target = v,
+        Err(_) => eprintln!("[config] Invalid value for [{}] {}: {}", section, key, value),
+    }
+}
+
+/// Load tunable settings from INI config. Missing file or keys use defaults.
+pub fn load_settings(paths: &Paths) -> Settings {
+    let mut settings = Settings::default();
+    let content = match fs::read_to_string(&paths.config_file) {
+        Ok(c) => c,
+        Err(_) => return settings,
+    };
+
+    let mut keyframes: Vec<Keyframe> = Vec::new();
+    let mut blend_min = SCHEDULE_BLEND_MIN;
+    // Named windows as read, completed after the loop
+    struct RawWindow {
+        name: String,
+        start: Option<i32>,
+        end: Option<i32>,
+        temp: Option<i32>,
+    }
+    let mut windows: Vec<RawWindow> = Vec::new();
+
+    for_each_ini_entry(&content, |section, key, value| {
+        let s = &mut settings;
+        match (section, key) {
+            ("temperatures", "day_clear") => parse_setting(section, key, value, &mut s.temperatures.day_clear),
+            ("temperatures", "day_dark") => parse_setting(section, key, value, &mut s.temperatures.day_dark),
+            ("temperatures", "night") => parse_setting(section, key, value, &mut s.temperatures.night),
+            ("temperatures", "cloud_threshold") => parse_setting(section, key, value, &mut s.temperatures.cloud_threshold),
+            ("temperatures", "golden_hour_temp") => parse_setting(section, key, value, &mut s.temperatures.golden_hour_temp),
+            ("temperatures", "moon_brightness_reduction") => {
+                parse_setting(section, key, value, &mut s.temperatures.moon_brightness_reduction)
+            }
+            ("transitions", "dawn_duration") => parse_setting(section, key, value, &mut s.transitions.dawn_duration),
+            ("transitions", "dusk_duration") => parse_setting(section, key, value, &mut s.transitions.dusk_duration),
+            ("transitions", "dawn_offset") => parse_setting(section, key, value, &mut s.transitions.dawn_offset_min),
+            ("transitions", "dusk_offset" | "dusk_offset_minutes") => {
+                parse_setting(section, key, value, &mut s.transitions.dusk_offset_min)
+            }
+            ("transitions", "steepness_dawn") => parse_setting(section, key, value, &mut s.transitions.steepness_dawn),
+            ("transitions", "steepness_dusk") => parse_setting(section, key, value, &mut s.transitions.steepness_dusk),
+            ("transitions", "transition_tick_seconds") => {
+                parse_setting(section, key, value, &mut s.transitions.transition_tick_sec)
+            }
+            ("transitions", "temp_change_threshold") => parse_setting(section, key, value, &mut s.transitions.temp_change_threshold),
+            ("display", "brightness") => parse_setting(section, key, value, &mut s.display.brightness),
+            ("display", "monitor_white_x") => parse_setting(section, key, value, &mut s.display.monitor_white_x),
+            ("display", "monitor_white_y") => parse_setting(section, key, value, &mut s.display.monitor_white_y),
+            ("display", "ramp_quantize_step") => parse_setting(section, key, value, &mut s.display.ramp_quantize_step),
+            ("network", "weather_refresh_minutes") => {
+                let mut minutes = s.network.weather_refresh_sec / 60;
+                parse_setting(section, key, value, &mut minutes);
+                s.network.weather_refresh_sec = minutes.saturating_mul(60);
+            }
+            ("network", "weather_provider") => match WeatherProvider::from_name(value) {
+                Some(p) if p.available() => s.network.weather_provider = p,
+                Some(p) => eprintln!("[config] [network] weather_provider {} is not compiled into this build", p.name()),
+                None => eprintln!(
+                    "[config] Invalid [network] weather_provider: {} (expected {})",
+                    value,
+                    WeatherProvider::choices()
+                ),
+            },
+            ("network", "weather_timeout_seconds") => parse_setting(section, key, value, &mut s.network.weather_timeout_sec),
+            ("network", "connect_timeout_seconds") => parse_setting(section, key, value, &mut s.network.connect_timeout_sec),
+            ("temperature_limits", "min") => parse_setting(section, key, value, &mut s.limits.min),
+            ("temperature_limits", "max") => parse_setting(section, key, value, &mut s.limits.max),
+            ("daemon", "mode") => match value {
+                "time" => s.daemon.mode = SolarMode::Time,
+                "elevation" => s.daemon.mode = SolarMode::Elevation,
+                _ => eprintln!("[config] Invalid [daemon] mode: {} (expected time or elevation)", value),
+            },
+            ("daemon", "elevation_high") => parse_setting(section, key, value, &mut s.daemon.elevation_high),
+            ("daemon", "elevation_low") => parse_setting(section, key, value, &mut s.daemon.elevation_low),
+            ("daemon", "notify") => parse_setting(section, key, value, &mut s.daemon.notify),
+            ("schedule", "blend_minutes") => parse_setting(section, key, value, &mut blend_min),
+            ("schedule", _) => match key.rsplit_once('_') {
+                Some((name, field @ ("start" | "end" | "temp"))) => {
+                    let idx = match windows.iter().position(|w| w.name == name) {
+                        Some(i) => i,
+                        None => {
+                            windows.push(RawWindow { name: name.to_string(), start: None, end: None, temp: None });
+                            windows.len() - 1
+                        }
+                    };
+                    let w = &mut windows[idx];
+                    let parsed = match field {
+                        "start" => parse_hhmm(value).map(|v| w.start = Some(v)),
+                        "end" => parse_hhmm(value).map(|v| w.end = Some(v)),
+                        _ => value.parse().ok().map(|v| w.temp = Some(v)),
+                    };
+                    if parsed.is_none() {
+                        eprintln!("[config] Invalid value for [schedule] {}: {}", key, value);
+                    }
+                }
+                _ => match (parse_hhmm(key), value.parse::<i32>()) {
+                    (Some(minute), Ok(temp)) => keyframes.push(Keyframe { minute, temp }),
+                    _ => eprintln!("[config] Invalid [schedule] keyframe: {} = {}", key, value),
+                },
+            },
+            _ => {}
+        }
+    });
+
+    if !keyframes.is_empty() {
+        settings.schedule = Some(Schedule { keyframes, blend_min });
+    }
+    for w in windows {
+        match (w.start, w.temp) {
+            // No end: runs until midnight
+            (Some(start), Some(temp)) => settings.schedule_windows.push(ScheduleEntry {
+                start_hhmm: start as u32,
+                end_hhmm: w.end.unwrap_or(1440) as u32,
+                temp_k: temp,
+                name: w.name,
+            }),
+            _ => eprintln!(
+                "[config] [schedule] window \"{0}\" needs {0}_start and {0}_temp, ignored",
+                w.name
+            ),
+        }
+    }
+
+    sanitize_settings(&mut settings);
+    settings
+}
+
+/// Hook script path: absolute, or empty for none.
+fn hook_path(key: &str, value: &str) -> Option<PathBuf> {
+    let path = PathBuf::from(value);
+    if value.is_empty() {
+        None
+    } else if path.is_absolute() {
+        Some(path)
+    } else {
+        eprintln!("[config] [hooks] {} must be an absolute path: {}", key, value);
+        None
+    }
+}
+
+/// Load [hooks] from INI config. Missing file or keys mean no hooks.
+pub fn load_hook_params(paths: &Paths) -> HookParams {
+    let mut h = HookParams {
+        change_delta: HOOK_CHANGE_DELTA,
+        min_interval_sec: HOOK_MIN_INTERVAL_SEC,
+        ..HookParams::default()
+    };
+    let content = match fs::read_to_string(&paths.config_file) {
+        Ok(c) => c,
+        Err(_) => return h,
+    };
+
+    for_each_ini_entry(&content, |section, key, value| match (section, key) {
+        ("hooks", "on_change") => h.on_change = hook_path(key, value),
+        ("hooks", "on_mode_change") => h.on_mode_change = hook_path(key, value),
+        ("hooks", "change_delta") => parse_setting(section, key, value, &mut h.change_delta),
+        ("hooks", "min_interval_seconds") => parse_setting(section, key, value, &mut h.min_interval_sec),
+        _ => {}
+    });
+
+    h.change_delta = h.change_delta.max(1);
+    h.min_interval_sec = h.min_interval_sec.max(0);
+    h
+}
+
+/// Load [thermal] settings from INI config. Missing file or keys use defaults.
+#[cfg(feature = "thermal")]
+pub fn load_thermal_params(paths: &Paths) -> ThermalParams {
+    let mut t = ThermalParams::default();
+    let content = match fs::read_to_string(&paths.config_file) {
+        Ok(c) => c,
+        Err(_) => return t,
+    };
+
+    for_each_ini_entry(&content, |section, key, value| match (section, key) {
+        ("thermal", "enabled") => parse_setting(section, key, value, &mut t.enabled),
+        ("thermal", "cpu_temp_file") => t.cpu_temp_file = PathBuf::from(value),
+        ("thermal", "temp_threshold_c") => parse_setting(section, key, value, &mut t.temp_threshold_c),
+        ("thermal", "brightness_reduction") => {
+            parse_setting(section, key, value, &mut t.brightness_reduction)
+        }
+        _ => {}
+    });
+
+    if !t.brightness_reduction.is_finite() {
+        t.brightness_reduction = ThermalParams::default().brightness_reduction;
+    }
+    t.brightness_reduction = t.brightness_reduction.clamp(0.0, 0.9);
+    t
+}
+
+/// Clamp settings into ranges the solar and gamma math can handle.
+fn sanitize_settings(s: &mut Settings) {
+    let defaults = Settings::default();
+
+    s.limits.min = s.limits.min.clamp(TEMP_MIN, TEMP_MAX);
+    s.limits.max = s.limits.max.clamp(TEMP_MIN, TEMP_MAX);
+    if s.limits.min > s.limits.max {
+        eprintln!("[config] temperature_limits min > max, using defaults");
+        s.limits = defaults.limits;
+    }
+
+    let t = &mut s.temperatures;
+    t.day_clear = t.day_clear.clamp(TEMP_MIN, TEMP_MAX);
+    t.day_dark = t.day_dark.clamp(TEMP_MIN, TEMP_MAX);
+    t.night = t.night.clamp(TEMP_MIN, TEMP_MAX);
+    t.cloud_threshold = t.cloud_threshold.clamp(0, 101);
+    if t.golden_hour_temp != 0 {
+        t.golden_hour_temp = t.golden_hour_temp.clamp(TEMP_MIN, TEMP_MAX);
+    }
+
+    let d = s.transitions.dawn_duration;
+    if !d.is_finite() || d <= 0.0 {
+        eprintln!("[config] dawn_duration must be positive, using default");
+        s.transitions.dawn_duration = defaults.transitions.dawn_duration;
+    }
+    let d = s.transitions.dusk_duration;
+    if !d.is_finite() || d <= 0.0 {
+        eprintln!("[config] dusk_duration must be positive, using default");
+        s.transitions.dusk_duration = defaults.transitions.dusk_duration;
+    }
+    if !s.transitions.dawn_offset_min.is_finite() {
+        s.transitions.dawn_offset_min = defaults.transitions.dawn_offset_min;
+    }
+    if !DUSK_OFFSET_RANGE.contains(&s.transitions.dusk_offset_min) {
+        eprintln!(
+            "[config] dusk_offset must be {} to {} minutes, using default",
+            DUSK_OFFSET_RANGE.start(), DUSK_OFFSET_RANGE.end()
+        );
+        s.transitions.dusk_offset_min = defaults.transitions.dusk_offset_min;
+    }
+    for (name, value) in [
+        ("steepness_dawn", &mut s.transitions.steepness_dawn),
+        ("steepness_dusk", &mut s.transitions.steepness_dusk),
+    ] {
+        if !STEEPNESS_RANGE.contains(value) {
+            eprintln!(
+                "[config] {} must be {} to {}, using default",
+                name, STEEPNESS_RANGE.start(), STEEPNESS_RANGE.end()
+            );
+            *value = SIGMOID_STEEPNESS;
+        }
+    }
+    s.transitions.transition_tick_sec = s.transitions.transition_tick_sec.clamp(1, TEMP_UPDATE_SEC);
+    s.transitions.temp_change_threshold = s.transitions.temp_change_threshold.max(1);
+
+    let e = &mut s.daemon;
+    if !e.elevation_high.is_finite() || !e.elevation_low.is_finite() || e.elevation_low >= e.elevation_high {
+        eprintln!("[config] elevation_low must be below elevation_high, using defaults");
+        e.elevation_high = defaults.daemon.elevation_high;
+        e.elevation_low = defaults.daemon.elevation_low;
+    }
+
+    s.display.brightness = if s.display.brightness.is_finite() {
+        s.display.brightness.clamp(0.1, 1.0)
+    } else {
+        defaults.display.brightness
+    };
+    let dp = &mut s.display;
+    if !WHITE_POINT_RANGE.contains(&dp.monitor_white_x) || !WHITE_POINT_RANGE.contains(&dp.monitor_white_y) {
+        eprintln!(
+            "[config] monitor_white_x and monitor_white_y must be {} to {}, using D65",
+            WHITE_POINT_RANGE.start(), WHITE_POINT_RANGE.end()
+        );
+        dp.monitor_white_x = D65_X;
+        dp.monitor_white_y = D65_Y;
+    }
+    if !RAMP_QUANTIZE_RANGE.contains(&dp.ramp_quantize_step) {
+        eprintln!(
+            "[config] ramp_quantize_step must be {} to {} Kelvin, using default",
+            RAMP_QUANTIZE_RANGE.start(), RAMP_QUANTIZE_RANGE.end()
+        );
+        dp.ramp_quantize_step = RAMP_QUANTIZE_STEP;
+    }
+    s.network.weather_refresh_sec = s.network.weather_refresh_sec.clamp(60, WEATHER_REFRESH_MAX_SEC);
+    let (lo, hi) = (*NETWORK_TIMEOUT_RANGE.start(), *NETWORK_TIMEOUT_RANGE.end());
+    s.network.weather_timeout_sec = s.network.weather_timeout_sec.clamp(lo, hi);
+    s.network.connect_timeout_sec = s.network.connect_timeout_sec.clamp(lo, hi);
+
+    if let Some(ref mut sched) = s.schedule {
+        sanitize_schedule(sched);
+        if sched.keyframes.is_empty() {
+            s.schedule = None;
         }
+    }
 
-        if !in_location {
-            continue;
+    s.schedule_windows.retain(|w| {
+        let ok = (TEMP_MIN..=TEMP_MAX).contains(&w.temp_k) && w.start_hhmm != w.end_hhmm;
+        if !ok {
+            eprintln!(
+                "[config] [schedule] window \"{}\" ({}K) empty or outside {}-{}K, ignored",
+                w.name, w.temp_k, TEMP_MIN, TEMP_MAX
+            );
         }
+        ok
+    });
+}
 
-        if let Some((key, value)) = trimmed.split_once('=') {
-            let key = key.trim();
-            let value = value.trim();
-            match key {
-                "latitude" => lat = value.parse().ok(),
-                "longitude" => lon = value.parse().ok(),
-                _ => {}
-            }
+/// Sort keyframes, drop out-of-range temperatures and duplicate times, and
+/// keep the blend window narrower than the tightest gap between keyframes.
+fn sanitize_schedule(sched: &mut Schedule) {
+    sched.keyframes.retain(|k| {
+        let ok = (TEMP_MIN..=TEMP_MAX).contains(&k.temp);
+        if !ok {
+            eprintln!(
+                "[config] [schedule] {:02}:{:02} = {} outside {}-{}K, ignored",
+                k.minute / 60, k.minute % 60, k.temp, TEMP_MIN, TEMP_MAX
+            );
         }
+        ok
+    });
+    sched.keyframes.sort_by_key(|k| k.minute);
+    let before = sched.keyframes.len();
+    sched.keyframes.dedup_by_key(|k| k.minute);
+    if sched.keyframes.len() != before {
+        eprintln!("[config] [schedule] duplicate keyframe times, keeping the first");
     }
 
-    match (lat, lon) {
-        (Some(lat), Some(lon)) => Some(Location { lat, lon }),
-        _ => None,
+    if !sched.blend_min.is_finite() || sched.blend_min < 0.0 {
+        sched.blend_min = SCHEDULE_BLEND_MIN;
+    }
+    let n = sched.keyframes.len();
+    if n > 1 {
+        let min_gap = (0..n)
+            .map(|i| (sched.keyframes[(i + 1) % n].minute - sched.keyframes[i].minute).rem_euclid(1440))
+            .min()
+            .unwrap_or(1440);
+        sched.blend_min = sched.blend_min.min(min_gap as f64);
     }
-}
-
-/// Save location to INI config
-pub fn save_location(paths: &Paths, lat: f64, lon: f64) -> Result<(), io::Error> {
-    let content = format!("[location]\nlatitude = {:.6}\nlongitude = {:.6}\n", lat, lon);
-    fs::write(&paths.config_file, content)
 }
 
 /// Load override state from JSON
 pub fn load_override(paths: &Paths) -> Option<OverrideState> {
-    let content = fs::read_to_string(&paths.override_file).ok()?;
-    if content.len() > 4096 {
+    let content = fs::read(&paths.override_file).ok()?;
+    override_from_bytes(paths, &content)
+}
+
+/// Override state from override.json contents the caller read (the daemon
+/// reads it through io_uring). Invalid JSON is quarantined as by
+/// load_override().
+pub fn override_from_bytes(paths: &Paths, content: &[u8]) -> Option<OverrideState> {
+    let content = std::str::from_utf8(content).ok()?;
+    // Empty (e.g. emptied by hand): no override, nothing worth quarantining
+    if content.trim().is_empty() {
         return None;
     }
-    serde_json::from_str(&content).ok()
+    match parse_override(content) {
+        Ok(ovr) => Some(ovr),
+        Err(e) => {
+            quarantine_override(paths, &e);
+            None
+        }
+    }
+}
+
+/// Parse and validate override JSON (see OverrideState::validate).
+fn parse_override(content: &str) -> Result<OverrideState, String> {
+    if content.len() > OVERRIDE_MAX_BYTES {
+        return Err(format!("{} bytes, expected at most {}", content.len(), OVERRIDE_MAX_BYTES));
+    }
+    let mut value: serde_json::Value = serde_json::from_str(content).map_err(|e| e.to_string())?;
+
+    // Older versions and the C23 daemon only write whole minutes
+    if let Some(obj) = value.as_object_mut() {
+        if !obj.contains_key("duration_secs") {
+            if let Some(min) = obj.get("duration_minutes").and_then(|m| m.as_i64()) {
+                obj.insert("duration_secs".into(), min.saturating_mul(60).into());
+            }
+        }
+    }
+    let mut ovr: OverrideState = serde_json::from_value(value).map_err(|e| e.to_string())?;
+    ovr.validate(now_epoch())?;
+    Ok(ovr)
+}
+
+/// Move a corrupt override.json aside as override.json.bad for inspection.
+fn quarantine_override(paths: &Paths, reason: &str) {
+    let bad = paths.override_file.with_extension("json.bad");
+    match fs::rename(&paths.override_file, &bad) {
+        Ok(()) => eprintln!("[override] Invalid override ({}), moved to {}", reason, bad.display()),
+        Err(e) => eprintln!("[override] Invalid override ({}), cannot move aside: {}", reason, e),
+    }
 }
 
 /// Save override state to JSON
 pub fn save_override(paths: &Paths, ovr: &OverrideState) -> Result<(), io::Error> {
-    let json = serde_json::to_string_pretty(ovr)
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-    fs::write(&paths.override_file, json)
+    let mut value = serde_json::to_value(ovr)
+        .map_err(io::Error::other)?;
+    // Rounded-up minutes for readers that predate duration_secs
+    value["duration_minutes"] = ((ovr.duration_secs + 59) / 60).into();
+    let json = serde_json::to_string_pretty(&value)
+        .map_err(io::Error::other)?;
+    let (dir, name) = match (paths.override_file.parent(), paths.override_file.file_name().and_then(|n| n.to_str())) {
+        (Some(dir), Some(name)) => (dir, name),
+        _ => return write_atomic(&paths.override_file, json),
+    };
+    write_atomic_tmpfile(dir, name, json.as_bytes())
+}
+
+/// Sequence for the next --set/--resume: one past the file's current one.
+pub fn next_override_sequence(paths: &Paths) -> u64 {
+    load_override(paths).map_or(1, |o| o.sequence + 1)
+}
+
+/// Daemon write-back (resolved start/target temperatures): save `ovr` only
+/// if the file still holds the command it was loaded from. The pause flag
+/// is owned by --toggle and kept from disk. Returns false, writing nothing,
+/// when a newer --set/--resume has replaced the file in the meantime; the
+/// check and the write happen under lock_override.
+pub fn update_override(paths: &Paths, ovr: &OverrideState) -> Result<bool, io::Error> {
+    let _lock = lock_override(paths);
+    match load_override(paths) {
+        Some(cur) if cur.sequence == ovr.sequence && cur.issued_at == ovr.issued_at => {
+            save_override(paths, &OverrideState { paused: cur.paused, ..*ovr })?;
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
 }
 
 /// Clear override file
@@ -131,19 +1636,16 @@ pub fn clear_override(paths: &Paths) {
     let _ = fs::remove_file(&paths.override_file);
 }
 
-/// JSON structure for weather cache (serde)
-#[derive(Serialize, Deserialize)]
+/// JSON structure for weather cache (serde). Missing fields default.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
 struct WeatherCacheJson {
     cloud_cover: i32,
-    #[serde(default)]
+    cloud_cover_3h: Option<i32>,
     forecast: String,
-    #[serde(default)]
     temperature: f64,
-    #[serde(default)]
     is_day: bool,
-    #[serde(default)]
     fetched_at: i64,
-    #[serde(default)]
     error: Option<String>,
 }
 
@@ -160,6 +1662,7 @@ pub fn load_weather_cache(paths: &Paths) -> Option<WeatherData> {
 
     Some(WeatherData {
         cloud_cover: cached.cloud_cover,
+        cloud_cover_3h: cached.cloud_cover_3h.unwrap_or(cached.cloud_cover),
         forecast: cached.forecast,
         temperature: cached.temperature,
         is_day: cached.is_day,
@@ -173,6 +1676,7 @@ pub fn save_weather_cache(paths: &Paths, wd: &WeatherData) -> Result<(), io::Err
     let cached = if wd.has_error {
         WeatherCacheJson {
             cloud_cover: 0,
+            cloud_cover_3h: None,
             forecast: String::new(),
             temperature: 0.0,
             is_day: true,
@@ -182,6 +1686,7 @@ pub fn save_weather_cache(paths: &Paths, wd: &WeatherData) -> Result<(), io::Err
     } else {
         WeatherCacheJson {
             cloud_cover: wd.cloud_cover,
+            cloud_cover_3h: Some(wd.cloud_cover_3h),
             forecast: wd.forecast.clone(),
             temperature: wd.temperature,
             is_day: wd.is_day,
@@ -191,42 +1696,937 @@ pub fn save_weather_cache(paths: &Paths, wd: &WeatherData) -> Result<(), io::Err
     };
 
     let json = serde_json::to_string_pretty(&cached)
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-    fs::write(&paths.cache_file, json)
+        .map_err(io::Error::other)?;
+    write_atomic(&paths.cache_file, json)
+}
+
+/// Take a flock guarding the weather cache, released when the File drops.
+/// The cache is replaced by rename, so a lock on the cache itself would stay
+/// with the old inode; it goes on a sibling `weather_cache.json.lock`
+/// instead. Best effort: None (unlocked) if the lock file can't be opened.
+fn lock_weather_cache(paths: &Paths, op: libc::c_int) -> Option<fs::File> {
+    lock_sidecar(&paths.cache_file, op)
 }
 
-/// Check if weather cache needs refresh
-pub fn weather_needs_refresh(wd: &WeatherData) -> bool {
-    if wd.has_error || wd.fetched_at == 0 {
+/// Exclusive flock on `override.json.lock` (see lock_weather_cache), held
+/// across a read-modify-write of override.json so a concurrent --set,
+/// --resume or daemon write-back can't land in between and be lost
+pub fn lock_override(paths: &Paths) -> Option<fs::File> {
+    lock_sidecar(&paths.override_file, libc::LOCK_EX)
+}
+
+/// flock `<file>.lock`, retrying on EINTR
+fn lock_sidecar(file: &Path, op: libc::c_int) -> Option<fs::File> {
+    use std::os::unix::io::AsRawFd;
+
+    let mut lock_path = file.as_os_str().to_owned();
+    lock_path.push(".lock");
+    let file = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(PathBuf::from(lock_path))
+        .ok()?;
+    loop {
+        if unsafe { libc::flock(file.as_raw_fd(), op) } == 0 {
+            return Some(file);
+        }
+        if io::Error::last_os_error().kind() != io::ErrorKind::Interrupted {
+            return None;
+        }
+    }
+}
+
+/// load_weather_cache under a shared lock, so it never reads while another
+/// process (a second daemon, --refresh) is saving
+pub fn load_weather_cache_locked(paths: &Paths) -> Option<WeatherData> {
+    let _lock = lock_weather_cache(paths, libc::LOCK_SH);
+    load_weather_cache(paths)
+}
+
+/// save_weather_cache under an exclusive lock, one writer at a time
+pub fn save_weather_cache_locked(paths: &Paths, wd: &WeatherData) -> Result<(), io::Error> {
+    let _lock = lock_weather_cache(paths, libc::LOCK_EX);
+    save_weather_cache(paths, wd)
+}
+
+/// Check if weather cache needs refresh. A failed fetch is retried after
+/// WEATHER_RETRY_SEC, however often the daemon ticks meanwhile.
+pub fn weather_needs_refresh(wd: &WeatherData, refresh_sec: i64, now: i64) -> bool {
+    if wd.fetched_at == 0 {
         return true;
     }
-    let now = now_epoch();
-    (now - wd.fetched_at) > WEATHER_REFRESH_SEC
+    if wd.has_error {
+        return now - wd.fetched_at >= WEATHER_RETRY_SEC;
+    }
+    (now - wd.fetched_at) > refresh_sec
 }
 
-/// Check if daemon process is alive via PID file
-pub fn check_daemon_alive(paths: &Paths) -> bool {
-    let content = match fs::read_to_string(&paths.pid_file) {
-        Ok(c) => c,
-        Err(_) => return false,
+/// Runtime state published by the daemon (state.json)
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DaemonStatus {
+    pub pid: i32,
+    /// Last temperature successfully applied (0 = none yet)
+    pub applied_temp: i32,
+    pub brightness: f32,
+    pub backend: String,
+    /// "clear", "dark", "manual" or "paused"
+    pub mode: String,
+    pub last_tick: i64,
+    pub tick_count: u64,
+    pub started_at: i64,
+    #[serde(default)]
+    pub last_error: Option<String>,
+    /// Sun position at last_tick (degrees)
+    #[serde(default)]
+    pub sun_elevation: f64,
+    #[serde(default)]
+    pub sun_azimuth: f64,
+    /// Today's solar noon and day length (None in polar day/night)
+    #[serde(default)]
+    pub solar_noon: Option<i64>,
+    #[serde(default)]
+    pub day_length_min: Option<i64>,
+    /// Temperature updates that left every gamma ramp as it was
+    #[serde(default)]
+    pub ramp_cache_hits: u64,
+    /// Last desktop notification that could not be sent
+    #[serde(default)]
+    pub last_notify_error: Option<String>,
+}
+
+/// Write a file atomically: write a sibling temp file, then rename over.
+/// Readers see the old or the new contents, never a truncated file.
+pub fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> Result<(), io::Error> {
+    write_atomic_verified(path, contents, |_| Ok(()))
+}
+
+/// write_atomic, running `verify` on the temp file before it is renamed
+/// over `path`. If it fails, the temp file is removed and `path` is left
+/// as it was.
+pub fn write_atomic_verified(
+    path: &Path,
+    contents: impl AsRef<[u8]>,
+    verify: impl FnOnce(&Path) -> Result<(), io::Error>,
+) -> Result<(), io::Error> {
+    let tmp = atomic_tmp_path(path);
+    let result = fs::write(&tmp, contents)
+        .and_then(|()| verify(&tmp))
+        .and_then(|()| fs::rename(&tmp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+/// Set once O_TMPFILE has failed with EISDIR (kernel without it) or
+/// EOPNOTSUPP (filesystem without it); later writes go to write_atomic.
+static TMPFILE_UNSUPPORTED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+/// Write `dir/name` atomically without a temp file ever holding partial
+/// contents: the data goes into a nameless O_TMPFILE inode, which is
+/// fsynced and then linked in. A new file appears with linkat alone
+/// (inotify sees IN_CREATE). linkat can't replace a name, so an existing
+/// file is replaced by linking the complete inode under a temp name and
+/// renaming that over it (IN_MOVED_TO). Falls back to write_atomic where
+/// O_TMPFILE or the link is unavailable.
+pub fn write_atomic_tmpfile(dir: &Path, name: &str, data: &[u8]) -> io::Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::sync::atomic::Ordering;
+
+    let path = dir.join(name);
+    if TMPFILE_UNSUPPORTED.load(Ordering::Relaxed) {
+        return write_atomic(&path, data);
+    }
+    let mut file = match fs::OpenOptions::new().write(true).mode(0o666).custom_flags(libc::O_TMPFILE).open(dir) {
+        Ok(f) => f,
+        Err(e) if matches!(e.raw_os_error(), Some(libc::EISDIR | libc::EOPNOTSUPP)) => {
+            TMPFILE_UNSUPPORTED.store(true, Ordering::Relaxed);
+            return write_atomic(&path, data);
+        }
+        Err(e) => return Err(e),
     };
-    let pid: i32 = match content.trim().parse() {
-        Ok(p) => p,
-        Err(_) => return false,
+    file.write_all(data)?;
+    file.sync_all()?;
+
+    match link_tmpfile(&file, &path) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+            let tmp = atomic_tmp_path(&path);
+            let result = link_tmpfile(&file, &tmp).and_then(|()| fs::rename(&tmp, &path));
+            match result {
+                Ok(()) => Ok(()),
+                Err(_) => {
+                    let _ = fs::remove_file(&tmp);
+                    write_atomic(&path, data)
+                }
+            }
+        }
+        // e.g. a sandbox that refuses the link: the data is still in hand
+        Err(_) => write_atomic(&path, data),
+    }
+}
+
+/// Give the O_TMPFILE inode behind `file` the name `path`. linkat with
+/// AT_EMPTY_PATH needs CAP_DAC_READ_SEARCH (ENOENT without it), so an
+/// unprivileged caller links through /proc/self/fd instead.
+fn link_tmpfile(file: &fs::File, path: &Path) -> io::Result<()> {
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::io::AsRawFd;
+
+    let target = std::ffi::CString::new(path.as_os_str().as_bytes())?;
+    let fd = file.as_raw_fd();
+    let r = unsafe { libc::linkat(fd, c"".as_ptr(), libc::AT_FDCWD, target.as_ptr(), libc::AT_EMPTY_PATH) };
+    if r == 0 {
+        return Ok(());
+    }
+    let err = io::Error::last_os_error();
+    if err.raw_os_error() != Some(libc::ENOENT) {
+        return Err(err);
+    }
+    let proc_fd = std::ffi::CString::new(format!("/proc/self/fd/{}", fd))?;
+    let r = unsafe {
+        libc::linkat(libc::AT_FDCWD, proc_fd.as_ptr(), libc::AT_FDCWD, target.as_ptr(), libc::AT_SYMLINK_FOLLOW)
     };
+    if r == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+/// Temp file next to `path`, unique per process and call so concurrent
+/// writers (CLI and daemon) never share one.
+fn atomic_tmp_path(path: &Path) -> PathBuf {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    static SEQ: AtomicU64 = AtomicU64::new(0);
+
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(format!(".{}.{}.tmp", std::process::id(), SEQ.fetch_add(1, Ordering::Relaxed)));
+    PathBuf::from(tmp)
+}
+
+/// Load daemon-published state
+pub fn load_daemon_status(paths: &Paths) -> Option<DaemonStatus> {
+    let content = fs::read_to_string(&paths.state_file).ok()?;
+    if content.len() > 4096 {
+        return None;
+    }
+    serde_json::from_str(&content).ok()
+}
+
+/// Publish daemon state (atomic replace so readers never see partial JSON)
+pub fn save_daemon_status(paths: &Paths, status: &DaemonStatus) -> Result<(), io::Error> {
+    let json = serde_json::to_string_pretty(status).map_err(io::Error::other)?;
+    write_atomic(&paths.state_file, &json)
+}
+
+/// Remove daemon state file
+pub fn clear_daemon_status(paths: &Paths) {
+    let _ = fs::remove_file(&paths.state_file);
+}
+
+/// Read daemon PID from PID file
+pub fn read_pid(paths: &Paths) -> Option<i32> {
+    let content = fs::read_to_string(&paths.pid_file).ok()?;
+    let pid: i32 = content.trim().parse().ok()?;
     if pid <= 0 {
-        return false;
+        return None;
+    }
+    Some(pid)
+}
+
+/// Check if daemon process is alive via PID file
+pub fn check_daemon_alive(paths: &Paths) -> bool {
+    match read_pid(paths) {
+        Some(pid) => unsafe { libc::kill(pid, 0) == 0 },
+        None => false,
+    }
+}
+
+/// Take an exclusive flock on the PID file, held for as long as the returned
+/// File stays open. Fails with `WouldBlock` if another daemon holds it.
+pub fn lock_pid_file(paths: &Paths) -> Result<fs::File, io::Error> {
+    use std::os::unix::io::AsRawFd;
+
+    let file = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&paths.pid_file)?;
+
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(file)
+}
+
+/// A flock on Paths::lock_file, released when dropped
+pub struct ConfigLock {
+    file: fs::File,
+}
+
+impl Drop for ConfigLock {
+    fn drop(&mut self) {
+        use std::os::unix::io::AsRawFd;
+        unsafe { libc::flock(self.file.as_raw_fd(), libc::LOCK_UN) };
+    }
+}
+
+fn lock_config_dir_with(paths: &Paths, op: libc::c_int) -> Result<ConfigLock, io::Error> {
+    use std::os::unix::io::AsRawFd;
+
+    let file = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&paths.lock_file)?;
+    loop {
+        if unsafe { libc::flock(file.as_raw_fd(), op) } == 0 {
+            return Ok(ConfigLock { file });
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
     }
-    unsafe { libc::kill(pid, 0) == 0 }
 }
 
-/// Write daemon PID to PID file
-pub fn write_pid(paths: &Paths) -> Result<(), io::Error> {
+/// Lock the config dir exclusively, waiting for any CLI command that holds
+/// it shared. The daemon holds this while it loads its startup state
+/// (override, weather cache, location) so no write lands half-way through.
+pub fn lock_config_dir(paths: &Paths) -> Result<ConfigLock, io::Error> {
+    lock_config_dir_with(paths, libc::LOCK_EX)
+}
+
+/// Lock the config dir shared, for a CLI command reading or writing config
+/// files; waits while a daemon is loading its startup state
+pub fn lock_config_dir_shared(paths: &Paths) -> Result<ConfigLock, io::Error> {
+    lock_config_dir_with(paths, libc::LOCK_SH)
+}
+
+/// Write daemon PID to PID file, atomically. The replacement is flocked
+/// before it is renamed into place so the single-instance lock carries
+/// over; hold the returned File (and lock_pid_file's) until exit.
+pub fn write_pid(paths: &Paths) -> Result<fs::File, io::Error> {
+    use std::io::Write;
+    use std::os::unix::io::AsRawFd;
+
     let pid = unsafe { libc::getpid() };
-    fs::write(&paths.pid_file, format!("{}\n", pid))
+    let tmp = atomic_tmp_path(&paths.pid_file);
+    let result = fs::File::create(&tmp).and_then(|mut file| {
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        file.write_all(format!("{}\n", pid).as_bytes())?;
+        fs::rename(&tmp, &paths.pid_file)?;
+        Ok(file)
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
 }
 
 /// Remove daemon PID file
 pub fn remove_pid(paths: &Paths) {
     let _ = fs::remove_file(&paths.pid_file);
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) fn test_paths(name: &str) -> Paths {
+        let dir = std::env::temp_dir().join(format!("abraxas-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        Paths::in_dirs(PathMode::Custom, dir.clone(), dir.clone(), dir)
+    }
+
+    #[test]
+    fn coordinates_parsed_and_range_checked() {
+        assert_eq!(parse_coordinates("41.88,-87.63"), Ok((41.88, -87.63)));
+        assert_eq!(parse_coordinates(" 41.88 , -87.63 "), Ok((41.88, -87.63)));
+        assert_eq!(parse_coordinates("41.88N, 87.63W"), Ok((41.88, -87.63)));
+        assert_eq!(parse_coordinates("33.87 s,151.21 e"), Ok((-33.87, 151.21)));
+        assert_eq!(parse_coordinates("90,180"), Ok((90.0, 180.0)));
+        assert_eq!(parse_coordinates("-90,-180"), Ok((-90.0, -180.0)));
+        assert_eq!(parse_coordinates("90S,180W"), Ok((-90.0, -180.0)));
+
+        assert!(parse_coordinates("90.0001,0").unwrap_err().contains("Latitude"));
+        assert!(parse_coordinates("0,-180.0001").unwrap_err().contains("Longitude"));
+        assert!(parse_coordinates("412.88,-870.63").is_err());
+        assert!(parse_coordinates("91N,0").is_err());
+        for bad in ["41.88", "41.88,", ",87.63", "41.88,-87.63,1", "abc,def", "-41.88N,87.63W",
+            "41.88E,87.63W", "41.88N,87.63N", "nan,0", "inf,0", "N,W"]
+        {
+            assert!(parse_coordinates(bad).unwrap_err().starts_with("Invalid format"), "{}", bad);
+        }
+
+        let paths = test_paths("location");
+        fs::write(&paths.config_file, "[location]\nlatitude = 41.88\nlongitude = -87.63\n").unwrap();
+        assert_eq!(load_location(&paths).map(|l| (l.lat, l.lon)), Some((41.88, -87.63)));
+        fs::write(&paths.config_file, "[location]\nlatitude = 412.88\nlongitude = -87.63\n").unwrap();
+        assert!(load_location(&paths).is_none());
+        fs::write(&paths.config_file, "[location]\nlatitude = 41.88\nlongitude = -180.5\n").unwrap();
+        assert!(load_location(&paths).is_none());
+        let _ = fs::remove_dir_all(&paths.config_dir);
+    }
+
+    #[test]
+    fn auto_location_flag_round_trips() {
+        let paths = test_paths("auto-location");
+        save_location(&paths, 41.88, -87.63).unwrap();
+        assert!(!location_is_auto(&paths));
+        assert!(!fs::read_to_string(&paths.config_file).unwrap().contains("auto"));
+
+        save_auto_location(&paths, 52.52, 13.40).unwrap();
+        assert!(location_is_auto(&paths));
+        assert_eq!(load_location(&paths).map(|l| (l.lat, l.lon)), Some((52.52, 13.40)));
+
+        // Setting it by hand turns auto off again
+        save_location(&paths, 41.88, -87.63).unwrap();
+        assert!(!location_is_auto(&paths));
+        assert!(fs::read_to_string(&paths.config_file).unwrap().contains("auto = false"));
+        let _ = fs::remove_dir_all(&paths.config_dir);
+    }
+
+    #[test]
+    fn named_locations_selected() {
+        let paths = test_paths("named-locations");
+        let ini = "[location]\nlatitude = 41.88\nlongitude = -87.63\nactive = office\n\n\
+            [location \"home\"]\nlatitude = 45.52\nlongitude = -122.68\n\n\
+            [location  \"office\" ]\nlatitude = 47.61\nlongitude = -122.33\n\n\
+            [location \"broken\"]\nlatitude = 47.61\n\n[locationx]\nlatitude = 1\nlongitude = 1\n";
+        fs::write(&paths.config_file, ini).unwrap();
+
+        let locations = load_locations(&paths);
+        let names: Vec<&str> = locations.named.iter().map(|n| n.name.as_str()).collect();
+        assert_eq!(names, ["home", "office", "broken"]);
+        assert!(locations.named[2].location.is_none());
+        assert_eq!(load_location(&paths).map(|l| (l.lat, l.lon)), Some((47.61, -122.33)));
+
+        // --set-location changes the active entry, not the default
+        save_location(&paths, 47.62, -122.35).unwrap();
+        assert_eq!(load_location(&paths).map(|l| l.lat), Some(47.62));
+        assert_eq!(load_locations(&paths).default.map(|l| l.lat), Some(41.88));
+
+        save_active_location(&paths, Some("home")).unwrap();
+        assert_eq!(load_location(&paths).map(|l| l.lat), Some(45.52));
+        // Missing or unusable names fall back to the unnamed [location]
+        save_active_location(&paths, Some("broken")).unwrap();
+        assert_eq!(load_location(&paths).map(|l| l.lat), Some(41.88));
+        save_active_location(&paths, Some("gone")).unwrap();
+        assert_eq!(load_location(&paths).map(|l| l.lat), Some(41.88));
+        save_active_location(&paths, None).unwrap();
+        assert!(load_locations(&paths).active.is_none());
+        assert!(!fs::read_to_string(&paths.config_file).unwrap().contains("active"));
+        assert_eq!(load_location(&paths).map(|l| l.lat), Some(41.88));
+        let _ = fs::remove_dir_all(&paths.config_dir);
+    }
+
+    #[test]
+    fn spaced_section_header_updated_in_place() {
+        let paths = test_paths("spaced-header");
+        let ini = "[location]\nactive = home\n\n[location  \"home\"]\nlatitude = 45.52\nlongitude = -122.68\nauto = true\n";
+        fs::write(&paths.config_file, ini).unwrap();
+        assert!(location_is_auto(&paths));
+
+        save_location(&paths, 45.53, -122.67).unwrap();
+        let content = fs::read_to_string(&paths.config_file).unwrap();
+        assert_eq!(content.matches("\"home\"").count(), 1, "{}", content);
+        assert!(content.contains("[location  \"home\"]\nlatitude = 45.530000\nlongitude = -122.670000\nauto = false\n"));
+        assert!(!location_is_auto(&paths));
+        let _ = fs::remove_dir_all(&paths.config_dir);
+    }
+
+    #[test]
+    fn v1_config_migrated() {
+        let paths = test_paths("v1-config");
+        assert_eq!(detect_config_version(&paths), CONFIG_VERSION); // no file yet
+
+        let v1 = "[location]\nlatitude = 41.880000\nlongitude = -87.630000\n";
+        fs::write(&paths.config_file, v1).unwrap();
+        assert_eq!(detect_config_version(&paths), 1);
+
+        migrate_config(&paths);
+        assert_eq!(detect_config_version(&paths), CONFIG_VERSION);
+        assert_eq!(fs::read_to_string(paths.config_dir.join("config.ini.bak")).unwrap(), v1);
+        let migrated = fs::read_to_string(&paths.config_file).unwrap();
+        assert!(migrated.starts_with(v1));
+        assert_eq!(ini_sections(&migrated), ["location", "temperatures", "transitions", "display", "network"]);
+        assert_eq!(load_location(&paths).map(|l| l.lat), Some(41.88));
+        let settings = load_settings(&paths);
+        assert_eq!(settings.temperatures.night, TEMP_NIGHT);
+        assert_eq!(settings.transitions.dusk_offset_min, DUSK_OFFSET);
+
+        // Sections already there are left alone
+        let partial = append_new_sections("[display]\nbrightness = 0.8\n");
+        assert_eq!(ini_sections(&partial), ["display", "temperatures", "transitions", "network"]);
+        assert!(partial.starts_with("[display]\nbrightness = 0.8\n\n"));
+        let _ = fs::remove_dir_all(&paths.config_dir);
+    }
+
+    #[test]
+    fn redshift_config_imported() {
+        let conf = "; Global settings\n[redshift]\ntemp-day=5700\ntemp-night=3500\n\
+            transition=0\nlocation-provider=manual\n\n[manual]\nlat=48.1\nlon=11.6\n";
+        let rs = parse_redshift(conf);
+        assert_eq!(
+            rs,
+            RedshiftConfig {
+                lat: Some(48.1),
+                lon: Some(11.6),
+                temp_day: Some(5700),
+                temp_night: Some(3500),
+                transition: Some(false),
+                ..Default::default()
+            }
+        );
+
+        let rs = parse_redshift("[general]\ndawn-time=6:00-7:45\ndusk-time=18:35-20:15\n");
+        assert_eq!(rs.dawn_time.as_deref(), Some("6:00-7:45"));
+        assert_eq!(rs.dusk_time.as_deref(), Some("18:35-20:15"));
+        assert_eq!((rs.lat, rs.temp_day), (None, None));
+
+        // Only the given keys change; the rest of the file is kept
+        let paths = test_paths("redshift");
+        fs::write(&paths.config_file, "[temperatures]\nday_dark = 4000\nnight = 2700\n").unwrap();
+        save_temp_params(&paths, None, Some(3500)).unwrap();
+        let settings = load_settings(&paths);
+        assert_eq!((settings.temperatures.day_dark, settings.temperatures.night), (4000, 3500));
+        assert_eq!(settings.temperatures.day_clear, TEMP_DAY_CLEAR);
+        let _ = fs::remove_dir_all(&paths.config_dir);
+    }
+
+    #[test]
+    fn config_and_cache_dirs_resolve() {
+        let lookup = |uid: u32| (uid == 1000).then(|| PathBuf::from("/home/pw"));
+        let resolve_as = |uid: u32, vars: &[(&str, &str)]| {
+            let vars: Vec<(String, String)> =
+                vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+            resolve_paths(|name| vars.iter().find(|(k, _)| k == name).map(|(_, v)| v.clone()), uid, lookup)
+                .map(|p| {
+                    let s = |d: &Path| d.to_string_lossy().into_owned();
+                    (p.mode, s(&p.config_dir), s(&p.state_dir), s(&p.cache_dir))
+                })
+        };
+        let resolve = |vars: &[(&str, &str)]| resolve_as(1000, vars).unwrap();
+        let user = |config: &str, cache: &str| (PathMode::User, config.into(), config.into(), cache.into());
+
+        assert_eq!(
+            resolve(&[("HOME", "/home/u")]),
+            user("/home/u/.config/abraxas", "/home/u/.cache/abraxas")
+        );
+        assert_eq!(
+            resolve(&[("HOME", "/home/u"), ("XDG_CONFIG_HOME", "/cfg"), ("XDG_CACHE_HOME", "rel")]),
+            user("/cfg/abraxas", "/home/u/.cache/abraxas")
+        );
+        // The override wins over everything, HOME not needed
+        let scratch = String::from("/scratch");
+        assert_eq!(
+            resolve(&[("ABRAXAS_CONFIG_DIR", "/scratch"), ("ABRAXAS_SYSTEM", "1")]),
+            (PathMode::Custom, scratch.clone(), scratch.clone(), scratch)
+        );
+
+        // No (or empty) HOME: the passwd entry, else an error
+        assert_eq!(resolve(&[("HOME", "")]), user("/home/pw/.config/abraxas", "/home/pw/.cache/abraxas"));
+        assert!(resolve_as(1001, &[]).is_err());
+
+        // System service: asked for, or root without HOME
+        let lib = String::from("/var/lib/abraxas");
+        let system = (PathMode::System, "/etc/abraxas".into(), lib.clone(), lib);
+        assert_eq!(resolve(&[("HOME", "/root"), ("ABRAXAS_SYSTEM", "1")]), system);
+        assert_eq!(resolve_as(0, &[]).unwrap(), system);
+        let p = resolve_paths(|_| None, 0, |_| None).unwrap();
+        assert_eq!(p.config_file, Path::new("/etc/abraxas/config.ini"));
+        assert_eq!(p.override_file, Path::new("/var/lib/abraxas/override.json"));
+        assert_eq!(p.pid_file, Path::new("/var/lib/abraxas/daemon.pid"));
+    }
+
+    #[test]
+    fn home_from_passwd_without_home_env() {
+        // The real passwd database: whoever runs the tests has an entry
+        let uid = unsafe { libc::getuid() };
+        let home = passwd_home(uid).expect("passwd entry");
+        assert!(home.is_absolute());
+
+        // HOME unset entirely: still resolves (system mode as root)
+        let paths = resolve_paths(|name| std::env::var(name).ok().filter(|_| name != "HOME"), uid, passwd_home)
+            .unwrap();
+        if uid != 0 {
+            assert_eq!(paths.config_dir, home.join(".config").join("abraxas"));
+        }
+        assert!(passwd_home(u32::MAX - 1).is_none());
+    }
+
+    #[test]
+    fn cache_files_copied_once() {
+        let root = test_paths("migrate").config_dir;
+        let (config_dir, cache_dir) = (root.join("config"), root.join("cache"));
+        fs::create_dir_all(&config_dir).unwrap();
+        fs::create_dir_all(&cache_dir).unwrap();
+        fs::write(config_dir.join(CACHE_FILE), "old").unwrap();
+
+        migrate_cache(&config_dir, &cache_dir);
+        assert_eq!(fs::read_to_string(cache_dir.join(CACHE_FILE)).unwrap(), "old");
+        assert!(config_dir.join(CACHE_FILE).exists());
+        assert!(!cache_dir.join(ZIPDB_FILE).exists());
+
+        // Never over a newer cache
+        fs::write(cache_dir.join(CACHE_FILE), "new").unwrap();
+        migrate_cache(&config_dir, &cache_dir);
+        assert_eq!(fs::read_to_string(cache_dir.join(CACHE_FILE)).unwrap(), "new");
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn weather_cache_save_waits_for_lock() {
+        let paths = test_paths("cachelock");
+        fn cloudy() -> WeatherData {
+            WeatherData {
+                cloud_cover: 80,
+                cloud_cover_3h: 60,
+                forecast: "Cloudy".to_string(),
+                temperature: 12.5,
+                is_day: true,
+                fetched_at: 1_700_000_000,
+                has_error: false,
+            }
+        }
+
+        // A reader holding the lock keeps a writer out
+        let reader = lock_weather_cache(&paths, libc::LOCK_SH).expect("lock file");
+        let writer = {
+            let paths = paths.clone();
+            std::thread::spawn(move || save_weather_cache_locked(&paths, &cloudy()))
+        };
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        assert!(!paths.cache_file.exists());
+
+        drop(reader);
+        writer.join().unwrap().unwrap();
+        let loaded = load_weather_cache_locked(&paths).unwrap();
+        assert_eq!((loaded.cloud_cover, loaded.forecast.as_str()), (80, "Cloudy"));
+        let _ = fs::remove_dir_all(paths.cache_file.parent().unwrap());
+    }
+
+    #[test]
+    fn pid_lock_is_exclusive() {
+        let paths = test_paths("pidlock");
+
+        let first = lock_pid_file(&paths).expect("first lock");
+        let second = lock_pid_file(&paths);
+        assert_eq!(second.unwrap_err().kind(), io::ErrorKind::WouldBlock);
+
+        // Released when the holder closes
+        drop(first);
+        assert!(lock_pid_file(&paths).is_ok());
+
+        let _ = fs::remove_dir_all(paths.pid_file.parent().unwrap());
+    }
+
+    #[test]
+    fn pid_lock_survives_atomic_pid_write() {
+        let paths = test_paths("pidwrite");
+
+        let lock = lock_pid_file(&paths).expect("lock");
+        let written = write_pid(&paths).expect("write pid");
+        assert_eq!(read_pid(&paths), Some(unsafe { libc::getpid() }));
+
+        // The renamed-in file is locked too
+        assert_eq!(lock_pid_file(&paths).unwrap_err().kind(), io::ErrorKind::WouldBlock);
+        drop(lock);
+        assert_eq!(lock_pid_file(&paths).unwrap_err().kind(), io::ErrorKind::WouldBlock);
+        drop(written);
+        assert!(lock_pid_file(&paths).is_ok());
+
+        let _ = fs::remove_dir_all(paths.pid_file.parent().unwrap());
+    }
+
+    #[test]
+    fn concurrent_override_writes_never_tear() {
+        let paths = test_paths("atomic");
+        let ovr = |target_temp| OverrideState {
+            active: true,
+            target_temp,
+            duration_secs: 60,
+            issued_at: now_epoch(),
+            ..Default::default()
+        };
+        save_override(&paths, &ovr(3000)).unwrap();
+
+        std::thread::scope(|s| {
+            for temp in [3000, 4000] {
+                let paths = &paths;
+                s.spawn(move || {
+                    for _ in 0..300 {
+                        save_override(paths, &ovr(temp)).unwrap();
+                    }
+                });
+            }
+            for _ in 0..600 {
+                let o = load_override(&paths).expect("torn or missing override");
+                assert!(o.target_temp == 3000 || o.target_temp == 4000);
+            }
+        });
+
+        // No quarantine, no leftover temp files
+        let names: Vec<_> = fs::read_dir(paths.override_file.parent().unwrap())
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        assert_eq!(names, ["override.json"]);
+        let _ = fs::remove_dir_all(paths.override_file.parent().unwrap());
+    }
+
+    #[test]
+    fn tmpfile_write_wakes_the_daemon_without_temp_files() {
+        let paths = test_paths("tmpfile");
+        let dir = paths.override_file.parent().unwrap();
+        let fd = crate::inotify::setup(&paths);
+        assert!(fd >= 0);
+
+        // New file, then a replacement: each is one override change
+        for data in [&b"{}"[..], b"{\"active\": false}"] {
+            write_atomic_tmpfile(dir, "override.json", data).unwrap();
+            assert_eq!(fs::read(&paths.override_file).unwrap(), data);
+            assert_eq!(crate::inotify::read_events(fd, &paths), crate::daemon::FLAG_OVERRIDE);
+        }
+        unsafe { libc::close(fd) };
+
+        let names: Vec<_> = fs::read_dir(dir).unwrap().map(|e| e.unwrap().file_name()).collect();
+        assert_eq!(names, ["override.json"]);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn override_fields_validated() {
+        let now = 1_750_000_000;
+        let valid = r#"{"active":true,"target_temp":3500,"duration_secs":600,"issued_at":1750000000,"start_temp":0}"#;
+        let o = parse_override(valid).unwrap();
+        assert_eq!((o.target_temp, o.start_temp, o.issued_at), (3500, 0, now));
+
+        // Repaired: temperatures clamped, future issued_at pulled back, brightness clamped
+        let wild = r#"{"active":true,"target_temp":500,"duration_secs":60,"issued_at":1850000000,
+            "start_temp":50000,"brightness":3.0}"#;
+        let mut o: OverrideState = serde_json::from_str(wild).unwrap();
+        o.validate(now).unwrap();
+        assert_eq!((o.target_temp, o.start_temp), (TEMP_MIN, TEMP_MAX));
+        assert_eq!(o.issued_at, now);
+        assert_eq!(o.brightness, 1.0);
+
+        // Small skew is left alone; relative targets keep their sign
+        let mut o = OverrideState { issued_at: now + 30, is_relative: true, target_temp: -99_999, ..o };
+        o.validate(now).unwrap();
+        assert_eq!(o.issued_at, now + 30);
+        assert_eq!(o.target_temp, -(TEMP_MAX - TEMP_MIN));
+
+        // Rejected: negative or absurd durations and holds, bad JSON
+        for bad in [
+            r#"{"active":true,"target_temp":3500,"duration_secs":-5,"issued_at":1,"start_temp":0}"#,
+            r#"{"active":true,"target_temp":3500,"duration_secs":90000,"issued_at":1,"start_temp":0}"#,
+            r#"{"active":true,"target_temp":3500,"duration_minutes":9999999,"issued_at":1,"start_temp":0}"#,
+            r#"{"active":true,"target_temp":3500,"duration_secs":60,"issued_at":1,"start_temp":0,"hold_minutes":-1}"#,
+            r#"{"active":true,"#,
+        ] {
+            assert!(parse_override(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn override_json_forward_and_backward_compatible() {
+        // v1 file from before most optional fields existed
+        let v1 = r#"{"active":true,"target_temp":3500,"duration_minutes":5,"issued_at":1750000000}"#;
+        let o = parse_override(v1).unwrap();
+        assert_eq!(o.schema_version, 1);
+        assert_eq!((o.duration_secs, o.start_temp, o.brightness), (300, 0, 1.0));
+        assert_eq!((o.paused, o.hold, o.at, o.easing), (false, false, None, Easing::Sigmoid));
+
+        // A newer version's file: unknown fields are ignored, not fatal
+        let v2 = r#"{"schema_version":2,"active":true,"target_temp":3500,"duration_secs":60,
+            "issued_at":1750000000,"start_temp":4000,"tint":"rose","curve":{"kind":"bezier"}}"#;
+        let o = parse_override(v2).unwrap();
+        assert_eq!((o.schema_version, o.target_temp, o.start_temp), (2, 3500, 4000));
+
+        // Even an empty object loads as an inactive override
+        let o = parse_override("{}").unwrap();
+        assert!(!o.active);
+        assert_eq!(o.schema_version, 1);
+
+        // Weather cache likewise
+        let w: WeatherCacheJson = serde_json::from_str(r#"{"forecast":"Fog","extra":1}"#).unwrap();
+        assert_eq!((w.cloud_cover, w.forecast.as_str(), w.cloud_cover_3h), (0, "Fog", None));
+        assert_eq!(serde_json::to_value(OverrideState::default()).unwrap()["schema_version"], OVERRIDE_SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn corrupt_override_quarantined() {
+        let paths = test_paths("quarantine");
+        let bad = paths.override_file.with_extension("json.bad");
+        fs::write(&paths.override_file, "{not json").unwrap();
+
+        assert!(load_override(&paths).is_none());
+        assert!(!paths.override_file.exists());
+        assert_eq!(fs::read_to_string(&bad).unwrap(), "{not json");
+
+        // Empty (mid-write) is just "no override"
+        fs::write(&paths.override_file, "").unwrap();
+        assert!(load_override(&paths).is_none());
+        assert!(paths.override_file.exists());
+
+        let _ = fs::remove_dir_all(paths.override_file.parent().unwrap());
+    }
+
+    #[test]
+    fn network_timeouts_clamped() {
+        let paths = test_paths("timeouts");
+        fs::write(&paths.config_file, "[network]\nweather_timeout_seconds = 500\nconnect_timeout_seconds = 0\n").unwrap();
+        let net = load_settings(&paths).network;
+        assert_eq!((net.weather_timeout_sec, net.connect_timeout_sec), (120, 1));
+
+        fs::write(&paths.config_file, "[network]\nweather_refresh_minutes = 30\n").unwrap();
+        let net = load_settings(&paths).network;
+        assert_eq!((net.weather_timeout_sec, net.connect_timeout_sec), (WEATHER_TIMEOUT_SEC, CONNECT_TIMEOUT_SEC));
+        let _ = fs::remove_dir_all(&paths.config_dir);
+    }
+
+    #[test]
+    fn weather_refresh_clamped() {
+        let paths = test_paths("refresh-minutes");
+        for (minutes, secs) in [("0", 60), ("30", 1800), ("9223372036854775807", WEATHER_REFRESH_MAX_SEC), ("-5", 60)] {
+            fs::write(&paths.config_file, format!("[network]\nweather_refresh_minutes = {}\n", minutes)).unwrap();
+            assert_eq!(load_settings(&paths).network.weather_refresh_sec, secs, "{}", minutes);
+        }
+        let _ = fs::remove_dir_all(&paths.config_dir);
+    }
+
+    #[test]
+    fn monitor_white_point_range_checked() {
+        let paths = test_paths("whitepoint");
+        assert_eq!(load_settings(&paths).display.white_point(), (D65_X, D65_Y));
+
+        fs::write(&paths.config_file, "[display]\nmonitor_white_x = 0.2990\nmonitor_white_y = 0.3150\n").unwrap();
+        assert_eq!(load_settings(&paths).display.white_point(), (0.2990, 0.3150));
+
+        // One bad coordinate resets both
+        fs::write(&paths.config_file, "[display]\nmonitor_white_x = 0.2990\nmonitor_white_y = 3290\n").unwrap();
+        assert_eq!(load_settings(&paths).display.white_point(), (D65_X, D65_Y));
+        let _ = fs::remove_dir_all(&paths.config_dir);
+    }
+
+    #[test]
+    fn ramp_quantize_step_range_checked() {
+        let paths = test_paths("ramp-quantize");
+        assert_eq!(load_settings(&paths).display.ramp_quantize_step, RAMP_QUANTIZE_STEP);
+
+        fs::write(&paths.config_file, "[display]\nramp_quantize_step = 0\n").unwrap();
+        assert_eq!(load_settings(&paths).display.ramp_quantize_step, 0);
+
+        // A visible step is refused
+        fs::write(&paths.config_file, "[display]\nramp_quantize_step = 50\n").unwrap();
+        assert_eq!(load_settings(&paths).display.ramp_quantize_step, RAMP_QUANTIZE_STEP);
+        let _ = fs::remove_dir_all(&paths.config_dir);
+    }
+
+    #[test]
+    fn weather_provider_saved_and_checked() {
+        let paths = test_paths("provider");
+        fs::write(&paths.config_file, "[network]\nweather_refresh_minutes = 30\n").unwrap();
+        assert_eq!(load_settings(&paths).network.weather_provider, WeatherProvider::default());
+
+        save_network_params(&paths, WeatherProvider::Disabled).unwrap();
+        let net = load_settings(&paths).network;
+        assert_eq!((net.weather_provider, net.weather_refresh_sec), (WeatherProvider::Disabled, 1800));
+
+        // Unknown or not compiled in: keep the default
+        fs::write(&paths.config_file, "[network]\nweather_provider = open-meteo\n").unwrap();
+        assert_eq!(load_settings(&paths).network.weather_provider, WeatherProvider::default());
+        let missing = WeatherProvider::ALL.into_iter().find(|p| !p.available());
+        if let Some(p) = missing {
+            fs::write(&paths.config_file, format!("[network]\nweather_provider = {}\n", p.name())).unwrap();
+            assert_eq!(load_settings(&paths).network.weather_provider, WeatherProvider::default());
+        }
+        assert!(WeatherProvider::choices().ends_with("or disabled") || WeatherProvider::choices() == "disabled");
+        let _ = fs::remove_dir_all(&paths.config_dir);
+    }
+
+    #[test]
+    fn schedule_windows_parse_alongside_keyframes() {
+        let paths = test_paths("windows");
+        fs::write(
+            &paths.config_file,
+            "[schedule]\n07:00 = 6500\nfocus_start = 09:00\nfocus_end = 17:00\nfocus_temp = 6000\n\
+             evening_start = 20:00\nevening_temp = 3500\nlate_start = 23:00\nlate_end = 01:30\n\
+             late_temp = 2700\nbroken_start = 10:00\nhot_start = 1:00\nhot_temp = 90000\n",
+        )
+        .unwrap();
+        let s = load_settings(&paths);
+        let _ = fs::remove_dir_all(paths.config_file.parent().unwrap());
+
+        assert_eq!(s.schedule.unwrap().keyframes.len(), 1);
+        let names: Vec<&str> = s.schedule_windows.iter().map(|w| w.name.as_str()).collect();
+        assert_eq!(names, ["focus", "evening", "late"]);
+
+        let (focus, evening, late) = (&s.schedule_windows[0], &s.schedule_windows[1], &s.schedule_windows[2]);
+        assert_eq!((focus.start_hhmm, focus.end_hhmm, focus.temp_k), (540, 1020, 6000));
+        assert!(focus.contains(540) && focus.contains(1019) && !focus.contains(1020));
+        // No end: until midnight
+        assert!(evening.contains(1439) && !evening.contains(0));
+        // Wraps past midnight
+        assert!(late.contains(1400) && late.contains(60) && !late.contains(90));
+    }
+
+    #[test]
+    fn config_lock_excludes_until_dropped() {
+        use std::os::unix::io::AsRawFd;
+
+        let paths = test_paths("config-lock");
+        let try_lock = |op| {
+            let file = fs::File::open(&paths.lock_file).unwrap();
+            unsafe { libc::flock(file.as_raw_fd(), op | libc::LOCK_NB) == 0 }
+        };
+
+        let lock = lock_config_dir(&paths).unwrap();
+        assert!(!try_lock(libc::LOCK_SH));
+        drop(lock);
+        assert!(try_lock(libc::LOCK_SH));
+
+        // Shared holders only keep out the daemon
+        let shared = lock_config_dir_shared(&paths).unwrap();
+        assert!(try_lock(libc::LOCK_SH));
+        assert!(!try_lock(libc::LOCK_EX));
+        drop(shared);
+        assert!(try_lock(libc::LOCK_EX));
+        let _ = fs::remove_dir_all(&paths.config_dir);
+    }
+
+    #[test]
+    fn update_override_waits_for_override_lock() {
+        let paths = test_paths("override-lock");
+        fs::create_dir_all(&paths.state_dir).unwrap();
+        let ovr = OverrideState { active: true, target_temp: 4000, sequence: 1, ..Default::default() };
+        save_override(&paths, &ovr).unwrap();
+
+        let lock = lock_override(&paths).unwrap();
+        let writer = {
+            let paths = paths.clone();
+            let updated = OverrideState { active: true, target_temp: 5000, sequence: 1, ..Default::default() };
+            std::thread::spawn(move || update_override(&paths, &updated).unwrap())
+        };
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        assert_eq!(load_override(&paths).unwrap().target_temp, 4000);
+
+        // A --set landing while the lock is held wins; the update backs off
+        let newer = OverrideState { sequence: 2, ..ovr };
+        save_override(&paths, &newer).unwrap();
+        drop(lock);
+        assert!(!writer.join().unwrap());
+        assert_eq!(load_override(&paths).unwrap().sequence, 2);
+        let _ = fs::remove_dir_all(&paths.config_dir);
+    }
+}
@@ -1,43 +1,114 @@
 //! Daemon event loop.
 //!
-//! Linux kernel interfaces: io_uring (60s timeout + poll), inotify (config
-//! changes), signalfd (clean shutdown via SIGTERM/SIGINT). Single
-//! io_uring_enter per tick. Gamma control via auto-detected backend.
+//! Linux kernel interfaces: io_uring (60s timeout + poll, override.json
+//! reads), inotify (config changes), signalfd (clean shutdown via
+//! SIGTERM/SIGINT). Single io_uring_enter per tick. Gamma control via auto-detected backend.
 
-use crate::config::{self, Location, Paths, WeatherData};
+use crate::config::{self, DaemonStatus, Location, Paths, Settings, SolarMode, TempParams, WeatherData, WeatherProvider};
 use crate::{
-    sigmoid, solar, weather, CLOUD_THRESHOLD, TEMP_UPDATE_SEC, now_epoch,
+    inotify, weather, CliError, HOLD_RESUME_FADE_MIN, OVERRIDE_MAX_BYTES, OVERRIDE_TICK_SEC, TEMP_UPDATE_SEC,
+    format_duration,
     landlock, seccomp,
 };
-use crate::weather::FetchState;
+use crate::weather::{ReadResult, WeatherFetcher};
 use crate::gamma;
+use crate::hooks::HookRunner;
+use crate::notify::Notifier;
+use crate::clock::{Clock, SystemClock};
+use abraxas_core::sigmoid::{self, SolarWindow};
+use abraxas_core::solar::{self, PolarStatus};
 use crate::uring::{self, AbraxasRing, KernelTimespec};
+#[cfg(feature = "thermal")]
+use crate::thermal::ThermalMonitor;
+#[cfg(feature = "geoclue")]
+use crate::{geoclue, GEOCLUE_RECHECK_SEC, GEOCLUE_TIMEOUT_SEC};
 
-use std::ffi::CString;
 use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Instant;
 
 const GAMMA_INIT_MAX_RETRIES: i32 = 60;
 const GAMMA_INIT_RETRY_MS: u64 = 500;
 
+/// Seconds between warnings about io_uring polls that completed with an error
+const POLL_ERROR_WARN_SEC: u64 = 60;
+
+/// A GeoClue2 fix closer than this (degrees, ~1 km) to the saved location
+/// is not written back
+#[cfg(feature = "geoclue")]
+const GEOCLUE_MOVE_DEG: f64 = 0.01;
+
 // Atomic event flag bitmask
 const FLAG_TIMER:    u32 = 1 << 0;
 const FLAG_SIGNAL:   u32 = 1 << 1;
 const FLAG_WEATHER:  u32 = 1 << 2;
-const FLAG_OVERRIDE: u32 = 1 << 3;
-const FLAG_CONFIG:   u32 = 1 << 4;
+pub(crate) const FLAG_OVERRIDE: u32 = 1 << 3;
+pub(crate) const FLAG_CONFIG:   u32 = 1 << 4;
+const FLAG_WAYLAND:  u32 = 1 << 5;
+const FLAG_TIMER_FAST: u32 = 1 << 6;
+const FLAG_OVERRIDE_READ: u32 = 1 << 7;
 
 /// Multi-shot poll liveness tracking
 struct PollState {
     inotify: bool,
     signal: bool,
+    /// Weather pipe poll (or, with splice-weather, splice) in flight
     weather: bool,
+    wayland: bool,
+    /// Regular tick timeout armed (multi-shot ones stay armed across ticks)
+    timeout: bool,
+    /// Kernel takes IORING_TIMEOUT_MULTISHOT (cleared on its -EINVAL)
+    multishot_timeout: bool,
+    /// Interval of the multi-shot fast timeout armed (0 = none)
+    fast_timeout: i64,
+    /// Result of the last weather splice
+    #[cfg(feature = "splice-weather")]
+    splice_res: i32,
+    /// Fetch generation of the splice in flight
+    #[cfg(feature = "splice-weather")]
+    splice_generation: u32,
+    /// Result of the last override.json read
+    override_res: i32,
+    /// Last failed-poll warning, to rate-limit them
+    poll_error_warned: Option<Instant>,
+}
+
+impl PollState {
+    fn new() -> Self {
+        PollState {
+            inotify: false,
+            signal: false,
+            weather: false,
+            wayland: false,
+            timeout: false,
+            multishot_timeout: true,
+            fast_timeout: 0,
+            #[cfg(feature = "splice-weather")]
+            splice_res: 0,
+            #[cfg(feature = "splice-weather")]
+            splice_generation: 0,
+            override_res: 0,
+            poll_error_warned: None,
+        }
+    }
+}
+
+/// override.json read in flight through io_uring
+struct OverrideRead {
+    file: std::fs::File,
+    /// One byte over the limit, so an oversized file still fails to parse
+    buf: Box<[u8; OVERRIDE_MAX_BYTES + 1]>,
 }
 
 /// Full daemon runtime state
 struct DaemonState {
+    /// Time and zone everything below is computed in (a FakeClock in tests)
+    clock: Box<dyn Clock>,
     location: Location,
     paths: Paths,
+    settings: Settings,
     weather: Option<WeatherData>,
+    /// Async fetch for [network] weather_provider
+    weather_fetcher: Box<dyn WeatherFetcher>,
     gamma: Option<gamma::GammaState>,
 
     // Manual mode tracking
@@ -45,62 +116,137 @@ struct DaemonState {
     manual_start_temp: i32,
     manual_target_temp: i32,
     manual_start_time: i64,
-    manual_duration_min: i32,
+    manual_duration_sec: i64,
+    manual_easing: config::Easing,
     manual_issued_at: i64,
+    /// OverrideState::sequence of the override being run
+    manual_sequence: u64,
     manual_resume_time: i64,
-
-    // Last applied temperature
+    manual_hold_min: Option<i32>,
+    manual_brightness: f32,
+    // --hold: no auto-resume at the next dawn/dusk
+    manual_held: bool,
+    // Start of a scheduled (--at) override not yet due (0 = none)
+    pending_at: i64,
+    // Shorter tick interval while the temperature is moving (0 = off)
+    fast_tick_sec: i64,
+
+    // Fade back to solar control after a timed hold expires
+    resume_fade_start: i64,
+    resume_fade_from: i32,
+
+    // Paused via --toggle (gamma restored, no temperature applied)
+    paused: bool,
+
+    // override.json read submitted to the ring (the kernel writes into its
+    // buffer until the completion is reaped), and whether the file changed
+    // again meanwhile
+    override_read: Option<OverrideRead>,
+    override_reread: bool,
+
+    // Last applied temperature and brightness
     last_temp: i32,
     last_temp_valid: bool,
+    last_brightness: f32,
+    // Curve still moving (dawn/dusk, blend, override, fade): apply every 1K
+    transition_in_progress: bool,
+
+    // [hooks] scripts (paths allowed through the sandbox at startup)
+    hooks: HookRunner,
+    // Desktop notifications ([daemon] notify)
+    notifier: Notifier,
+
+    // CPU temperature dimming (opened before sandboxing)
+    #[cfg(feature = "thermal")]
+    thermal: Option<ThermalMonitor>,
+
+    // Last GeoClue2 lookup for an auto location (0 = not yet), and whether
+    // its failure has been reported
+    #[cfg(feature = "geoclue")]
+    geoclue_checked: i64,
+    #[cfg(feature = "geoclue")]
+    geoclue_warned: bool,
+
+    // ABRAXAS_VERBOSE=1: log the curve's working every tick
+    verbose: bool,
+
+    // Published runtime state (state.json)
+    started_at: i64,
+    tick_count: u64,
+    last_gamma_error: Option<String>,
+    published: Option<DaemonStatus>,
 }
 
-// --- Linux kernel fd helpers ---
-
-/// Set up inotify watching the config directory for file writes.
-fn setup_inotify(paths: &Paths) -> i32 {
-    let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC) };
-    if fd < 0 {
-        return -1;
-    }
-
-    let dir = match paths.override_file.parent() {
-        Some(d) => d,
-        None => {
-            unsafe { libc::close(fd) };
-            return -1;
-        }
-    };
-
-    let dir_cstr = match CString::new(dir.to_string_lossy().as_bytes()) {
-        Ok(c) => c,
-        Err(_) => {
-            unsafe { libc::close(fd) };
-            return -1;
+impl DaemonState {
+    fn new(
+        location: Location,
+        paths: &Paths,
+        settings: Settings,
+        weather: Option<WeatherData>,
+        gamma: Option<gamma::GammaState>,
+        clock: Box<dyn Clock>,
+    ) -> Self {
+        DaemonState {
+            started_at: clock.now(),
+            clock,
+            location,
+            paths: paths.clone(),
+            weather_fetcher: weather::fetcher(&settings.network),
+            notifier: Notifier::new(settings.daemon.notify),
+            settings,
+            weather,
+            gamma,
+            manual_mode: false,
+            manual_start_temp: 0,
+            manual_target_temp: 0,
+            manual_start_time: 0,
+            manual_duration_sec: 0,
+            manual_easing: config::Easing::Sigmoid,
+            manual_issued_at: 0,
+            manual_sequence: 0,
+            manual_resume_time: 0,
+            manual_hold_min: None,
+            manual_brightness: 1.0,
+            manual_held: false,
+            pending_at: 0,
+            fast_tick_sec: 0,
+            resume_fade_start: 0,
+            resume_fade_from: 0,
+            paused: false,
+            override_read: None,
+            override_reread: false,
+            last_temp: 0,
+            last_temp_valid: false,
+            last_brightness: 0.0,
+            transition_in_progress: false,
+            hooks: HookRunner::new(config::load_hook_params(paths)),
+            #[cfg(feature = "thermal")]
+            thermal: ThermalMonitor::open(&config::load_thermal_params(paths)),
+            #[cfg(feature = "geoclue")]
+            geoclue_checked: 0,
+            #[cfg(feature = "geoclue")]
+            geoclue_warned: false,
+            verbose: std::env::var_os("ABRAXAS_VERBOSE").is_some_and(|v| v == "1"),
+            tick_count: 0,
+            last_gamma_error: None,
+            published: None,
         }
-    };
-
-    let wd = unsafe {
-        libc::inotify_add_watch(
-            fd,
-            dir_cstr.as_ptr(),
-            libc::IN_CLOSE_WRITE,
-        )
-    };
-    if wd < 0 {
-        unsafe { libc::close(fd) };
-        return -1;
     }
-
-    fd
 }
 
-/// Block SIGTERM/SIGINT and create a signalfd for clean shutdown.
+// --- Linux kernel fd helpers ---
+
+/// Block SIGTERM/SIGINT/SIGHUP/SIGUSR1/SIGUSR2 and create a signalfd for
+/// clean shutdown, config reload and keybinding-friendly quick controls.
 fn setup_signalfd() -> i32 {
     unsafe {
         let mut mask: libc::sigset_t = std::mem::zeroed();
         libc::sigemptyset(&mut mask);
         libc::sigaddset(&mut mask, libc::SIGTERM);
         libc::sigaddset(&mut mask, libc::SIGINT);
+        libc::sigaddset(&mut mask, libc::SIGHUP);
+        libc::sigaddset(&mut mask, libc::SIGUSR1);
+        libc::sigaddset(&mut mask, libc::SIGUSR2);
 
         if libc::sigprocmask(libc::SIG_BLOCK, &mask, std::ptr::null_mut()) < 0 {
             return -1;
@@ -110,91 +256,404 @@ fn setup_signalfd() -> i32 {
     }
 }
 
-/// Parse inotify event buffer, returning flag bits for changed files.
-fn parse_inotify_events(buf: &[u8], paths: &Paths) -> u32 {
-    let override_name = paths.override_file.file_name().and_then(|n| n.to_str()).unwrap_or("override.json");
-    let config_name = paths.config_file.file_name().and_then(|n| n.to_str()).unwrap_or("config.ini");
-
-    const EVENT_HEADER_SIZE: usize = 16;
-    let mut offset = 0;
-    let mut flags = 0u32;
-
-    while offset + EVENT_HEADER_SIZE <= buf.len() {
-        let name_len = u32::from_ne_bytes([
-            buf[offset + 12], buf[offset + 13], buf[offset + 14], buf[offset + 15],
-        ]) as usize;
+/// Actions requested by signals received since the last drain
+#[derive(Default)]
+struct SignalActions {
+    shutdown: bool,
+    reload: bool,
+    toggle_pause: bool,
+    refresh_weather: bool,
+}
 
-        let event_size = EVENT_HEADER_SIZE + name_len;
-        if offset + event_size > buf.len() {
-            break;
-        }
+/// Drain pending signalfd records.
+fn read_signals(fd: i32) -> SignalActions {
+    const SI_SIZE: usize = std::mem::size_of::<libc::signalfd_siginfo>();
+    let mut buf: [libc::signalfd_siginfo; 4] = unsafe { std::mem::zeroed() };
+    let len = unsafe {
+        libc::read(fd, buf.as_mut_ptr() as *mut libc::c_void, SI_SIZE * buf.len())
+    };
+    let mut actions = SignalActions::default();
+    if len <= 0 {
+        return actions;
+    }
 
-        if name_len > 0 {
-            let name_bytes = &buf[offset + EVENT_HEADER_SIZE..offset + event_size];
-            let name_end = name_bytes.iter().position(|&b| b == 0).unwrap_or(name_bytes.len());
-            if let Ok(name) = std::str::from_utf8(&name_bytes[..name_end]) {
-                if name == override_name {
-                    flags |= FLAG_OVERRIDE;
-                }
-                if name == config_name {
-                    flags |= FLAG_CONFIG;
-                }
+    for si in &buf[..len as usize / SI_SIZE] {
+        match si.ssi_signo as i32 {
+            libc::SIGHUP => {
+                eprintln!("\nReceived SIGHUP, reloading configuration...");
+                actions.reload = true;
+            }
+            libc::SIGUSR1 => {
+                eprintln!("[signal] SIGUSR1: toggling pause");
+                actions.toggle_pause = !actions.toggle_pause;
             }
+            libc::SIGUSR2 => {
+                eprintln!("[signal] SIGUSR2: forcing weather refresh");
+                actions.refresh_weather = true;
+            }
+            libc::SIGTERM => {
+                eprintln!("\nReceived SIGTERM...");
+                actions.shutdown = true;
+            }
+            libc::SIGINT => {
+                eprintln!("\nReceived SIGINT...");
+                actions.shutdown = true;
+            }
+            other => eprintln!("[signal] Ignoring unexpected signal {}", other),
         }
+    }
+    actions
+}
+
+/// Flip the pause flag in override.json, the same file --toggle writes.
+/// Returns true if the write succeeded (tick then applies the change).
+fn toggle_pause(state: &DaemonState) -> bool {
+    let _lock = config::lock_override(&state.paths);
+    let mut ovr = config::load_override(&state.paths).unwrap_or_default();
+    ovr.paused = !state.paused;
+    config::save_override(&state.paths, &ovr).is_ok()
+}
+
+fn hhmm(epoch: i64, clock: &dyn Clock) -> String {
+    let lt = clock.local(epoch);
+    format!("{:02}:{:02}", lt.hour, lt.minute)
+}
+
+/// Calculate solar temperature given current state.
+fn solar_temperature(state: &DaemonState, now: i64) -> i32 {
+    solar_temp_at(&state.location, &state.settings, state.weather.as_ref(), now, &*state.clock)
+}
 
-        offset += event_size;
+/// Named [schedule] window covering `now`, if any (first match wins).
+pub fn active_window<'a>(settings: &'a Settings, now: i64, clock: &dyn Clock) -> Option<&'a config::ScheduleEntry> {
+    let minute = minute_of_day(now, clock) as u32;
+    settings.schedule_windows.iter().find(|w| w.contains(minute))
+}
+
+/// golden_hour_temp while the sun is in golden hour, if configured.
+pub fn golden_hour_temp(location: &Location, settings: &Settings, now: i64, clock: &dyn Clock) -> Option<i32> {
+    match settings.temperatures.golden_hour_temp {
+        0 => None,
+        temp => solar::golden_hour(now, location.lat, location.lon, clock).map(|_| temp),
     }
-    flags
 }
 
-struct LocalTime {
-    hour: i32,
-    min: i32,
-    sec: i32,
+/// Automatic temperature for a location, settings and (cached) weather:
+/// a named [schedule] window while one is active, else the [schedule]
+/// keyframes when configured, otherwise the solar curve (sunrise/sunset
+/// windows, or sun elevation with [daemon] mode = elevation), replaced by
+/// golden_hour_temp during golden hour.
+/// Shared with --oneshot so it applies exactly what the daemon would.
+pub fn solar_temp_at(
+    location: &Location,
+    settings: &Settings,
+    weather: Option<&WeatherData>,
+    now: i64,
+    clock: &dyn Clock,
+) -> i32 {
+    solar_temp_traced(location, settings, weather, now, clock).0
+}
+
+/// Inputs and working of the dawn/dusk curve at one instant
+pub struct SolarCurve {
+    pub minutes_from_sunrise: f64,
+    pub minutes_to_sunset: f64,
+    /// Cloud cover now and in 3h as the curve saw it (0 when unknown)
+    pub cloud_cover: i32,
+    pub cloud_cover_3h: i32,
+    pub trace: sigmoid::SolarTrace,
 }
 
-fn local_time(epoch: i64) -> LocalTime {
-    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
-    let t = epoch;
-    unsafe { libc::localtime_r(&t, &mut tm) };
-    LocalTime {
-        hour: tm.tm_hour,
-        min: tm.tm_min,
-        sec: tm.tm_sec,
+/// solar_temp_at, plus the curve's working when the dawn/dusk curve decided
+/// it (None for a schedule window, keyframes, golden hour, elevation mode
+/// or polar day/night).
+pub fn solar_temp_traced(
+    location: &Location,
+    settings: &Settings,
+    weather: Option<&WeatherData>,
+    now: i64,
+    clock: &dyn Clock,
+) -> (i32, Option<SolarCurve>) {
+    if let Some(w) = active_window(settings, now, clock) {
+        return (w.temp_k, None);
+    }
+    if let Some(ref sched) = settings.schedule {
+        return (sigmoid::calculate_schedule_temp(minute_of_day(now, clock), sched), None);
+    }
+    if let Some(temp) = golden_hour_temp(location, settings, now, clock) {
+        return (temp, None);
+    }
+
+    let (lat, lon) = (location.lat, location.lon);
+    let is_dark = is_dark_weather(weather, settings);
+    let temps = &TempParams { night: night_temp(settings, is_dark, now), ..settings.temperatures };
+
+    if settings.daemon.mode == SolarMode::Elevation {
+        let sp = solar::position(now, lat, lon, clock);
+        let temp = sigmoid::calculate_elevation_temp(sp.elevation, is_dark, temps, settings.daemon.elevation_low, settings.daemon.elevation_high);
+        return (temp, None);
     }
+    // Polar day holds the day endpoint (weather still applies), polar
+    // night holds night: neither has a transition to sit in
+    let times = match solar::polar_status(now, lat, lon, clock) {
+        PolarStatus::Normal(t) => t,
+        PolarStatus::Day if is_dark => return (temps.day_dark, None),
+        PolarStatus::Day => return (temps.day_clear, None),
+        PolarStatus::Night => return (temps.night, None),
+    };
+
+    // A failed fetch counts as clear, like is_dark_weather
+    let (cloud_cover, cloud_cover_3h) = match weather {
+        Some(w) if !w.has_error => (w.cloud_cover, w.cloud_cover_3h),
+        _ => (0, 0),
+    };
+    let minutes_from_sunrise = (now - times.sunrise) as f64 / 60.0;
+    let minutes_to_sunset = (times.sunset - now) as f64 / 60.0;
+    let trace = sigmoid::trace_solar_temp(
+        minutes_from_sunrise,
+        minutes_to_sunset,
+        cloud_cover,
+        cloud_cover_3h,
+        temps,
+        &settings.transitions,
+    );
+    (trace.temp, Some(SolarCurve { minutes_from_sunrise, minutes_to_sunset, cloud_cover, cloud_cover_3h, trace }))
 }
 
-/// Calculate solar temperature given current state.
-fn solar_temperature(now: i64, lat: f64, lon: f64, weather: &Option<WeatherData>) -> i32 {
-    let st = solar::sunrise_sunset(now, lat, lon);
-    let is_dark = weather
-        .as_ref()
-        .map(|w| !w.has_error && w.cloud_cover >= CLOUD_THRESHOLD)
-        .unwrap_or(false);
-
-    let (min_from_sunrise, min_to_sunset) = if let Some(ref times) = st {
-        (
+/// [temperatures] night, lowered under a bright moon when
+/// moon_brightness_reduction is on and no overcast hides it.
+fn night_temp(settings: &Settings, is_dark: bool, now: i64) -> i32 {
+    let night = settings.temperatures.night;
+    if !settings.temperatures.moon_brightness_reduction || is_dark {
+        return night;
+    }
+    sigmoid::moonlit_night_temp(night, solar::moon_illumination(now))
+}
+
+/// Whether the automatic curve is mid-transition at `now`.
+fn solar_transition_active(state: &DaemonState, now: i64) -> bool {
+    let clock = &*state.clock;
+    if active_window(&state.settings, now, clock).is_some() {
+        return false;
+    }
+    if let Some(ref sched) = state.settings.schedule {
+        return sigmoid::in_schedule_blend(minute_of_day(now, clock), sched);
+    }
+    if golden_hour_temp(&state.location, &state.settings, now, clock).is_some() {
+        return false;
+    }
+    let (lat, lon) = (state.location.lat, state.location.lon);
+    if state.settings.daemon.mode == SolarMode::Elevation {
+        let sp = solar::position(now, lat, lon, clock);
+        return sigmoid::in_elevation_transition(sp.elevation, state.settings.daemon.elevation_low, state.settings.daemon.elevation_high);
+    }
+    match solar::polar_status(now, lat, lon, clock) {
+        PolarStatus::Normal(times) => sigmoid::in_solar_transition(
+            (now - times.sunrise) as f64 / 60.0,
+            (times.sunset - now) as f64 / 60.0,
+            &state.settings.transitions,
+        ),
+        PolarStatus::Day | PolarStatus::Night => false,
+    }
+}
+
+/// The dawn or dusk window the solar curve is in, with its minutes left
+/// (unknown in elevation mode). None while a [schedule], a schedule
+/// window or golden hour sets the temperature, and on polar days.
+fn solar_window_at(state: &DaemonState, now: i64) -> Option<(SolarWindow, Option<f64>)> {
+    let (settings, clock) = (&state.settings, &*state.clock);
+    if settings.schedule.is_some()
+        || active_window(settings, now, clock).is_some()
+        || golden_hour_temp(&state.location, settings, now, clock).is_some()
+    {
+        return None;
+    }
+    let (lat, lon) = (state.location.lat, state.location.lon);
+    if settings.daemon.mode == SolarMode::Elevation {
+        let elevation = solar::position(now, lat, lon, clock).elevation;
+        if !sigmoid::in_elevation_transition(elevation, settings.daemon.elevation_low, settings.daemon.elevation_high) {
+            return None;
+        }
+        let rising = solar::position(now + 60, lat, lon, clock).elevation > elevation;
+        return Some((if rising { SolarWindow::Dawn } else { SolarWindow::Dusk }, None));
+    }
+    match solar::polar_status(now, lat, lon, clock) {
+        PolarStatus::Normal(times) => sigmoid::solar_window(
             (now - times.sunrise) as f64 / 60.0,
             (times.sunset - now) as f64 / 60.0,
+            &settings.transitions,
         )
+        .map(|(window, left)| (window, Some(left))),
+        PolarStatus::Day | PolarStatus::Night => None,
+    }
+}
+
+/// Local time of day in minutes (with seconds as a fraction).
+pub fn minute_of_day(epoch: i64, clock: &dyn Clock) -> f64 {
+    let lt = clock.local(epoch);
+    (lt.hour * 60 + lt.minute) as f64 + lt.second as f64 / 60.0
+}
+
+/// Configured (or --set --brightness override) brightness, reduced while
+/// the CPU runs hot (feature = "thermal").
+fn effective_brightness(state: &mut DaemonState) -> f32 {
+    let brightness = if state.manual_mode {
+        state.manual_brightness
     } else {
-        (0.0, 0.0)
+        state.settings.display.brightness
+    };
+    #[cfg(feature = "thermal")]
+    {
+        if let Some(ref mut m) = state.thermal {
+            return m.adjust_brightness(brightness);
+        }
+    }
+    brightness
+}
+
+/// Weather to start from: the cache, or clear skies when fetching is off
+fn initial_weather(paths: &Paths, settings: &Settings) -> Option<WeatherData> {
+    match settings.network.weather_provider {
+        WeatherProvider::Disabled => Some(weather::disabled()),
+        _ => config::load_weather_cache_locked(paths),
+    }
+}
+
+/// Overcast enough to use the dark daytime target.
+fn weather_is_dark(state: &DaemonState) -> bool {
+    is_dark_weather(state.weather.as_ref(), &state.settings)
+}
+
+pub fn is_dark_weather(weather: Option<&WeatherData>, settings: &Settings) -> bool {
+    weather
+        .map(|w| !w.has_error && w.cloud_cover >= settings.temperatures.cloud_threshold)
+        .unwrap_or(false)
+}
+
+/// Re-locate an `--set-location auto` config through GeoClue2, at startup
+/// and then daily. A move is saved to config.ini and picked up by the
+/// inotify reload like any other edit. Blocks for up to
+/// GEOCLUE_TIMEOUT_SEC, which once a day is fine. Failures keep the saved
+/// location and are reported once until a lookup succeeds again.
+#[cfg(feature = "geoclue")]
+fn check_geoclue(state: &mut DaemonState) {
+    let now = state.clock.now();
+    if state.geoclue_checked != 0 && now - state.geoclue_checked < GEOCLUE_RECHECK_SEC {
+        return;
+    }
+    state.geoclue_checked = now;
+    if !config::location_is_auto(&state.paths) {
+        return;
+    }
+
+    let fix = match geoclue::locate(std::time::Duration::from_secs(GEOCLUE_TIMEOUT_SEC)) {
+        Ok(f) => f,
+        Err(e) => {
+            if !state.geoclue_warned {
+                eprintln!("[warn] GeoClue2: {}; keeping the configured location", e);
+                state.geoclue_warned = true;
+            }
+            return;
+        }
     };
+    state.geoclue_warned = false;
 
-    sigmoid::calculate_solar_temp(min_from_sunrise, min_to_sunset, is_dark)
+    // The configured location, not a profile's
+    let saved = match config::load_location(&state.paths) {
+        Some(l) => l,
+        None => Location { lat: f64::NAN, lon: f64::NAN },
+    };
+    if (saved.lat - fix.lat).abs() < GEOCLUE_MOVE_DEG && (saved.lon - fix.lon).abs() < GEOCLUE_MOVE_DEG {
+        return;
+    }
+    match config::save_auto_location(&state.paths, fix.lat, fix.lon) {
+        Ok(()) => eprintln!(
+            "[geoclue] Moved to {:.4}, {:.4} (within {:.1} km)",
+            fix.lat, fix.lon, fix.accuracy_m / 1000.0
+        ),
+        Err(e) => eprintln!("[warn] GeoClue2: cannot save the new location: {}", e),
+    }
 }
 
-/// Read inotify events from fd, returning flag bits.
-fn parse_inotify_fd(fd: i32, paths: &Paths) -> u32 {
-    let mut buf = [0u8; 4096];
-    let len = unsafe { libc::read(fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) };
-    if len > 0 {
-        parse_inotify_events(&buf[..len as usize], paths)
+/// "clear", "dark", "manual" or "paused", as published in state.json
+fn status_mode(state: &DaemonState) -> &'static str {
+    if state.paused {
+        "paused"
+    } else if state.manual_mode {
+        "manual"
+    } else if weather_is_dark(state) {
+        "dark"
     } else {
-        0
+        "clear"
+    }
+}
+
+/// A status mode as hooks and status bars see it: clear and dark read as
+/// "night" while the sun is below the horizon.
+pub fn mode_with_night(mode: &str, sun_elevation: f64) -> &str {
+    match mode {
+        "clear" | "dark" if !solar::sun_is_up(sun_elevation) => "night",
+        mode => mode,
+    }
+}
+
+/// Tell the [hooks] scripts what is applied now.
+fn run_hooks(state: &mut DaemonState) {
+    let now = state.clock.now();
+    let sp = solar::position(now, state.location.lat, state.location.lon, &*state.clock);
+    let mode = mode_with_night(status_mode(state), sp.elevation);
+    let temp = Some(state.last_temp).filter(|_| state.last_temp_valid);
+    state.hooks.notify(temp, mode, state.last_brightness, now);
+}
+
+/// Write state.json when the applied state changes, or on every timer tick
+/// (`force`) so last_tick stays fresh. Writes are atomic renames; the
+/// inotify wakeup they cause is harmless since nothing changes on re-tick.
+fn publish_status(state: &mut DaemonState, force: bool) {
+    let mode = status_mode(state);
+    let now = state.clock.now();
+    let (lat, lon) = (state.location.lat, state.location.lon);
+    let sp = solar::position(now, lat, lon, &*state.clock);
+    let st = solar::sunrise_sunset(now, lat, lon, &*state.clock);
+
+    let status = DaemonStatus {
+        pid: unsafe { libc::getpid() },
+        applied_temp: if state.last_temp_valid { state.last_temp } else { 0 },
+        brightness: state.last_brightness,
+        backend: state.gamma.as_ref().map(|g| g.backend_name()).unwrap_or("none").to_string(),
+        mode: mode.to_string(),
+        last_tick: now,
+        tick_count: state.tick_count,
+        started_at: state.started_at,
+        last_error: state.last_gamma_error.clone(),
+        sun_elevation: sp.elevation,
+        sun_azimuth: sp.azimuth,
+        solar_noon: st.as_ref().map(|t| t.solar_noon),
+        day_length_min: st.as_ref().map(|t| (t.sunset - t.sunrise) / 60),
+        ramp_cache_hits: state.gamma.as_ref().map(|g| g.ramp_cache_hits()).unwrap_or(0),
+        last_notify_error: state.notifier.last_error().map(str::to_string),
+    };
+
+    let changed = match state.published {
+        Some(ref p) => {
+            p.applied_temp != status.applied_temp
+                || p.brightness != status.brightness
+                || p.mode != status.mode
+                || p.last_error != status.last_error
+                || p.last_notify_error != status.last_notify_error
+        }
+        None => true,
+    };
+
+    if force || changed {
+        if let Err(e) = config::save_daemon_status(&state.paths, &status) {
+            eprintln!("[warn] Failed to write state file: {}", e);
+        }
+        state.published = Some(status);
     }
 }
 
+
 /// Unified CQE handler -- used by both main drain and cancel drain.
 fn process_cqe(
     cqe: &uring::IoUringCqe,
@@ -205,8 +664,29 @@ fn process_cqe(
 ) {
     let more = cqe.flags & uring::IORING_CQE_F_MORE != 0;
     match cqe.user_data {
+        // Only an expiry counts; a timeout cancelled after an early wake is just drained
         uring::EV_TIMEOUT => {
-            events.fetch_or(FLAG_TIMER, Ordering::Relaxed);
+            if cqe.res == -libc::ETIME {
+                events.fetch_or(FLAG_TIMER, Ordering::Relaxed);
+            } else if cqe.res == -libc::EINVAL && polls.multishot_timeout {
+                eprintln!("[uring] Kernel lacks IORING_TIMEOUT_MULTISHOT, re-arming the timeout each tick");
+                polls.multishot_timeout = false;
+            }
+            if !more { polls.timeout = false; }
+        }
+        uring::EV_TIMEOUT_FAST => {
+            if cqe.res == -libc::ETIME {
+                events.fetch_or(FLAG_TIMER_FAST, Ordering::Relaxed);
+            } else if cqe.res == -libc::EINVAL {
+                // Refused multi-shot; the regular timeout's -EINVAL switches
+                // both to one-shot
+                polls.fast_timeout = 0;
+            }
+        }
+        // A poll that completes with an error (EPOLLERR, cancelled) is gone
+        // whatever the flags say; re-arm it next iteration
+        uring::EV_SIGNAL | uring::EV_INOTIFY | uring::EV_WEATHER | uring::EV_WAYLAND if cqe.res < 0 => {
+            poll_failed(polls, cqe.user_data, cqe.res);
         }
         uring::EV_SIGNAL => {
             events.fetch_or(FLAG_SIGNAL, Ordering::Relaxed);
@@ -214,7 +694,7 @@ fn process_cqe(
         }
         uring::EV_INOTIFY => {
             if cqe.res > 0 {
-                let bits = parse_inotify_fd(ino_fd, paths);
+                let bits = inotify::read_events(ino_fd, paths);
                 events.fetch_or(bits, Ordering::Relaxed);
             }
             if !more { polls.inotify = false; }
@@ -225,15 +705,72 @@ fn process_cqe(
             }
             if !more { polls.weather = false; }
         }
+        // From an aborted fetch: its memfd and pipe are gone
+        #[cfg(feature = "splice-weather")]
+        tag if tag as u32 as u64 == uring::EV_SPLICE && tag != uring::splice_tag(polls.splice_generation) => {}
+        #[cfg(feature = "splice-weather")]
+        tag if tag as u32 as u64 == uring::EV_SPLICE => {
+            // One-shot; EOF (0) and errors need handling as much as data
+            polls.splice_res = cqe.res;
+            polls.weather = false;
+            events.fetch_or(FLAG_WEATHER, Ordering::Relaxed);
+        }
+        uring::EV_WAYLAND => {
+            if cqe.res > 0 {
+                events.fetch_or(FLAG_WAYLAND, Ordering::Relaxed);
+            }
+            if !more { polls.wayland = false; }
+        }
+        uring::EV_OVERRIDE_READ => {
+            polls.override_res = cqe.res;
+            events.fetch_or(FLAG_OVERRIDE_READ, Ordering::Relaxed);
+        }
         uring::EV_CANCEL => {}
         _ => {}
     }
 }
 
+/// Mark a failed poll for re-arming, warning at most every
+/// POLL_ERROR_WARN_SEC (a poll that keeps failing fails every iteration)
+fn poll_failed(polls: &mut PollState, user_data: u64, res: i32) {
+    let name = match user_data {
+        uring::EV_SIGNAL => {
+            polls.signal = false;
+            "signalfd"
+        }
+        uring::EV_INOTIFY => {
+            polls.inotify = false;
+            "inotify"
+        }
+        uring::EV_WEATHER => {
+            polls.weather = false;
+            "weather pipe"
+        }
+        _ => {
+            polls.wayland = false;
+            "compositor socket"
+        }
+    };
+    let now = Instant::now();
+    let quiet = polls
+        .poll_error_warned
+        .is_some_and(|t| now.duration_since(t).as_secs() < POLL_ERROR_WARN_SEC);
+    if !quiet {
+        eprintln!(
+            "[uring] Poll on {} failed ({}), re-arming",
+            name,
+            std::io::Error::from_raw_os_error(-res)
+        );
+        polls.poll_error_warned = Some(now);
+    }
+}
+
 /// io_uring event loop with multi-shot polls and atomic event flags.
+/// `polls` starts out as PollState::new(), nothing armed yet.
 fn event_loop_uring(
     state: &mut DaemonState,
     ring: &mut AbraxasRing,
+    mut polls: PollState,
     ino_fd: i32,
     signal_fd: i32,
 ) {
@@ -242,30 +779,67 @@ fn event_loop_uring(
         tv_nsec: 0,
     };
 
-    let mut wfs = FetchState::new();
-    let mut polls = PollState {
-        inotify: false,
-        signal: false,
-        weather: false,
-    };
+    // Compositor socket (Wayland backend only) for output hotplug events
+    let wayland_fd = state.gamma.as_ref().and_then(|g| g.event_fd()).unwrap_or(-1);
 
     loop {
-        // Register multi-shot polls only when not alive
+        // Register multi-shot polls only when not alive; one that found
+        // the SQ ring full is tried again next iteration
         if ino_fd >= 0 && !polls.inotify {
-            ring.prep_poll(ino_fd, uring::EV_INOTIFY);
-            polls.inotify = true;
+            polls.inotify = ring.prep_poll(ino_fd, uring::EV_INOTIFY);
         }
         if signal_fd >= 0 && !polls.signal {
-            ring.prep_poll(signal_fd, uring::EV_SIGNAL);
-            polls.signal = true;
+            polls.signal = ring.prep_poll(signal_fd, uring::EV_SIGNAL);
         }
-        if wfs.needs_poll() && !polls.weather {
-            ring.prep_poll(wfs.pipe_fd, uring::EV_WEATHER);
-            polls.weather = true;
+        let wf = &state.weather_fetcher;
+        if wf.needs_poll() && !polls.weather {
+            #[cfg(feature = "splice-weather")]
+            let armed = {
+                polls.splice_generation = wf.splice_generation();
+                let tag = uring::splice_tag(polls.splice_generation);
+                ring.prep_splice(wf.pipe_fd(), wf.splice_fd(), uring::PIPE_SPLICE_MAX, tag)
+            };
+            #[cfg(not(feature = "splice-weather"))]
+            let armed = ring.prep_poll(wf.pipe_fd(), uring::EV_WEATHER);
+            polls.weather = armed;
+        }
+        if wayland_fd >= 0 && !polls.wayland {
+            polls.wayland = ring.prep_poll(wayland_fd, uring::EV_WAYLAND);
+        }
+
+        // Multi-shot timeouts stay armed across wakes, so an early wake costs
+        // one io_uring_enter; one-shot ones (pre-5.19 kernels) are
+        // re-submitted every iteration
+        if !polls.timeout {
+            polls.timeout = if polls.multishot_timeout {
+                ring.prep_timeout_multishot(&ts, uring::EV_TIMEOUT)
+            } else {
+                ring.prep_timeout(&ts, uring::EV_TIMEOUT)
+            };
         }
 
-        // Fresh timeout each iteration (one-shot)
-        ring.prep_timeout(&ts, uring::EV_TIMEOUT);
+        // Plus a shorter one while a transition runs
+        // (the kernel copies the timespec at submit)
+        let fast_ts = KernelTimespec {
+            tv_sec: state.fast_tick_sec,
+            tv_nsec: 0,
+        };
+        let mut fast_oneshot = false;
+        if !polls.multishot_timeout {
+            fast_oneshot = state.fast_tick_sec > 0 && ring.prep_timeout(&fast_ts, uring::EV_TIMEOUT_FAST);
+        } else if polls.fast_timeout != state.fast_tick_sec {
+            // Replaced only when the transition changes its interval or ends
+            // (the cancel goes in first, so it can't hit the new one)
+            if polls.fast_timeout > 0 && ring.prep_cancel(uring::EV_TIMEOUT_FAST, uring::EV_CANCEL) {
+                polls.fast_timeout = 0;
+            }
+            if polls.fast_timeout == 0
+                && state.fast_tick_sec > 0
+                && ring.prep_timeout_multishot(&fast_ts, uring::EV_TIMEOUT_FAST)
+            {
+                polls.fast_timeout = state.fast_tick_sec;
+            }
+        }
 
         let ret = ring.submit_and_wait();
         if ret < 0 {
@@ -281,9 +855,14 @@ fn event_loop_uring(
 
         let mut flags = events.load(Ordering::Relaxed);
 
-        // Cancel timeout if woke early -- drain through same handler
-        if flags & FLAG_TIMER == 0 {
-            ring.prep_cancel(uring::EV_TIMEOUT, uring::EV_CANCEL);
+        // One-shot timeouts that didn't fire (woke early) are cancelled, so
+        // the next tick is a full interval from now -- drain through same
+        // handler. Multi-shot ones just keep their cadence.
+        let cancel_regular = !polls.multishot_timeout && polls.timeout && flags & FLAG_TIMER == 0
+            && ring.prep_cancel(uring::EV_TIMEOUT, uring::EV_CANCEL);
+        let cancel_fast = fast_oneshot && flags & FLAG_TIMER_FAST == 0
+            && ring.prep_cancel(uring::EV_TIMEOUT_FAST, uring::EV_CANCEL);
+        if cancel_regular || cancel_fast {
             ring.submit_and_wait();
             while let Some(cqe) = ring.peek_cqe() {
                 process_cqe(cqe, &events, &mut polls, ino_fd, &state.paths);
@@ -292,95 +871,271 @@ fn event_loop_uring(
             flags = events.load(Ordering::Relaxed);
         }
 
-        if flags & FLAG_SIGNAL != 0 {
-            if signal_fd >= 0 {
-                let mut buf = [0u8; 128];
-                unsafe {
-                    libc::read(signal_fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len());
-                }
-            }
-            eprintln!("\nReceived shutdown signal...");
-            wfs.abort();
+        // Nothing we watch for (e.g. our own state.json write woke inotify):
+        // skip the tick, or publishing its result would wake us again
+        if flags == 0 {
+            continue;
+        }
+
+        if !handle_flags(state, flags, signal_fd, &mut polls, Some(ring)) {
             break;
         }
+    }
+}
+
+/// Everything after the wait, shared by both event loops: signals,
+/// compositor events, override.json, the tick and the weather fetch.
+/// `ring` is None under ppoll, where the tick reads override.json itself.
+/// Returns false on shutdown.
+fn handle_flags(
+    state: &mut DaemonState,
+    mut flags: u32,
+    signal_fd: i32,
+    polls: &mut PollState,
+    ring: Option<&mut AbraxasRing>,
+) -> bool {
+    // SIGTERM/SIGINT shut down; SIGHUP reloads like a config.ini write;
+    // SIGUSR1 toggles pause like --toggle; SIGUSR2 refreshes weather now
+    let mut sig = SignalActions::default();
+    if flags & FLAG_SIGNAL != 0 && signal_fd >= 0 {
+        sig = read_signals(signal_fd);
+        if sig.shutdown {
+            state.weather_fetcher.abort();
+            return false;
+        }
+        if sig.toggle_pause && toggle_pause(state) {
+            flags |= FLAG_OVERRIDE;
+        }
+    }
 
-        tick(state, flags & FLAG_OVERRIDE != 0, flags & FLAG_CONFIG != 0);
+    if flags & FLAG_WAYLAND != 0 {
+        if let Some(ref mut g) = state.gamma {
+            if g.dispatch_events() {
+                eprintln!("[wayland] Outputs changed, re-applying temperature");
+                state.last_temp_valid = false;
+            }
+        }
+    }
 
-        // Async weather fetch (non-blocking, io_uring integrated)
-        #[cfg(feature = "noaa")]
-        {
-            use crate::weather::{FetchPhase, ReadResult};
+    // override.json is read through the ring and applied once the read
+    // completes; without a ring, or if it can't be submitted, the tick
+    // reads it directly
+    let mut override_changed = false;
+    if flags & FLAG_OVERRIDE_READ != 0 && state.override_read.is_some() {
+        let ovr = finish_override_read(state, polls.override_res);
+        let now = state.clock.now();
+        apply_override(state, ovr, now);
+    }
+    let reread = std::mem::take(&mut state.override_reread);
+    if flags & FLAG_OVERRIDE != 0 || reread {
+        match ring {
+            Some(_) if state.override_read.is_some() => state.override_reread = true,
+            Some(ring) => override_changed = !start_override_read(state, ring),
+            None => override_changed = true,
+        }
+    }
 
-            if wfs.phase == FetchPhase::Idle {
-                let needs = if let Some(ref w) = state.weather {
-                    config::weather_needs_refresh(w)
-                } else {
-                    true
-                };
-                if needs {
-                    let lt = local_time(now_epoch());
-                    eprintln!(
-                        "[{:02}:{:02}:{:02}] Starting weather fetch...",
-                        lt.hour, lt.min, lt.sec
-                    );
-                    wfs.start(state.location.lat, state.location.lon);
-                    polls.weather = false; // new pipe_fd needs registration
+    tick(state, override_changed, flags & FLAG_CONFIG != 0 || sig.reload);
+    state.notifier.flush();
+    publish_status(state, flags & FLAG_TIMER != 0);
+    run_hooks(state);
+    #[cfg(feature = "geoclue")]
+    check_geoclue(state);
+
+    // Async weather fetch (non-blocking, its pipe polled by the event loop)
+    // A reload that switched provider takes effect between fetches
+    if state.weather_fetcher.is_idle()
+        && state.weather_fetcher.provider() != state.settings.network.weather_provider
+    {
+        state.weather_fetcher = weather::fetcher(&state.settings.network);
+        polls.weather = false;
+        eprintln!("[config] Weather provider: {}", state.weather_fetcher.provider().name());
+    }
+
+    let wf = &mut state.weather_fetcher;
+    if wf.is_idle() && wf.provider() != WeatherProvider::Disabled {
+        let refresh_sec = state.settings.network.weather_refresh_sec;
+        let now = state.clock.now();
+        let needs = sig.refresh_weather
+            || state.weather
+                .as_ref()
+                .map(|w| config::weather_needs_refresh(w, refresh_sec, now))
+                .unwrap_or(true);
+        if needs {
+            let lt = state.clock.local(state.clock.now());
+            eprintln!(
+                "[{:02}:{:02}:{:02}] Starting weather fetch...",
+                lt.hour, lt.minute, lt.second
+            );
+            wf.set_timeouts(&state.settings.network); // [network] may have been reloaded
+            wf.start(state.location.lat, state.location.lon);
+            polls.weather = false; // new pipe_fd needs registration
+        }
+    }
+
+    // Stray completions from a fetch that was replaced are not ours
+    if flags & FLAG_WEATHER != 0 && !wf.is_idle() {
+        #[cfg(feature = "splice-weather")]
+        let read = wf.spliced(polls.splice_res);
+        #[cfg(not(feature = "splice-weather"))]
+        let read = wf.read_response();
+        match read {
+            ReadResult::Pending => {}
+            ReadResult::NewPipe => {
+                polls.weather = false; // new pipe_fd needs registration
+            }
+            ReadResult::Done(result) => {
+                polls.weather = false;
+                match result {
+                    Ok(wd) => {
+                        let _ = config::save_weather_cache_locked(&state.paths, &wd);
+                        eprintln!(
+                            "  Weather: {} ({}% clouds)",
+                            wd.forecast, wd.cloud_cover
+                        );
+                        state.weather = Some(wd);
+                    }
+                    Err(_) => {
+                        eprintln!("  Weather fetch failed");
+                        state.weather = Some(WeatherData {
+                            cloud_cover: 0,
+                            cloud_cover_3h: 0,
+                            forecast: "Unknown".to_string(),
+                            temperature: 0.0,
+                            is_day: true,
+                            fetched_at: state.clock.now(),
+                            has_error: true,
+                        });
+                    }
                 }
             }
+        }
+    }
+    true
+}
+
+/// Fallback event loop on ppoll(2) for kernels without io_uring (or with
+/// kernel.io_uring_disabled set): the same fds and flags, and the same
+/// full TEMP_UPDATE_SEC (or fast tick) from every wake.
+fn event_loop_ppoll(state: &mut DaemonState, ino_fd: i32, signal_fd: i32) {
+    let mut polls = PollState::new();
+    let wayland_fd = state.gamma.as_ref().and_then(|g| g.event_fd()).unwrap_or(-1);
+
+    loop {
+        let wf = &state.weather_fetcher;
+        let watched = [
+            (ino_fd, uring::EV_INOTIFY),
+            (signal_fd, uring::EV_SIGNAL),
+            (if wf.needs_poll() { wf.pipe_fd() } else { -1 }, uring::EV_WEATHER),
+            (wayland_fd, uring::EV_WAYLAND),
+        ];
+        let mut fds: Vec<libc::pollfd> = watched
+            .iter()
+            .map(|&(fd, _)| libc::pollfd { fd, events: libc::POLLIN, revents: 0 })
+            .collect();
+
+        // Negative fds are skipped by ppoll, so the slots line up with `watched`
+        let fast = state.fast_tick_sec > 0 && state.fast_tick_sec < TEMP_UPDATE_SEC;
+        let wait_sec = if fast { state.fast_tick_sec } else { TEMP_UPDATE_SEC };
+        let ts = libc::timespec { tv_sec: wait_sec, tv_nsec: 0 };
+        let ret = unsafe { libc::ppoll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, &ts, std::ptr::null()) };
+        if ret < 0 {
+            if std::io::Error::last_os_error().raw_os_error() == Some(libc::EINTR) {
+                continue;
+            }
+            break;
+        }
 
-            if flags & FLAG_WEATHER != 0 {
-                match wfs.read_response() {
-                    ReadResult::Pending => {}
-                    ReadResult::NewPipe => {
-                        polls.weather = false; // new pipe_fd needs registration
-                    }
-                    ReadResult::Done(result) => {
-                        polls.weather = false;
-                        match result {
-                            Ok(wd) => {
-                                let _ = config::save_weather_cache(&state.paths, &wd);
-                                eprintln!(
-                                    "  Weather: {} ({}% clouds)",
-                                    wd.forecast, wd.cloud_cover
-                                );
-                                state.weather = Some(wd);
-                            }
-                            Err(_) => {
-                                eprintln!("  Weather fetch failed");
-                                state.weather = Some(WeatherData {
-                                    cloud_cover: 0,
-                                    forecast: "Unknown".to_string(),
-                                    temperature: 0.0,
-                                    is_day: true,
-                                    fetched_at: now_epoch(),
-                                    has_error: true,
-                                });
-                            }
-                        }
+        let mut flags = 0;
+        if ret == 0 {
+            flags |= if fast { FLAG_TIMER_FAST } else { FLAG_TIMER };
+        }
+        for (pfd, &(_, ev)) in fds.iter().zip(&watched) {
+            if pfd.revents == 0 {
+                continue;
+            }
+            match ev {
+                uring::EV_INOTIFY => flags |= inotify::read_events(ino_fd, &state.paths),
+                uring::EV_SIGNAL => flags |= FLAG_SIGNAL,
+                #[cfg(feature = "splice-weather")]
+                uring::EV_WEATHER => {
+                    // The pipe is blocking; a readable one won't stall the splice
+                    let n = unsafe {
+                        libc::splice(
+                            pfd.fd,
+                            std::ptr::null_mut(),
+                            state.weather_fetcher.splice_fd(),
+                            std::ptr::null_mut(),
+                            uring::PIPE_SPLICE_MAX as usize,
+                            libc::SPLICE_F_NONBLOCK,
+                        )
+                    };
+                    polls.splice_res = if n >= 0 {
+                        n as i32
+                    } else {
+                        -std::io::Error::last_os_error().raw_os_error().unwrap_or(libc::EIO)
+                    };
+                    if polls.splice_res != -libc::EAGAIN {
+                        flags |= FLAG_WEATHER;
                     }
                 }
+                #[cfg(not(feature = "splice-weather"))]
+                uring::EV_WEATHER => flags |= FLAG_WEATHER,
+                uring::EV_WAYLAND => flags |= FLAG_WAYLAND,
+                _ => {}
             }
         }
+
+        // Nothing we watch for (e.g. our own state.json write woke inotify)
+        if flags == 0 {
+            continue;
+        }
+
+        if !handle_flags(state, flags, signal_fd, &mut polls, None) {
+            break;
+        }
     }
 }
 
-pub fn run(location: Location, paths: &Paths) {
-    // Block SIGTERM/SIGINT immediately and create signalfd.
+/// Run the daemon until a shutdown signal. Fatal errors come back as Err
+/// after whatever was set up (PID file, gamma, fds) has been undone.
+pub fn run(location: Location, paths: &Paths, force_gnome: bool) -> Result<(), CliError> {
+    // Block SIGTERM/SIGINT/SIGHUP immediately and create signalfd.
     // Must happen before gamma retry so SIGTERM is never lost during init.
     let signal_fd = setup_signalfd();
 
+    // Single instance: the PID file lock is authoritative, held until exit
+    let _pid_lock = match config::lock_pid_file(paths) {
+        Ok(f) => f,
+        Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+            close_fd(signal_fd);
+            return Err(CliError::Fatal(match config::read_pid(paths) {
+                Some(pid) => format!("[fatal] daemon already running (pid {})", pid),
+                None => "[fatal] daemon already running".to_string(),
+            }));
+        }
+        Err(e) => {
+            close_fd(signal_fd);
+            return Err(CliError::Fatal(format!(
+                "[fatal] Cannot lock PID file {}: {}",
+                paths.pid_file.display(),
+                e
+            )));
+        }
+    };
+
     // Initialize gamma with retries
     let mut gamma_state = None;
     for attempt in 0..GAMMA_INIT_MAX_RETRIES {
-        match gamma::init() {
+        match gamma::init_card(0, force_gnome) {
             Ok(state) => {
                 gamma_state = Some(state);
                 break;
             }
             Err(e) => {
                 if attempt == GAMMA_INIT_MAX_RETRIES - 1 {
-                    eprintln!("[fatal] No gamma backend after 30s: {}", e);
-                    std::process::exit(1);
+                    close_fd(signal_fd);
+                    return Err(CliError::Fatal(format!("[fatal] No gamma backend after 30s: {}", e)));
                 }
                 // Check for SIGTERM between retries (non-blocking)
                 if signal_fd >= 0 {
@@ -389,10 +1144,11 @@ pub fn run(location: Location, paths: &Paths) {
                         events: libc::POLLIN,
                         revents: 0,
                     };
-                    if unsafe { libc::poll(&mut pfd, 1, 0) } > 0 {
+                    // SIGHUP here is consumed; config is loaded after init anyway
+                    if unsafe { libc::poll(&mut pfd, 1, 0) } > 0 && read_signals(signal_fd).shutdown {
                         eprintln!("Received signal during gamma init, exiting...");
-                        unsafe { libc::close(signal_fd) };
-                        std::process::exit(0);
+                        close_fd(signal_fd);
+                        return Ok(());
                     }
                 }
                 std::thread::sleep(std::time::Duration::from_millis(GAMMA_INIT_RETRY_MS));
@@ -400,32 +1156,45 @@ pub fn run(location: Location, paths: &Paths) {
         }
     }
 
-    // Load initial weather
-    let weather = config::load_weather_cache(paths);
-
-    let mut state = DaemonState {
-        location,
-        paths: paths.clone(),
-        weather,
-        gamma: gamma_state,
-        manual_mode: false,
-        manual_start_temp: 0,
-        manual_target_temp: 0,
-        manual_start_time: 0,
-        manual_duration_min: 0,
-        manual_issued_at: 0,
-        manual_resume_time: 0,
-        last_temp: 0,
-        last_temp_valid: false,
+    // Hold the config dir while the startup state is read, so a CLI write
+    // (--set, use-profile) can't land between the reads. Taken after gamma
+    // init so the retries don't hold up CLI commands.
+    let config_lock = match config::lock_config_dir(paths) {
+        Ok(lock) => Some(lock),
+        Err(e) => {
+            eprintln!("[warn] Cannot lock {}: {}", paths.lock_file.display(), e);
+            None
+        }
     };
 
-    // Create kernel fds
-    let ino_fd = setup_inotify(&state.paths);
+    // Active profile overrides location and temperatures
+    let mut location = location;
+    let mut settings = config::load_settings(paths);
+    if let Some(p) = config::active_profile(paths) {
+        p.apply_location(&mut location);
+        p.apply_settings(&mut settings);
+        eprintln!("[config] Profile: {}", p.name);
+    }
+
+    // Load initial weather
+    let weather = initial_weather(paths, &settings);
 
-    // Write PID file
-    if let Err(e) = config::write_pid(&state.paths) {
-        eprintln!("[warn] Failed to write PID file: {}", e);
+    if let Some(ref mut g) = gamma_state {
+        g.set_ramp_quantize_step(settings.display.ramp_quantize_step);
     }
+    let mut state = DaemonState::new(location, paths, settings, weather, gamma_state, Box::new(SystemClock));
+
+    // Create kernel fds
+    let ino_fd = inotify::setup(&state.paths);
+
+    // Write PID file (the new file carries the lock; keep it open too)
+    let _pid_file = match config::write_pid(&state.paths) {
+        Ok(f) => Some(f),
+        Err(e) => {
+            eprintln!("[warn] Failed to write PID file: {}", e);
+            None
+        }
+    };
 
     // prctl hardening
     unsafe {
@@ -436,20 +1205,34 @@ pub fn run(location: Location, paths: &Paths) {
     eprintln!("[kernel] prctl: timerslack=1ns, no_new_privs, !dumpable");
 
     // Landlock filesystem sandbox
-    let config_dir = state.paths.override_file.parent()
-        .map(|p| p.to_string_lossy().to_string())
-        .unwrap_or_default();
-    if !config_dir.is_empty() {
-        if landlock::install_sandbox(&config_dir) {
-            eprintln!("[kernel] landlock: filesystem sandbox active");
-        } else {
-            eprintln!("[kernel] landlock: unavailable (running unsandboxed)");
-        }
+    // The system config dir stays read-only (under the /etc rule)
+    let paths = &state.paths;
+    let mut writable = vec![paths.state_dir.to_string_lossy(), paths.cache_dir.to_string_lossy()];
+    if paths.mode != config::PathMode::System {
+        writable.push(paths.config_dir.to_string_lossy());
+    }
+    let writable: Vec<&str> = writable.iter().map(|d| d.as_ref()).collect();
+    let hooks: Vec<_> = state.hooks.paths().map(|p| p.to_string_lossy()).collect();
+    let hooks: Vec<&str> = hooks.iter().map(|p| p.as_ref()).collect();
+    if landlock::install_sandbox(&writable, &hooks) {
+        eprintln!("[kernel] landlock: filesystem sandbox active");
+    } else {
+        eprintln!("[kernel] landlock: unavailable (running unsandboxed)");
     }
 
     // seccomp-bpf syscall whitelist (must be last -- no new syscalls after this)
-    if seccomp::install_filter() {
-        eprintln!("[kernel] seccomp: syscall whitelist active (~81 syscalls)");
+    #[cfg(debug_assertions)]
+    let installed = if std::env::var_os("ABRAXAS_SECCOMP_LOG").is_some() {
+        eprintln!("[kernel] seccomp: ABRAXAS_SECCOMP_LOG set, violations logged (not killed)");
+        seccomp::install_filter_permissive()
+    } else {
+        seccomp::install_filter()
+    };
+    #[cfg(not(debug_assertions))]
+    let installed = seccomp::install_filter();
+
+    if installed {
+        eprintln!("[kernel] seccomp: syscall whitelist active ({} syscalls)", seccomp::whitelist_len());
     } else {
         eprintln!("[kernel] seccomp: failed to install filter");
     }
@@ -457,160 +1240,417 @@ pub fn run(location: Location, paths: &Paths) {
     // Recover from active override on restart
     recover_override(&mut state);
 
-    // Apply gamma immediately at startup (force override check)
-    tick(&mut state, true, false);
-
     // Initialize weather subsystem
     weather::init();
 
-    // io_uring event loop (no fallback -- requires kernel >= 5.1)
-    let mut ring = match AbraxasRing::init(8) {
-        Some(r) => r,
-        None => {
-            eprintln!("[fatal] io_uring_setup failed (kernel >= 5.1 required)");
-            std::process::exit(1);
-        }
-    };
-    eprintln!(
-        "[abraxas] daemon started (backend: {}, io_uring: multi-shot, inotify: {}, signalfd: {})",
-        state.gamma.as_ref().map(|g| g.backend_name()).unwrap_or("none"),
-        if ino_fd >= 0 { "active" } else { "unavailable" },
-        if signal_fd >= 0 { "active" } else { "unavailable" },
+    // Startup state is loaded; anything written from here on is seen
+    // through inotify
+    drop(config_lock);
+
+    // io_uring event loop, or ppoll where io_uring is missing or disabled
+    let mut ring = AbraxasRing::init(8);
+    if ring.is_none() {
+        eprintln!(
+            "[uring] io_uring_setup failed ({}), falling back to ppoll",
+            std::io::Error::last_os_error()
+        );
+    }
+
+    // From here on a panic must not leave the screen stuck at a warm ramp
+    let result = restore_on_panic(
+        &mut state,
+        |state| {
+            // Apply gamma immediately at startup (force override check)
+            tick(state, true, false);
+            state.notifier.flush();
+            publish_status(state, true);
+            run_hooks(state);
+
+            eprintln!(
+                "[abraxas] daemon started (backend: {}, paths: {}, event loop: {}, inotify: {}, signalfd: {})",
+                state.gamma.as_ref().map(|g| g.backend_name()).unwrap_or("none"),
+                state.paths.mode.name(),
+                if ring.is_some() { "io_uring multi-shot" } else { "ppoll" },
+                if ino_fd >= 0 { "active" } else { "unavailable" },
+                if signal_fd >= 0 { "active" } else { "unavailable" },
+            );
+            match ring {
+                Some(ref mut ring) => event_loop_uring(state, ring, PollState::new(), ino_fd, signal_fd),
+                None => event_loop_ppoll(state, ino_fd, signal_fd),
+            }
+        },
     );
-    event_loop_uring(&mut state, &mut ring, ino_fd, signal_fd);
+    if result.is_err() {
+        eprintln!("[fatal] daemon panicked, original gamma restored");
+        shutdown(&mut state, ino_fd, signal_fd);
+        return Err(CliError::Exit(101));
+    }
 
-    // Clean shutdown
     eprintln!("[abraxas] shutting down...");
+    shutdown(&mut state, ino_fd, signal_fd);
+    Ok(())
+}
+
+/// Undo startup: original gamma back, PID file and status gone, fds closed
+fn shutdown(state: &mut DaemonState, ino_fd: i32, signal_fd: i32) {
     weather::cleanup();
     if let Some(ref mut g) = state.gamma {
         let _ = g.restore();
     }
     config::remove_pid(&state.paths);
+    config::clear_daemon_status(&state.paths);
+    close_fd(ino_fd);
+    close_fd(signal_fd);
+}
 
-    if ino_fd >= 0 { unsafe { libc::close(ino_fd) }; }
-    if signal_fd >= 0 { unsafe { libc::close(signal_fd) }; }
+fn close_fd(fd: i32) {
+    if fd >= 0 {
+        unsafe { libc::close(fd) };
+    }
 }
 
-/// Recover from an active override that was in progress before daemon restart.
-fn recover_override(state: &mut DaemonState) {
-    let ovr = match config::load_override(&state.paths) {
-        Some(o) => o,
-        None => return,
-    };
+/// Run `f`, restoring the original gamma if it panics. The panic is caught
+/// (the default hook has already printed it) and returned as Err so the
+/// caller can exit. Requires panic = "unwind" in the build profile.
+fn restore_on_panic<R>(
+    state: &mut DaemonState,
+    f: impl FnOnce(&mut DaemonState) -> R,
+) -> std::thread::Result<R> {
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| f(state)));
+    if result.is_err() {
+        if let Some(ref mut g) = state.gamma {
+            let _ = g.restore();
+        }
+    }
+    result
+}
+
+/// End the active override. Keeps the override file when paused so the
+/// pause flag survives; otherwise removes it.
+fn end_override(state: &DaemonState) {
+    let _lock = config::lock_override(&state.paths);
+    // A newer --set arrived since this override was loaded: leave it for tick
+    if let Some(o) = config::load_override(&state.paths) {
+        if o.active && o.sequence != state.manual_sequence {
+            return;
+        }
+    }
+    if state.paused {
+        let ovr = config::OverrideState { paused: true, ..Default::default() };
+        let _ = config::save_override(&state.paths, &ovr);
+    } else {
+        config::clear_override(&state.paths);
+    }
+}
+
+/// Resolve a relative override (`--set +500`) against the current temperature.
+fn resolve_relative(state: &DaemonState, delta: i32, now: i64) -> i32 {
+    let base = if state.last_temp_valid {
+        state.last_temp
+    } else {
+        solar_temperature(state, now)
+    };
+    let limits = &state.settings.limits;
+    let temp = (base + delta).clamp(limits.min, limits.max);
+    eprintln!("[manual] Relative override: {}K {:+}K -> {}K", base, delta, temp);
+    temp
+}
+
+/// Recover from an active override that was in progress before daemon restart.
+fn recover_override(state: &mut DaemonState) {
+    let ovr = match config::load_override(&state.paths) {
+        Some(o) => o,
+        None => return,
+    };
+
+    state.paused = ovr.paused;
+    if state.paused {
+        eprintln!("[pause] Recovered paused state (use --toggle to resume)");
+    }
 
     if !ovr.active {
         return;
     }
 
-    let now = now_epoch();
-    let elapsed_min = (now - ovr.issued_at) as f64 / 60.0;
+    let now = state.clock.now();
+    if ovr.is_pending(now) {
+        state.pending_at = ovr.starts_at();
+        eprintln!(
+            "[manual] Recovered scheduled override: -> {}K at {}",
+            ovr.target_temp, hhmm(state.pending_at, &*state.clock)
+        );
+        return;
+    }
+
+    state.manual_sequence = ovr.sequence;
+    let elapsed_min = (now - ovr.starts_at()) as f64 / 60.0;
+    let done_min = ovr.duration_secs as f64 / 60.0 + ovr.hold_minutes.unwrap_or(0) as f64;
 
-    if elapsed_min >= ovr.duration_minutes as f64 {
+    // Held overrides (--hold) only end on --resume
+    if !ovr.hold && elapsed_min >= done_min {
         // Override already completed before restart -- discard
-        config::clear_override(&state.paths);
+        end_override(state);
         eprintln!(
             "[manual] Cleared stale override (completed {:.0} min ago)",
-            elapsed_min - ovr.duration_minutes as f64
+            elapsed_min - done_min
         );
         return;
     }
 
     // Still active -- recover state
     state.manual_mode = true;
-    state.manual_target_temp = ovr.target_temp;
-    state.manual_duration_min = ovr.duration_minutes;
+    state.manual_target_temp = if ovr.is_relative {
+        resolve_relative(state, ovr.target_temp, now)
+    } else {
+        ovr.target_temp
+    };
+    state.manual_duration_sec = ovr.duration_secs;
+    state.manual_easing = ovr.easing;
     state.manual_issued_at = ovr.issued_at;
-    state.manual_start_time = ovr.issued_at;
+    state.manual_start_time = ovr.starts_at();
+    state.manual_hold_min = ovr.hold_minutes;
+    state.manual_held = ovr.hold;
+    state.manual_brightness = ovr.brightness.clamp(0.1, 1.0);
 
-    state.manual_start_temp = if ovr.start_temp != 0 {
+    state.manual_start_temp = if ovr.start_temp != 0 && !ovr.is_relative {
         ovr.start_temp
     } else {
-        let temp = solar_temperature(now, state.location.lat, state.location.lon, &state.weather);
-        // Save start_temp back so subsequent restarts have it
+        let temp = solar_temperature(state, now);
+        // Save start_temp (and resolved target) back so subsequent restarts have it
         let updated = config::OverrideState {
-            active: true,
-            target_temp: ovr.target_temp,
-            duration_minutes: ovr.duration_minutes,
-            issued_at: ovr.issued_at,
+            target_temp: state.manual_target_temp,
             start_temp: temp,
+            is_relative: false,
+            ..ovr
         };
-        let _ = config::save_override(&state.paths, &updated);
+        if let Ok(false) = config::update_override(&state.paths, &updated) {
+            eprintln!("[manual] Override replaced on disk, not writing back start temperature");
+        }
         temp
     };
 
     state.manual_resume_time = sigmoid::next_transition_resume(
-        now, state.location.lat, state.location.lon,
+        now, state.location.lat, state.location.lon, &state.settings.transitions, &*state.clock,
     );
 
     eprintln!(
-        "[manual] Recovered override: -> {}K ({} min)",
-        state.manual_target_temp, state.manual_duration_min
+        "[manual] Recovered override: -> {}K ({})",
+        state.manual_target_temp, format_duration(state.manual_duration_sec)
     );
 }
 
-fn tick(state: &mut DaemonState, override_changed: bool, config_changed: bool) {
-    let now = now_epoch();
+/// Queue an async read of override.json for the next submit. False if it
+/// can't be opened (e.g. removed by --resume) or the SQ ring stays full;
+/// the caller then loads it synchronously.
+fn start_override_read(state: &mut DaemonState, ring: &mut AbraxasRing) -> bool {
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::os::fd::AsRawFd;
+
+    let file = match std::fs::OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(&state.paths.override_file)
+    {
+        Ok(f) => f,
+        Err(_) => return false,
+    };
+    let mut read = OverrideRead { file, buf: Box::new([0; OVERRIDE_MAX_BYTES + 1]) };
+    let fd = read.file.as_raw_fd();
+    let len = read.buf.len() as u32;
+    if !ring.prep_read(fd, read.buf.as_mut_ptr() as u64, len, 0, uring::EV_OVERRIDE_READ) {
+        return false;
+    }
+    state.override_read = Some(read);
+    true
+}
 
-    // Check for override changes -- ONLY when inotify detected a change
-    if override_changed {
-        let ovr = config::load_override(&state.paths);
-        if let Some(ref o) = ovr {
-            if o.active {
-                if !state.manual_mode || o.issued_at != state.manual_issued_at {
-                    // New or changed override
-                    state.manual_mode = true;
-                    state.manual_target_temp = o.target_temp;
-                    state.manual_duration_min = o.duration_minutes;
-                    state.manual_start_time = o.issued_at;
-                    state.manual_issued_at = o.issued_at;
-                    state.manual_start_temp = if state.last_temp_valid {
-                        state.last_temp
-                    } else {
-                        o.target_temp
-                    };
+/// Parse the completed override.json read (`res`: bytes read or -errno).
+/// A failed read (e.g. -EINVAL before Linux 5.6) falls back to a plain read.
+fn finish_override_read(state: &mut DaemonState, res: i32) -> Option<config::OverrideState> {
+    let read = state.override_read.take()?;
+    if res < 0 {
+        eprintln!(
+            "[uring] Reading override.json failed: {}",
+            std::io::Error::from_raw_os_error(-res)
+        );
+        return config::load_override(&state.paths);
+    }
+    config::override_from_bytes(&state.paths, &read.buf[..res as usize])
+}
 
-                    // Save start_temp back
-                    if o.start_temp == 0 {
-                        let updated = config::OverrideState {
-                            start_temp: state.manual_start_temp,
-                            ..*o
-                        };
-                        let _ = config::save_override(&state.paths, &updated);
-                    }
+/// Take up override.json's contents: pause, a scheduled or running
+/// override, or its removal
+fn apply_override(state: &mut DaemonState, ovr: Option<config::OverrideState>, now: i64) {
+    if ovr.is_none() && state.pending_at > 0 {
+        state.pending_at = 0;
+        eprintln!("[manual] Scheduled override cancelled");
+    }
+    if let Some(ref o) = ovr {
+        if o.paused != state.paused {
+            state.paused = o.paused;
+            if state.paused {
+                if let Some(ref mut g) = state.gamma {
+                    let _ = g.restore();
+                }
+                state.last_temp_valid = false;
+                eprintln!("[pause] Gamma adjustment paused, original gamma restored");
+            } else {
+                eprintln!("[pause] Gamma adjustment resumed");
+            }
+        }
 
-                    state.manual_resume_time = sigmoid::next_transition_resume(
-                        now, state.location.lat, state.location.lon,
-                    );
+        if o.is_pending(now) {
+            if o.starts_at() != state.pending_at {
+                state.pending_at = o.starts_at();
+                eprintln!(
+                    "[manual] Override: -> {}K scheduled for {}",
+                    o.target_temp, hhmm(state.pending_at, &*state.clock)
+                );
+                // Replaces a running override: fade back to solar until then
+                if state.manual_mode {
+                    state.manual_mode = false;
+                    state.manual_issued_at = 0;
+                    if state.last_temp_valid {
+                        state.resume_fade_start = now;
+                        state.resume_fade_from = state.last_temp;
+                    }
+                }
+            }
+        } else if o.active {
+            state.pending_at = 0;
+            if !state.manual_mode
+                || o.issued_at != state.manual_issued_at
+                || o.sequence != state.manual_sequence
+            {
+                // New or changed override
+                state.manual_mode = true;
+                state.manual_target_temp = if o.is_relative {
+                    resolve_relative(state, o.target_temp, now)
+                } else {
+                    o.target_temp
+                };
+                state.manual_duration_sec = o.duration_secs;
+                state.manual_easing = o.easing;
+                state.manual_start_time = o.starts_at();
+                state.manual_issued_at = o.issued_at;
+                state.manual_sequence = o.sequence;
+                state.manual_hold_min = o.hold_minutes;
+                state.manual_held = o.hold;
+                state.manual_brightness = o.brightness.clamp(0.1, 1.0);
+                state.resume_fade_start = 0;
+                state.manual_start_temp = if state.last_temp_valid {
+                    state.last_temp
+                } else {
+                    state.manual_target_temp
+                };
 
-                    if state.manual_duration_min > 0 {
-                        eprintln!(
-                            "[manual] Override: {}K -> {}K over {} min",
-                            state.manual_start_temp, state.manual_target_temp,
-                            state.manual_duration_min
-                        );
-                    } else {
-                        eprintln!("[manual] Override: -> {}K (instant)", state.manual_target_temp);
+                // Save start_temp (and resolved relative target) back
+                if o.start_temp == 0 || o.is_relative {
+                    let updated = config::OverrideState {
+                        start_temp: state.manual_start_temp,
+                        target_temp: state.manual_target_temp,
+                        is_relative: false,
+                        ..*o
+                    };
+                    if let Ok(false) = config::update_override(&state.paths, &updated) {
+                        eprintln!("[manual] Override replaced on disk, not writing back start temperature");
                     }
                 }
-            } else if state.manual_mode {
+
+                state.manual_resume_time = sigmoid::next_transition_resume(
+                    now, state.location.lat, state.location.lon,
+                    &state.settings.transitions, &*state.clock,
+                );
+
+                if state.manual_duration_sec > 0 {
+                    eprintln!(
+                        "[manual] Override: {}K -> {}K over {} ({})",
+                        state.manual_start_temp, state.manual_target_temp,
+                        format_duration(state.manual_duration_sec), state.manual_easing.name()
+                    );
+                } else {
+                    eprintln!("[manual] Override: -> {}K (instant)", state.manual_target_temp);
+                }
+                if let Some(hold) = state.manual_hold_min {
+                    eprintln!("[manual] Holding {} min after transition", hold);
+                } else if state.manual_held {
+                    eprintln!("[manual] Holding until --resume");
+                }
+                if state.manual_brightness < 1.0 {
+                    eprintln!("[manual] Brightness: {:.0}%", state.manual_brightness * 100.0);
+                }
+            }
+        } else {
+            if state.pending_at > 0 {
+                state.pending_at = 0;
+                eprintln!("[manual] Scheduled override cancelled");
+            }
+            if state.manual_mode {
                 state.manual_mode = false;
                 state.manual_issued_at = 0;
-                config::clear_override(&state.paths);
+                end_override(state);
                 eprintln!("[manual] Override cleared, resuming solar control");
             }
         }
     }
+}
+
+fn tick(state: &mut DaemonState, override_changed: bool, config_changed: bool) {
+    let now = state.clock.now();
+    state.tick_count += 1;
+
+    // Check for override changes -- ONLY when inotify detected a change,
+    // or a scheduled (--at) override has come due
+    if override_changed || (state.pending_at > 0 && now >= state.pending_at) {
+        let ovr = config::load_override(&state.paths);
+        apply_override(state, ovr, now);
+    }
 
     // Reload config if inotify detected a config file change
     if config_changed {
-        if let Some(new_loc) = config::load_location(&state.paths) {
+        let profile = config::active_profile(&state.paths);
+        let mut moved = false;
+        if let Some(mut new_loc) = config::load_location(&state.paths) {
+            if let Some(ref p) = profile {
+                p.apply_location(&mut new_loc);
+            }
+            moved = new_loc.lat != state.location.lat || new_loc.lon != state.location.lon;
             state.location = new_loc;
             eprintln!(
                 "[config] Location updated: {:.4}, {:.4}",
                 state.location.lat, state.location.lon
             );
         }
-        state.weather = config::load_weather_cache(&state.paths);
+        state.settings = config::load_settings(&state.paths);
+        if let Some(ref p) = profile {
+            p.apply_settings(&mut state.settings);
+            eprintln!("[config] Profile: {}", p.name);
+        }
+        state.weather = initial_weather(&state.paths, &state.settings);
+        // Weather for the old place (cached or in flight) doesn't apply:
+        // the event loop fetches afresh for the new one
+        if moved && state.settings.network.weather_provider != WeatherProvider::Disabled {
+            state.weather_fetcher.abort();
+            state.weather = None;
+        }
+        state.last_temp_valid = false; // brightness or targets may have changed
+        state.hooks.update(config::load_hook_params(&state.paths));
+        state.notifier.set_enabled(state.settings.daemon.notify);
+        // A reload (SIGHUP included) rewrites every ramp, in case
+        // something else changed them behind our back
+        if let Some(ref mut g) = state.gamma {
+            g.set_ramp_quantize_step(state.settings.display.ramp_quantize_step);
+            g.invalidate_ramps();
+        }
+
+        #[cfg(feature = "thermal")]
+        {
+            let params = config::load_thermal_params(&state.paths);
+            match state.thermal {
+                Some(ref mut m) if params.enabled => m.update(&params),
+                _ => state.thermal = ThermalMonitor::open(&params),
+            }
+        }
     }
 
     // Weather refresh is now async via io_uring POLL_ADD in event_loop_uring()
@@ -621,62 +1661,1043 @@ fn tick(state: &mut DaemonState, override_changed: bool, config_changed: bool) {
             state.manual_start_temp,
             state.manual_target_temp,
             state.manual_start_time,
-            state.manual_duration_min,
+            state.manual_duration_sec,
+            state.manual_easing,
             now,
         );
 
         // Check auto-resume: after manual transition completes, resume solar
-        // control when the next dawn/dusk transition window approaches
-        let elapsed_min = (now - state.manual_start_time) as f64 / 60.0;
-        if elapsed_min >= state.manual_duration_min as f64
+        // control when the hold expires, or (without a hold) when the next
+        // dawn/dusk transition window approaches. --hold never auto-resumes.
+        let elapsed = now - state.manual_start_time;
+        if let Some(hold) = state.manual_hold_min {
+            if elapsed >= state.manual_duration_sec + hold as i64 * 60 {
+                state.manual_mode = false;
+                state.manual_issued_at = 0;
+                end_override(state);
+                state.resume_fade_start = now;
+                state.resume_fade_from = temp;
+                eprintln!("[manual] Hold expired, fading back to solar control");
+                let solar = solar_temperature(state, now);
+                state.notifier.resumed(solar, Some(HOLD_RESUME_FADE_MIN), now);
+            }
+            temp
+        } else if elapsed >= state.manual_duration_sec
+            && !state.manual_held
             && state.manual_resume_time > 0
             && now >= state.manual_resume_time
         {
             state.manual_mode = false;
             state.manual_issued_at = 0;
-            config::clear_override(&state.paths);
+            end_override(state);
             eprintln!("[manual] Auto-resuming solar control (transition window approaching)");
-            solar_temperature(now, state.location.lat, state.location.lon, &state.weather)
+            let solar = solar_temperature(state, now);
+            state.notifier.resumed(solar, None, now);
+            solar
         } else {
             temp
         }
+    } else if state.resume_fade_start > 0 {
+        let solar = solar_temperature(state, now);
+        if now - state.resume_fade_start >= HOLD_RESUME_FADE_MIN as i64 * 60 {
+            state.resume_fade_start = 0;
+            solar
+        } else {
+            sigmoid::calculate_manual_temp(
+                state.resume_fade_from,
+                solar,
+                state.resume_fade_start,
+                HOLD_RESUME_FADE_MIN as i64 * 60,
+                config::Easing::Sigmoid,
+                now,
+            )
+        }
+    } else {
+        let (temp, curve) = solar_temp_traced(&state.location, &state.settings, state.weather.as_ref(), now, &*state.clock);
+        if state.verbose {
+            match curve {
+                Some(c) => eprintln!(
+                    "[verbose] {} x={:.3} factor={:.3} -> {}K",
+                    c.trace.phase.name(),
+                    c.trace.x,
+                    c.trace.factor,
+                    temp
+                ),
+                None => eprintln!("[verbose] {}K (window, schedule, golden hour, elevation or polar: no sigmoid)", temp),
+            }
+        }
+        temp
+    };
+
+    let in_transition = if state.manual_mode {
+        now - state.manual_start_time < state.manual_duration_sec
+    } else {
+        state.resume_fade_start > 0 || solar_transition_active(state, now)
+    };
+
+    // Announce dawn/dusk while the solar curve is in charge
+    let window = if state.manual_mode || state.paused || state.resume_fade_start > 0 {
+        None
+    } else {
+        solar_window_at(state, now)
+    };
+    let window_target = match window {
+        Some((SolarWindow::Dusk, _)) => night_temp(&state.settings, weather_is_dark(state), now),
+        Some((SolarWindow::Dawn, _)) if weather_is_dark(state) => state.settings.temperatures.day_dark,
+        _ => state.settings.temperatures.day_clear,
+    };
+    state.notifier.transition(window, window_target, now);
+
+    // Tick faster while the temperature moves, so it glides instead of
+    // stepping once a minute: every second through a --set fade, every
+    // transition_tick_seconds through dawn/dusk. Back to TEMP_UPDATE_SEC after.
+    state.fast_tick_sec = if !in_transition || state.paused {
+        0
+    } else if state.manual_mode {
+        OVERRIDE_TICK_SEC
+    } else {
+        state.settings.transitions.transition_tick_sec
+    };
+
+    // Wake for a scheduled override on time, not up to a tick late
+    if state.pending_at > 0 {
+        let wait = (state.pending_at - now).max(1);
+        if wait < TEMP_UPDATE_SEC && (state.fast_tick_sec == 0 || wait < state.fast_tick_sec) {
+            state.fast_tick_sec = wait;
+        }
+    }
+
+    // Paused: leave the restored gamma alone
+    if state.paused {
+        return;
+    }
+
+    if !state.manual_mode {
+        if let Some(w) = active_window(&state.settings, now, &*state.clock) {
+            eprintln!("[schedule] Active: \"{}\" ({}K)", w.name, w.temp_k);
+        } else if state.settings.schedule.is_none() {
+            if let Some(temp) = golden_hour_temp(&state.location, &state.settings, now, &*state.clock) {
+                eprintln!("[solar] Golden hour active: {}K", temp);
+            }
+        }
+    }
+
+    // Apply if changed. Stable day/night ignores float jitter below the
+    // configured threshold; transitions (and the tick right after one, so
+    // the endpoint lands exactly) keep 1K resolution.
+    let threshold = if in_transition || state.transition_in_progress {
+        1
     } else {
-        solar_temperature(now, state.location.lat, state.location.lon, &state.weather)
+        state.settings.transitions.temp_change_threshold
     };
+    state.transition_in_progress = in_transition;
 
-    // Apply if changed
-    if !state.last_temp_valid || target_temp != state.last_temp {
-        let lt = local_time(now);
+    let brightness = effective_brightness(state);
+    if !state.last_temp_valid
+        || (target_temp - state.last_temp).abs() >= threshold
+        || brightness != state.last_brightness
+    {
+        let lt = state.clock.local(now);
 
         if state.manual_mode {
-            let elapsed_min = (now - state.manual_start_time) as f64 / 60.0;
-            if elapsed_min < state.manual_duration_min as f64 {
-                let pct = (elapsed_min / state.manual_duration_min as f64 * 100.0) as i32;
-                let pct = pct.min(100);
+            if now - state.manual_start_time < state.manual_duration_sec {
+                // Share of the temperature change done (not of the time):
+                // meaningful for every easing curve
+                let span = state.manual_target_temp - state.manual_start_temp;
+                let pct = if span == 0 {
+                    100
+                } else {
+                    ((target_temp - state.manual_start_temp) * 100 / span).clamp(0, 100)
+                };
                 eprintln!(
                     "[{:02}:{:02}:{:02}] Manual: {}K ({}%)",
-                    lt.hour, lt.min, lt.sec, target_temp, pct
+                    lt.hour, lt.minute, lt.second, target_temp, pct
                 );
             } else {
                 eprintln!(
                     "[{:02}:{:02}:{:02}] Manual: {}K (holding)",
-                    lt.hour, lt.min, lt.sec, target_temp
+                    lt.hour, lt.minute, lt.second, target_temp
                 );
             }
+        } else if let Some(ref sched) = state.settings.schedule {
+            let next = sched.next_keyframe(minute_of_day(now, &*state.clock));
+            eprintln!(
+                "[{:02}:{:02}:{:02}] Schedule: {}K (next: {:02}:{:02} -> {}K)",
+                lt.hour, lt.minute, lt.second, target_temp, next.minute / 60, next.minute % 60, next.temp
+            );
         } else {
-            let sp = solar::position(now, state.location.lat, state.location.lon);
+            let sp = solar::position(now, state.location.lat, state.location.lon, &*state.clock);
             let cloud_cover = state.weather.as_ref().map(|w| w.cloud_cover).unwrap_or(0);
             eprintln!(
                 "[{:02}:{:02}:{:02}] Solar: {}K (sun: {:.1}, clouds: {}%)",
-                lt.hour, lt.min, lt.sec, target_temp, sp.elevation, cloud_cover
+                lt.hour, lt.minute, lt.second, target_temp, sp.elevation, cloud_cover
             );
         }
 
         if let Some(ref mut g) = state.gamma {
-            if g.set_temperature(target_temp, 1.0).is_ok() {
-                state.last_temp = target_temp;
-                state.last_temp_valid = true;
+            match g.set_temperature(target_temp, brightness, state.settings.display.white_point()) {
+                Ok(()) => {
+                    state.last_temp = target_temp;
+                    state.last_temp_valid = true;
+                    state.last_brightness = brightness;
+                    state.last_gamma_error = None;
+                }
+                Err(e) => {
+                    // Log once per distinct failure, not every tick
+                    let msg = e.to_string();
+                    if state.last_gamma_error.as_deref() != Some(msg.as_str()) {
+                        eprintln!("[gamma] {}: {}", g.backend_name(), msg);
+                    }
+                    state.last_gamma_error = Some(msg);
+                }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FakeClock;
+    use crate::{now_epoch, TEMP_DAY_CLEAR, TEMP_DAY_DARK, TEMP_NIGHT};
+
+    // --- Tick logic against the mock gamma backend ---
+
+    fn mock_state(name: &str) -> DaemonState {
+        std::env::set_var("ABRAXAS_MOCK_GAMMA", "1");
+        let paths = config::tests::test_paths(name);
+        config::clear_override(&paths);
+        let gamma = gamma::init_card(0, false).expect("mock backend");
+        assert_eq!(gamma.backend_name(), "mock");
+        let location = Location { lat: 41.88, lon: -87.63 };
+        let settings = config::load_settings(&paths);
+        DaemonState::new(location, &paths, settings, None, Some(gamma), Box::new(SystemClock))
+    }
+
+    fn calls(state: &DaemonState) -> Vec<(&'static str, i32)> {
+        state.gamma.as_ref().unwrap().mock_calls().unwrap()
+            .iter().map(|c| (c.op, c.temp)).collect()
+    }
+
+    #[test]
+    fn mock_backend_names_one_output() {
+        let state = mock_state("mock-outputs");
+        assert_eq!(state.gamma.as_ref().unwrap().output_names(), ["CRTC 0"]);
+    }
+
+    #[test]
+    fn restore_called_when_tick_panics() {
+        let mut state = mock_state("tick-panic");
+        state.gamma.as_mut().unwrap().mock_panic_on_set();
+
+        let result = restore_on_panic(&mut state, |state| tick(state, true, false));
+
+        assert!(result.is_err());
+        assert!(calls(&state).contains(&("restore", 0)));
+    }
+
+    #[test]
+    fn restore_not_called_without_panic() {
+        let mut state = mock_state("tick-no-panic");
+
+        let result = restore_on_panic(&mut state, |state| tick(state, true, false));
+
+        assert!(result.is_ok());
+        let calls = calls(&state);
+        assert_eq!(calls.iter().filter(|c| c.0 == "set").count(), 1);
+        assert!(!calls.contains(&("restore", 0)));
+    }
+
+    fn save_override(state: &DaemonState, target_temp: i32, duration_secs: i64, issued_at: i64) {
+        let ovr = config::OverrideState {
+            active: true,
+            target_temp,
+            duration_secs,
+            issued_at,
+            start_temp: 5000,
+            hold_minutes: Some(60),
+            ..Default::default()
+        };
+        config::save_override(&state.paths, &ovr).unwrap();
+    }
+
+    /// What --resume (paused = false) and --toggle (paused = true) write
+    fn save_inactive_override(state: &DaemonState, paused: bool) {
+        let ovr = config::OverrideState { paused, ..Default::default() };
+        config::save_override(&state.paths, &ovr).unwrap();
+    }
+
+    #[test]
+    fn startup_tick_applies_once() {
+        let mut state = mock_state("tick-startup");
+
+        tick(&mut state, true, false);
+        tick(&mut state, false, false);
+
+        assert_eq!(calls(&state), vec![("set", state.last_temp)]);
+        assert!(state.last_temp_valid);
+    }
+
+    /// The temperature applied at the top of each hour of 2024-06-21 in
+    /// Chicago (CDT), ticking every 10 minutes from local midnight
+    fn fake_day(name: &str, clock: &FakeClock) -> (DaemonState, Vec<i32>) {
+        let mut state = mock_state(name);
+        state.clock = Box::new(clock.clone());
+        tick(&mut state, true, false);
+        let mut hourly = vec![state.last_temp];
+        for i in 1..24 * 6 {
+            clock.advance(600);
+            tick(&mut state, false, false);
+            if i % 6 == 0 {
+                hourly.push(state.last_temp);
+            }
+        }
+        (state, hourly)
+    }
+
+    const CHICAGO_SOLSTICE_MIDNIGHT: i64 = 1718946000;
+    const CDT: i64 = -5 * 3600;
+
+    #[test]
+    fn fake_clock_day_matches_golden() {
+        let clock = FakeClock::new(CHICAGO_SOLSTICE_MIDNIGHT, CDT);
+        let (state, hourly) = fake_day("tick-fake-day", &clock);
+        assert_eq!(hourly, vec![
+            2900, 2900, 2900, 2900, 2900, 2900, 6241, 6500, 6500, 6500, 6500, 6500,
+            6500, 6500, 6500, 6500, 6500, 6500, 6500, 6482, 4639, 2914, 2900, 2900,
+        ]);
+        // Every temperature set: midnight, then dawn 05:10-06:40 and dusk
+        // 18:30-21:30 in 10 minute steps, nothing through the day or night
+        let applied: Vec<i32> = calls(&state).iter().map(|c| c.1).collect();
+        assert_eq!(applied, vec![
+            2900, 2905, 2936, 3109, 3868, 5368, 6241, 6454, 6493, 6499, 6500,
+            6499, 6498, 6493, 6482, 6456, 6394, 6252, 5949, 5401, 4639, 3899, 3390, 3118, 2992, 2937,
+            2914, 2905, 2901, 2900,
+        ]);
+    }
+
+    #[test]
+    fn fake_clock_override_holds_then_fades_back() {
+        let clock = FakeClock::new(CHICAGO_SOLSTICE_MIDNIGHT + 12 * 3600, CDT);
+        let mut state = mock_state("tick-fake-override");
+        state.clock = Box::new(clock.clone());
+        tick(&mut state, true, false);
+
+        // 10 minute fade to 3000K, then the 60 minute hold save_override sets
+        save_override(&state, 3000, 10 * 60, clock.now());
+        let mut applied = vec![];
+        for (advance, changed) in [(0, true), (5 * 60, false), (5 * 60, false), (60 * 60, false), (90, false), (90, false)] {
+            clock.advance(advance);
+            tick(&mut state, changed, false);
+            applied.push(state.last_temp);
+        }
+        assert_eq!(applied, vec![6500, 4750, 3000, 3000, 4750, 6500]);
+        assert!(!state.manual_mode);
+    }
+
+    /// Counts start() calls and finishes nothing, like a fetch that never
+    /// gets through
+    struct CountingFetcher {
+        provider: WeatherProvider,
+        starts: std::rc::Rc<std::cell::Cell<u32>>,
+    }
+
+    impl WeatherFetcher for CountingFetcher {
+        fn provider(&self) -> WeatherProvider {
+            self.provider
+        }
+        fn set_timeouts(&mut self, _net: &config::NetworkParams) {}
+        fn is_idle(&self) -> bool {
+            true
+        }
+        fn pipe_fd(&self) -> i32 {
+            -1
+        }
+        #[cfg(feature = "splice-weather")]
+        fn splice_fd(&self) -> i32 {
+            -1
+        }
+        #[cfg(feature = "splice-weather")]
+        fn splice_generation(&self) -> u32 {
+            0
+        }
+        fn start(&mut self, _lat: f64, _lon: f64) -> bool {
+            self.starts.set(self.starts.get() + 1);
+            true
+        }
+        #[cfg(not(feature = "splice-weather"))]
+        fn read_response(&mut self) -> ReadResult {
+            ReadResult::Pending
+        }
+        #[cfg(feature = "splice-weather")]
+        fn spliced(&mut self, _res: i32) -> ReadResult {
+            ReadResult::Pending
+        }
+        fn abort(&mut self) {}
+    }
+
+    /// Put `state` just after a failed weather fetch; returns the counter
+    /// of fetches started from then on.
+    fn after_failed_fetch(state: &mut DaemonState) -> std::rc::Rc<std::cell::Cell<u32>> {
+        let starts = std::rc::Rc::new(std::cell::Cell::new(0));
+        state.settings.network.weather_provider = WeatherProvider::Noaa;
+        state.weather_fetcher = Box::new(CountingFetcher { provider: WeatherProvider::Noaa, starts: starts.clone() });
+        state.weather = Some(WeatherData {
+            cloud_cover: 0,
+            cloud_cover_3h: 0,
+            forecast: String::new(),
+            temperature: 0.0,
+            is_day: true,
+            fetched_at: state.clock.now(),
+            has_error: true,
+        });
+        starts
+    }
+
+    #[test]
+    fn failed_fetch_not_restarted_by_fade_ticks() {
+        let clock = FakeClock::new(CHICAGO_SOLSTICE_MIDNIGHT + 12 * 3600, CDT);
+        let mut state = mock_state("weather-retry-fade");
+        state.clock = Box::new(clock.clone());
+        tick(&mut state, true, false);
+        let starts = after_failed_fetch(&mut state);
+        let mut polls = PollState::new();
+
+        save_override(&state, 3000, 10 * 60, clock.now());
+        assert!(handle_flags(&mut state, FLAG_OVERRIDE, -1, &mut polls, None));
+        assert_eq!(state.fast_tick_sec, OVERRIDE_TICK_SEC);
+        for _ in 0..30 {
+            clock.advance(OVERRIDE_TICK_SEC);
+            assert!(handle_flags(&mut state, FLAG_TIMER_FAST, -1, &mut polls, None));
+        }
+        assert_eq!(starts.get(), 0);
+
+        // Retried once WEATHER_RETRY_SEC has passed
+        clock.advance(crate::WEATHER_RETRY_SEC - 30);
+        assert!(handle_flags(&mut state, FLAG_TIMER, -1, &mut polls, None));
+        assert_eq!(starts.get(), 1);
+    }
+
+    #[test]
+    fn failed_fetch_not_restarted_by_dawn_ticks() {
+        // Chicago sunrise is 05:16 CDT: inside the dawn window
+        let clock = FakeClock::new(CHICAGO_SOLSTICE_MIDNIGHT + 5 * 3600 + 16 * 60, CDT);
+        let mut state = mock_state("weather-retry-dawn");
+        state.clock = Box::new(clock.clone());
+        tick(&mut state, true, false);
+        assert_eq!(state.fast_tick_sec, crate::TRANSITION_TICK_SEC);
+        let starts = after_failed_fetch(&mut state);
+        let mut polls = PollState::new();
+
+        // Fast ticks only move the temperature
+        let before = state.last_temp;
+        for _ in 0..5 {
+            clock.advance(crate::TRANSITION_TICK_SEC);
+            assert!(handle_flags(&mut state, FLAG_TIMER_FAST, -1, &mut polls, None));
+        }
+        assert_ne!(state.last_temp, before);
+        assert_eq!(starts.get(), 0);
+
+        clock.advance(crate::WEATHER_RETRY_SEC);
+        assert!(handle_flags(&mut state, FLAG_TIMER, -1, &mut polls, None));
+        assert_eq!(starts.get(), 1);
+    }
+
+    #[test]
+    fn bright_moon_lowers_clear_night() {
+        // 2024-04-23 23:00 CDT, an hour before full moon
+        let clock = FakeClock::new(1713931200, CDT);
+        let mut state = mock_state("tick-full-moon");
+        state.clock = Box::new(clock.clone());
+        tick(&mut state, true, false);
+        assert_eq!(state.last_temp, TEMP_NIGHT);
+
+        state.settings.temperatures.moon_brightness_reduction = true;
+        clock.advance(60);
+        tick(&mut state, false, false);
+        let moonlit = TEMP_NIGHT - sigmoid::MOON_REDUCTION_MAX;
+        assert!((moonlit..moonlit + 5).contains(&state.last_temp), "{}", state.last_temp);
+
+        // Overcast hides it
+        state.weather = Some(WeatherData {
+            cloud_cover: 100,
+            cloud_cover_3h: 100,
+            forecast: String::new(),
+            temperature: 0.0,
+            is_day: false,
+            fetched_at: clock.now(),
+            has_error: false,
+        });
+        clock.advance(60);
+        tick(&mut state, false, false);
+        assert_eq!(state.last_temp, TEMP_NIGHT);
+    }
+
+    #[test]
+    fn override_read_through_ring() {
+        let mut ring = match AbraxasRing::init(4) {
+            Some(r) => r,
+            None => return, // io_uring disabled
+        };
+        let mut state = mock_state("tick-override-read");
+        tick(&mut state, true, false);
+
+        save_override(&state, 3100, 0, now_epoch());
+        assert!(start_override_read(&mut state, &mut ring));
+        ring.submit_and_wait();
+        let events = AtomicU32::new(0);
+        let mut polls = PollState::new();
+        while let Some(cqe) = ring.peek_cqe() {
+            process_cqe(cqe, &events, &mut polls, -1, &state.paths);
+            ring.cqe_seen();
+        }
+        assert_ne!(events.load(Ordering::Relaxed) & FLAG_OVERRIDE_READ, 0);
+
+        let ovr = finish_override_read(&mut state, polls.override_res);
+        assert_eq!(ovr.map(|o| o.target_temp), Some(3100));
+        assert!(state.override_read.is_none());
+
+        // Gone (--resume after a crash, or removed by hand): read directly
+        config::clear_override(&state.paths);
+        assert!(!start_override_read(&mut state, &mut ring));
+    }
+
+    #[test]
+    fn ppoll_loop_applies_override_until_sigterm() {
+        let mut state = mock_state("ppoll-loop");
+        // Fresh weather, so the loop doesn't start a fetch
+        state.weather = Some(WeatherData {
+            cloud_cover: 0,
+            cloud_cover_3h: 0,
+            forecast: "Clear".to_string(),
+            temperature: 20.0,
+            is_day: true,
+            fetched_at: now_epoch(),
+            has_error: false,
+        });
+        tick(&mut state, true, false);
+        let ino_fd = inotify::setup(&state.paths);
+        assert!(ino_fd >= 0);
+
+        // A pipe stands in for the signalfd: SIGTERM arrives as a
+        // signalfd_siginfo once the override has had time to land
+        let mut pipe = [0; 2];
+        assert_eq!(unsafe { libc::pipe(pipe.as_mut_ptr()) }, 0);
+        let writer = pipe[1];
+        let sigterm = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(300));
+            let mut si: libc::signalfd_siginfo = unsafe { std::mem::zeroed() };
+            si.ssi_signo = libc::SIGTERM as u32;
+            let size = std::mem::size_of_val(&si);
+            unsafe { libc::write(writer, &si as *const _ as *const libc::c_void, size) };
+        });
+
+        save_override(&state, 3100, 0, now_epoch());
+        event_loop_ppoll(&mut state, ino_fd, pipe[0]);
+        sigterm.join().unwrap();
+
+        assert!(state.manual_mode);
+        assert_eq!(calls(&state).last(), Some(&("set", 3100)));
+        close_fd(ino_fd);
+        close_fd(pipe[0]);
+        close_fd(pipe[1]);
+    }
+
+    #[cfg(feature = "splice-weather")]
+    #[test]
+    fn stale_splice_completion_ignored() {
+        let paths = config::tests::test_paths("splice-generation");
+        let events = AtomicU32::new(0);
+        let mut polls = PollState::new();
+        polls.splice_generation = 2;
+        polls.weather = true;
+        let cqe = |generation, res| uring::IoUringCqe { user_data: uring::splice_tag(generation), res, flags: 0 };
+
+        // Finishing after the fetch it belonged to was aborted
+        process_cqe(&cqe(1, 512), &events, &mut polls, -1, &paths);
+        assert_eq!(events.load(Ordering::Relaxed), 0);
+        assert!(polls.weather);
+        assert_eq!(polls.splice_res, 0);
+
+        process_cqe(&cqe(2, 256), &events, &mut polls, -1, &paths);
+        assert_eq!(events.load(Ordering::Relaxed), FLAG_WEATHER);
+        assert!(!polls.weather);
+        assert_eq!(polls.splice_res, 256);
+        let _ = std::fs::remove_dir_all(&paths.config_dir);
+    }
+
+    /// Run event_loop_uring through `wakes` inotify wakes with nothing to
+    /// do, then a SIGTERM; returns the io_uring_enter calls it took.
+    fn enters_for_idle_wakes(name: &str, multishot_timeout: bool, wakes: u64) -> u64 {
+        let mut ring = AbraxasRing::init(8).unwrap();
+        let mut state = mock_state(name);
+        state.weather = Some(WeatherData {
+            cloud_cover: 0,
+            cloud_cover_3h: 0,
+            forecast: "Clear".to_string(),
+            temperature: 20.0,
+            is_day: true,
+            fetched_at: now_epoch(),
+            has_error: false,
+        });
+        tick(&mut state, true, false);
+        // Exists already, so each write is one IN_CLOSE_WRITE the daemon
+        // doesn't care about: a wake with nothing to do
+        let unrelated = state.paths.state_dir.join("unrelated");
+        std::fs::write(&unrelated, "0").unwrap();
+        let ino_fd = inotify::setup(&state.paths);
+        assert!(ino_fd >= 0);
+
+        let mut pipe = [0; 2];
+        assert_eq!(unsafe { libc::pipe(pipe.as_mut_ptr()) }, 0);
+        let writer = pipe[1];
+        let waker = std::thread::spawn(move || {
+            for i in 0..wakes {
+                std::thread::sleep(std::time::Duration::from_millis(30));
+                std::fs::write(&unrelated, i.to_string()).unwrap();
+            }
+            std::thread::sleep(std::time::Duration::from_millis(30));
+            let mut si: libc::signalfd_siginfo = unsafe { std::mem::zeroed() };
+            si.ssi_signo = libc::SIGTERM as u32;
+            let size = std::mem::size_of_val(&si);
+            unsafe { libc::write(writer, &si as *const _ as *const libc::c_void, size) };
+        });
+
+        let polls = PollState { multishot_timeout, ..PollState::new() };
+        event_loop_uring(&mut state, &mut ring, polls, ino_fd, pipe[0]);
+        waker.join().unwrap();
+        close_fd(ino_fd);
+        close_fd(pipe[0]);
+        close_fd(pipe[1]);
+        ring.enter_count()
+    }
+
+    #[test]
+    fn early_wakes_cost_one_enter_each() {
+        if AbraxasRing::init(8).is_none() {
+            return; // io_uring disabled
+        }
+        // Pre-5.19 kernels only have the one-shot path, nothing to compare;
+        // the probe ring's timeout goes when it is dropped
+        let mut probe = AbraxasRing::init(2).unwrap();
+        let probe_ts = KernelTimespec { tv_sec: 0, tv_nsec: 1_000_000 };
+        assert!(probe.prep_timeout_multishot(&probe_ts, uring::EV_TIMEOUT));
+        probe.submit_and_wait();
+        if probe.peek_cqe().map(|cqe| cqe.res) == Some(-libc::EINVAL) {
+            return;
+        }
+        drop(probe);
+
+        const WAKES: u64 = 10;
+        let oneshot = enters_for_idle_wakes("uring-enters-oneshot", false, WAKES);
+        let multishot = enters_for_idle_wakes("uring-enters-multishot", true, WAKES);
+
+        // Each early wake drops the cancel round trip; one spare either way
+        // for a fast tick landing
+        assert!(
+            multishot + WAKES <= oneshot + 2,
+            "{} wakes took {} io_uring_enter calls, {} with one-shot timeouts",
+            WAKES,
+            multishot,
+            oneshot
+        );
+    }
+
+    #[test]
+    fn override_applied_then_cleared() {
+        let mut state = mock_state("tick-override");
+        tick(&mut state, true, false);
+
+        save_override(&state, 3100, 0, now_epoch());
+        tick(&mut state, true, false);
+        assert!(state.manual_mode);
+        assert_eq!(calls(&state).last(), Some(&("set", 3100)));
+
+        save_inactive_override(&state, false);
+        tick(&mut state, true, false);
+        assert!(!state.manual_mode);
+        assert_eq!(calls(&state).last(), Some(&("set", solar_temperature(&state, now_epoch()))));
+    }
+
+    #[test]
+    fn override_brightness_applied_then_restored() {
+        let mut state = mock_state("tick-brightness");
+        tick(&mut state, true, false);
+
+        // Older override files without the field still parse as full brightness
+        let legacy = r#"{"active":true,"target_temp":3100,"duration_minutes":0,"issued_at":1,"start_temp":0}"#;
+        let o: config::OverrideState = serde_json::from_str(legacy).unwrap();
+        assert_eq!(o.brightness, 1.0);
+
+        let ovr = config::OverrideState { brightness: 0.7, issued_at: now_epoch(), ..o };
+        config::save_override(&state.paths, &ovr).unwrap();
+        tick(&mut state, true, false);
+        let last = |state: &DaemonState| {
+            let c = state.gamma.as_ref().unwrap().mock_calls().unwrap().last().unwrap().clone();
+            (c.temp, c.brightness)
+        };
+        assert_eq!(last(&state), (3100, 0.7));
+
+        save_inactive_override(&state, false);
+        tick(&mut state, true, false);
+        assert_eq!(last(&state).1, state.settings.display.brightness);
+    }
+
+    #[test]
+    fn override_transition_ticks_faster_until_done() {
+        let mut state = mock_state("tick-fast");
+        tick(&mut state, true, false);
+        assert_eq!(state.fast_tick_sec, 0);
+
+        save_override(&state, 3100, 90, now_epoch());
+        tick(&mut state, true, false);
+        assert_eq!(state.fast_tick_sec, OVERRIDE_TICK_SEC);
+
+        // Mid-fade ticks land between the endpoints
+        state.manual_start_time -= 45;
+        tick(&mut state, false, false);
+        let mid = state.last_temp;
+        assert!(mid > 3100 && mid < 5000, "{mid}");
+        assert_eq!(state.fast_tick_sec, OVERRIDE_TICK_SEC);
+
+        // Transition finished: back to the regular interval
+        state.manual_start_time -= 60;
+        tick(&mut state, false, false);
+        assert_eq!(state.last_temp, 3100);
+        assert_eq!(state.fast_tick_sec, 0);
+    }
+
+    #[test]
+    fn minute_override_files_still_load() {
+        let state = mock_state("tick-legacy-duration");
+        let legacy = r#"{"active":true,"target_temp":3100,"duration_minutes":2,"issued_at":1,"start_temp":0}"#;
+        std::fs::write(&state.paths.override_file, legacy).unwrap();
+        let o = config::load_override(&state.paths).unwrap();
+        assert_eq!(o.duration_secs, 120);
+
+        // Saved files keep a rounded-up duration_minutes for older readers
+        config::save_override(&state.paths, &config::OverrideState { duration_secs: 90, ..o }).unwrap();
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&state.paths.override_file).unwrap()).unwrap();
+        assert_eq!(json["duration_secs"], 90);
+        assert_eq!(json["duration_minutes"], 2);
+        assert_eq!(config::load_override(&state.paths).unwrap().duration_secs, 90);
+    }
+
+    #[test]
+    fn stable_jitter_below_threshold_skipped() {
+        let mut state = mock_state("tick-threshold");
+        state.settings.schedule = Some(config::Schedule {
+            keyframes: vec![config::Keyframe { minute: 0, temp: 4000 }],
+            blend_min: 30.0,
+        });
+        tick(&mut state, false, false);
+        assert_eq!(calls(&state), vec![("set", 4000)]);
+
+        // Off by less than the threshold while stable: no ioctl
+        state.last_temp = 3997;
+        tick(&mut state, false, false);
+        assert_eq!(calls(&state).len(), 1);
+
+        // Just left a transition: land exactly on the endpoint
+        state.transition_in_progress = true;
+        tick(&mut state, false, false);
+        assert_eq!(calls(&state), vec![("set", 4000), ("set", 4000)]);
+        assert!(!state.transition_in_progress);
+    }
+
+    #[test]
+    fn transition_window_ticks_faster() {
+        let mut state = mock_state("tick-window");
+        let minute = minute_of_day(now_epoch(), &SystemClock) as i32;
+        let blend_at = |m: i32| config::Schedule {
+            keyframes: vec![
+                config::Keyframe { minute: m.rem_euclid(1440), temp: 3000 },
+                config::Keyframe { minute: (m + 720).rem_euclid(1440), temp: 6000 },
+            ],
+            blend_min: 30.0,
+        };
+
+        // Mid-blend: sub-minute ticks
+        state.settings.schedule = Some(blend_at(minute));
+        tick(&mut state, true, false);
+        assert_eq!(state.fast_tick_sec, state.settings.transitions.transition_tick_sec);
+
+        // Paused: nothing to smooth
+        state.paused = true;
+        tick(&mut state, false, false);
+        assert_eq!(state.fast_tick_sec, 0);
+        state.paused = false;
+
+        // Between keyframes: regular interval
+        state.settings.schedule = Some(blend_at(minute + 180));
+        tick(&mut state, false, false);
+        assert_eq!(state.fast_tick_sec, 0);
+    }
+
+    // 2025-03-20 and 2025-06-21/12-21, UTC
+    const EQUINOX_MIDNIGHT: i64 = 1742428800;
+    const EQUINOX_NOON: i64 = 1742472000;
+    const SOLSTICE_JUNE_MIDNIGHT: i64 = 1750464000;
+    const SOLSTICE_DEC_MIDNIGHT: i64 = 1766275200;
+    const SOLSTICE_DEC_NOON: i64 = 1766318400;
+
+    fn mode_temps(loc: &Location, now: i64, weather: Option<&WeatherData>) -> (i32, i32) {
+        let time = Settings::default();
+        let mut elevation = Settings::default();
+        elevation.daemon.mode = SolarMode::Elevation;
+        // Locations below sit on UTC meridians (or near enough)
+        crate::tests::with_tz("UTC", || {
+            (solar_temp_at(loc, &time, weather, now, &SystemClock), solar_temp_at(loc, &elevation, weather, now, &SystemClock))
+        })
+    }
+
+    #[test]
+    fn elevation_mode_matches_time_mode_at_midlatitude() {
+        let loc = Location { lat: 40.0, lon: 0.0 };
+        assert_eq!(mode_temps(&loc, EQUINOX_NOON, None), (TEMP_DAY_CLEAR, TEMP_DAY_CLEAR));
+        assert_eq!(mode_temps(&loc, EQUINOX_MIDNIGHT, None), (TEMP_NIGHT, TEMP_NIGHT));
+
+        let overcast = WeatherData {
+            cloud_cover: 100,
+            cloud_cover_3h: 100,
+            forecast: String::new(),
+            temperature: 0.0,
+            is_day: true,
+            fetched_at: EQUINOX_NOON,
+            has_error: false,
+        };
+        assert_eq!(mode_temps(&loc, EQUINOX_NOON, Some(&overcast)), (TEMP_DAY_DARK, TEMP_DAY_DARK));
+    }
+
+    #[test]
+    fn elevation_mode_follows_polar_day_and_night() {
+        let svalbard = Location { lat: 78.2, lon: 15.6 };
+        assert_eq!(mode_temps(&svalbard, SOLSTICE_JUNE_MIDNIGHT, None), (TEMP_DAY_CLEAR, TEMP_DAY_CLEAR));
+        assert_eq!(mode_temps(&svalbard, SOLSTICE_DEC_NOON, None), (TEMP_NIGHT, TEMP_NIGHT));
+
+        // 55N midsummer midnight: sun well below -6 degrees
+        let north_sea = Location { lat: 55.0, lon: 0.0 };
+        assert_eq!(mode_temps(&north_sea, SOLSTICE_JUNE_MIDNIGHT, None).1, TEMP_NIGHT);
+    }
+
+    #[test]
+    fn polar_day_and_night_in_time_mode() {
+        // Tromso: no sunrise/sunset at either solstice
+        let tromso = Location { lat: 69.65, lon: 18.96 };
+        crate::tests::with_tz("UTC", || {
+            assert!(solar::sunrise_sunset(SOLSTICE_JUNE_MIDNIGHT, tromso.lat, tromso.lon, &SystemClock).is_none());
+            assert!(solar::sunrise_sunset(SOLSTICE_DEC_MIDNIGHT, tromso.lat, tromso.lon, &SystemClock).is_none());
+        });
+
+        // Midnight sun: day all night, not a half-finished dawn
+        assert_eq!(mode_temps(&tromso, SOLSTICE_JUNE_MIDNIGHT, None).0, TEMP_DAY_CLEAR);
+        assert_eq!(mode_temps(&tromso, SOLSTICE_JUNE_MIDNIGHT + 12 * 3600, None).0, TEMP_DAY_CLEAR);
+        let overcast = WeatherData {
+            cloud_cover: 100,
+            cloud_cover_3h: 100,
+            forecast: String::new(),
+            temperature: 0.0,
+            is_day: true,
+            fetched_at: SOLSTICE_JUNE_MIDNIGHT,
+            has_error: false,
+        };
+        assert_eq!(mode_temps(&tromso, SOLSTICE_JUNE_MIDNIGHT, Some(&overcast)).0, TEMP_DAY_DARK);
+
+        // Polar night: night all day, civil twilight at noon included
+        // (elevation mode still blends there)
+        assert_eq!(mode_temps(&tromso, SOLSTICE_DEC_MIDNIGHT, None).0, TEMP_NIGHT);
+        let (time, elevation) = mode_temps(&tromso, SOLSTICE_DEC_NOON, None);
+        assert_eq!(time, TEMP_NIGHT);
+        assert!(elevation > TEMP_NIGHT && elevation < TEMP_DAY_CLEAR, "{}", elevation);
+    }
+
+    #[test]
+    fn elevation_mode_dusk_descends_monotonically() {
+        let loc = Location { lat: 40.0, lon: 0.0 };
+        let temps: Vec<i32> = (0..=24)
+            .map(|i| mode_temps(&loc, EQUINOX_NOON + 5 * 3600 + i * 300, None).1)
+            .collect();
+        assert_eq!(temps[0], TEMP_DAY_CLEAR);
+        assert_eq!(temps[24], TEMP_NIGHT);
+        assert!(temps.windows(2).all(|w| w[1] <= w[0]), "{:?}", temps);
+        assert!(temps.iter().any(|&t| t > TEMP_NIGHT && t < TEMP_DAY_CLEAR));
+    }
+
+    #[test]
+    fn recovered_override_applied_after_restart() {
+        let mut state = mock_state("tick-recover");
+        save_override(&state, 3300, 10 * 60, now_epoch() - 20 * 60);
+
+        recover_override(&mut state);
+        tick(&mut state, false, false);
+
+        assert!(state.manual_mode);
+        assert_eq!(calls(&state), vec![("set", 3300)]);
+    }
+
+    #[test]
+    fn stale_override_discarded_on_restart() {
+        let mut state = mock_state("tick-stale");
+        save_override(&state, 3300, 10 * 60, now_epoch() - 120 * 60);
+
+        recover_override(&mut state);
+        tick(&mut state, false, false);
+
+        assert!(!state.manual_mode);
+        assert!(config::load_override(&state.paths).is_none());
+        assert_ne!(calls(&state), vec![("set", 3300)]);
+    }
+
+    #[test]
+    fn scheduled_override_waits_until_due() {
+        let mut state = mock_state("tick-scheduled");
+        let now = now_epoch();
+        let schedule = |state: &DaemonState, at: i64| {
+            save_override(state, 3300, 0, now);
+            let o = config::load_override(&state.paths).unwrap();
+            let o = config::OverrideState { at: Some(at), start_temp: 0, ..o };
+            config::save_override(&state.paths, &o).unwrap();
+        };
+
+        // Pending: solar control continues, with a wakeup in time for it
+        schedule(&state, now + 30);
+        tick(&mut state, true, false);
+        assert!(!state.manual_mode);
+        assert_eq!(state.pending_at, now + 30);
+        assert!(state.fast_tick_sec > 0 && state.fast_tick_sec <= 30);
+        assert!(!calls(&state).contains(&("set", 3300)));
+
+        // Still pending after a restart
+        state.pending_at = 0;
+        recover_override(&mut state);
+        assert_eq!(state.pending_at, now + 30);
+        assert!(!state.manual_mode);
+
+        // Due: activates without an inotify event, transition starts at `at`
+        schedule(&state, now - 1);
+        state.pending_at = now - 1;
+        tick(&mut state, false, false);
+        assert!(state.manual_mode);
+        assert_eq!(state.pending_at, 0);
+        assert_eq!(state.manual_start_time, now - 1);
+        assert_eq!(calls(&state).last(), Some(&("set", 3300)));
+
+        // A new scheduled override ends the running one; --resume cancels it
+        schedule(&state, now + 600);
+        tick(&mut state, true, false);
+        assert!(!state.manual_mode);
+        assert_eq!(state.pending_at, now + 600);
+        save_inactive_override(&state, false);
+        tick(&mut state, true, false);
+        assert_eq!(state.pending_at, 0);
+        assert!(!state.manual_mode);
+    }
+
+    #[test]
+    fn later_override_wins_over_daemon_write_back() {
+        let mut state = mock_state("tick-sequence");
+        let now = now_epoch();
+        // What two quick --set commands write (same second, next sequence)
+        let cli_set = |state: &DaemonState, target_temp: i32| {
+            let ovr = config::OverrideState {
+                active: true,
+                target_temp,
+                issued_at: now,
+                hold_minutes: Some(60),
+                sequence: config::next_override_sequence(&state.paths),
+                ..Default::default()
+            };
+            config::save_override(&state.paths, &ovr).unwrap();
+        };
+
+        // Daemon loads the first, the second lands before its write-back
+        cli_set(&state, 3300);
+        let first = config::load_override(&state.paths).unwrap();
+        cli_set(&state, 2700);
+        let stale = config::OverrideState { start_temp: 5000, ..first };
+        assert!(!config::update_override(&state.paths, &stale).unwrap());
+        let o = config::load_override(&state.paths).unwrap();
+        assert_eq!((o.target_temp, o.start_temp, o.sequence), (2700, 0, 2));
+
+        // Both applied in turn despite sharing issued_at; the later one stays
+        tick(&mut state, true, false);
+        assert_eq!(state.manual_target_temp, 2700);
+        cli_set(&state, 4100);
+        tick(&mut state, true, false);
+        assert_eq!(state.manual_target_temp, 4100);
+        assert_eq!(calls(&state).last(), Some(&("set", 4100)));
+        let o = config::load_override(&state.paths).unwrap();
+        assert_eq!((o.target_temp, o.sequence), (4100, 3));
+        assert_ne!(o.start_temp, 0);
+
+        // The write-back keeps a --toggle made in between
+        let o = config::OverrideState { paused: true, ..o };
+        config::save_override(&state.paths, &o).unwrap();
+        let back = config::OverrideState { paused: false, start_temp: 4500, ..o };
+        assert!(config::update_override(&state.paths, &back).unwrap());
+        assert!(config::load_override(&state.paths).unwrap().paused);
+    }
+
+    #[test]
+    fn held_override_survives_transitions_and_restarts() {
+        let mut state = mock_state("tick-held");
+        save_override(&state, 2700, 0, now_epoch() - 24 * 3600);
+        let o = config::load_override(&state.paths).unwrap();
+        let o = config::OverrideState { hold: true, hold_minutes: None, ..o };
+        config::save_override(&state.paths, &o).unwrap();
+
+        // A day old, past every dawn/dusk: still recovered and applied
+        recover_override(&mut state);
+        assert!(state.manual_mode && state.manual_held);
+        state.manual_resume_time = now_epoch() - 60;
+        tick(&mut state, false, false);
+        assert!(state.manual_mode);
+        assert_eq!(calls(&state), vec![("set", 2700)]);
+        assert!(config::load_override(&state.paths).unwrap().hold);
+
+        save_inactive_override(&state, false);
+        tick(&mut state, true, false);
+        assert!(!state.manual_mode);
+    }
+
+    #[test]
+    fn pause_restores_gamma() {
+        let mut state = mock_state("tick-pause");
+        tick(&mut state, true, false);
+
+        save_inactive_override(&state, true);
+        tick(&mut state, true, false);
+        tick(&mut state, false, false);
+
+        assert!(state.paused);
+        assert_eq!(calls(&state).len(), 2);
+        assert_eq!(calls(&state)[1], ("restore", 0));
+    }
+
+    #[test]
+    fn failed_poll_is_rearmed_without_an_event() {
+        let paths = config::tests::test_paths("poll-error");
+        let events = AtomicU32::new(0);
+        let mut polls = PollState::new();
+        polls.signal = true;
+        polls.inotify = true;
+        let cqe = |user_data, res| uring::IoUringCqe { user_data, res, flags: uring::IORING_CQE_F_MORE };
+
+        // An error ends the poll even if F_MORE is set, and is no signal
+        process_cqe(&cqe(uring::EV_SIGNAL, -libc::EBADF), &events, &mut polls, -1, &paths);
+        assert!(!polls.signal && polls.inotify);
+        assert_eq!(events.load(Ordering::Relaxed), 0);
+        let warned = polls.poll_error_warned;
+        assert!(warned.is_some());
+
+        // Another failure straight after is not warned about again
+        process_cqe(&cqe(uring::EV_INOTIFY, -libc::ECANCELED), &events, &mut polls, -1, &paths);
+        assert!(!polls.inotify);
+        assert_eq!(polls.poll_error_warned, warned);
+
+        process_cqe(&cqe(uring::EV_SIGNAL, libc::POLLIN as i32), &events, &mut polls, -1, &paths);
+        assert_eq!(events.load(Ordering::Relaxed), FLAG_SIGNAL);
+        let _ = std::fs::remove_dir_all(&paths.config_dir);
+    }
+}
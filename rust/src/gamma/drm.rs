@@ -3,7 +3,7 @@
 //! Pure kernel interface -- no libdrm dependency.
 //! Opens /dev/dri/card* directly.
 
-use super::{colorramp, Error};
+use super::{colorramp, Error, ErrorKind, RampCache};
 use std::fs::OpenOptions;
 use std::os::unix::io::{AsRawFd, RawFd};
 
@@ -13,6 +13,29 @@ const DRM_IOCTL_MODE_GETRESOURCES: u8 = 0xA0;
 const DRM_IOCTL_MODE_GETCRTC: u8 = 0xA1;
 const DRM_IOCTL_MODE_GETGAMMA: u8 = 0xA4;
 const DRM_IOCTL_MODE_SETGAMMA: u8 = 0xA5;
+const DRM_IOCTL_MODE_GETENCODER: u8 = 0xA6;
+const DRM_IOCTL_MODE_GETCONNECTOR: u8 = 0xA7;
+
+/// DRM_MODE_CONNECTOR_* names as the kernel spells them (connector_type
+/// indexes this), so outputs read "card0-eDP-1" like /sys/class/drm
+const CONNECTOR_TYPES: &[&str] = &[
+    "Unknown", "VGA", "DVI-I", "DVI-D", "DVI-A", "Composite", "SVIDEO", "LVDS", "Component",
+    "DIN", "DP", "HDMI-A", "HDMI-B", "TV", "eDP", "Virtual", "DSI", "DPI", "Writeback", "SPI",
+    "USB",
+];
+
+/// Name of a DRM ioctl, for errors
+fn ioctl_name(nr: u8) -> &'static str {
+    match nr {
+        DRM_IOCTL_MODE_GETRESOURCES => "DRM_IOCTL_MODE_GETRESOURCES",
+        DRM_IOCTL_MODE_GETCRTC => "DRM_IOCTL_MODE_GETCRTC",
+        DRM_IOCTL_MODE_GETGAMMA => "DRM_IOCTL_MODE_GETGAMMA",
+        DRM_IOCTL_MODE_SETGAMMA => "DRM_IOCTL_MODE_SETGAMMA",
+        DRM_IOCTL_MODE_GETENCODER => "DRM_IOCTL_MODE_GETENCODER",
+        DRM_IOCTL_MODE_GETCONNECTOR => "DRM_IOCTL_MODE_GETCONNECTOR",
+        _ => "DRM ioctl",
+    }
+}
 
 /// drm_mode_card_res
 #[repr(C)]
@@ -62,6 +85,39 @@ impl Default for DrmModeCrtc {
     }
 }
 
+/// drm_mode_get_encoder
+#[repr(C)]
+#[derive(Default)]
+struct DrmModeGetEncoder {
+    encoder_id: u32,
+    encoder_type: u32,
+    crtc_id: u32,
+    possible_crtcs: u32,
+    possible_clones: u32,
+}
+
+/// drm_mode_get_connector
+#[repr(C)]
+#[derive(Default)]
+struct DrmModeGetConnector {
+    encoders_ptr: u64,
+    modes_ptr: u64,
+    props_ptr: u64,
+    prop_values_ptr: u64,
+    count_modes: u32,
+    count_props: u32,
+    count_encoders: u32,
+    encoder_id: u32,
+    connector_id: u32,
+    connector_type: u32,
+    connector_type_id: u32,
+    connection: u32,
+    mm_width: u32,
+    mm_height: u32,
+    subpixel: u32,
+    pad: u32,
+}
+
 /// drm_mode_crtc_lut
 #[repr(C)]
 #[derive(Default)]
@@ -82,15 +138,51 @@ fn ioctl_rw<T>(fd: RawFd, nr: u8, data: &mut T) -> Result<(), Error> {
 
     let ret = unsafe { libc::ioctl(fd, request as libc::Ioctl, data as *mut T) };
     if ret < 0 {
-        Err(Error::Resources)
+        Err(Error::last_os(ErrorKind::Resources).during(ioctl_name(nr)))
     } else {
         Ok(())
     }
 }
 
+/// "cardN-TYPE-ID" for each connected connector, keyed by the CRTC its
+/// encoder drives. Best effort: a connector that can't be read is left out.
+fn connector_names(fd: RawFd, card_num: i32, connector_ids: &[u32]) -> Vec<(u32, String)> {
+    let mut names = Vec::new();
+    for &connector_id in connector_ids {
+        // count_modes 0 would make the kernel re-probe the connector (slow
+        // on some panels); room for one mode reads the current state only
+        let mut mode = [0u8; 68];
+        let mut conn = DrmModeGetConnector {
+            connector_id,
+            count_modes: 1,
+            modes_ptr: mode.as_mut_ptr() as u64,
+            ..Default::default()
+        };
+        // DRM_MODE_CONNECTED = 1
+        if ioctl_rw(fd, DRM_IOCTL_MODE_GETCONNECTOR, &mut conn).is_err() || conn.connection != 1 || conn.encoder_id == 0 {
+            continue;
+        }
+
+        let mut encoder = DrmModeGetEncoder { encoder_id: conn.encoder_id, ..Default::default() };
+        if ioctl_rw(fd, DRM_IOCTL_MODE_GETENCODER, &mut encoder).is_err() || encoder.crtc_id == 0 {
+            continue;
+        }
+
+        names.push((encoder.crtc_id, connector_name(card_num, conn.connector_type, conn.connector_type_id)));
+    }
+    names
+}
+
+fn connector_name(card_num: i32, connector_type: u32, type_id: u32) -> String {
+    let kind = CONNECTOR_TYPES.get(connector_type as usize).unwrap_or(&"Unknown");
+    format!("card{}-{}-{}", card_num, kind, type_id)
+}
+
 /// Per-CRTC saved state
 struct CrtcState {
     crtc_id: u32,
+    /// Connector it drives ("card0-eDP-1"), if any
+    name: Option<String>,
     gamma_size: u32,
     saved_r: Vec<u16>,
     saved_g: Vec<u16>,
@@ -99,10 +191,14 @@ struct CrtcState {
     work_r: Vec<u16>,
     work_g: Vec<u16>,
     work_b: Vec<u16>,
+    /// Last ramp written by set_temperature
+    ramp: RampCache,
 }
 
 /// DRM gamma state
 pub struct DrmState {
+    /// /dev/dri/cardN
+    device: String,
     fd: RawFd,
     _file: std::fs::File, // owns the fd
     crtcs: Vec<CrtcState>,
@@ -116,40 +212,52 @@ impl DrmState {
             .write(true)
             .open(&path)
             .map_err(|e| {
-                if e.kind() == std::io::ErrorKind::PermissionDenied {
-                    Error::Permission
+                let kind = if e.kind() == std::io::ErrorKind::PermissionDenied {
+                    ErrorKind::Permission
                 } else {
-                    Error::Open
-                }
+                    ErrorKind::Open
+                };
+                Error::with_errno(kind, e.raw_os_error().unwrap_or(0)).on_device(&path)
             })?;
 
         let fd = file.as_raw_fd();
 
         // First call: get count of CRTCs
         let mut res = DrmModeCardRes::default();
-        ioctl_rw(fd, DRM_IOCTL_MODE_GETRESOURCES, &mut res)?;
+        ioctl_rw(fd, DRM_IOCTL_MODE_GETRESOURCES, &mut res).map_err(|e| e.on_device(&path))?;
 
         if res.count_crtcs == 0 {
-            return Err(Error::NoCrtc);
+            return Err(Error::from(ErrorKind::NoCrtc).on_device(&path));
         }
 
-        // Allocate array for CRTC IDs
+        // Allocate arrays for every ID the first call counted: the kernel
+        // fails the second call with EFAULT rather than skip a null array
         let mut crtc_ids = vec![0u32; res.count_crtcs as usize];
+        let mut connector_ids = vec![0u32; res.count_connectors as usize];
+        let mut encoder_ids = vec![0u32; res.count_encoders as usize];
         res.crtc_id_ptr = crtc_ids.as_mut_ptr() as u64;
+        res.connector_id_ptr = connector_ids.as_mut_ptr() as u64;
+        res.encoder_id_ptr = encoder_ids.as_mut_ptr() as u64;
+        res.count_fbs = 0;
 
-        // Second call: get CRTC IDs
-        ioctl_rw(fd, DRM_IOCTL_MODE_GETRESOURCES, &mut res)?;
+        // Second call: get the IDs
+        ioctl_rw(fd, DRM_IOCTL_MODE_GETRESOURCES, &mut res).map_err(|e| e.on_device(&path))?;
+        // Connectors can appear between the calls; only the first N are filled
+        connector_ids.truncate(res.count_connectors as usize);
+        let names = connector_names(fd, card_num, &connector_ids);
 
         // Initialize each CRTC and save original gamma
         let mut crtcs = Vec::with_capacity(res.count_crtcs as usize);
 
-        for &crtc_id in &crtc_ids[..res.count_crtcs as usize] {
+        for &crtc_id in &crtc_ids[..(res.count_crtcs as usize).min(crtc_ids.len())] {
+            let name = names.iter().find(|(id, _)| *id == crtc_id).map(|(_, n)| n.clone());
             let mut crtc_info = DrmModeCrtc::default();
             crtc_info.crtc_id = crtc_id;
 
             if ioctl_rw(fd, DRM_IOCTL_MODE_GETCRTC, &mut crtc_info).is_err() {
                 crtcs.push(CrtcState {
                     crtc_id,
+                    name,
                     gamma_size: 0,
                     saved_r: Vec::new(),
                     saved_g: Vec::new(),
@@ -157,6 +265,7 @@ impl DrmState {
                     work_r: Vec::new(),
                     work_g: Vec::new(),
                     work_b: Vec::new(),
+                    ramp: RampCache::default(),
                 });
                 continue;
             }
@@ -165,6 +274,7 @@ impl DrmState {
             if gamma_size <= 1 {
                 crtcs.push(CrtcState {
                     crtc_id,
+                    name,
                     gamma_size: 0,
                     saved_r: Vec::new(),
                     saved_g: Vec::new(),
@@ -172,6 +282,7 @@ impl DrmState {
                     work_r: Vec::new(),
                     work_g: Vec::new(),
                     work_b: Vec::new(),
+                    ramp: RampCache::default(),
                 });
                 continue;
             }
@@ -192,6 +303,7 @@ impl DrmState {
             if ioctl_rw(fd, DRM_IOCTL_MODE_GETGAMMA, &mut lut).is_err() {
                 crtcs.push(CrtcState {
                     crtc_id,
+                    name,
                     gamma_size: 0,
                     saved_r: Vec::new(),
                     saved_g: Vec::new(),
@@ -199,12 +311,14 @@ impl DrmState {
                     work_r: Vec::new(),
                     work_g: Vec::new(),
                     work_b: Vec::new(),
+                    ramp: RampCache::default(),
                 });
                 continue;
             }
 
             crtcs.push(CrtcState {
                 crtc_id,
+                name,
                 gamma_size,
                 saved_r,
                 saved_g,
@@ -212,10 +326,12 @@ impl DrmState {
                 work_r: vec![0u16; gamma_size as usize],
                 work_g: vec![0u16; gamma_size as usize],
                 work_b: vec![0u16; gamma_size as usize],
+                ramp: RampCache::default(),
             });
         }
 
         Ok(Self {
+            device: path,
             fd,
             _file: file,
             crtcs,
@@ -233,21 +349,40 @@ impl DrmState {
             .unwrap_or(0)
     }
 
+    /// Connector driven by the CRTC ("card0-eDP-1"), None when it drives none
+    pub fn output_name(&self, crtc_idx: usize) -> Option<&str> {
+        self.crtcs.get(crtc_idx)?.name.as_deref()
+    }
+
+    /// Temperature of the ramp found on the first CRTC with gamma at init
+    pub fn initial_temperature(&self) -> Option<i32> {
+        let crtc = self.crtcs.iter().find(|c| !c.saved_r.is_empty())?;
+        colorramp::estimate_temperature(&crtc.saved_r, &crtc.saved_g, &crtc.saved_b)
+    }
+
+    /// Write one CRTC's ramp unless it is within `quantize_step` K of the
+    /// one already there. Returns whether it was written.
     pub fn set_temperature_crtc(
         &mut self,
         crtc_idx: usize,
         temp: i32,
         brightness: f32,
-    ) -> Result<(), Error> {
-        let crtc = self.crtcs.get_mut(crtc_idx).ok_or(Error::Crtc)?;
+        white_point: (f64, f64),
+        quantize_step: i32,
+    ) -> Result<bool, Error> {
+        let crtc = self.crtcs.get_mut(crtc_idx).ok_or(ErrorKind::Crtc)?;
         if crtc.gamma_size <= 1 {
-            return Err(Error::Crtc);
+            return Err(ErrorKind::Crtc.into());
+        }
+
+        if crtc.ramp.covers(temp, brightness, white_point, quantize_step) {
+            return Ok(false);
         }
 
         let size = crtc.gamma_size as usize;
 
         // Reuse pre-allocated working buffers
-        colorramp::fill_gamma_ramps(temp, size, &mut crtc.work_r, &mut crtc.work_g, &mut crtc.work_b, brightness)?;
+        colorramp::fill_gamma_ramps_adapted(temp, size, &mut crtc.work_r, &mut crtc.work_g, &mut crtc.work_b, brightness, white_point.0, white_point.1)?;
 
         let mut lut = DrmModeCrtcLut {
             crtc_id: crtc.crtc_id,
@@ -258,30 +393,73 @@ impl DrmState {
         };
 
         ioctl_rw(self.fd, DRM_IOCTL_MODE_SETGAMMA, &mut lut)
-            .map_err(|_| Error::Gamma)
+            .map_err(|e| Error { kind: ErrorKind::Gamma, ..e }.on_device(&self.device).on_crtc(crtc_idx))?;
+        crtc.ramp.store(temp, brightness, white_point);
+        Ok(true)
+    }
+
+    /// Read the CRTC's current ramp and write it straight back (no visible
+    /// change). Returns the ramp size, 0 for a CRTC without gamma.
+    pub fn check_crtc(&mut self, crtc_idx: usize) -> Result<usize, Error> {
+        let fd = self.fd;
+        let crtc = self.crtcs.get_mut(crtc_idx).ok_or(ErrorKind::Crtc)?;
+        if crtc.gamma_size <= 1 {
+            return Ok(0);
+        }
+
+        let mut lut = DrmModeCrtcLut {
+            crtc_id: crtc.crtc_id,
+            gamma_size: crtc.gamma_size,
+            red: crtc.work_r.as_mut_ptr() as u64,
+            green: crtc.work_g.as_mut_ptr() as u64,
+            blue: crtc.work_b.as_mut_ptr() as u64,
+        };
+        let device = &self.device;
+        ioctl_rw(fd, DRM_IOCTL_MODE_GETGAMMA, &mut lut).map_err(|e| e.on_device(device).on_crtc(crtc_idx))?;
+        ioctl_rw(fd, DRM_IOCTL_MODE_SETGAMMA, &mut lut)
+            .map_err(|e| Error { kind: ErrorKind::Gamma, ..e }.on_device(device).on_crtc(crtc_idx))?;
+        Ok(crtc.gamma_size as usize)
     }
 
-    pub fn set_temperature(&mut self, temp: i32, brightness: f32) -> Result<(), Error> {
+    /// Returns whether any CRTC's ramp was rewritten.
+    pub fn set_temperature(
+        &mut self,
+        temp: i32,
+        brightness: f32,
+        white_point: (f64, f64),
+        quantize_step: i32,
+    ) -> Result<bool, Error> {
         let mut last_err = None;
         let mut success_count = 0;
+        let mut written = false;
 
         for i in 0..self.crtcs.len() {
             if self.crtcs[i].gamma_size > 1 {
-                match self.set_temperature_crtc(i, temp, brightness) {
-                    Ok(()) => success_count += 1,
+                match self.set_temperature_crtc(i, temp, brightness, white_point, quantize_step) {
+                    Ok(w) => {
+                        success_count += 1;
+                        written |= w;
+                    }
                     Err(e) => last_err = Some(e),
                 }
             }
         }
 
         if success_count > 0 {
-            Ok(())
+            Ok(written)
         } else {
-            Err(last_err.unwrap_or(Error::NoCrtc))
+            Err(last_err.unwrap_or(ErrorKind::NoCrtc.into()))
+        }
+    }
+
+    pub fn invalidate_ramps(&mut self) {
+        for crtc in &mut self.crtcs {
+            crtc.ramp.clear();
         }
     }
 
     pub fn restore(&mut self) -> Result<(), Error> {
+        self.invalidate_ramps();
         for crtc in &mut self.crtcs {
             if crtc.gamma_size > 1 && !crtc.saved_r.is_empty() {
                 let mut lut = DrmModeCrtcLut {
@@ -303,3 +481,18 @@ impl Drop for DrmState {
         let _ = self.restore();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn connector_names_match_sysfs() {
+        assert_eq!(connector_name(0, 14, 1), "card0-eDP-1");
+        assert_eq!(connector_name(1, 11, 2), "card1-HDMI-A-2");
+        assert_eq!(connector_name(0, 10, 3), "card0-DP-3");
+        assert_eq!(connector_name(0, 99, 1), "card0-Unknown-1");
+        assert_eq!(std::mem::size_of::<DrmModeGetConnector>(), 80);
+        assert_eq!(std::mem::size_of::<DrmModeGetEncoder>(), 20);
+    }
+}
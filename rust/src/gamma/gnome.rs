@@ -3,12 +3,13 @@
 //! Uses org.gnome.Mutter.DisplayConfig.SetCrtcGamma to set gamma
 //! ramps on GNOME Wayland sessions (Mutter compositor).
 //!
-//! Links directly against libsystemd -- same approach as the C23
-//! implementation. No async runtime, no zbus.
+//! Links directly against libsystemd (see sdbus.rs) -- same approach as
+//! the C23 implementation. No async runtime, no zbus.
 //!
 //! Covers: GNOME on Debian, Ubuntu, Fedora, RHEL, etc.
 
-use super::{colorramp, Error};
+use super::{colorramp, Error, ErrorKind, RampCache};
+use crate::sdbus::*;
 use std::ffi::{c_char, c_int, c_void};
 use std::ptr;
 
@@ -19,109 +20,136 @@ const DBUS_NAME: &[u8] = b"org.gnome.Mutter.DisplayConfig\0";
 const DBUS_PATH: &[u8] = b"/org/gnome/Mutter/DisplayConfig\0";
 const DBUS_IFACE: &[u8] = b"org.gnome.Mutter.DisplayConfig\0";
 
-// --- sd-bus FFI declarations ---
-
-#[repr(C)]
-struct SdBus {
-    _opaque: [u8; 0],
+// gsd-color exports the org.gnome.settings-daemon.plugins.color state here
+const COLOR_NAME: &[u8] = b"org.gnome.SettingsDaemon.Color\0";
+const COLOR_PATH: &[u8] = b"/org/gnome/SettingsDaemon/Color\0";
+const COLOR_IFACE: &[u8] = b"org.gnome.SettingsDaemon.Color\0";
+const PROPS_IFACE: &[u8] = b"org.freedesktop.DBus.Properties\0";
+
+/// GnomeDbus error for a failed sd-bus call: the DBus error text when
+/// Mutter sent one, else the errno sd-bus returned (negated)
+fn dbus_error(op: &'static str, r: c_int, error: &SdBusError) -> Error {
+    match error.describe() {
+        Some(text) => Error::with_detail(ErrorKind::GnomeDbus, text),
+        None => Error::with_errno(ErrorKind::GnomeDbus, -r),
+    }
+    .during(op)
 }
 
-#[repr(C)]
-struct SdBusMessage {
-    _opaque: [u8; 0],
-}
+// --- Night Light detection ---
 
-#[repr(C)]
-struct SdBusError {
-    name: *const c_char,
-    message: *const c_char,
-    _need_free: c_int,
-}
+/// Query gsd-color's NightLightActive property on an open bus.
+/// Any failure (no gsd-color, not a GNOME session) counts as inactive.
+fn night_light_active(bus: *mut SdBus) -> bool {
+    let mut error = SdBusError::null();
+    let mut reply: *mut SdBusMessage = ptr::null_mut();
 
-impl SdBusError {
-    fn null() -> Self {
-        SdBusError {
-            name: ptr::null(),
-            message: ptr::null(),
-            _need_free: 0,
+    let r = unsafe {
+        sd_bus_call_method(
+            bus,
+            COLOR_NAME.as_ptr() as *const c_char,
+            COLOR_PATH.as_ptr() as *const c_char,
+            PROPS_IFACE.as_ptr() as *const c_char,
+            c"Get".as_ptr(),
+            &mut error,
+            &mut reply,
+            c"ss".as_ptr(),
+            COLOR_IFACE.as_ptr() as *const c_char,
+            c"NightLightActive".as_ptr(),
+        )
+    };
+    if r < 0 {
+        unsafe { sd_bus_error_free(&mut error) };
+        return false;
+    }
+
+    // Reply is a variant wrapping a boolean (sd-bus booleans are C ints)
+    let mut active: c_int = 0;
+    let r = unsafe {
+        let r = sd_bus_message_enter_container(reply, b'v' as c_char, c"b".as_ptr());
+        if r < 0 {
+            r
+        } else {
+            sd_bus_message_read(reply, c"b".as_ptr(), &mut active as *mut c_int)
         }
+    };
+
+    unsafe {
+        sd_bus_message_unref(reply);
+        sd_bus_error_free(&mut error);
     }
+
+    r >= 0 && active != 0
 }
 
-#[link(name = "systemd")]
-extern "C" {
-    fn sd_bus_open_user(bus: *mut *mut SdBus) -> c_int;
-    fn sd_bus_unref(bus: *mut SdBus) -> *mut SdBus;
+/// Whether GNOME Night Light is currently tinting the screen.
+/// Both adjusting gamma at once would double-correct.
+pub fn detect_night_light_active() -> bool {
+    let mut bus: *mut SdBus = ptr::null_mut();
+    if unsafe { sd_bus_open_user(&mut bus) } < 0 {
+        return false;
+    }
+    let active = night_light_active(bus);
+    unsafe { sd_bus_unref(bus) };
+    active
+}
 
-    fn sd_bus_call_method(
-        bus: *mut SdBus,
-        destination: *const c_char,
-        path: *const c_char,
-        interface: *const c_char,
-        member: *const c_char,
-        error: *mut SdBusError,
-        reply: *mut *mut SdBusMessage,
-        types: *const c_char,
-        ...
-    ) -> c_int;
-
-    fn sd_bus_message_read(
-        msg: *mut SdBusMessage,
-        types: *const c_char,
-        ...
-    ) -> c_int;
-
-    fn sd_bus_message_enter_container(
-        msg: *mut SdBusMessage,
-        type_: c_char,
-        contents: *const c_char,
-    ) -> c_int;
-
-    fn sd_bus_message_exit_container(msg: *mut SdBusMessage) -> c_int;
-
-    fn sd_bus_message_skip(
-        msg: *mut SdBusMessage,
-        types: *const c_char,
-    ) -> c_int;
-
-    fn sd_bus_message_new_method_call(
-        bus: *mut SdBus,
-        msg: *mut *mut SdBusMessage,
-        destination: *const c_char,
-        path: *const c_char,
-        interface: *const c_char,
-        member: *const c_char,
-    ) -> c_int;
-
-    fn sd_bus_message_append(
-        msg: *mut SdBusMessage,
-        types: *const c_char,
-        ...
-    ) -> c_int;
-
-    fn sd_bus_message_append_array(
-        msg: *mut SdBusMessage,
-        type_: c_char,
-        ptr: *const c_void,
-        size: usize,
-    ) -> c_int;
-
-    fn sd_bus_call(
-        bus: *mut SdBus,
-        msg: *mut SdBusMessage,
-        usec: u64,
-        error: *mut SdBusError,
-        reply: *mut *mut SdBusMessage,
-    ) -> c_int;
-
-    fn sd_bus_message_unref(msg: *mut SdBusMessage) -> *mut SdBusMessage;
-    fn sd_bus_error_free(error: *mut SdBusError);
+/// Name each CRTC after the output it drives, from GetResources' output
+/// array a(uxiausauaua{sv}): id, winsys id, CRTC index (-1 = none),
+/// possible CRTCs, connector name, modes, clones, properties. Best effort:
+/// stops at the first field that fails to read.
+fn read_output_names(reply: *mut SdBusMessage, crtcs: &mut [GnomeCrtc]) {
+    let r = unsafe { sd_bus_message_enter_container(reply, b'a' as c_char, c"(uxiausauaua{sv})".as_ptr()) };
+    if r < 0 {
+        return;
+    }
+
+    loop {
+        let r = unsafe { sd_bus_message_enter_container(reply, b'r' as c_char, c"uxiausauaua{sv}".as_ptr()) };
+        if r <= 0 {
+            break;
+        }
+
+        let mut id: u32 = 0;
+        let mut winsys_id: i64 = 0;
+        let mut crtc: i32 = -1;
+        let mut name: *const c_char = ptr::null();
+        let read = unsafe {
+            sd_bus_message_read(
+                reply,
+                c"uxi".as_ptr(),
+                &mut id as *mut u32,
+                &mut winsys_id as *mut i64,
+                &mut crtc as *mut i32,
+            ) >= 0
+                && sd_bus_message_skip(reply, c"au".as_ptr()) >= 0
+                && sd_bus_message_read(reply, c"s".as_ptr(), &mut name as *mut *const c_char) >= 0
+                && sd_bus_message_skip(reply, c"auaua{sv}".as_ptr()) >= 0
+        };
+        if !read {
+            break;
+        }
+        unsafe { sd_bus_message_exit_container(reply) };
+
+        // The string lives in the reply, so copy it before the unref
+        if let Some(c) = usize::try_from(crtc).ok().and_then(|i| crtcs.get_mut(i)) {
+            if !name.is_null() {
+                c.name = Some(unsafe { std::ffi::CStr::from_ptr(name) }.to_string_lossy().into_owned());
+            }
+        }
+    }
+
+    unsafe { sd_bus_message_exit_container(reply) };
 }
 
 // --- GNOME state ---
 
 struct GnomeCrtc {
     crtc_id: u32,
+    /// Connector of the output it drives ("eDP-1"), if any
+    name: Option<String>,
+    /// Last ramp written by set_temperature
+    ramp: RampCache,
 }
 
 pub struct GnomeState {
@@ -138,11 +166,18 @@ pub struct GnomeState {
 unsafe impl Send for GnomeState {}
 
 impl GnomeState {
-    pub fn init() -> Result<Self, Error> {
+    /// Connect to Mutter. Refuses while GNOME Night Light is active
+    /// unless `force` is set.
+    pub fn init(force: bool) -> Result<Self, Error> {
         let mut bus: *mut SdBus = ptr::null_mut();
         let r = unsafe { sd_bus_open_user(&mut bus) };
         if r < 0 {
-            return Err(Error::GnomeDbus);
+            return Err(Error::with_errno(ErrorKind::GnomeDbus, -r).during("sd_bus_open_user"));
+        }
+
+        if !force && night_light_active(bus) {
+            unsafe { sd_bus_unref(bus) };
+            return Err(ErrorKind::GnomeNightLight.into());
         }
 
         let mut state = GnomeState {
@@ -157,7 +192,7 @@ impl GnomeState {
         state.get_resources()?;
 
         if state.crtcs.is_empty() {
-            return Err(Error::NoCrtc);
+            return Err(ErrorKind::NoCrtc.into());
         }
 
         Ok(state)
@@ -165,8 +200,9 @@ impl GnomeState {
 
     /// Call GetResources to discover CRTC IDs and serial number.
     ///
-    /// GetResources returns: (ua(uxiiiiiuaua{sv})a(uxiausauau)a(uxuudu)ii)
-    /// We only need: serial (first u) and crtc_id (first u in each CRTC struct).
+    /// GetResources returns: (ua(uxiiiiiuaua{sv})a(uxiausauaua{sv})a(uxuudu)ii)
+    /// We only need: serial (first u), crtc_id (first u in each CRTC struct)
+    /// and each output's CRTC index and connector name.
     fn get_resources(&mut self) -> Result<(), Error> {
         let mut error = SdBusError::null();
         let mut reply: *mut SdBusMessage = ptr::null_mut();
@@ -184,8 +220,9 @@ impl GnomeState {
             )
         };
         if r < 0 {
+            let e = dbus_error("GetResources", r, &error);
             unsafe { sd_bus_error_free(&mut error) };
-            return Err(Error::GnomeDbus);
+            return Err(e);
         }
 
         // Read serial
@@ -202,7 +239,7 @@ impl GnomeState {
                 sd_bus_message_unref(reply);
                 sd_bus_error_free(&mut error);
             }
-            return Err(Error::GnomeDbus);
+            return Err(Error::with_errno(ErrorKind::GnomeDbus, -r).during("GetResources reply (serial)"));
         }
         self.serial = serial;
 
@@ -219,7 +256,7 @@ impl GnomeState {
                 sd_bus_message_unref(reply);
                 sd_bus_error_free(&mut error);
             }
-            return Err(Error::GnomeDbus);
+            return Err(Error::with_errno(ErrorKind::GnomeDbus, -r).during("GetResources reply (CRTCs)"));
         }
 
         self.crtcs.clear();
@@ -261,17 +298,18 @@ impl GnomeState {
 
             unsafe { sd_bus_message_exit_container(reply) };
 
-            self.crtcs.push(GnomeCrtc { crtc_id });
+            self.crtcs.push(GnomeCrtc { crtc_id, name: None, ramp: RampCache::default() });
         }
 
+        unsafe { sd_bus_message_exit_container(reply) };
+        read_output_names(reply, &mut self.crtcs);
         unsafe {
-            sd_bus_message_exit_container(reply);
             sd_bus_message_unref(reply);
             sd_bus_error_free(&mut error);
         }
 
         if self.crtcs.is_empty() {
-            Err(Error::NoCrtc)
+            Err(ErrorKind::NoCrtc.into())
         } else {
             Ok(())
         }
@@ -281,6 +319,11 @@ impl GnomeState {
         self.crtcs.len()
     }
 
+    /// Connector of the output on the CRTC ("eDP-1"), None when it drives none
+    pub fn output_name(&self, crtc_idx: usize) -> Option<&str> {
+        self.crtcs.get(crtc_idx)?.name.as_deref()
+    }
+
     /// Set gamma ramp on a specific CRTC via SetCrtcGamma DBus call.
     /// Signature: SetCrtcGamma(uu aq aq aq) = (serial, crtc_id, red[], green[], blue[])
     fn set_gamma_crtc_raw(
@@ -305,7 +348,7 @@ impl GnomeState {
             )
         };
         if ret < 0 {
-            return Err(Error::GnomeDbus);
+            return Err(Error::with_errno(ErrorKind::GnomeDbus, -ret).during("SetCrtcGamma"));
         }
 
         // Append serial and CRTC ID
@@ -319,7 +362,7 @@ impl GnomeState {
         };
         if ret < 0 {
             unsafe { sd_bus_message_unref(msg) };
-            return Err(Error::GnomeDbus);
+            return Err(Error::with_errno(ErrorKind::GnomeDbus, -ret).during("SetCrtcGamma"));
         }
 
         // Append three gamma ramp arrays (aq = array of uint16)
@@ -336,69 +379,113 @@ impl GnomeState {
             };
             if ret < 0 {
                 unsafe { sd_bus_message_unref(msg) };
-                return Err(Error::GnomeDbus);
+                return Err(Error::with_errno(ErrorKind::GnomeDbus, -ret).during("SetCrtcGamma"));
             }
         }
 
         let ret = unsafe {
             sd_bus_call(bus, msg, 0, &mut error, ptr::null_mut())
         };
+        let result = if ret < 0 { Err(dbus_error("SetCrtcGamma", ret, &error)) } else { Ok(()) };
 
         unsafe {
             sd_bus_message_unref(msg);
             sd_bus_error_free(&mut error);
         }
-
-        if ret < 0 {
-            Err(Error::GnomeDbus)
-        } else {
-            Ok(())
-        }
+        result
     }
 
+    /// Write one CRTC's ramp unless it is within `quantize_step` K of the
+    /// one already there. Returns whether it was written.
     pub fn set_temperature_crtc(
         &mut self,
         crtc_idx: usize,
         temp: i32,
         brightness: f32,
-    ) -> Result<(), Error> {
-        let crtc_id = match self.crtcs.get(crtc_idx) {
-            Some(c) => c.crtc_id,
-            None => return Err(Error::GnomeDbus),
+        white_point: (f64, f64),
+        quantize_step: i32,
+    ) -> Result<bool, Error> {
+        let crtc = match self.crtcs.get_mut(crtc_idx) {
+            Some(c) => c,
+            None => return Err(Error::from(ErrorKind::Crtc).on_crtc(crtc_idx)),
         };
+        if crtc.ramp.covers(temp, brightness, white_point, quantize_step) {
+            return Ok(false);
+        }
 
         // Reuse pre-allocated working buffers
-        colorramp::fill_gamma_ramps(temp, GNOME_GAMMA_SIZE, &mut self.work_r, &mut self.work_g, &mut self.work_b, brightness)?;
+        colorramp::fill_gamma_ramps_adapted(temp, GNOME_GAMMA_SIZE, &mut self.work_r, &mut self.work_g, &mut self.work_b, brightness, white_point.0, white_point.1)?;
 
-        Self::set_gamma_crtc_raw(self.bus, self.serial, crtc_id, &self.work_r, &self.work_g, &self.work_b)
+        Self::set_gamma_crtc_raw(self.bus, self.serial, crtc.crtc_id, &self.work_r, &self.work_g, &self.work_b)
+            .map_err(|e| e.on_crtc(crtc_idx))?;
+        crtc.ramp.store(temp, brightness, white_point);
+        Ok(true)
     }
 
-    pub fn set_temperature(&mut self, temp: i32, brightness: f32) -> Result<(), Error> {
+    /// Returns whether any CRTC's ramp was rewritten.
+    pub fn set_temperature(
+        &mut self,
+        temp: i32,
+        brightness: f32,
+        white_point: (f64, f64),
+        quantize_step: i32,
+    ) -> Result<bool, Error> {
         let mut last_err = None;
         let mut success_count = 0;
+        let mut written = false;
 
         for i in 0..self.crtcs.len() {
-            match self.set_temperature_crtc(i, temp, brightness) {
-                Ok(()) => success_count += 1,
+            match self.set_temperature_crtc(i, temp, brightness, white_point, quantize_step) {
+                Ok(w) => {
+                    success_count += 1;
+                    written |= w;
+                }
                 Err(e) => last_err = Some(e),
             }
         }
 
         if success_count > 0 {
-            Ok(())
+            Ok(written)
         } else {
-            Err(last_err.unwrap_or(Error::NoCrtc))
+            Err(last_err.unwrap_or(ErrorKind::NoCrtc.into()))
         }
     }
 
-    pub fn restore(&mut self) -> Result<(), Error> {
-        // Fill work buffers with linear identity ramp
+    /// Fill work buffers with linear identity ramp
+    fn fill_identity(&mut self) {
         for i in 0..GNOME_GAMMA_SIZE {
             let val = (i as f32 / (GNOME_GAMMA_SIZE - 1) as f32 * u16::MAX as f32) as u16;
             self.work_r[i] = val;
             self.work_g[i] = val;
             self.work_b[i] = val;
         }
+    }
+
+    /// Write the identity ramp (what restore leaves behind) to one CRTC.
+    /// Ramps are never read back over DBus, so this is the closest no-op.
+    pub fn check_crtc(&mut self, crtc_idx: usize) -> Result<usize, Error> {
+        let crtc_id = match self.crtcs.get_mut(crtc_idx) {
+            Some(c) => {
+                c.ramp.clear();
+                c.crtc_id
+            }
+            None => return Err(ErrorKind::Crtc.into()),
+        };
+        self.fill_identity();
+        Self::set_gamma_crtc_raw(self.bus, self.serial, crtc_id, &self.work_r, &self.work_g, &self.work_b)
+            .map_err(|e| e.on_crtc(crtc_idx))?;
+        Ok(GNOME_GAMMA_SIZE)
+    }
+
+    pub fn invalidate_ramps(&mut self) {
+        for crtc in &mut self.crtcs {
+            crtc.ramp.clear();
+        }
+    }
+
+    pub fn restore(&mut self) -> Result<(), Error> {
+        self.invalidate_ramps();
+        self.fill_identity();
 
         let mut last_err = None;
         for crtc in &self.crtcs {
@@ -0,0 +1,88 @@
+//! Mock gamma backend: records calls instead of touching the display.
+//!
+//! Selected by ABRAXAS_MOCK_GAMMA=1 (or `--daemon --dry-run`). Every
+//! set_temperature/restore is kept in memory and printed to stderr;
+//! ABRAXAS_MOCK_GAMMA_LOG=PATH additionally appends one JSON line per call.
+//!
+//! Lets the real tick/override/recovery logic run without display hardware.
+
+use super::{colorramp, Error};
+use serde::Serialize;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// One recorded backend call. `temp` is 0 for a restore.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MockCall {
+    pub op: &'static str,
+    pub temp: i32,
+    pub brightness: f32,
+    pub timestamp: i64,
+}
+
+pub struct MockState {
+    calls: Vec<MockCall>,
+    log: Option<File>,
+    /// Panic in set_temperature, standing in for a backend bug (tests)
+    panic_on_set: bool,
+}
+
+impl MockState {
+    /// Opens the JSONL log (if requested) now, before the sandbox is up.
+    pub fn init() -> Self {
+        let log = std::env::var_os("ABRAXAS_MOCK_GAMMA_LOG").and_then(|path| {
+            match OpenOptions::new().create(true).append(true).open(&path) {
+                Ok(f) => Some(f),
+                Err(e) => {
+                    eprintln!("[mock] Cannot open {}: {}", path.to_string_lossy(), e);
+                    None
+                }
+            }
+        });
+        MockState { calls: Vec::new(), log, panic_on_set: false }
+    }
+
+    #[cfg(test)]
+    pub fn calls(&self) -> &[MockCall] {
+        &self.calls
+    }
+
+    #[cfg(test)]
+    pub fn panic_on_set(&mut self) {
+        self.panic_on_set = true;
+    }
+
+    fn record(&mut self, op: &'static str, temp: i32, brightness: f32) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+        let call = MockCall { op, temp, brightness, timestamp };
+
+        if op == "restore" {
+            eprintln!("[mock] restore");
+        } else {
+            eprintln!("[mock] {}K at {:.0}% brightness", temp, brightness * 100.0);
+        }
+        if let Some(ref mut f) = self.log {
+            if let Ok(line) = serde_json::to_string(&call) {
+                let _ = writeln!(f, "{}", line);
+            }
+        }
+        self.calls.push(call);
+    }
+
+    pub fn set_temperature(&mut self, temp: i32, brightness: f32, _white_point: (f64, f64)) -> Result<(), Error> {
+        assert!(!self.panic_on_set, "mock backend panicked at {}K", temp);
+        // Reject what a real backend would reject
+        colorramp::temp_to_rgb(temp)?;
+        self.record("set", temp, brightness);
+        Ok(())
+    }
+
+    pub fn restore(&mut self) -> Result<(), Error> {
+        self.record("restore", 0, 1.0);
+        Ok(())
+    }
+}
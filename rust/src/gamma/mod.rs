@@ -5,9 +5,22 @@
 //!   2. GNOME (Mutter DBus) - GNOME Wayland sessions
 //!   3. DRM (kernel ioctl) - always available
 //!   4. X11 (RandR) - NVIDIA fallback, Xorg sessions
+//!
+//! ABRAXAS_MOCK_GAMMA=1 replaces all of these with a recording mock.
+//!
+//! There is no wp-color-management-v1 backend: that protocol only lets a
+//! client describe the colorimetry of its own surfaces, and
+//! wp_color_management_output_v1 is read-only. A surface-less daemon cannot
+//! shift an output's white point through it, so wlr-gamma-control stays the
+//! Wayland path until compositors expose an output-level equivalent.
+//!
+//! There is no PipeWire backend either. PipeWire carries media streams
+//! (screencasts included) and has no parameter for a display's color
+//! ramps; on GNOME the output gamma still belongs to Mutter, which the
+//! DBus backend already drives.
 
-pub mod colorramp;
 pub mod drm;
+pub mod mock;
 
 #[cfg(feature = "wayland")]
 pub mod wayland;
@@ -18,11 +31,13 @@ pub mod x11;
 #[cfg(feature = "gnome")]
 pub mod gnome;
 
+pub use abraxas_core::colorramp;
 use std::fmt;
 
-/// Error type for gamma operations
+/// What went wrong in a gamma operation
 #[derive(Debug, Clone, Copy, PartialEq)]
-pub enum Error {
+#[non_exhaustive]
+pub enum ErrorKind {
     InvalidTemp,
     Open,
     Resources,
@@ -36,33 +51,212 @@ pub enum Error {
     WaylandProtocol,
     #[cfg(feature = "gnome")]
     GnomeDbus,
+    #[cfg(feature = "gnome")]
+    GnomeNightLight,
 }
 
-impl fmt::Display for Error {
+impl fmt::Display for ErrorKind {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Error::InvalidTemp => write!(f, "Invalid temperature"),
-            Error::Open => write!(f, "Failed to open display device"),
-            Error::Resources => write!(f, "Failed to get display resources"),
-            Error::Crtc => write!(f, "Failed to get CRTC info"),
-            Error::Gamma => write!(f, "Failed to set gamma ramp"),
-            Error::NoCrtc => write!(f, "No usable CRTC found"),
-            Error::Permission => write!(f, "Permission denied (need video group?)"),
+            ErrorKind::InvalidTemp => write!(f, "Invalid temperature"),
+            ErrorKind::Open => write!(f, "Failed to open display device"),
+            ErrorKind::Resources => write!(f, "Failed to get display resources"),
+            ErrorKind::Crtc => write!(f, "Failed to get CRTC info"),
+            ErrorKind::Gamma => write!(f, "Failed to set gamma ramp"),
+            ErrorKind::NoCrtc => write!(f, "No usable CRTC found"),
+            ErrorKind::Permission => write!(f, "Permission denied (need video group?)"),
             #[cfg(feature = "wayland")]
-            Error::WaylandConnect => write!(f, "Failed to connect to Wayland display"),
+            ErrorKind::WaylandConnect => write!(f, "Failed to connect to Wayland display"),
             #[cfg(feature = "wayland")]
-            Error::WaylandProtocol => write!(f, "Wayland compositor lacks gamma control protocol"),
+            ErrorKind::WaylandProtocol => write!(f, "Wayland compositor lacks gamma control protocol"),
             #[cfg(feature = "gnome")]
-            Error::GnomeDbus => write!(f, "Failed to communicate with Mutter via DBus"),
+            ErrorKind::GnomeDbus => write!(f, "Failed to communicate with Mutter via DBus"),
+            #[cfg(feature = "gnome")]
+            ErrorKind::GnomeNightLight => write!(f, "GNOME Night Light is active, skipping GNOME backend"),
+        }
+    }
+}
+
+/// Error type for gamma operations: the kind plus whatever context the
+/// backend had -- where it happened, the request that failed, and the OS
+/// errno or protocol/DBus error text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Error {
+    pub kind: ErrorKind,
+    /// Backend name ("drm", "wayland", ...), as backend_name() reports it
+    pub backend: Option<&'static str>,
+    /// Device path, X display or output name
+    pub device: Option<String>,
+    pub crtc_index: Option<usize>,
+    /// Request that failed (an ioctl or protocol call)
+    pub op: Option<&'static str>,
+    pub errno: Option<i32>,
+    /// Error text from the display server or DBus
+    pub detail: Option<String>,
+}
+
+impl Error {
+    /// Error carrying the raw errno of the failed syscall.
+    pub fn with_errno(kind: ErrorKind, errno: i32) -> Error {
+        Error { errno: Some(errno), ..kind.into() }
+    }
+
+    /// Error carrying the display server's (or DBus) error text.
+    #[cfg_attr(not(any(feature = "wayland", feature = "x11", feature = "gnome")), allow(dead_code))]
+    pub fn with_detail(kind: ErrorKind, detail: impl ToString) -> Error {
+        Error { detail: Some(detail.to_string()), ..kind.into() }
+    }
+
+    /// Error from the current thread's errno (call right after the syscall).
+    pub fn last_os(kind: ErrorKind) -> Error {
+        let errno = std::io::Error::last_os_error().raw_os_error().unwrap_or(0);
+        Error::with_errno(kind, errno)
+    }
+
+    /// Attach the index of the CRTC/output the error happened on.
+    pub fn on_crtc(self, crtc_index: usize) -> Error {
+        Error { crtc_index: Some(crtc_index), ..self }
+    }
+
+    /// Attach the device path, display or output name (ignored if empty,
+    /// as with an unset $DISPLAY).
+    pub fn on_device(self, device: &str) -> Error {
+        if device.is_empty() {
+            return self;
+        }
+        Error { device: Some(device.to_string()), ..self }
+    }
+
+    /// Attach the name of the request that failed.
+    pub fn during(self, op: &'static str) -> Error {
+        Error { op: Some(op), ..self }
+    }
+
+    /// Attach the backend name (kept if the backend already set one).
+    pub fn in_backend(self, backend: &'static str) -> Error {
+        Error { backend: self.backend.or(Some(backend)), ..self }
+    }
+}
+
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Self {
+        Error { kind, backend: None, device: None, crtc_index: None, op: None, errno: None, detail: None }
+    }
+}
+
+impl From<colorramp::RampError> for Error {
+    fn from(_: colorramp::RampError) -> Self {
+        ErrorKind::InvalidTemp.into()
+    }
+}
+
+/// Symbolic name for the errnos gamma ioctls and sockets commonly return.
+fn errno_name(errno: i32) -> Option<&'static str> {
+    Some(match errno {
+        libc::EPERM => "EPERM",
+        libc::ENOENT => "ENOENT",
+        libc::EINTR => "EINTR",
+        libc::EIO => "EIO",
+        libc::ENXIO => "ENXIO",
+        libc::EBADF => "EBADF",
+        libc::EAGAIN => "EAGAIN",
+        libc::ENOMEM => "ENOMEM",
+        libc::EACCES => "EACCES",
+        libc::EFAULT => "EFAULT",
+        libc::EBUSY => "EBUSY",
+        libc::ENODEV => "ENODEV",
+        libc::EINVAL => "EINVAL",
+        libc::ENOSPC => "ENOSPC",
+        libc::ENOSYS => "ENOSYS",
+        libc::EOPNOTSUPP => "EOPNOTSUPP",
+        libc::ECONNREFUSED => "ECONNREFUSED",
+        _ => return None,
+    })
+}
+
+/// OS description of an errno via strerror_r.
+fn strerror(errno: i32) -> String {
+    let mut buf = [0 as libc::c_char; 128];
+    let ret = unsafe { libc::strerror_r(errno, buf.as_mut_ptr(), buf.len()) };
+    if ret != 0 {
+        return format!("unknown error {}", errno);
+    }
+    unsafe { std::ffi::CStr::from_ptr(buf.as_ptr()) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// "drm /dev/dri/card0 CRTC 1: Failed to set gamma ramp (SETGAMMA): EBUSY
+/// (Device or resource busy)", leaving out what isn't known.
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut place = Vec::new();
+        place.extend(self.backend.map(str::to_string));
+        place.extend(self.device.clone());
+        place.extend(self.crtc_index.map(|idx| format!("CRTC {}", idx)));
+        if !place.is_empty() {
+            write!(f, "{}: ", place.join(" "))?;
+        }
+        write!(f, "{}", self.kind)?;
+        if let Some(op) = self.op {
+            write!(f, " ({})", op)?;
+        }
+        if let Some(errno) = self.errno {
+            match errno_name(errno) {
+                Some(name) => write!(f, ": {} ({})", name, strerror(errno))?,
+                None => write!(f, ": errno {} ({})", errno, strerror(errno))?,
+            }
         }
+        if let Some(ref detail) = self.detail {
+            write!(f, ": {}", detail)?;
+        }
+        Ok(())
     }
 }
 
-impl std::error::Error for Error {}
+impl std::error::Error for Error {
+    /// No wrapped error yet; errno is rendered inline by Display.
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+/// What one CRTC's ramp was last filled with. Rewriting it for a
+/// temperature within the quantization step would produce a ramp no one
+/// can tell apart (the color JND is around 50K), so that write is skipped.
+#[derive(Default)]
+pub struct RampCache {
+    /// 0 until a ramp is written (and again after a restore)
+    last_ramp_temp: i32,
+    brightness: f32,
+    white_point: (f64, f64),
+}
+
+impl RampCache {
+    /// True if the ramp on screen already matches `temp` to within `step` K
+    /// at the same brightness and white point.
+    pub fn covers(&self, temp: i32, brightness: f32, white_point: (f64, f64), step: i32) -> bool {
+        self.last_ramp_temp != 0
+            && (temp - self.last_ramp_temp).abs() <= step
+            && self.brightness == brightness
+            && self.white_point == white_point
+    }
+
+    /// Record a ramp that was actually written.
+    pub fn store(&mut self, temp: i32, brightness: f32, white_point: (f64, f64)) {
+        *self = RampCache { last_ramp_temp: temp, brightness, white_point };
+    }
+
+    /// The CRTC no longer shows what was stored (restored or overwritten).
+    pub fn clear(&mut self) {
+        self.last_ramp_temp = 0;
+    }
+}
 
 /// Backend type
 enum Backend {
     Drm(drm::DrmState),
+    Mock(mock::MockState),
     #[cfg(feature = "wayland")]
     Wayland(wayland::WaylandState),
     #[cfg(feature = "x11")]
@@ -74,12 +268,21 @@ enum Backend {
 /// Unified gamma state
 pub struct GammaState {
     backend: Backend,
+    /// Skip CRTCs whose ramp is within this many K of the request
+    ramp_quantize_step: i32,
+    /// set_temperature calls that left every ramp as it was
+    ramp_cache_hits: u64,
 }
 
 impl GammaState {
-    pub fn backend_name(&self) -> &str {
+    fn new(backend: Backend) -> GammaState {
+        GammaState { backend, ramp_quantize_step: crate::RAMP_QUANTIZE_STEP, ramp_cache_hits: 0 }
+    }
+
+    pub fn backend_name(&self) -> &'static str {
         match &self.backend {
             Backend::Drm(_) => "drm",
+            Backend::Mock(_) => "mock",
             #[cfg(feature = "wayland")]
             Backend::Wayland(_) => "wayland",
             #[cfg(feature = "x11")]
@@ -89,21 +292,171 @@ impl GammaState {
         }
     }
 
-    pub fn set_temperature(&mut self, temp: i32, brightness: f32) -> Result<(), Error> {
+    /// Apply `temp` at `brightness` on monitors whose native white is
+    /// `white_point` (CIE xy; colorramp::D65_X/D65_Y for a calibrated panel).
+    /// CRTCs already within the quantization step of `temp` are left alone.
+    pub fn set_temperature(&mut self, temp: i32, brightness: f32, white_point: (f64, f64)) -> Result<(), Error> {
+        let step = self.ramp_quantize_step;
+        let backend = self.backend_name();
+        let written = match &mut self.backend {
+            Backend::Drm(state) => state.set_temperature(temp, brightness, white_point, step),
+            Backend::Mock(state) => state.set_temperature(temp, brightness, white_point).map(|()| true),
+            #[cfg(feature = "wayland")]
+            Backend::Wayland(state) => state.set_temperature(temp, brightness, white_point, step),
+            #[cfg(feature = "x11")]
+            Backend::X11(state) => state.set_temperature(temp, brightness, white_point, step),
+            #[cfg(feature = "gnome")]
+            Backend::Gnome(state) => state.set_temperature(temp, brightness, white_point, step),
+        }
+        .map_err(|e| e.in_backend(backend))?;
+        if !written {
+            self.ramp_cache_hits += 1;
+        }
+        Ok(())
+    }
+
+    /// Set the ramp quantization step in K (0 skips only identical ramps).
+    pub fn set_ramp_quantize_step(&mut self, step: i32) {
+        self.ramp_quantize_step = step;
+    }
+
+    pub fn ramp_cache_hits(&self) -> u64 {
+        self.ramp_cache_hits
+    }
+
+    /// Forget the cached ramps so the next set_temperature rewrites every
+    /// CRTC (something else may have changed them).
+    pub fn invalidate_ramps(&mut self) {
         match &mut self.backend {
-            Backend::Drm(state) => state.set_temperature(temp, brightness),
+            Backend::Drm(state) => state.invalidate_ramps(),
+            Backend::Mock(_) => {}
             #[cfg(feature = "wayland")]
-            Backend::Wayland(state) => state.set_temperature(temp, brightness),
+            Backend::Wayland(state) => state.invalidate_ramps(),
             #[cfg(feature = "x11")]
-            Backend::X11(state) => state.set_temperature(temp, brightness),
+            Backend::X11(state) => state.invalidate_ramps(),
             #[cfg(feature = "gnome")]
-            Backend::Gnome(state) => state.set_temperature(temp, brightness),
+            Backend::Gnome(state) => state.invalidate_ramps(),
         }
     }
 
+    /// Temperature on screen when the backend started, estimated from the
+    /// ramp it saved for restore. None where the ramp can't be read back
+    /// (wlr-gamma-control is write-only; GNOME and the mock keep none).
+    pub fn initial_temperature(&self) -> Option<i32> {
+        match &self.backend {
+            Backend::Drm(state) => state.initial_temperature(),
+            #[cfg(feature = "x11")]
+            Backend::X11(state) => state.initial_temperature(),
+            _ => None,
+        }
+    }
+
+    /// Number of CRTCs/outputs the backend found (the mock has one).
+    pub fn crtc_count(&self) -> usize {
+        match &self.backend {
+            Backend::Drm(state) => state.crtc_count(),
+            Backend::Mock(_) => 1,
+            #[cfg(feature = "wayland")]
+            Backend::Wayland(state) => state.crtc_count(),
+            #[cfg(feature = "x11")]
+            Backend::X11(state) => state.crtc_count(),
+            #[cfg(feature = "gnome")]
+            Backend::Gnome(state) => state.crtc_count(),
+        }
+    }
+
+    /// Connector name of the output on a CRTC: "card0-eDP-1" on DRM, the
+    /// compositor's or RandR's name ("DP-1") elsewhere. None for a CRTC
+    /// driving nothing, for Wayland compositors without xdg-output, and
+    /// for the mock.
+    pub fn output_name(&self, crtc_idx: usize) -> Option<&str> {
+        match &self.backend {
+            Backend::Drm(state) => state.output_name(crtc_idx),
+            Backend::Mock(_) => None,
+            #[cfg(feature = "wayland")]
+            Backend::Wayland(state) => state.output_name(crtc_idx),
+            #[cfg(feature = "x11")]
+            Backend::X11(state) => state.output_name(crtc_idx),
+            #[cfg(feature = "gnome")]
+            Backend::Gnome(state) => state.output_name(crtc_idx),
+        }
+    }
+
+    /// One name per CRTC, in CRTC order; unnamed ones read "CRTC n".
+    pub fn output_names(&self) -> Vec<String> {
+        (0..self.crtc_count())
+            .map(|i| match self.output_name(i) {
+                Some(name) => name.to_string(),
+                None => format!("CRTC {}", i),
+            })
+            .collect()
+    }
+
+    /// Verify one CRTC can be written without changing what is on screen.
+    /// Returns its ramp size; 0 means the CRTC has no gamma (e.g. unused).
+    pub fn check_crtc(&mut self, crtc_idx: usize) -> Result<usize, Error> {
+        let backend = self.backend_name();
+        match &mut self.backend {
+            Backend::Drm(state) => state.check_crtc(crtc_idx),
+            Backend::Mock(_) => Ok(256),
+            #[cfg(feature = "wayland")]
+            Backend::Wayland(state) => state.check_crtc(crtc_idx),
+            #[cfg(feature = "x11")]
+            Backend::X11(state) => state.check_crtc(crtc_idx),
+            #[cfg(feature = "gnome")]
+            Backend::Gnome(state) => state.check_crtc(crtc_idx),
+        }
+        .map_err(|e| e.in_backend(backend))
+    }
+
+    /// Fd to poll for backend events (Wayland compositor socket), if any.
+    pub fn event_fd(&self) -> Option<i32> {
+        match &self.backend {
+            #[cfg(feature = "wayland")]
+            Backend::Wayland(state) => Some(state.event_fd()),
+            _ => None,
+        }
+    }
+
+    /// Dispatch pending backend events. Returns true if the set of outputs
+    /// changed and the current temperature should be re-applied.
+    pub fn dispatch_events(&mut self) -> bool {
+        match &mut self.backend {
+            #[cfg(feature = "wayland")]
+            Backend::Wayland(state) => state.dispatch_events(),
+            _ => false,
+        }
+    }
+
+    /// Calls recorded by the mock backend (None for real backends).
+    #[cfg(test)]
+    pub fn mock_calls(&self) -> Option<&[mock::MockCall]> {
+        match &self.backend {
+            Backend::Mock(state) => Some(state.calls()),
+            _ => None,
+        }
+    }
+
+    /// Make the mock backend panic on its next set_temperature.
+    #[cfg(test)]
+    pub fn mock_panic_on_set(&mut self) {
+        if let Backend::Mock(state) = &mut self.backend {
+            state.panic_on_set();
+        }
+    }
+
+    /// Drop without restoring, so the applied ramp outlives the process
+    /// (every backend restores in its Drop impl). Wayland compositors still
+    /// reset gamma once the client disconnects.
+    pub fn leak(self) {
+        std::mem::forget(self);
+    }
+
     pub fn restore(&mut self) -> Result<(), Error> {
+        let backend = self.backend_name();
         match &mut self.backend {
             Backend::Drm(state) => state.restore(),
+            Backend::Mock(state) => state.restore(),
             #[cfg(feature = "wayland")]
             Backend::Wayland(state) => state.restore(),
             #[cfg(feature = "x11")]
@@ -111,19 +464,30 @@ impl GammaState {
             #[cfg(feature = "gnome")]
             Backend::Gnome(state) => state.restore(),
         }
+        .map_err(|e| e.in_backend(backend))
     }
 }
 
 /// Initialize gamma control with automatic backend selection.
 /// Tries DRM first (card0).
 pub fn init() -> Result<GammaState, Error> {
-    init_card(0)
+    init_card(0, false)
 }
 
 /// Initialize gamma control for a specific graphics card.
 ///
 /// Detection order: Wayland > GNOME > DRM > X11
-pub fn init_card(card_num: i32) -> Result<GammaState, Error> {
+///
+/// The GNOME backend is skipped while GNOME Night Light is active
+/// unless `force_gnome` is set.
+#[cfg_attr(not(feature = "gnome"), allow(unused_variables))]
+pub fn init_card(card_num: i32, force_gnome: bool) -> Result<GammaState, Error> {
+    // 0. Mock backend (testing / --dry-run) -- never touches the display
+    if std::env::var("ABRAXAS_MOCK_GAMMA").map(|v| v == "1").unwrap_or(false) {
+        eprintln!("[gamma] mock: ABRAXAS_MOCK_GAMMA=1, recording calls only");
+        return Ok(GammaState::new(Backend::Mock(mock::MockState::init())));
+    }
+
     // 1. Try Wayland (wlr-gamma-control) -- only if WAYLAND_DISPLAY is set
     #[cfg(feature = "wayland")]
     {
@@ -134,13 +498,11 @@ pub fn init_card(card_num: i32) -> Result<GammaState, Error> {
                         .filter(|&i| state.gamma_size(i) > 0)
                         .count();
                     if usable > 0 {
-                        return Ok(GammaState {
-                            backend: Backend::Wayland(state),
-                        });
+                        return Ok(GammaState::new(Backend::Wayland(state)));
                     }
                     eprintln!("[gamma] wayland: connected but 0 usable CRTCs");
                 }
-                Err(e) => eprintln!("[gamma] wayland: {}", e),
+                Err(e) => eprintln!("[gamma] {}", e.in_backend("wayland")),
             }
         } else {
             eprintln!("[gamma] wayland: skipped (WAYLAND_DISPLAY not set)");
@@ -150,16 +512,14 @@ pub fn init_card(card_num: i32) -> Result<GammaState, Error> {
     // 2. Try GNOME (Mutter DBus)
     #[cfg(feature = "gnome")]
     {
-        match gnome::GnomeState::init() {
+        match gnome::GnomeState::init(force_gnome) {
             Ok(state) => {
                 if state.crtc_count() > 0 {
-                    return Ok(GammaState {
-                        backend: Backend::Gnome(state),
-                    });
+                    return Ok(GammaState::new(Backend::Gnome(state)));
                 }
                 eprintln!("[gamma] gnome: connected but 0 CRTCs");
             }
-            Err(e) => eprintln!("[gamma] gnome: {}", e),
+            Err(e) => eprintln!("[gamma] {}", e.in_backend("gnome")),
         }
     }
 
@@ -170,13 +530,11 @@ pub fn init_card(card_num: i32) -> Result<GammaState, Error> {
                 .filter(|&i| state.gamma_size(i) > 1)
                 .count();
             if usable > 0 {
-                return Ok(GammaState {
-                    backend: Backend::Drm(state),
-                });
+                return Ok(GammaState::new(Backend::Drm(state)));
             }
             eprintln!("[gamma] drm: opened card{} but 0 usable CRTCs (compositor owns gamma?)", card_num);
         }
-        Err(e) => eprintln!("[gamma] drm: {}", e),
+        Err(e) => eprintln!("[gamma] {}", e.in_backend("drm")),
     }
 
     // 4. Try X11 (RandR)
@@ -188,15 +546,113 @@ pub fn init_card(card_num: i32) -> Result<GammaState, Error> {
                     .filter(|&i| state.gamma_size(i) > 0)
                     .count();
                 if usable > 0 {
-                    return Ok(GammaState {
-                        backend: Backend::X11(state),
-                    });
+                    return Ok(GammaState::new(Backend::X11(state)));
                 }
                 eprintln!("[gamma] x11: connected but 0 usable CRTCs");
             }
-            Err(e) => eprintln!("[gamma] x11: {}", e),
+            Err(e) => eprintln!("[gamma] {}", e.in_backend("x11")),
         }
     }
 
-    Err(Error::NoCrtc)
+    Err(ErrorKind::NoCrtc.into())
+}
+
+/// One backend as `probe_all` found it
+pub struct Probe {
+    /// output_names(), in CRTC order
+    pub names: Vec<String>,
+    /// Ramp size per CRTC/output (0 = no gamma), or why it can't be written
+    pub crtcs: Vec<Result<usize, Error>>,
+    /// Writing a ramp and restoring the saved one (None: no CRTC to try)
+    pub round_trip: Option<Result<(), Error>>,
+}
+
+/// Try every compiled backend, not just the first that works, for
+/// --diagnose. Each one that connects gets its CRTCs checked and a ramp
+/// written and restored; the round trip uses the temperature already on
+/// screen where it can be read back, so it should not flash.
+#[cfg_attr(not(feature = "gnome"), allow(unused_variables))]
+pub fn probe_all(card_num: i32, force_gnome: bool) -> Vec<(&'static str, Result<Probe, Error>)> {
+    vec![
+        #[cfg(feature = "wayland")]
+        ("wayland", probe(wayland::WaylandState::init().map(Backend::Wayland))),
+        #[cfg(feature = "gnome")]
+        ("gnome", probe(gnome::GnomeState::init(force_gnome).map(Backend::Gnome))),
+        ("drm", probe(drm::DrmState::init(card_num).map(Backend::Drm))),
+        #[cfg(feature = "x11")]
+        ("x11", probe(x11::X11State::init().map(Backend::X11))),
+    ]
+}
+
+fn probe(backend: Result<Backend, Error>) -> Result<Probe, Error> {
+    let mut state = GammaState::new(backend?);
+    let crtcs: Vec<_> = (0..state.crtc_count()).map(|i| state.check_crtc(i)).collect();
+    let round_trip = if crtcs.iter().any(|c| matches!(c, Ok(size) if *size > 0)) {
+        let temp = state.initial_temperature().unwrap_or(crate::TEMP_DAY_CLEAR);
+        let d65 = (colorramp::D65_X, colorramp::D65_Y);
+        Some(state.set_temperature(temp, 1.0, d65).and_then(|()| state.restore()))
+    } else {
+        None
+    };
+    Ok(Probe { names: state.output_names(), crtcs, round_trip })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_display_includes_crtc_and_errno() {
+        let e = Error::with_errno(ErrorKind::Gamma, libc::EBUSY).on_crtc(1);
+        // strerror text differs between glibc and musl
+        let msg = e.to_string();
+        assert!(msg.starts_with("CRTC 1: Failed to set gamma ramp: EBUSY ("), "{msg}");
+        assert!(msg.to_lowercase().contains("busy"), "{msg}");
+    }
+
+    #[test]
+    fn error_display_full_context() {
+        let e = Error::with_errno(ErrorKind::Gamma, libc::EACCES)
+            .during("DRM_IOCTL_MODE_SETGAMMA")
+            .on_device("/dev/dri/card1")
+            .on_crtc(2)
+            .in_backend("drm")
+            .in_backend("x11");
+        let msg = e.to_string();
+        assert!(
+            msg.starts_with("drm /dev/dri/card1 CRTC 2: Failed to set gamma ramp (DRM_IOCTL_MODE_SETGAMMA): EACCES ("),
+            "{msg}"
+        );
+
+        let e = Error::with_detail(ErrorKind::Gamma, "BadMatch").during("RRSetCrtcGamma").on_device(":0");
+        assert_eq!(e.to_string(), ":0: Failed to set gamma ramp (RRSetCrtcGamma): BadMatch");
+    }
+
+    #[test]
+    fn error_display_plain_kind() {
+        let e: Error = ErrorKind::NoCrtc.into();
+        assert_eq!(e.to_string(), "No usable CRTC found");
+        assert!(std::error::Error::source(&e).is_none());
+    }
+
+    #[test]
+    fn ramp_cache_skips_within_step() {
+        let d65 = (colorramp::D65_X, colorramp::D65_Y);
+        let mut cache = RampCache::default();
+        assert!(!cache.covers(3011, 1.0, d65, 10), "nothing written yet");
+
+        cache.store(3011, 1.0, d65);
+        assert!(cache.covers(3013, 1.0, d65, 10));
+        assert!(cache.covers(3001, 1.0, d65, 10));
+        assert!(!cache.covers(3022, 1.0, d65, 10));
+        assert!(!cache.covers(3013, 1.0, d65, 0), "step 0 only skips identical ramps");
+        assert!(cache.covers(3011, 1.0, d65, 0));
+
+        // Brightness and white point changes always rewrite
+        assert!(!cache.covers(3011, 0.9, d65, 10));
+        assert!(!cache.covers(3011, 1.0, (0.2990, 0.3150), 10));
+
+        cache.clear();
+        assert!(!cache.covers(3011, 1.0, d65, 10));
+    }
 }
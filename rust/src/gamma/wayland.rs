@@ -5,13 +5,22 @@
 //!
 //! Uses memfd for gamma ramp transfer (no tmpfile needed).
 //! Protocol auto-restores gamma when controls are destroyed.
+//!
+//! Outputs are tracked across hotplug: registry global_remove marks an
+//! output failed, and a new wl_output global acquires a fresh control.
+//! Connector names ("DP-1") come from xdg-output where the compositor
+//! offers it.
 
-use super::{colorramp, Error};
+use super::{colorramp, Error, ErrorKind, RampCache};
 use std::os::fd::AsFd;
 use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd};
 
 use wayland_client::protocol::{wl_output::WlOutput, wl_registry};
 use wayland_client::{Connection, Dispatch, EventQueue, QueueHandle, delegate_noop};
+use wayland_protocols::xdg::xdg_output::zv1::client::{
+    zxdg_output_manager_v1::ZxdgOutputManagerV1,
+    zxdg_output_v1::{self, ZxdgOutputV1},
+};
 use wayland_protocols_wlr::gamma_control::v1::client::{
     zwlr_gamma_control_manager_v1::ZwlrGammaControlManagerV1,
     zwlr_gamma_control_v1::{self, ZwlrGammaControlV1},
@@ -20,15 +29,28 @@ use wayland_protocols_wlr::gamma_control::v1::client::{
 /// Per-output state
 struct OutputState {
     output: WlOutput,
+    global_name: u32,
     gamma_control: Option<ZwlrGammaControlV1>,
+    /// Connector name from xdg_output.name ("DP-1")
+    name: Option<String>,
+    xdg_output: Option<ZxdgOutputV1>,
     gamma_size: u32,
     failed: bool,
+    /// Output global was removed (unplugged)
+    removed: bool,
+    /// Last ramp sent through the current gamma control
+    ramp: RampCache,
 }
 
 /// Internal state used during Wayland dispatch
 struct WaylandInner {
     gamma_manager: Option<ZwlrGammaControlManagerV1>,
+    xdg_output_manager: Option<ZxdgOutputManagerV1>,
     outputs: Vec<OutputState>,
+    /// Set once init completes; new outputs after this are hotplugged
+    hotplug: bool,
+    /// Output set or gamma sizes changed since last dispatch
+    changed: bool,
 }
 
 // Registry listener: discover globals
@@ -41,24 +63,65 @@ impl Dispatch<wl_registry::WlRegistry, ()> for WaylandInner {
         _conn: &Connection,
         qh: &QueueHandle<Self>,
     ) {
-        if let wl_registry::Event::Global {
-            name,
-            interface,
-            version: _,
-        } = event
-        {
-            if interface == "zwlr_gamma_control_manager_v1" {
-                state.gamma_manager =
-                    Some(registry.bind::<ZwlrGammaControlManagerV1, _, _>(name, 1, qh, ()));
-            } else if interface == "wl_output" {
-                let output = registry.bind::<WlOutput, _, _>(name, 1, qh, ());
-                state.outputs.push(OutputState {
-                    output,
-                    gamma_control: None,
-                    gamma_size: 0,
-                    failed: false,
-                });
+        match event {
+            wl_registry::Event::Global {
+                name,
+                interface,
+                version,
+            } => {
+                if interface == "zwlr_gamma_control_manager_v1" {
+                    state.gamma_manager =
+                        Some(registry.bind::<ZwlrGammaControlManagerV1, _, _>(name, 1, qh, ()));
+                } else if interface == "zxdg_output_manager_v1" {
+                    // xdg_output.name arrived in version 2
+                    state.xdg_output_manager =
+                        Some(registry.bind::<ZxdgOutputManagerV1, _, _>(name, version.min(2), qh, ()));
+                } else if interface == "wl_output" {
+                    let output = registry.bind::<WlOutput, _, _>(name, 1, qh, ());
+                    let idx = state.outputs.len();
+
+                    // Hotplugged output: acquire its gamma control now.
+                    // During init, controls are acquired after discovery.
+                    let gamma_control = match state.gamma_manager {
+                        Some(ref m) if state.hotplug => {
+                            state.changed = true;
+                            Some(m.get_gamma_control(&output, qh, idx))
+                        }
+                        _ => None,
+                    };
+                    let xdg_output = match state.xdg_output_manager {
+                        Some(ref m) if state.hotplug => Some(m.get_xdg_output(&output, qh, idx)),
+                        _ => None,
+                    };
+
+                    state.outputs.push(OutputState {
+                        output,
+                        global_name: name,
+                        gamma_control,
+                        name: None,
+                        xdg_output,
+                        gamma_size: 0,
+                        failed: false,
+                        removed: false,
+                        ramp: RampCache::default(),
+                    });
+                }
+            }
+            wl_registry::Event::GlobalRemove { name } => {
+                // Keep the slot: gamma control user data indexes into outputs
+                if let Some(out) = state.outputs.iter_mut().find(|o| o.global_name == name) {
+                    out.failed = true;
+                    out.removed = true;
+                    if let Some(ctrl) = out.gamma_control.take() {
+                        ctrl.destroy();
+                    }
+                    if let Some(xdg) = out.xdg_output.take() {
+                        xdg.destroy();
+                    }
+                    state.changed = true;
+                }
             }
+            _ => {}
         }
     }
 }
@@ -77,7 +140,10 @@ impl Dispatch<ZwlrGammaControlV1, usize> for WaylandInner {
         if let Some(out) = state.outputs.get_mut(*idx) {
             match event {
                 zwlr_gamma_control_v1::Event::GammaSize { size } => {
+                    // A fresh control: the compositor shows its own ramp
                     out.gamma_size = size;
+                    out.ramp.clear();
+                    state.changed = true;
                 }
                 zwlr_gamma_control_v1::Event::Failed => {
                     out.failed = true;
@@ -91,12 +157,32 @@ impl Dispatch<ZwlrGammaControlV1, usize> for WaylandInner {
     }
 }
 
+// xdg_output listener: keep the connector name, ignore the geometry.
+// The usize user data is the output index
+impl Dispatch<ZxdgOutputV1, usize> for WaylandInner {
+    fn event(
+        state: &mut Self,
+        _proxy: &ZxdgOutputV1,
+        event: zxdg_output_v1::Event,
+        idx: &usize,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        if let (Some(out), zxdg_output_v1::Event::Name { name }) = (state.outputs.get_mut(*idx), event) {
+            out.name = Some(name);
+        }
+    }
+}
+
 // No-op dispatchers for types we don't handle events on
 delegate_noop!(WaylandInner: ignore WlOutput);
 delegate_noop!(WaylandInner: ignore ZwlrGammaControlManagerV1);
+delegate_noop!(WaylandInner: ignore ZxdgOutputManagerV1);
 
 /// Public Wayland gamma state
 pub struct WaylandState {
+    /// $WAYLAND_DISPLAY, for errors
+    display: String,
     conn: Connection,
     queue: EventQueue<WaylandInner>,
     inner: WaylandInner,
@@ -104,12 +190,17 @@ pub struct WaylandState {
 
 impl WaylandState {
     pub fn init() -> Result<Self, Error> {
-        let conn = Connection::connect_to_env().map_err(|_| Error::WaylandConnect)?;
+        let name = std::env::var("WAYLAND_DISPLAY").unwrap_or_default();
+        let conn = Connection::connect_to_env()
+            .map_err(|e| Error::with_detail(ErrorKind::WaylandConnect, e).on_device(&name))?;
         let display = conn.display();
 
         let mut inner = WaylandInner {
             gamma_manager: None,
+            xdg_output_manager: None,
             outputs: Vec::new(),
+            hotplug: false,
+            changed: false,
         };
 
         let mut queue = conn.new_event_queue();
@@ -119,29 +210,35 @@ impl WaylandState {
         let _registry = display.get_registry(&qh, ());
         queue
             .roundtrip(&mut inner)
-            .map_err(|_| Error::WaylandConnect)?;
+            .map_err(|e| roundtrip_error(e, &name))?;
 
         // Check gamma manager was found
         let manager = match inner.gamma_manager {
             Some(ref m) => m.clone(),
-            None => return Err(Error::WaylandProtocol),
+            None => {
+                let e = Error::with_detail(ErrorKind::WaylandProtocol, "no zwlr_gamma_control_manager_v1 global");
+                return Err(e.on_device(&name));
+            }
         };
 
         if inner.outputs.is_empty() {
-            return Err(Error::NoCrtc);
+            return Err(Error::from(ErrorKind::NoCrtc).on_device(&name));
         }
 
-        // Acquire gamma control for each output
+        // Acquire gamma control (and xdg_output, for the name) for each output
         for i in 0..inner.outputs.len() {
             let ctrl =
                 manager.get_gamma_control(&inner.outputs[i].output, &qh, i);
             inner.outputs[i].gamma_control = Some(ctrl);
+            if let Some(ref m) = inner.xdg_output_manager {
+                inner.outputs[i].xdg_output = Some(m.get_xdg_output(&inner.outputs[i].output, &qh, i));
+            }
         }
 
-        // Second roundtrip: receive gamma_size events
+        // Second roundtrip: receive gamma_size and name events
         queue
             .roundtrip(&mut inner)
-            .map_err(|_| Error::WaylandConnect)?;
+            .map_err(|e| roundtrip_error(e, &name))?;
 
         // Check at least one output has usable gamma
         let usable = inner
@@ -150,16 +247,42 @@ impl WaylandState {
             .filter(|o| !o.failed && o.gamma_size > 0)
             .count();
         if usable == 0 {
-            return Err(Error::NoCrtc);
+            return Err(Error::with_detail(ErrorKind::NoCrtc, "every gamma control failed").on_device(&name));
         }
 
-        Ok(WaylandState { conn, queue, inner })
+        inner.hotplug = true;
+        inner.changed = false;
+
+        Ok(WaylandState { display: name, conn, queue, inner })
+    }
+
+    /// Compositor socket fd, for polling in the daemon event loop.
+    pub fn event_fd(&self) -> i32 {
+        self.conn.backend().poll_fd().as_raw_fd()
+    }
+
+    /// Read and dispatch pending compositor events (output hotplug,
+    /// gamma_size, failed). Returns true if outputs changed and the
+    /// current temperature should be re-applied.
+    pub fn dispatch_events(&mut self) -> bool {
+        if let Some(guard) = self.queue.prepare_read() {
+            let _ = guard.read();
+        }
+        let _ = self.queue.dispatch_pending(&mut self.inner);
+        let _ = self.conn.flush();
+
+        std::mem::take(&mut self.inner.changed)
     }
 
     pub fn crtc_count(&self) -> usize {
         self.inner.outputs.len()
     }
 
+    /// Connector name ("DP-1"); None without xdg-output v2
+    pub fn output_name(&self, crtc_idx: usize) -> Option<&str> {
+        self.inner.outputs.get(crtc_idx)?.name.as_deref()
+    }
+
     pub fn gamma_size(&self, crtc_idx: usize) -> usize {
         self.inner
             .outputs
@@ -169,15 +292,23 @@ impl WaylandState {
             .unwrap_or(0)
     }
 
+    /// Send one output's ramp unless it is within `quantize_step` K of the
+    /// one already there. Returns whether it was sent.
     pub fn set_temperature_crtc(
         &mut self,
         crtc_idx: usize,
         temp: i32,
         brightness: f32,
-    ) -> Result<(), Error> {
-        let out = self.inner.outputs.get(crtc_idx).ok_or(Error::Crtc)?;
+        white_point: (f64, f64),
+        quantize_step: i32,
+    ) -> Result<bool, Error> {
+        let display = &self.display;
+        let out = self.inner.outputs.get_mut(crtc_idx).ok_or(ErrorKind::Crtc)?;
         if out.failed || out.gamma_control.is_none() || out.gamma_size == 0 {
-            return Err(Error::WaylandProtocol);
+            return Err(output_unusable(out).on_device(display).on_crtc(crtc_idx));
+        }
+        if out.ramp.covers(temp, brightness, white_point, quantize_step) {
+            return Ok(false);
         }
 
         let gs = out.gamma_size as usize;
@@ -185,7 +316,7 @@ impl WaylandState {
         let total = ramp_bytes * 3; // R + G + B contiguous
 
         // Create memfd for gamma ramp transfer
-        let fd: OwnedFd = create_memfd(total)?;
+        let fd: OwnedFd = create_memfd(total).map_err(|e| e.on_device(display).on_crtc(crtc_idx))?;
         let raw_fd = fd.as_raw_fd();
 
         // mmap, fill, munmap
@@ -200,7 +331,7 @@ impl WaylandState {
             )
         };
         if map == libc::MAP_FAILED {
-            return Err(Error::Resources);
+            return Err(Error::last_os(ErrorKind::Resources).during("mmap").on_device(display).on_crtc(crtc_idx));
         }
 
         let r_ptr = map as *mut u16;
@@ -211,7 +342,7 @@ impl WaylandState {
         let g_slice = unsafe { std::slice::from_raw_parts_mut(g_ptr, gs) };
         let b_slice = unsafe { std::slice::from_raw_parts_mut(b_ptr, gs) };
 
-        let fill_result = colorramp::fill_gamma_ramps(temp, gs, r_slice, g_slice, b_slice, brightness);
+        let fill_result = colorramp::fill_gamma_ramps_adapted(temp, gs, r_slice, g_slice, b_slice, brightness, white_point.0, white_point.1);
 
         unsafe { libc::munmap(map, total) };
 
@@ -233,27 +364,55 @@ impl WaylandState {
         // Flush to compositor
         let _ = self.conn.flush();
 
-        Ok(())
+        out.ramp.store(temp, brightness, white_point);
+        Ok(true)
+    }
+
+    /// The protocol has no way to read a ramp back: an output checks out
+    /// when the compositor granted a control and reported its ramp size.
+    pub fn check_crtc(&mut self, crtc_idx: usize) -> Result<usize, Error> {
+        let out = self.inner.outputs.get(crtc_idx).ok_or(ErrorKind::Crtc)?;
+        if out.failed || out.gamma_control.is_none() || out.gamma_size == 0 {
+            return Err(output_unusable(out).on_device(&self.display).on_crtc(crtc_idx));
+        }
+        Ok(out.gamma_size as usize)
     }
 
-    pub fn set_temperature(&mut self, temp: i32, brightness: f32) -> Result<(), Error> {
+    /// Returns whether any output's ramp was resent.
+    pub fn set_temperature(
+        &mut self,
+        temp: i32,
+        brightness: f32,
+        white_point: (f64, f64),
+        quantize_step: i32,
+    ) -> Result<bool, Error> {
         let mut last_err = None;
         let mut success_count = 0;
+        let mut written = false;
 
         for i in 0..self.inner.outputs.len() {
             let out = &self.inner.outputs[i];
             if !out.failed && out.gamma_size > 0 {
-                match self.set_temperature_crtc(i, temp, brightness) {
-                    Ok(()) => success_count += 1,
+                match self.set_temperature_crtc(i, temp, brightness, white_point, quantize_step) {
+                    Ok(w) => {
+                        success_count += 1;
+                        written |= w;
+                    }
                     Err(e) => last_err = Some(e),
                 }
             }
         }
 
         if success_count > 0 {
-            Ok(())
+            Ok(written)
         } else {
-            Err(last_err.unwrap_or(Error::NoCrtc))
+            Err(last_err.unwrap_or(ErrorKind::NoCrtc.into()))
+        }
+    }
+
+    pub fn invalidate_ramps(&mut self) {
+        for out in &mut self.inner.outputs {
+            out.ramp.clear();
         }
     }
 
@@ -266,15 +425,19 @@ impl WaylandState {
             if let Some(ctrl) = out.gamma_control.take() {
                 ctrl.destroy();
             }
-            out.failed = false;
+            out.failed = out.removed;
             out.gamma_size = 0;
+            out.ramp.clear();
         }
 
         let _ = self.conn.flush();
 
-        // Re-acquire gamma controls
+        // Re-acquire gamma controls (unplugged outputs stay failed)
         if let Some(ref manager) = self.inner.gamma_manager {
             for i in 0..self.inner.outputs.len() {
+                if self.inner.outputs[i].removed {
+                    continue;
+                }
                 let ctrl =
                     manager.get_gamma_control(&self.inner.outputs[i].output, &qh, i);
                 self.inner.outputs[i].gamma_control = Some(ctrl);
@@ -283,7 +446,7 @@ impl WaylandState {
 
         self.queue
             .roundtrip(&mut self.inner)
-            .map_err(|_| Error::WaylandConnect)?;
+            .map_err(|e| roundtrip_error(e, &self.display))?;
 
         Ok(())
     }
@@ -301,6 +464,23 @@ impl Drop for WaylandState {
     }
 }
 
+/// Lost connection (or protocol error) while waiting on the compositor
+fn roundtrip_error(e: wayland_client::DispatchError, display: &str) -> Error {
+    Error::with_detail(ErrorKind::WaylandConnect, e).during("wl_display.sync roundtrip").on_device(display)
+}
+
+/// Why an output can't take a ramp
+fn output_unusable(out: &OutputState) -> Error {
+    let why = if out.removed {
+        "output unplugged"
+    } else if out.failed {
+        "compositor sent gamma_control.failed"
+    } else {
+        "no gamma_size from the compositor"
+    };
+    Error::with_detail(ErrorKind::WaylandProtocol, why)
+}
+
 /// Create a sealed memfd of the given size
 fn create_memfd(size: usize) -> Result<OwnedFd, Error> {
     let name = c"meridian-gamma";
@@ -308,13 +488,13 @@ fn create_memfd(size: usize) -> Result<OwnedFd, Error> {
         libc::memfd_create(name.as_ptr(), libc::MFD_CLOEXEC | libc::MFD_ALLOW_SEALING)
     };
     if fd < 0 {
-        return Err(Error::Resources);
+        return Err(Error::last_os(ErrorKind::Resources).during("memfd_create"));
     }
 
     let owned = unsafe { OwnedFd::from_raw_fd(fd) };
 
     if unsafe { libc::ftruncate(owned.as_raw_fd(), size as libc::off_t) } < 0 {
-        return Err(Error::Resources);
+        return Err(Error::last_os(ErrorKind::Resources).during("ftruncate"));
     }
 
     Ok(owned)
@@ -3,7 +3,7 @@
 //! Used when DRM gamma fails (NVIDIA proprietary, etc.)
 //! Uses x11rb crate -- no libX11/libXrandr link dependency.
 
-use super::{colorramp, Error};
+use super::{colorramp, Error, ErrorKind, RampCache};
 use x11rb::connection::Connection;
 use x11rb::protocol::randr::ConnectionExt as RandrExt;
 use x11rb::rust_connection::RustConnection;
@@ -11,6 +11,8 @@ use x11rb::rust_connection::RustConnection;
 /// Saved per-CRTC gamma state
 struct CrtcState {
     crtc: u32,
+    /// RandR output it drives ("DP-1"), if any
+    name: Option<String>,
     gamma_size: u16,
     saved_r: Vec<u16>,
     saved_g: Vec<u16>,
@@ -19,46 +21,76 @@ struct CrtcState {
     work_r: Vec<u16>,
     work_g: Vec<u16>,
     work_b: Vec<u16>,
+    /// Last ramp written by set_temperature
+    ramp: RampCache,
 }
 
 /// X11 RandR gamma state
 pub struct X11State {
+    /// $DISPLAY, for errors
+    display: String,
     conn: RustConnection,
     crtcs: Vec<CrtcState>,
 }
 
+/// map_err for a failed connection or RandR request: x11rb's description
+/// of it, the request name and the display
+fn request_error<'a, E: std::fmt::Display>(
+    kind: ErrorKind,
+    op: &'static str,
+    display: &'a str,
+) -> impl FnOnce(E) -> Error + 'a {
+    move |e| Error::with_detail(kind, e).during(op).on_device(display)
+}
+
+/// Name of the first output the CRTC drives. Best effort: any failed
+/// request leaves the CRTC unnamed.
+fn crtc_output_name(conn: &RustConnection, crtc: u32, timestamp: u32) -> Option<String> {
+    let info = conn.randr_get_crtc_info(crtc, timestamp).ok()?.reply().ok()?;
+    let output = *info.outputs.first()?;
+    let output_info = conn.randr_get_output_info(output, timestamp).ok()?.reply().ok()?;
+    Some(String::from_utf8_lossy(&output_info.name).into_owned())
+}
+
 impl X11State {
     pub fn init() -> Result<Self, Error> {
+        let display = std::env::var("DISPLAY").unwrap_or_default();
         let (conn, screen_num) =
-            RustConnection::connect(None).map_err(|_| Error::Open)?;
+            RustConnection::connect(None).map_err(request_error(ErrorKind::Open, "connect", &display))?;
 
         let screen = &conn.setup().roots[screen_num];
         let root = screen.root;
 
         // Get screen resources
+        let op = "RRGetScreenResourcesCurrent";
         let resources = conn
             .randr_get_screen_resources_current(root)
-            .map_err(|_| Error::Resources)?
+            .map_err(request_error(ErrorKind::Resources, op, &display))?
             .reply()
-            .map_err(|_| Error::Resources)?;
+            .map_err(request_error(ErrorKind::Resources, op, &display))?;
 
         if resources.crtcs.is_empty() {
-            return Err(Error::NoCrtc);
+            return Err(Error::from(ErrorKind::NoCrtc).on_device(&display));
         }
 
         let mut crtcs = Vec::with_capacity(resources.crtcs.len());
 
-        for &crtc_id in &resources.crtcs {
+        for (idx, &crtc_id) in resources.crtcs.iter().enumerate() {
+            let name = crtc_output_name(&conn, crtc_id, resources.config_timestamp);
+            let op = "RRGetCrtcGammaSize";
             let gamma_size = conn
                 .randr_get_crtc_gamma_size(crtc_id)
-                .map_err(|_| Error::Crtc)?
+                .map_err(request_error(ErrorKind::Crtc, op, &display))
+                .map_err(|e| e.on_crtc(idx))?
                 .reply()
-                .map_err(|_| Error::Crtc)?
+                .map_err(request_error(ErrorKind::Crtc, op, &display))
+                .map_err(|e| e.on_crtc(idx))?
                 .size;
 
             if gamma_size == 0 {
                 crtcs.push(CrtcState {
                     crtc: crtc_id,
+                    name,
                     gamma_size: 0,
                     saved_r: Vec::new(),
                     saved_g: Vec::new(),
@@ -66,19 +98,24 @@ impl X11State {
                     work_r: Vec::new(),
                     work_g: Vec::new(),
                     work_b: Vec::new(),
+                    ramp: RampCache::default(),
                 });
                 continue;
             }
 
             // Save original gamma
+            let op = "RRGetCrtcGamma";
             let gamma = conn
                 .randr_get_crtc_gamma(crtc_id)
-                .map_err(|_| Error::Gamma)?
+                .map_err(request_error(ErrorKind::Gamma, op, &display))
+                .map_err(|e| e.on_crtc(idx))?
                 .reply()
-                .map_err(|_| Error::Gamma)?;
+                .map_err(request_error(ErrorKind::Gamma, op, &display))
+                .map_err(|e| e.on_crtc(idx))?;
 
             crtcs.push(CrtcState {
                 crtc: crtc_id,
+                name,
                 gamma_size,
                 saved_r: gamma.red,
                 saved_g: gamma.green,
@@ -86,10 +123,11 @@ impl X11State {
                 work_r: vec![0u16; gamma_size as usize],
                 work_g: vec![0u16; gamma_size as usize],
                 work_b: vec![0u16; gamma_size as usize],
+                ramp: RampCache::default(),
             });
         }
 
-        Ok(X11State { conn, crtcs })
+        Ok(X11State { display, conn, crtcs })
     }
 
     pub fn crtc_count(&self) -> usize {
@@ -103,53 +141,118 @@ impl X11State {
             .unwrap_or(0)
     }
 
+    /// RandR output the CRTC drives ("DP-1"), None when it drives none
+    pub fn output_name(&self, crtc_idx: usize) -> Option<&str> {
+        self.crtcs.get(crtc_idx)?.name.as_deref()
+    }
+
+    /// Temperature of the ramp found on the first CRTC with gamma at init
+    pub fn initial_temperature(&self) -> Option<i32> {
+        let crtc = self.crtcs.iter().find(|c| !c.saved_r.is_empty())?;
+        colorramp::estimate_temperature(&crtc.saved_r, &crtc.saved_g, &crtc.saved_b)
+    }
+
+    /// Write one CRTC's ramp unless it is within `quantize_step` K of the
+    /// one already there. Returns whether it was written.
     pub fn set_temperature_crtc(
         &mut self,
         crtc_idx: usize,
         temp: i32,
         brightness: f32,
-    ) -> Result<(), Error> {
-        let crtc = self.crtcs.get_mut(crtc_idx).ok_or(Error::Crtc)?;
+        white_point: (f64, f64),
+        quantize_step: i32,
+    ) -> Result<bool, Error> {
+        let crtc = self.crtcs.get_mut(crtc_idx).ok_or(ErrorKind::Crtc)?;
         if crtc.gamma_size == 0 {
-            return Err(Error::Crtc);
+            return Err(ErrorKind::Crtc.into());
+        }
+
+        if crtc.ramp.covers(temp, brightness, white_point, quantize_step) {
+            return Ok(false);
         }
 
         let size = crtc.gamma_size as usize;
 
         // Reuse pre-allocated working buffers
-        colorramp::fill_gamma_ramps(temp, size, &mut crtc.work_r, &mut crtc.work_g, &mut crtc.work_b, brightness)?;
+        colorramp::fill_gamma_ramps_adapted(temp, size, &mut crtc.work_r, &mut crtc.work_g, &mut crtc.work_b, brightness, white_point.0, white_point.1)?;
 
         let crtc_id = crtc.crtc;
+        let display = &self.display;
         self.conn
             .randr_set_crtc_gamma(crtc_id, &crtc.work_r, &crtc.work_g, &crtc.work_b)
-            .map_err(|_| Error::Gamma)?;
+            .map_err(request_error(ErrorKind::Gamma, "RRSetCrtcGamma", display))
+            .map_err(|e| e.on_crtc(crtc_idx))?;
 
-        self.conn.flush().map_err(|_| Error::Gamma)?;
+        self.conn.flush().map_err(request_error(ErrorKind::Gamma, "flush", display))?;
 
-        Ok(())
+        self.crtcs[crtc_idx].ramp.store(temp, brightness, white_point);
+        Ok(true)
     }
 
-    pub fn set_temperature(&mut self, temp: i32, brightness: f32) -> Result<(), Error> {
+    /// Read the CRTC's current ramp and write it straight back (no visible
+    /// change). Returns the ramp size, 0 for a CRTC without gamma.
+    pub fn check_crtc(&mut self, crtc_idx: usize) -> Result<usize, Error> {
+        let crtc = self.crtcs.get(crtc_idx).ok_or(ErrorKind::Crtc)?;
+        if crtc.gamma_size == 0 {
+            return Ok(0);
+        }
+
+        let display = &self.display;
+        let op = "RRGetCrtcGamma";
+        let gamma = self
+            .conn
+            .randr_get_crtc_gamma(crtc.crtc)
+            .map_err(request_error(ErrorKind::Resources, op, display))
+            .and_then(|c| c.reply().map_err(request_error(ErrorKind::Resources, op, display)))
+            .map_err(|e| e.on_crtc(crtc_idx))?;
+        let op = "RRSetCrtcGamma";
+        self.conn
+            .randr_set_crtc_gamma(crtc.crtc, &gamma.red, &gamma.green, &gamma.blue)
+            .map_err(request_error(ErrorKind::Gamma, op, display))
+            .and_then(|c| c.check().map_err(request_error(ErrorKind::Gamma, op, display)))
+            .map_err(|e| e.on_crtc(crtc_idx))?;
+        Ok(crtc.gamma_size as usize)
+    }
+
+    /// Returns whether any CRTC's ramp was rewritten.
+    pub fn set_temperature(
+        &mut self,
+        temp: i32,
+        brightness: f32,
+        white_point: (f64, f64),
+        quantize_step: i32,
+    ) -> Result<bool, Error> {
         let mut last_err = None;
         let mut success_count = 0;
+        let mut written = false;
 
         for i in 0..self.crtcs.len() {
             if self.crtcs[i].gamma_size > 0 {
-                match self.set_temperature_crtc(i, temp, brightness) {
-                    Ok(()) => success_count += 1,
+                match self.set_temperature_crtc(i, temp, brightness, white_point, quantize_step) {
+                    Ok(w) => {
+                        success_count += 1;
+                        written |= w;
+                    }
                     Err(e) => last_err = Some(e),
                 }
             }
         }
 
         if success_count > 0 {
-            Ok(())
+            Ok(written)
         } else {
-            Err(last_err.unwrap_or(Error::NoCrtc))
+            Err(last_err.unwrap_or(ErrorKind::NoCrtc.into()))
+        }
+    }
+
+    pub fn invalidate_ramps(&mut self) {
+        for crtc in &mut self.crtcs {
+            crtc.ramp.clear();
         }
     }
 
     pub fn restore(&mut self) -> Result<(), Error> {
+        self.invalidate_ramps();
         for crtc in &self.crtcs {
             if crtc.gamma_size > 0 && !crtc.saved_r.is_empty() {
                 let _ = self.conn.randr_set_crtc_gamma(
@@ -0,0 +1,186 @@
+//! GeoClue2 location lookup over the system bus (feature "geoclue").
+//!
+//! Manager.GetClient -> set DesktopId and RequestedAccuracyLevel (city)
+//! -> Start -> wait for the client's Location property -> read Latitude,
+//! Longitude and Accuracy from that object -> Stop.
+//!
+//! Blocking, with a deadline: callers use it at most once a day, and a
+//! city-level fix (a few km) is plenty for solar math.
+
+use crate::sdbus::*;
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::ptr;
+use std::time::{Duration, Instant};
+
+const GEOCLUE_NAME: &[u8] = b"org.freedesktop.GeoClue2\0";
+const MANAGER_PATH: &[u8] = b"/org/freedesktop/GeoClue2/Manager\0";
+const MANAGER_IFACE: &[u8] = b"org.freedesktop.GeoClue2.Manager\0";
+const CLIENT_IFACE: &[u8] = b"org.freedesktop.GeoClue2.Client\0";
+const LOCATION_IFACE: &[u8] = b"org.freedesktop.GeoClue2.Location\0";
+
+/// Identifies us to the GeoClue agent (matches the .desktop file name)
+const DESKTOP_ID: &[u8] = b"abraxas\0";
+/// GClueAccuracyLevel: CITY
+const ACCURACY_CITY: u32 = 4;
+
+/// How often the client's Location property is checked while waiting
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// A position fix
+pub struct Fix {
+    pub lat: f64,
+    pub lon: f64,
+    /// Radius in meters
+    pub accuracy_m: f64,
+}
+
+fn c(s: &[u8]) -> *const c_char {
+    s.as_ptr() as *const c_char
+}
+
+/// A system bus connection, unreferenced on drop
+struct Bus(*mut SdBus);
+
+impl Drop for Bus {
+    fn drop(&mut self) {
+        unsafe { sd_bus_unref(self.0) };
+    }
+}
+
+/// Turn a negative sd-bus return into an error naming the step.
+fn check(r: i32, error: &mut SdBusError, what: &str) -> Result<(), String> {
+    let detail = error.describe();
+    unsafe { sd_bus_error_free(error) };
+    if r >= 0 {
+        return Ok(());
+    }
+    match detail {
+        Some(d) => Err(format!("{}: {}", what, d)),
+        None => Err(format!("{}: {}", what, std::io::Error::from_raw_os_error(-r))),
+    }
+}
+
+/// Read an object path from `reply` (positioned at one) and unref it.
+fn read_path(reply: *mut SdBusMessage) -> Option<CString> {
+    let mut path: *const c_char = ptr::null();
+    let r = unsafe { sd_bus_message_read(reply, c(b"o\0"), &mut path as *mut *const c_char) };
+    let owned = if r < 0 || path.is_null() {
+        None
+    } else {
+        Some(unsafe { CStr::from_ptr(path) }.to_owned())
+    };
+    unsafe { sd_bus_message_unref(reply) };
+    owned
+}
+
+fn call(bus: &Bus, path: *const c_char, iface: &[u8], member: &[u8]) -> Result<*mut SdBusMessage, String> {
+    let mut error = SdBusError::null();
+    let mut reply: *mut SdBusMessage = ptr::null_mut();
+    let r = unsafe {
+        sd_bus_call_method(bus.0, c(GEOCLUE_NAME), path, c(iface), c(member), &mut error, &mut reply, c(b"\0"))
+    };
+    let member = String::from_utf8_lossy(&member[..member.len() - 1]).into_owned();
+    check(r, &mut error, &member)?;
+    Ok(reply)
+}
+
+fn get_double(bus: &Bus, path: &CStr, member: &[u8]) -> Result<f64, String> {
+    let mut error = SdBusError::null();
+    let mut value: f64 = 0.0;
+    let r = unsafe {
+        sd_bus_get_property_trivial(
+            bus.0,
+            c(GEOCLUE_NAME),
+            path.as_ptr(),
+            c(LOCATION_IFACE),
+            c(member),
+            &mut error,
+            b'd' as c_char,
+            &mut value as *mut f64 as *mut c_void,
+        )
+    };
+    check(r, &mut error, "reading the location")?;
+    Ok(value)
+}
+
+/// The client's current Location object, None until GeoClue has a fix.
+fn location_path(bus: &Bus, client: &CStr) -> Result<Option<CString>, String> {
+    let mut error = SdBusError::null();
+    let mut reply: *mut SdBusMessage = ptr::null_mut();
+    let r = unsafe {
+        sd_bus_get_property(
+            bus.0,
+            c(GEOCLUE_NAME),
+            client.as_ptr(),
+            c(CLIENT_IFACE),
+            c(b"Location\0"),
+            &mut error,
+            &mut reply,
+            c(b"o\0"),
+        )
+    };
+    check(r, &mut error, "Location")?;
+    Ok(read_path(reply).filter(|p| p.as_bytes() != b"/"))
+}
+
+/// Poll a started client until GeoClue reports a location or `deadline`.
+fn wait_for_fix(bus: &Bus, client: &CStr, deadline: Instant, timeout: Duration) -> Result<Fix, String> {
+    loop {
+        if let Some(location) = location_path(bus, client)? {
+            return Ok(Fix {
+                lat: get_double(bus, &location, b"Latitude\0")?,
+                lon: get_double(bus, &location, b"Longitude\0")?,
+                accuracy_m: get_double(bus, &location, b"Accuracy\0")?,
+            });
+        }
+        if Instant::now() >= deadline {
+            return Err(format!("no fix within {}s (is a location source enabled?)", timeout.as_secs()));
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Ask GeoClue2 where we are, waiting up to `timeout` for a fix.
+pub fn locate(timeout: Duration) -> Result<Fix, String> {
+    let deadline = Instant::now() + timeout;
+
+    let mut raw: *mut SdBus = ptr::null_mut();
+    let r = unsafe { sd_bus_open_system(&mut raw) };
+    if r < 0 {
+        return Err(format!("no system bus: {}", std::io::Error::from_raw_os_error(-r)));
+    }
+    let bus = Bus(raw);
+    // A hung service must not outlast the deadline either
+    unsafe { sd_bus_set_method_call_timeout(bus.0, timeout.as_micros() as u64) };
+
+    let reply = call(&bus, c(MANAGER_PATH), MANAGER_IFACE, b"GetClient\0")
+        .map_err(|e| format!("GeoClue2 not available ({})", e))?;
+    let client = read_path(reply).ok_or("GetClient returned no client")?;
+
+    let mut error = SdBusError::null();
+    let r = unsafe {
+        sd_bus_set_property(
+            bus.0, c(GEOCLUE_NAME), client.as_ptr(), c(CLIENT_IFACE), c(b"DesktopId\0"),
+            &mut error, c(b"s\0"), c(DESKTOP_ID),
+        )
+    };
+    check(r, &mut error, "DesktopId")?;
+    let r = unsafe {
+        sd_bus_set_property(
+            bus.0, c(GEOCLUE_NAME), client.as_ptr(), c(CLIENT_IFACE), c(b"RequestedAccuracyLevel\0"),
+            &mut error, c(b"u\0"), ACCURACY_CITY,
+        )
+    };
+    check(r, &mut error, "RequestedAccuracyLevel")?;
+
+    let reply = call(&bus, client.as_ptr(), CLIENT_IFACE, b"Start\0")?;
+    unsafe { sd_bus_message_unref(reply) };
+
+    let result = wait_for_fix(&bus, &client, deadline, timeout);
+
+    // Best effort: the client goes away with the connection anyway
+    if let Ok(reply) = call(&bus, client.as_ptr(), CLIENT_IFACE, b"Stop\0") {
+        unsafe { sd_bus_message_unref(reply) };
+    }
+    result
+}
@@ -0,0 +1,267 @@
+//! User scripts run on temperature and mode changes ([hooks]).
+//!
+//! The daemon starts a hook without waiting for it, like its curl child,
+//! and reaps it on a later tick. The script gets ABRAXAS_TEMP (0 while
+//! paused), ABRAXAS_MODE (clear, dark, night, manual or paused) and
+//! ABRAXAS_BRIGHTNESS in its environment.
+//!
+//! Hooks inherit the daemon's landlock and seccomp sandboxes. The paths
+//! configured at startup are allowed to execute; a hook added by a later
+//! config reload needs a daemon restart to get through landlock.
+
+use crate::config::HookParams;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+
+/// One hook script's last run
+#[derive(Default)]
+struct Hook {
+    child: Option<Child>,
+    /// Script the running child was started from
+    path: PathBuf,
+    /// When it was last started (0 = never)
+    last_run: i64,
+    /// Last failure reported, so each is logged once
+    last_error: Option<String>,
+}
+
+impl Hook {
+    /// Not running, and min_interval_sec since the last start.
+    fn ready(&mut self, now: i64, min_interval_sec: i64) -> bool {
+        self.reap();
+        self.child.is_none() && (self.last_run == 0 || now - self.last_run >= min_interval_sec)
+    }
+
+    /// Reap a finished child, reporting a failed exit.
+    fn reap(&mut self) {
+        let status = match self.child.as_mut().map(|c| c.try_wait()) {
+            Some(Ok(Some(status))) => status,
+            Some(Ok(None)) | None => return,
+            Some(Err(e)) => {
+                self.child = None;
+                let msg = format!("cannot wait for it: {}", e);
+                self.failed(msg);
+                return;
+            }
+        };
+        self.child = None;
+        if status.success() {
+            self.last_error = None;
+        } else {
+            self.failed(status.to_string());
+        }
+    }
+
+    fn failed(&mut self, msg: String) {
+        if self.last_error.as_deref() != Some(msg.as_str()) {
+            eprintln!("[hooks] {}: {}", self.path.display(), msg);
+        }
+        self.last_error = Some(msg);
+    }
+
+    /// Start `path` with the state in its environment. False if it could not
+    /// be started; `hint` is appended to a permission error.
+    fn run(&mut self, path: &Path, env: &[(&str, String)], now: i64, hint: &str) -> bool {
+        self.path = path.to_path_buf();
+        self.last_run = now;
+        // Output goes to the daemon's log (landlock keeps /dev read-only,
+        // so /dev/null can't be opened for writing)
+        let spawned = Command::new(path)
+            .envs(env.iter().map(|(k, v)| (k, v)))
+            .stdin(Stdio::null())
+            .stdout(Stdio::inherit())
+            .stderr(Stdio::inherit())
+            .spawn();
+        match spawned {
+            Ok(child) => {
+                self.child = Some(child);
+                true
+            }
+            Err(e) => {
+                let msg = if e.kind() == std::io::ErrorKind::PermissionDenied {
+                    format!("cannot run: {}{}", e, hint)
+                } else {
+                    format!("cannot run: {}", e)
+                };
+                self.failed(msg);
+                false
+            }
+        }
+    }
+}
+
+/// Decides when the [hooks] scripts run and keeps track of them
+pub struct HookRunner {
+    params: HookParams,
+    /// Paths the sandbox was set up for (those configured at startup)
+    startup_paths: Vec<PathBuf>,
+    on_change: Hook,
+    on_mode_change: Hook,
+    /// What each hook was last told (None = not yet)
+    reported_temp: Option<i32>,
+    reported_mode: Option<&'static str>,
+}
+
+impl HookRunner {
+    pub fn new(params: HookParams) -> Self {
+        HookRunner {
+            startup_paths: params.paths().cloned().collect(),
+            params,
+            on_change: Hook::default(),
+            on_mode_change: Hook::default(),
+            reported_temp: None,
+            reported_mode: None,
+        }
+    }
+
+    /// Hook scripts to let through the sandbox
+    pub fn paths(&self) -> impl Iterator<Item = &PathBuf> {
+        self.params.paths()
+    }
+
+    /// New [hooks] settings from a config reload.
+    pub fn update(&mut self, params: HookParams) {
+        if params != self.params {
+            for path in params.paths().filter(|p| !self.startup_paths.contains(p)) {
+                eprintln!("[hooks] {} is new; if it fails to run, restart the daemon", path.display());
+            }
+            self.params = params;
+        }
+    }
+
+    /// Run the hooks the new state calls for: on_change once the applied
+    /// temperature moved more than change_delta since its last run,
+    /// on_mode_change when the mode differs. Both run for the first state
+    /// after startup. A change held back by min_interval_sec (or a run
+    /// still in progress) goes out on a later call.
+    pub fn notify(&mut self, temp: Option<i32>, mode: &'static str, brightness: f32, now: i64) {
+        let env = [
+            ("ABRAXAS_TEMP", temp.unwrap_or(0).to_string()),
+            ("ABRAXAS_MODE", mode.to_string()),
+            ("ABRAXAS_BRIGHTNESS", format!("{:.2}", brightness)),
+        ];
+        let min_interval = self.params.min_interval_sec;
+
+        if let (Some(path), Some(temp)) = (self.params.on_change.as_ref(), temp) {
+            let moved = match self.reported_temp {
+                Some(t) => (temp - t).abs() > self.params.change_delta,
+                None => true,
+            };
+            if moved && self.on_change.ready(now, min_interval) {
+                let hint = sandbox_hint(&self.startup_paths, path);
+                if self.on_change.run(path, &env, now, hint) {
+                    self.reported_temp = Some(temp);
+                }
+            }
+        }
+
+        if let Some(path) = self.params.on_mode_change.as_ref() {
+            if self.reported_mode != Some(mode) && self.on_mode_change.ready(now, min_interval) {
+                let hint = sandbox_hint(&self.startup_paths, path);
+                if self.on_mode_change.run(path, &env, now, hint) {
+                    self.reported_mode = Some(mode);
+                }
+            }
+        }
+
+        self.on_change.reap();
+        self.on_mode_change.reap();
+    }
+
+    /// Block until both hooks have exited.
+    #[cfg(test)]
+    pub fn wait(&mut self) {
+        for hook in [&mut self.on_change, &mut self.on_mode_change] {
+            if let Some(ref mut c) = hook.child {
+                let _ = c.wait();
+            }
+            hook.reap();
+        }
+    }
+}
+
+fn sandbox_hint(startup_paths: &[PathBuf], path: &Path) -> &'static str {
+    if startup_paths.iter().any(|p| p == path) {
+        ""
+    } else {
+        " (hooks added after startup need a daemon restart to pass the sandbox)"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn hooks_run_on_changes_and_respect_interval() {
+        let dir = std::env::temp_dir().join(format!("abraxas-hooks-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("log");
+        let script = dir.join("hook.sh");
+        fs::write(
+            &script,
+            format!("#!/bin/sh\necho \"$ABRAXAS_TEMP $ABRAXAS_MODE $ABRAXAS_BRIGHTNESS\" >> {}\n", log.display()),
+        )
+        .unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut hooks = HookRunner::new(HookParams {
+            on_change: Some(script.clone()),
+            on_mode_change: None,
+            change_delta: 100,
+            min_interval_sec: 10,
+        });
+        let mut step = |temp: Option<i32>, mode, now| {
+            hooks.notify(temp, mode, 0.8, now);
+            hooks.wait();
+        };
+        step(Some(6500), "clear", 1000); // first state
+        step(Some(6450), "clear", 1002); // within change_delta
+        step(Some(6000), "clear", 1005); // within min_interval_sec
+        step(Some(5900), "clear", 1010); // held-back change goes out
+        step(None, "paused", 1200); // nothing applied: no on_change
+        assert_eq!(fs::read_to_string(&log).unwrap(), "6500 clear 0.80\n5900 clear 0.80\n");
+
+        // Mode changes only; the first state counts as one
+        fs::remove_file(&log).unwrap();
+        hooks.update(HookParams {
+            on_change: None,
+            on_mode_change: Some(script.clone()),
+            change_delta: 100,
+            min_interval_sec: 0,
+        });
+        let mut step = |temp: Option<i32>, mode, now| {
+            hooks.notify(temp, mode, 1.0, now);
+            hooks.wait();
+        };
+        step(Some(6500), "clear", 2000);
+        step(Some(4500), "clear", 2001);
+        step(Some(2900), "night", 2002);
+        step(None, "paused", 2003);
+        assert_eq!(fs::read_to_string(&log).unwrap(), "6500 clear 1.00\n2900 night 1.00\n0 paused 1.00\n");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn hook_failures_reported_once() {
+        let mut hook = Hook::default();
+        let missing = Path::new("/nonexistent/abraxas-hook");
+        assert!(!hook.run(missing, &[], 1, ""));
+        let first = hook.last_error.clone().unwrap();
+        assert!(first.starts_with("cannot run: "), "{first}");
+        assert!(!hook.run(missing, &[], 2, ""));
+        assert_eq!(hook.last_error.as_deref(), Some(first.as_str()));
+
+        // A failing exit status is kept until a run succeeds
+        assert!(hook.run(Path::new("/bin/false"), &[], 3, ""));
+        let _ = hook.child.as_mut().unwrap().wait();
+        hook.reap();
+        assert_eq!(hook.last_error.as_deref(), Some("exit status: 1"));
+        assert!(hook.run(Path::new("/bin/true"), &[], 4, ""));
+        let _ = hook.child.as_mut().unwrap().wait();
+        hook.reap();
+        assert!(hook.last_error.is_none());
+    }
+}
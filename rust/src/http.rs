@@ -0,0 +1,82 @@
+//! Blocking HTTP GET through curl(1) -- zero TLS dependencies.
+//!
+//! Shared by the weather providers' one-shot fetches and the IP
+//! geolocation lookup. The daemon's async weather requests drive their
+//! own curl child (weather::CurlFetch) but take the same timeout args.
+
+use std::process::{Command, Stdio};
+
+/// A completed request: the final HTTP status (after redirects) and body
+pub struct Response {
+    pub status: u16,
+    pub body: String,
+}
+
+/// curl --max-time / --connect-timeout arguments. curl takes whole
+/// seconds here, so milliseconds round up.
+pub fn curl_timeout_args(timeout_ms: u32, connect_timeout_ms: u32) -> [String; 4] {
+    [
+        "--max-time".to_string(),
+        timeout_ms.div_ceil(1000).to_string(),
+        "--connect-timeout".to_string(),
+        connect_timeout_ms.div_ceil(1000).to_string(),
+    ]
+}
+
+/// GET `url` with extra curl arguments (`-H ...`). Any HTTP status is a
+/// response; only transport failures (DNS, timeout, TLS) are errors.
+pub fn get(
+    url: &str,
+    timeout_sec: u32,
+    connect_timeout_sec: u32,
+    headers: &[&str],
+) -> Result<Response, Box<dyn std::error::Error>> {
+    let output = Command::new("curl")
+        .args(["-s", "-L", "-w", "\n%{http_code}"])
+        .args(curl_timeout_args(timeout_sec * 1000, connect_timeout_sec * 1000))
+        .args(headers)
+        .arg(url)
+        .stdin(Stdio::null())
+        .output()?;
+
+    if !output.status.success() {
+        return Err(match output.status.code() {
+            Some(6) => "cannot resolve host".into(),
+            Some(7) => "cannot connect".into(),
+            Some(28) => format!("timed out after {}s", timeout_sec).into(),
+            _ => format!("curl exit {}", output.status).into(),
+        });
+    }
+
+    parse_output(String::from_utf8(output.stdout)?)
+}
+
+/// Split curl's `-w "\n%{http_code}"` trailer off the body.
+fn parse_output(mut out: String) -> Result<Response, Box<dyn std::error::Error>> {
+    let split = out.rfind('\n').ok_or("no HTTP status from curl")?;
+    let status = out[split + 1..].parse().map_err(|_| "no HTTP status from curl")?;
+    out.truncate(split);
+    Ok(Response { status, body: out })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn curl_timeouts_round_up_to_seconds() {
+        assert_eq!(curl_timeout_args(5000, 3000), ["--max-time", "5", "--connect-timeout", "3"]);
+        assert_eq!(curl_timeout_args(1500, 1), ["--max-time", "2", "--connect-timeout", "1"]);
+    }
+
+    #[test]
+    fn status_split_from_body() {
+        let r = parse_output("{\"a\":1}\n200".to_string()).unwrap();
+        assert_eq!((r.status, r.body.as_str()), (200, "{\"a\":1}"));
+        let r = parse_output("line 1\nline 2\n\n429".to_string()).unwrap();
+        assert_eq!((r.status, r.body.as_str()), (429, "line 1\nline 2\n"));
+        // curl reports 000 when no response arrived
+        assert_eq!(parse_output("\n000".to_string()).unwrap().status, 0);
+        assert!(parse_output("no trailer".to_string()).is_err());
+    }
+}
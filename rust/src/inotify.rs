@@ -0,0 +1,180 @@
+//! inotify on the config and state directories: which watched files were rewritten.
+//!
+//! One IN_CLOSE_WRITE | IN_MOVED_TO | IN_CREATE watch per directory (editors
+//! and the CLI replace files, so per-file watches would go stale; atomic
+//! renames only show up as IN_MOVED_TO, and a new override.json linked in
+//! from an O_TMPFILE only as IN_CREATE). Event names are mapped to the
+//! daemon's FLAG_OVERRIDE / FLAG_CONFIG bits.
+
+use crate::config::Paths;
+use crate::daemon::{FLAG_CONFIG, FLAG_OVERRIDE};
+
+use std::ffi::CString;
+
+/// Set up inotify watching the state directory (override, profile) and,
+/// when separate (system mode), the config directory for file writes.
+pub fn setup(paths: &Paths) -> i32 {
+    // Non-blocking: a multishot poll can post a CQE for events an earlier
+    // read already drained, and a blocking read would then hang the loop
+    let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC | libc::IN_NONBLOCK) };
+    if fd < 0 {
+        return -1;
+    }
+
+    if !add_watch(fd, &paths.state_dir) {
+        unsafe { libc::close(fd) };
+        return -1;
+    }
+    // A missing /etc/abraxas only costs live config reloads
+    if paths.config_dir != paths.state_dir && !add_watch(fd, &paths.config_dir) {
+        eprintln!("[inotify] Cannot watch {}, config changes need SIGHUP", paths.config_dir.display());
+    }
+
+    fd
+}
+
+fn add_watch(fd: i32, dir: &std::path::Path) -> bool {
+    let dir_cstr = match CString::new(dir.to_string_lossy().as_bytes()) {
+        Ok(c) => c,
+        Err(_) => return false,
+    };
+    let wd = unsafe {
+        libc::inotify_add_watch(
+            fd,
+            dir_cstr.as_ptr(),
+            libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO | libc::IN_CREATE,
+        )
+    };
+    wd >= 0
+}
+
+/// Parse inotify event buffer, returning flag bits for changed files.
+pub(crate) fn parse_events(buf: &[u8], paths: &Paths) -> u32 {
+    let override_name = paths.override_file.file_name().and_then(|n| n.to_str()).unwrap_or("override.json");
+    let config_name = paths.config_file.file_name().and_then(|n| n.to_str()).unwrap_or("config.ini");
+    let profile_name = paths.active_profile_file.file_name().and_then(|n| n.to_str()).unwrap_or("active_profile.json");
+
+    const EVENT_HEADER_SIZE: usize = 16;
+    let mut offset = 0;
+    let mut flags = 0u32;
+
+    while offset + EVENT_HEADER_SIZE <= buf.len() {
+        let mask = u32::from_ne_bytes([
+            buf[offset + 4], buf[offset + 5], buf[offset + 6], buf[offset + 7],
+        ]);
+        let name_len = u32::from_ne_bytes([
+            buf[offset + 12], buf[offset + 13], buf[offset + 14], buf[offset + 15],
+        ]) as usize;
+
+        let event_size = EVENT_HEADER_SIZE + name_len;
+        if offset + event_size > buf.len() {
+            break;
+        }
+
+        if name_len > 0 && mask & (libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO | libc::IN_CREATE) != 0 {
+            let name_bytes = &buf[offset + EVENT_HEADER_SIZE..offset + event_size];
+            let name_end = name_bytes.iter().position(|&b| b == 0).unwrap_or(name_bytes.len());
+            let written = mask & (libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO) != 0;
+            if let Ok(name) = std::str::from_utf8(&name_bytes[..name_end]) {
+                // Only override.json is created complete (the CLI links it
+                // in); anything else may still be empty until its close
+                if name == override_name {
+                    flags |= FLAG_OVERRIDE;
+                }
+                if written && (name == config_name || name == profile_name) {
+                    flags |= FLAG_CONFIG;
+                }
+            }
+        }
+
+        offset += event_size;
+    }
+    flags
+}
+
+/// Read inotify events from fd, returning flag bits.
+pub fn read_events(fd: i32, paths: &Paths) -> u32 {
+    let mut buf = [0u8; 4096];
+    let len = unsafe { libc::read(fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) };
+    if len > 0 {
+        parse_events(&buf[..len as usize], paths)
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::tests::test_paths;
+
+    /// One event as the kernel writes it: 16-byte header, then `len` bytes
+    /// of NUL-padded name.
+    fn event(name: &[u8], len: u32) -> Vec<u8> {
+        event_mask(name, len, libc::IN_CLOSE_WRITE)
+    }
+
+    fn event_mask(name: &[u8], len: u32, mask: u32) -> Vec<u8> {
+        let mut ev = Vec::new();
+        ev.extend_from_slice(&1i32.to_ne_bytes()); // wd
+        ev.extend_from_slice(&mask.to_ne_bytes());
+        ev.extend_from_slice(&0u32.to_ne_bytes()); // cookie
+        ev.extend_from_slice(&len.to_ne_bytes());
+        let mut padded = name.to_vec();
+        padded.resize(len as usize, 0);
+        ev.extend_from_slice(&padded);
+        ev
+    }
+
+    #[test]
+    fn parse_event_payloads() {
+        let paths = test_paths("inotify");
+
+        // (1) empty buffer
+        assert_eq!(parse_events(&[], &paths), 0);
+
+        // (2)-(4) single events
+        assert_eq!(parse_events(&event(b"override.json", 16), &paths), FLAG_OVERRIDE);
+        assert_eq!(parse_events(&event(b"config.ini", 16), &paths), FLAG_CONFIG);
+        assert_eq!(parse_events(&event(b"active_profile.json", 20), &paths), FLAG_CONFIG);
+        assert_eq!(parse_events(&event(b"weather_cache.json", 32), &paths), 0);
+        // Directory events carry no name
+        assert_eq!(parse_events(&event(b"", 0), &paths), 0);
+
+        // (5) two events in one read
+        let mut buf = event(b"config.ini", 16);
+        buf.extend(event(b"override.json", 16));
+        assert_eq!(parse_events(&buf, &paths), FLAG_OVERRIDE | FLAG_CONFIG);
+
+        // (6) name_len runs past the buffer: earlier events still count
+        let mut buf = event(b"override.json", 16);
+        let mut truncated = event(b"config.ini", 16);
+        truncated[12..16].copy_from_slice(&4096u32.to_ne_bytes());
+        buf.extend(truncated);
+        assert_eq!(parse_events(&buf, &paths), FLAG_OVERRIDE);
+        assert_eq!(parse_events(&buf[..32], &paths), FLAG_OVERRIDE);
+        assert_eq!(parse_events(&buf[..20], &paths), 0);
+        assert_eq!(parse_events(&buf[..10], &paths), 0);
+        let mut huge = event(b"config.ini", 16);
+        huge[12..16].copy_from_slice(&u32::MAX.to_ne_bytes());
+        assert_eq!(parse_events(&huge, &paths), 0);
+
+        // (7) names end at the first NUL; no terminator and invalid UTF-8 are safe
+        assert_eq!(parse_events(&event(b"override\0.json", 16), &paths), 0);
+        assert_eq!(parse_events(&event(b"config.ini\0junk", 16), &paths), FLAG_CONFIG);
+        assert_eq!(parse_events(&event(b"config.ini", 10), &paths), FLAG_CONFIG);
+        assert_eq!(parse_events(&event(b"\xffconfig.ini", 16), &paths), 0);
+
+        // (8) atomic renames arrive as IN_MOVED_TO; the temp file's own
+        // close and unrelated masks are ignored
+        assert_eq!(parse_events(&event_mask(b"override.json", 16, libc::IN_MOVED_TO), &paths), FLAG_OVERRIDE);
+        assert_eq!(parse_events(&event(b"override.json.42.0.tmp", 32), &paths), 0);
+        assert_eq!(parse_events(&event_mask(b"config.ini", 16, libc::IN_MOVED_FROM), &paths), 0);
+        assert_eq!(parse_events(&event_mask(b"config.ini", 16, libc::IN_IGNORED), &paths), 0);
+
+        // (9) a new override.json linked in from an O_TMPFILE is only an
+        // IN_CREATE; other files wait for their close
+        assert_eq!(parse_events(&event_mask(b"override.json", 16, libc::IN_CREATE), &paths), FLAG_OVERRIDE);
+        assert_eq!(parse_events(&event_mask(b"config.ini", 16, libc::IN_CREATE), &paths), 0);
+    }
+}
@@ -0,0 +1,92 @@
+//! Approximate location from the public IP address (ipapi.co).
+//!
+//! Only ever run by an explicit `--set-location auto`, never by the
+//! daemon: it tells a third party our address. City-level at best, and
+//! wrong behind a VPN, so the result is shown for confirmation first.
+
+use crate::config;
+
+pub const IPGEO_URL: &str = "https://ipapi.co/json/";
+
+/// curl -H arguments (ipapi.co rejects requests without a User-Agent)
+const HTTP_HEADERS: [&str; 2] = ["-H", "User-Agent: abraxas/7.0 (weather color temp daemon)"];
+
+/// What the endpoint reports for our address
+#[derive(Debug, PartialEq)]
+pub struct IpLocation {
+    pub lat: f64,
+    pub lon: f64,
+    /// "Chicago, Illinois, US" (whichever parts were given)
+    pub place: String,
+}
+
+/// Blocking lookup with a short timeout.
+pub fn lookup(timeout_sec: u32) -> Result<IpLocation, String> {
+    let resp = crate::http::get(IPGEO_URL, timeout_sec, timeout_sec, &HTTP_HEADERS)
+        .map_err(|e| format!("request failed: {}", e))?;
+    if resp.status != 200 {
+        // Rate limits come back as 429 with a JSON reason
+        return Err(match error_reason(&resp.body) {
+            Some(reason) => format!("HTTP {}: {}", resp.status, reason),
+            None => format!("HTTP {}", resp.status),
+        });
+    }
+    parse(&resp.body)
+}
+
+fn error_reason(body: &str) -> Option<String> {
+    let v: serde_json::Value = serde_json::from_str(body).ok()?;
+    v["reason"].as_str().or(v["message"].as_str()).map(str::to_string)
+}
+
+fn parse(body: &str) -> Result<IpLocation, String> {
+    let v: serde_json::Value = serde_json::from_str(body).map_err(|_| "response is not JSON".to_string())?;
+    if v["error"].as_bool() == Some(true) {
+        return Err(error_reason(body).unwrap_or_else(|| "lookup refused".to_string()));
+    }
+
+    let (lat, lon) = match (v["latitude"].as_f64(), v["longitude"].as_f64()) {
+        (Some(lat), Some(lon)) => (lat, lon),
+        _ => return Err("response has no latitude/longitude".to_string()),
+    };
+    if !config::valid_coordinates(lat, lon) {
+        return Err(format!("response has an invalid location: {}, {}", lat, lon));
+    }
+
+    let place: Vec<&str> = ["city", "region", "country_code"]
+        .iter()
+        .filter_map(|k| v[*k].as_str())
+        .filter(|s| !s.is_empty())
+        .collect();
+    let place = if place.is_empty() { "unknown place".to_string() } else { place.join(", ") };
+    Ok(IpLocation { lat, lon, place })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ipapi_responses_parsed() {
+        let body = r#"{"ip":"192.0.2.1","city":"Chicago","region":"Illinois","country_code":"US",
+            "latitude":41.8500,"longitude":-87.6500,"timezone":"America/Chicago"}"#;
+        assert_eq!(
+            parse(body),
+            Ok(IpLocation { lat: 41.85, lon: -87.65, place: "Chicago, Illinois, US".to_string() })
+        );
+
+        // Missing name parts are skipped, missing coordinates are an error
+        let body = r#"{"city":"","country_code":"NO","latitude":59.91,"longitude":10.75}"#;
+        assert_eq!(parse(body).unwrap().place, "NO");
+        assert!(parse(r#"{"city":"Chicago","latitude":41.85}"#).unwrap_err().contains("latitude/longitude"));
+        assert!(parse(r#"{"latitude":"41.85","longitude":"-87.65"}"#).is_err());
+        assert!(parse(r#"{"latitude":141.85,"longitude":-87.65}"#).unwrap_err().contains("invalid"));
+        assert!(parse("<html>").unwrap_err().contains("not JSON"));
+
+        // Reserved addresses and rate limits
+        let body = r#"{"ip":"127.0.0.1","error":true,"reason":"Reserved IP Address","reserved":true}"#;
+        assert_eq!(parse(body), Err("Reserved IP Address".to_string()));
+        assert_eq!(error_reason(r#"{"error":true,"reason":"RateLimited"}"#).as_deref(), Some("RateLimited"));
+        assert_eq!(error_reason("Too many requests"), None);
+    }
+}
@@ -6,7 +6,8 @@
 
 use std::ffi::CString;
 
-// Syscall numbers (x86_64)
+// Syscall numbers: the same on every architecture (x86_64, aarch64, ...),
+// as for every syscall added since Linux 5.1 (424 on)
 const NR_LANDLOCK_CREATE_RULESET: libc::c_long = 444;
 const NR_LANDLOCK_ADD_RULE: libc::c_long = 445;
 const NR_LANDLOCK_RESTRICT_SELF: libc::c_long = 446;
@@ -66,8 +67,8 @@ fn add_path_rule(ruleset_fd: i32, path: &str, access: u64) -> bool {
     ret == 0
 }
 
-pub fn install_sandbox(config_dir: &str) -> bool {
-    // Check kernel support
+/// Landlock ABI version the kernel supports, None without landlock.
+pub fn abi_version() -> Option<i32> {
     let abi = unsafe {
         libc::syscall(
             NR_LANDLOCK_CREATE_RULESET,
@@ -77,6 +78,17 @@ pub fn install_sandbox(config_dir: &str) -> bool {
         )
     } as i32;
     if abi < 0 {
+        None
+    } else {
+        Some(abi)
+    }
+}
+
+/// Restrict this process (and its children) to the paths the daemon uses,
+/// plus read and execute access to each of `executables` (hook scripts).
+pub fn install_sandbox(writable_dirs: &[&str], executables: &[&str]) -> bool {
+    // Check kernel support
+    if abi_version().is_none() {
         return false;
     }
 
@@ -104,11 +116,14 @@ pub fn install_sandbox(config_dir: &str) -> bool {
         return false;
     }
 
-    // ~/.config/abraxas/ -- full read/write
+    // Config, state and cache dirs (~/.config/abraxas, ~/.cache/abraxas, or
+    // /var/lib/abraxas in system mode) -- full read/write
     let config_access =
         ACCESS_FS_READ_FILE | ACCESS_FS_READ_DIR | ACCESS_FS_WRITE_FILE
         | ACCESS_FS_REMOVE_FILE | ACCESS_FS_MAKE_REG | ACCESS_FS_MAKE_DIR;
-    add_path_rule(ruleset_fd, config_dir, config_access);
+    for dir in writable_dirs {
+        add_path_rule(ruleset_fd, dir, config_access);
+    }
 
     // /dev -- read for DRM ioctls
     let read_only = ACCESS_FS_READ_FILE | ACCESS_FS_READ_DIR;
@@ -120,13 +135,33 @@ pub fn install_sandbox(config_dir: &str) -> bool {
     // /usr -- execute for curl, read for shared libs
     add_path_rule(ruleset_fd, "/usr", read_only | ACCESS_FS_EXECUTE);
 
-    // /etc -- read for timezone, resolver
+    // /etc -- read for timezone, resolver, and /etc/abraxas in system mode
     add_path_rule(ruleset_fd, "/etc", read_only);
 
     // /lib, /lib64 -- shared libraries
     add_path_rule(ruleset_fd, "/lib", read_only);
     add_path_rule(ruleset_fd, "/lib64", read_only);
 
+    // $XDG_RUNTIME_DIR -- Wayland and session D-Bus sockets
+    let runtime_dir = std::env::var("XDG_RUNTIME_DIR")
+        .ok()
+        .filter(|d| !d.is_empty())
+        .unwrap_or_else(|| format!("/run/user/{}", unsafe { libc::getuid() }));
+    add_path_rule(ruleset_fd, &runtime_dir, read_only | ACCESS_FS_WRITE_FILE);
+
+    // /run/dbus -- system D-Bus socket
+    add_path_rule(ruleset_fd, "/run/dbus", read_only | ACCESS_FS_WRITE_FILE);
+
+    // Hook scripts, and /bin for their interpreters where /usr isn't merged
+    for path in executables {
+        if !add_path_rule(ruleset_fd, path, ACCESS_FS_READ_FILE | ACCESS_FS_EXECUTE) {
+            eprintln!("[hooks] {}: cannot allow it in the sandbox (missing?)", path);
+        }
+    }
+    if !executables.is_empty() {
+        add_path_rule(ruleset_fd, "/bin", read_only | ACCESS_FS_EXECUTE);
+    }
+
     // /tmp -- curl temp files
     add_path_rule(ruleset_fd, "/tmp",
         ACCESS_FS_READ_FILE | ACCESS_FS_WRITE_FILE | ACCESS_FS_MAKE_REG);
@@ -2,25 +2,60 @@
 //!
 //! Commands:
 //!   --daemon         Run as daemon (default)
-//!   --status         Show current status
-//!   --set-location   Set location (ZIP or lat,lon)
+//!   --status [--debug]  Show current status (--debug: daemon internals)
+//!   --status --bar   One-line status for waybar/i3status (--bar-format FMT)
+//!   --watch [SEC]    Redraw the status every SEC seconds (default 5)
+//!   --set-location   Set location (ZIP, lat,lon, city name or auto)
+//!   --download-zipdb Build the ZIP code database from Census data
+//!   --build-zipdb    Build the ZIP code database from a local CSV
+//!   --download-citydb Build the city name database from GeoNames downloads
+//!   --build-citydb   Build the city name database from a GeoNames extract
 //!   --refresh        Force weather refresh
-//!   --set TEMP [MIN] Manual override to TEMP over MIN minutes
+//!   --weather-provider  Choose the weather source (noaa, met-norway, disabled)
+//!   --validate-location  Sanity-check stored location against solar math
+//!   --set TEMP [DUR] Manual override to TEMP over DUR (minutes or 90s/2m/1h)
+//!   --fade TEMP DUR  Fade the screen to TEMP over DUR seconds and exit (no daemon)
 //!   --resume         Clear manual override
+//!   --toggle         Pause/unpause gamma adjustment
 //!   --reset          Restore gamma and exit
+//!   --stop           Stop the running daemon
+//!   --restart        Stop and re-launch the daemon
+//!   --export-config  Print annotated config.ini
+//!   --use-location   Switch to a [location "NAME"] entry
+//!   --list-locations List configured locations
+//!   --use-profile    Switch to a [profile.NAME] preset
+//!   --list-profiles  List configured profiles
+//!   --check-backend  Verify gamma access without changing the display
+//!   --diagnose       Report what every backend and the sandbox found (--json)
 //!   --help           Show usage
 
+mod citydb;
+mod clock;
 mod config;
 mod daemon;
 mod gamma;
+#[cfg(feature = "geoclue")]
+mod geoclue;
+mod hooks;
+mod http;
+mod inotify;
+mod ipgeo;
 mod landlock;
+mod mmapdb;
+mod notify;
+#[cfg(any(feature = "gnome", feature = "geoclue", feature = "notify"))]
+mod sdbus;
 mod seccomp;
-mod sigmoid;
 mod solar;
+mod statusbar;
+#[cfg(feature = "thermal")]
+mod thermal;
 mod uring;
 mod weather;
 mod zipdb;
 
+use clock::SystemClock;
+use abraxas_core::sigmoid;
 use std::process;
 
 /// Temperature bounds (Kelvin)
@@ -37,30 +72,182 @@ pub const CLOUD_THRESHOLD: i32 = 75;
 
 /// Timing
 pub const WEATHER_REFRESH_SEC: i64 = 900; // 15 minutes
+pub const WEATHER_RETRY_SEC: i64 = 60; // after a failed fetch
+pub const WEATHER_REFRESH_MAX_SEC: i64 = 24 * 3600; // a day-old forecast is stale
 pub const TEMP_UPDATE_SEC: i64 = 60; // 1 minute
+pub const OVERRIDE_TICK_SEC: i64 = 1; // while a --set transition runs
+pub const TRANSITION_TICK_SEC: i64 = 10; // inside dawn/dusk windows
 
 /// Transition windows (minutes)
 pub const DAWN_DURATION: f64 = 90.0;
 pub const DUSK_DURATION: f64 = 180.0;
 
 /// Dawn offset: shift sigmoid midpoint this many minutes after sunrise
+/// (default for [transitions] dawn_offset)
 pub const DAWN_OFFSET: f64 = 30.0;
 
 /// Dusk offset: shift sigmoid midpoint this many minutes before sunset
+/// (default for [transitions] dusk_offset)
 pub const DUSK_OFFSET: f64 = 30.0;
+/// Accepted [transitions] dusk_offset range (minutes)
+pub const DUSK_OFFSET_RANGE: std::ops::RangeInclusive<f64> = -120.0..=120.0;
+
+/// [daemon] mode = elevation: sun elevation (degrees) at or above which the
+/// day temperature applies, and at or below which night applies
+pub const ELEVATION_HIGH: f64 = 3.0;
+pub const ELEVATION_LOW: f64 = -6.0;
+
+/// Default [schedule] blend window around each keyframe (minutes)
+pub const SCHEDULE_BLEND_MIN: f64 = 30.0;
+
+/// Minimum Kelvin change worth a gamma update outside transitions
+/// (default for [transitions] temp_change_threshold)
+pub const TEMP_CHANGE_THRESHOLD: i32 = 5;
 
 /// Sigmoid steepness for transitions
-pub const SIGMOID_STEEPNESS: f64 = 8.0;
+/// (default for [transitions] steepness_dawn / steepness_dusk)
+pub const SIGMOID_STEEPNESS: f64 = sigmoid::STEEPNESS;
+/// Accepted steepness range: 1 is nearly linear, 20 nearly a step
+pub const STEEPNESS_RANGE: std::ops::RangeInclusive<f64> = 1.0..=20.0;
+
+/// curl timeouts for weather requests (defaults for [network]
+/// weather_timeout_seconds / connect_timeout_seconds)
+pub const WEATHER_TIMEOUT_SEC: u32 = 5;
+pub const CONNECT_TIMEOUT_SEC: u32 = 3;
+/// Accepted range for both (seconds)
+pub const NETWORK_TIMEOUT_RANGE: std::ops::RangeInclusive<u32> = 1..=120;
+
+/// Accepted CIE x and y for [display] monitor_white_x / monitor_white_y
+/// (every real display white falls well inside)
+pub const WHITE_POINT_RANGE: std::ops::RangeInclusive<f64> = 0.2..=0.5;
+
+/// Rewrite a CRTC's gamma ramp only when the temperature moved more than
+/// this many Kelvin from the one on screen (default for [display]
+/// ramp_quantize_step); absorbs sigmoid jitter like 3011K vs 3013K
+pub const RAMP_QUANTIZE_STEP: i32 = 10;
+/// Accepted range: anything from 50K up is a visible color step
+pub const RAMP_QUANTIZE_RANGE: std::ops::RangeInclusive<i32> = 0..=49;
+
+/// Default [hooks] change_delta: on_change runs once the applied
+/// temperature has moved this many Kelvin since its last run
+pub const HOOK_CHANGE_DELTA: i32 = 100;
+/// Default [hooks] min_interval_seconds between runs of one hook, so a
+/// fast manual fade doesn't start a process every tick
+pub const HOOK_MIN_INTERVAL_SEC: i64 = 10;
+
+/// Shortest gap between two desktop notifications of one kind (dawn,
+/// dusk, override ended), so a window flapping at its edge stays quiet
+pub const NOTIFY_COOLDOWN_SEC: i64 = 30 * 60;
+
+/// config.ini layout version: 1 = [location] only, 2 = tuning sections
+pub const CONFIG_VERSION: u32 = 2;
+
+/// Valid coordinates (degrees)
+pub const LAT_RANGE: std::ops::RangeInclusive<f64> = -90.0..=90.0;
+pub const LON_RANGE: std::ops::RangeInclusive<f64> = -180.0..=180.0;
+
+/// --status warns when cached weather is older than this (hours)
+pub const WEATHER_STALE_HOURS: i64 = 2;
+
+/// Fade back to solar control after a timed hold expires (minutes)
+pub const HOLD_RESUME_FADE_MIN: i32 = 3;
+
+/// Longest --set transition (seconds)
+pub const OVERRIDE_MAX_DURATION_SEC: i64 = 24 * 3600;
+/// Override issued_at this far in the future is taken as clock skew (seconds)
+pub const OVERRIDE_CLOCK_SKEW_SEC: i64 = 60;
+/// Largest override.json accepted (bytes)
+pub const OVERRIDE_MAX_BYTES: usize = 4096;
+
+/// Longest wait for a GeoClue2 fix (seconds)
+pub const GEOCLUE_TIMEOUT_SEC: u64 = 15;
+/// The daemon re-asks GeoClue2 this often for an auto location (seconds)
+pub const GEOCLUE_RECHECK_SEC: i64 = 24 * 3600;
+/// IP geolocation request timeout (seconds)
+pub const IPGEO_TIMEOUT_SEC: u32 = 5;
+
+/// Why a command failed. main prints it and exits with exit_code().
+#[derive(Debug, PartialEq)]
+pub enum CliError {
+    /// Bad arguments; the message says which
+    Usage(String),
+    /// Not a command; usage follows
+    UnknownCommand(String),
+    /// The command could not run (no location, daemon startup failed)
+    Fatal(String),
+    /// Exit with this code; already reported
+    Exit(i32),
+}
+
+impl CliError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CliError::Usage(_) | CliError::UnknownCommand(_) | CliError::Fatal(_) => 1,
+            CliError::Exit(code) => *code,
+        }
+    }
+
+    /// Print to stderr
+    fn report(&self) {
+        match self {
+            CliError::Usage(msg) | CliError::Fatal(msg) => eprintln!("{}", msg),
+            CliError::UnknownCommand(cmd) => {
+                eprintln!("Unknown command: {}", cmd);
+                print_usage();
+            }
+            CliError::Exit(_) => {}
+        }
+    }
+}
 
 enum Command {
-    Daemon,
-    Status,
-    SetLocation(String),
+    Daemon { force_gnome: bool, disable_night_light: bool, dry_run: bool },
+    Status { debug: bool, verbose: bool, bar: bool, bar_format: Option<String> },
+    Watch { interval_sec: u64 },
+    SetLocation { location: String, yes: bool },
+    DownloadZipdb,
+    BuildZipdb(String),
+    DownloadCitydb { min_population: u32 },
+    BuildCitydb { cities: String, admin1: Option<String>, min_population: u32 },
     Refresh,
-    Set { temp: i32, duration: i32 },
+    ValidateLocation,
+    Oneshot { refresh: bool },
+    Preview { date: Option<String>, json: bool },
+    DebugSolar(Option<String>),
+    Set(SetArgs),
+    Fade { temp: i32, duration_sec: u32 },
     Resume,
+    Toggle,
     Reset,
+    Stop,
+    Restart,
+    ExportConfig,
+    ImportRedshift(String),
+    WeatherProvider(String),
+    UseLocation(String),
+    ListLocations,
+    UseProfile(String),
+    ListProfiles,
     Benchmark,
+    CheckBackend,
+    Diagnose { json: bool },
+    Help,
+}
+
+/// Parsed `--set` (and `--brightness`) arguments
+#[derive(Clone, Copy)]
+struct SetArgs {
+    temp: i32,
+    /// Transition length in seconds
+    duration: i64,
+    hold: Option<i32>,
+    relative: bool,
+    brightness: Option<f32>,
+    easing: Option<config::Easing>,
+    /// Epoch to start at (--at)
+    at: Option<i64>,
+    /// Keep through dawn/dusk until --resume (--hold)
+    held: bool,
 }
 
 fn print_usage() {
@@ -69,349 +256,2906 @@ fn print_usage() {
     eprintln!("Usage: abraxas [COMMAND]");
     eprintln!();
     eprintln!("  --daemon              Run daemon (default)");
-    eprintln!("  --status              Show current status");
-    eprintln!("  --set-location LOC    Set location (ZIP code or LAT,LON)");
+    eprintln!("      --force-gnome     Use the GNOME backend even if Night Light is active");
+    eprintln!("      --disable-gnome-night-light");
+    eprintln!("                        Turn GNOME Night Light off before starting");
+    eprintln!("      --dry-run         Log the temperatures it would apply, leave the screen alone");
+    eprintln!("  --status [--debug] [--verbose]");
+    eprintln!("                        Show current status (--debug adds daemon internals,");
+    eprintln!("                        --verbose the dawn/dusk curve's working)");
+    eprintln!("  --status --bar [--bar-format FMT]");
+    eprintln!("                        One line for status bars: waybar JSON, or FMT with {{temp}},");
+    eprintln!("                        {{mode}}, {{icon}}, {{sun_elevation}} and {{cloud}} filled in");
+    eprintln!("  --watch [SECONDS]     Redraw the status every SECONDS (default {}) until Ctrl-C", WATCH_INTERVAL_SEC);
+    eprintln!("  --set-location LOC    Set location (ZIP code, LAT,LON, 41.88N,87.63W, or a city:");
+    eprintln!("                        Berlin, \"Portland, OR\", \"London, UK\")");
+    eprintln!("      auto [--yes]      Ask GeoClue2 (geoclue feature; the daemon re-checks daily),");
+    eprintln!("                        else look up the public IP address after confirmation");
+    eprintln!("  --download-zipdb      Download Census ZCTA data and build the ZIP code database");
+    eprintln!("  --build-zipdb FILE    Build the ZIP code database from a ZIP,LAT,LON CSV (or TSV)");
+    eprintln!("  --download-citydb [--min-population N]");
+    eprintln!("                        Download GeoNames data and build the city database");
+    eprintln!("                        (default: cities of {}+ people)", citydb::DEFAULT_MIN_POPULATION);
+    eprintln!("  --build-citydb CITIES [ADMIN1] [--min-population N]");
+    eprintln!("                        Build the city database from GeoNames cities15000.txt");
+    eprintln!("                        (and admin1CodesASCII.txt for region names), or from the");
+    eprintln!("                        tz database's zone.tab (how the bundled one is built)");
     eprintln!("  --refresh             Force weather refresh");
-    eprintln!("  --set TEMP [MINUTES]  Override to TEMP over MINUTES (default 3)");
+    eprintln!("  --weather-provider P  Weather source: {} (saved in config.ini)", config::WeatherProvider::choices());
+    eprintln!("  --validate-location   Check the stored location makes solar sense");
+    eprintln!("  --oneshot [--refresh] Apply the current solar temperature once and exit");
+    eprintln!("  --preview [DATE] [--json]");
+    eprintln!("                        Plot the planned temperature curve for DATE (YYYY-MM-DD,");
+    eprintln!("                        default today) in 10-minute steps");
+    eprintln!("  --debug-solar [DATETIME]");
+    eprintln!("                        Print the NOAA solar calculation step by step for");
+    eprintln!("                        DATETIME (\"YYYY-MM-DD HH:MM\" local, default now)");
+    eprintln!("  --set TEMP [DURATION] [HOLD]");
+    eprintln!("                        Override to TEMP over DURATION (minutes, or 90s / 2m / 1h;");
+    eprintln!("                        default 3), then hold for HOLD minutes (default: until");
+    eprintln!("                        next dawn/dusk)");
+    eprintln!("                        TEMP may be relative: +500 / -500");
+    eprintln!("      --for HOLD        Same as the HOLD argument");
+    eprintln!("      --brightness B    Also set brightness (0.1-1.0) for the override");
+    eprintln!("      --easing CURVE    Transition curve: sigmoid (default), linear, ease-in-out, step");
+    eprintln!("      --hold            Keep the override through dawn/dusk until --resume");
+    eprintln!("      --at TIME         Start at TIME (HH:MM today, or Unix timestamp) instead of now");
+    eprintln!("  --fade TEMP DURATION  Fade straight to TEMP over DURATION (seconds, or 2m / 1h)");
+    eprintln!("                        without the daemon, and leave it there");
+    eprintln!("  --brightness B        Override brightness only, keeping the temperature");
     eprintln!("  --resume              Clear override, resume solar control");
+    eprintln!("  --toggle              Pause/unpause gamma adjustment");
     eprintln!("  --reset               Restore gamma and exit");
+    eprintln!("  --stop                Stop the running daemon");
+    eprintln!("  --restart             Stop the daemon and run it again in the foreground");
+    eprintln!("  --export-config       Print annotated config.ini with current values");
+    eprintln!("  --import-redshift PATH  Copy location and temperatures from a redshift.conf");
+    eprintln!("  --use-location NAME   Use the [location \"NAME\"] coordinates (none = [location])");
+    eprintln!("  --list-locations      List configured locations");
+    eprintln!("  --use-profile NAME    Use [profile.NAME] location/temperatures (none = clear)");
+    eprintln!("  --list-profiles       List configured profiles");
+    eprintln!("  --check-backend       Verify the gamma backend can write every output (no visible change)");
+    eprintln!("  --diagnose [--json]   Try every gamma backend and report what each one saw, plus");
+    eprintln!("                        /dev/dri access, curl, io_uring, Landlock and seccomp");
     eprintln!("  --benchmark           Run nanosecond benchmark");
     eprintln!("  --help                Show this help");
+    eprintln!();
+    eprintln!("Global options:");
+    eprintln!("  --config-dir PATH     Keep config and cache in PATH (same as ABRAXAS_CONFIG_DIR)");
+    eprintln!("  --system              Read /etc/abraxas/config.ini, keep state in /var/lib/abraxas");
+    eprintln!("                        (automatic for root without HOME, e.g. a systemd system service)");
+    eprintln!();
+    eprintln!("Daemon signals:");
+    eprintln!("  SIGHUP                Reload config.ini and weather cache");
+    eprintln!("  SIGUSR1               Toggle pause (same as --toggle)");
+    eprintln!("  SIGUSR2               Refresh weather now");
+    eprintln!("  SIGTERM / SIGINT      Restore gamma and exit");
+}
+
+fn parse_brightness(arg: Option<&String>) -> Result<f32, CliError> {
+    let arg = match arg {
+        Some(a) => a,
+        None => return Err(CliError::Usage("--brightness requires a value between 0.1 and 1.0".into())),
+    };
+    match arg.parse::<f32>() {
+        Ok(v) if (0.1..=1.0).contains(&v) => Ok(v),
+        _ => Err(CliError::Usage(format!("Invalid brightness: {} (expected 0.1 to 1.0)", arg))),
+    }
+}
+
+/// Transition length in seconds: bare numbers are minutes, or suffixed
+/// with s / m / h ("90s", "2m", "1h").
+fn parse_duration(arg: &str) -> Option<i64> {
+    let (num, unit) = match arg.char_indices().last() {
+        Some((i, 's')) => (&arg[..i], 1),
+        Some((i, 'm')) => (&arg[..i], 60),
+        Some((i, 'h')) => (&arg[..i], 3600),
+        _ => (arg, 60),
+    };
+    let secs = num.parse::<i64>().ok()?.checked_mul(unit)?;
+    (0..=OVERRIDE_MAX_DURATION_SEC).contains(&secs).then_some(secs)
+}
+
+/// "90 s", "2 min", "2 min 30 s"
+pub fn format_duration(secs: i64) -> String {
+    match (secs / 60, secs % 60) {
+        (0, s) => format!("{} s", s),
+        (m, 0) => format!("{} min", m),
+        (m, s) => format!("{} min {} s", m, s),
+    }
+}
+
+/// `--at` time: "HH:MM" today (local), or a Unix timestamp.
+fn parse_at(arg: &str, now: i64) -> Option<i64> {
+    if arg.contains(':') {
+        let minute = config::parse_hhmm(arg)?;
+        return Some(day_start(now) + minute as i64 * 60);
+    }
+    arg.parse::<i64>().ok().filter(|&t| t > 0)
+}
+
+fn parse_hold(arg: &str) -> Result<i32, CliError> {
+    match arg.parse::<i32>() {
+        Ok(v) if v >= 0 => Ok(v),
+        _ => Err(CliError::Usage(format!("Invalid hold time: {}", arg))),
+    }
 }
 
-fn parse_args() -> Command {
-    let args: Vec<String> = std::env::args().collect();
+/// Parse the command line (program name first). --config-dir and --system
+/// are taken out wherever they are and exported for Paths::init.
+fn parse_args(mut args: Vec<String>) -> Result<Command, CliError> {
+    // Global --config-dir PATH, anywhere on the line. Exported so Paths::init
+    // and a daemon exec'd by --restart resolve the same directory.
+    if let Some(pos) = args.iter().position(|a| a == "--config-dir") {
+        if pos + 1 >= args.len() {
+            return Err(CliError::Usage("--config-dir requires a directory".into()));
+        }
+        let dir = args.remove(pos + 1);
+        args.remove(pos);
+        std::env::set_var("ABRAXAS_CONFIG_DIR", dir);
+    }
+    // Global --system: /etc/abraxas config, /var/lib/abraxas state (see Paths)
+    if let Some(pos) = args.iter().position(|a| a == "--system") {
+        args.remove(pos);
+        std::env::set_var("ABRAXAS_SYSTEM", "1");
+    }
 
     if args.len() < 2 {
-        return Command::Daemon;
+        return Ok(Command::Daemon { force_gnome: false, disable_night_light: false, dry_run: false });
     }
 
-    match args[1].as_str() {
-        "--daemon" | "daemon" => Command::Daemon,
-        "--status" | "status" => Command::Status,
+    Ok(match args[1].as_str() {
+        "--daemon" | "daemon" => {
+            let mut force_gnome = false;
+            let mut disable_night_light = false;
+            let mut dry_run = false;
+            for arg in &args[2..] {
+                match arg.as_str() {
+                    "--force-gnome" => force_gnome = true,
+                    "--disable-gnome-night-light" => disable_night_light = true,
+                    "--dry-run" => dry_run = true,
+                    other => return Err(CliError::Usage(format!("Unexpected argument: {}", other))),
+                }
+            }
+            Command::Daemon { force_gnome, disable_night_light, dry_run }
+        }
+        "--status" | "status" => {
+            let mut debug = false;
+            let mut verbose = false;
+            let mut bar = false;
+            let mut bar_format = None;
+            let mut rest = args[2..].iter();
+            while let Some(arg) = rest.next() {
+                match arg.as_str() {
+                    "--debug" => debug = true,
+                    "--verbose" | "-v" => verbose = true,
+                    "--bar" => bar = true,
+                    "--bar-format" => match rest.next() {
+                        Some(fmt) => bar_format = Some(fmt.clone()),
+                        None => return Err(CliError::Usage("--bar-format needs a template\n  Example: abraxas --status --bar --bar-format '{temp}K {icon}'".into())),
+                    },
+                    other => return Err(CliError::Usage(format!("Unexpected argument: {}", other))),
+                }
+            }
+            if bar_format.is_some() && !bar {
+                return Err(CliError::Usage("--bar-format only applies with --bar".into()));
+            }
+            Command::Status { debug, verbose, bar, bar_format }
+        }
+        "--watch" | "watch" => {
+            let interval_sec = match args.get(2) {
+                None => WATCH_INTERVAL_SEC,
+                Some(n) => match n.parse() {
+                    Ok(n) if n > 0 && args.len() == 3 => n,
+                    _ => return Err(CliError::Usage("--watch takes an optional refresh interval in seconds\n  Example: abraxas --watch 2".into())),
+                },
+            };
+            Command::Watch { interval_sec }
+        }
         "--set-location" | "set-location" => {
             if args.len() < 3 {
-                eprintln!("--set-location requires a location argument");
-                eprintln!("  Example: abraxas --set-location 60614");
-                eprintln!("  Example: abraxas --set-location 41.88,-87.63");
-                process::exit(1);
+                return Err(CliError::Usage(
+                    concat!(
+                        "--set-location requires a location argument\n",
+                        "  Example: abraxas --set-location 60614\n",
+                        "  Example: abraxas --set-location 41.88,-87.63\n",
+                        "  Example: abraxas --set-location \"Portland, OR\"\n",
+                        "  Example: abraxas --set-location auto",
+                    )
+                    .into(),
+                ));
+            }
+            let mut yes = false;
+            for arg in &args[3..] {
+                match arg.as_str() {
+                    "--yes" | "-y" => yes = true,
+                    other => return Err(CliError::Usage(format!("Unexpected argument: {}", other))),
+                }
+            }
+            Command::SetLocation { location: args[2].clone(), yes }
+        }
+        "--download-zipdb" | "download-zipdb" => Command::DownloadZipdb,
+        "--build-zipdb" | "build-zipdb" => {
+            if args.len() < 3 {
+                return Err(CliError::Usage("--build-zipdb requires a CSV file with zip, lat and lon columns\n  Example: abraxas --build-zipdb zipcodes.csv".into()));
+            }
+            Command::BuildZipdb(args[2].clone())
+        }
+        "--download-citydb" | "download-citydb" => {
+            let mut min_population = citydb::DEFAULT_MIN_POPULATION;
+            let mut rest = args[2..].iter();
+            while let Some(arg) = rest.next() {
+                match arg.as_str() {
+                    "--min-population" => match rest.next().and_then(|n| n.parse().ok()) {
+                        Some(n) => min_population = n,
+                        None => return Err(CliError::Usage("--min-population requires a number".into())),
+                    },
+                    other => return Err(CliError::Usage(format!("Unexpected argument: {}", other))),
+                }
+            }
+            Command::DownloadCitydb { min_population }
+        }
+        "--build-citydb" | "build-citydb" => {
+            let mut files = Vec::new();
+            let mut min_population = citydb::DEFAULT_MIN_POPULATION;
+            let mut rest = args[2..].iter();
+            while let Some(arg) = rest.next() {
+                match arg.as_str() {
+                    "--min-population" => match rest.next().and_then(|n| n.parse().ok()) {
+                        Some(n) => min_population = n,
+                        None => return Err(CliError::Usage("--min-population requires a number".into())),
+                    },
+                    other if files.len() < 2 && !other.starts_with('-') => files.push(other.to_string()),
+                    other => return Err(CliError::Usage(format!("Unexpected argument: {}", other))),
+                }
+            }
+            if files.is_empty() {
+                return Err(CliError::Usage("--build-citydb requires a GeoNames cities file\n  Example: abraxas --build-citydb cities15000.txt admin1CodesASCII.txt".into()));
             }
-            Command::SetLocation(args[2].clone())
+            let cities = files.remove(0);
+            Command::BuildCitydb { cities, admin1: files.pop(), min_population }
         }
         "--refresh" | "refresh" => Command::Refresh,
+        "--validate-location" | "validate-location" => Command::ValidateLocation,
+        "--oneshot" | "oneshot" => {
+            let mut refresh = false;
+            for arg in &args[2..] {
+                match arg.as_str() {
+                    "--refresh" => refresh = true,
+                    other => return Err(CliError::Usage(format!("Unexpected argument: {}", other))),
+                }
+            }
+            Command::Oneshot { refresh }
+        }
+        "--preview" | "preview" => {
+            let mut date = None;
+            let mut json = false;
+            for arg in &args[2..] {
+                match arg.as_str() {
+                    "--json" => json = true,
+                    other if date.is_none() && !other.starts_with('-') => {
+                        date = Some(other.to_string());
+                    }
+                    other => return Err(CliError::Usage(format!("Unexpected argument: {}", other))),
+                }
+            }
+            Command::Preview { date, json }
+        }
+        "--debug-solar" | "debug-solar" => {
+            // Accept the datetime quoted or as two separate arguments
+            let when = args[2..].join(" ");
+            Command::DebugSolar(if when.is_empty() { None } else { Some(when) })
+        }
+        "--fade" | "fade" => {
+            if args.len() != 4 {
+                return Err(CliError::Usage("--fade requires a temperature and a duration\n  Example: abraxas --fade 2700 60".into()));
+            }
+            let temp: i32 = match args[2].parse() {
+                Ok(v) => v,
+                Err(_) => return Err(CliError::Usage(format!("Invalid temperature: {}", args[2]))),
+            };
+            // Plain numbers are seconds here, unlike --set's minutes
+            let duration = if args[3].ends_with(|c: char| c.is_ascii_digit()) {
+                parse_duration(&format!("{}s", args[3]))
+            } else {
+                parse_duration(&args[3])
+            };
+            match duration {
+                Some(d) => Command::Fade { temp, duration_sec: d as u32 },
+                None => return Err(CliError::Usage(format!("Invalid duration: {} (seconds, or 90s / 2m / 1h, up to 24h)", args[3]))),
+            }
+        }
         "--set" | "set" => {
             if args.len() < 3 {
-                eprintln!("--set requires a temperature argument");
-                eprintln!("  Example: abraxas --set 3500 30");
-                process::exit(1);
+                return Err(CliError::Usage("--set requires a temperature argument\n  Example: abraxas --set 3500 30".into()));
             }
+            let relative = args[2].starts_with('+') || args[2].starts_with('-');
             let temp: i32 = match args[2].parse() {
                 Ok(v) => v,
-                Err(_) => {
-                    eprintln!("Invalid temperature: {}", args[2]);
-                    process::exit(1);
-                }
+                Err(_) => return Err(CliError::Usage(format!("Invalid temperature: {}", args[2]))),
             };
-            let duration: i32 = if args.len() >= 4 {
-                match args[3].parse() {
-                    Ok(v) => v,
-                    Err(_) => {
-                        eprintln!("Invalid duration: {}", args[3]);
-                        process::exit(1);
+            let mut duration: i64 = 3 * 60;
+            let mut hold: Option<i32> = None;
+            let mut brightness: Option<f32> = None;
+            let mut easing: Option<config::Easing> = None;
+            let mut at: Option<i64> = None;
+            let mut held = false;
+            let mut positional = 0;
+            let mut i = 3;
+            while i < args.len() {
+                if args[i] == "--brightness" {
+                    brightness = Some(parse_brightness(args.get(i + 1))?);
+                    i += 2;
+                    continue;
+                }
+                if args[i] == "--easing" {
+                    easing = match args.get(i + 1).and_then(|a| config::Easing::from_name(a)) {
+                        Some(e) => Some(e),
+                        None => return Err(CliError::Usage(format!("--easing requires one of: {}", config::Easing::NAMES))),
+                    };
+                    i += 2;
+                    continue;
+                }
+                if args[i] == "--at" {
+                    at = match args.get(i + 1).and_then(|a| parse_at(a, now_epoch())) {
+                        Some(t) => Some(t),
+                        None => return Err(CliError::Usage("--at requires a local time (HH:MM) or a Unix timestamp".into())),
+                    };
+                    i += 2;
+                    continue;
+                }
+                if args[i] == "--hold" {
+                    held = true;
+                    i += 1;
+                    continue;
+                }
+                if args[i] == "--for" {
+                    if i + 1 >= args.len() {
+                        return Err(CliError::Usage("--for requires a hold time in minutes".into()));
                     }
+                    hold = Some(parse_hold(&args[i + 1])?);
+                    i += 2;
+                    continue;
                 }
-            } else {
-                3
-            };
-            Command::Set { temp, duration }
+                match positional {
+                    0 => {
+                        duration = match parse_duration(&args[i]) {
+                            Some(v) => v,
+                            None => return Err(CliError::Usage(format!("Invalid duration: {} (minutes, or 90s / 2m / 1h)", args[i]))),
+                        };
+                    }
+                    1 => hold = Some(parse_hold(&args[i])?),
+                    _ => return Err(CliError::Usage(format!("Unexpected argument: {}", args[i]))),
+                }
+                positional += 1;
+                i += 1;
+            }
+            if held && hold.is_some() {
+                return Err(CliError::Usage("--hold keeps the override until --resume; drop the hold time".into()));
+            }
+            Command::Set(SetArgs { temp, duration, hold, relative, brightness, easing, at, held })
         }
+        // Brightness alone: instant override that keeps the current temperature
+        "--brightness" | "brightness" => Command::Set(SetArgs {
+            temp: 0,
+            duration: 0,
+            hold: None,
+            relative: true,
+            brightness: Some(parse_brightness(args.get(2))?),
+            easing: None,
+            at: None,
+            held: false,
+        }),
         "--resume" | "resume" => Command::Resume,
+        "--toggle" | "toggle" => Command::Toggle,
         "--reset" | "reset" => Command::Reset,
-        "--benchmark" | "benchmark" => Command::Benchmark,
-        "--help" | "-h" | "help" => {
-            print_usage();
-            process::exit(0);
+        "--stop" | "stop" => Command::Stop,
+        "--restart" | "restart" => Command::Restart,
+        "--export-config" | "export-config" => Command::ExportConfig,
+        "--import-redshift" | "import-redshift" => {
+            if args.len() < 3 {
+                return Err(CliError::Usage("--import-redshift requires the path of a Redshift config\n  Example: abraxas --import-redshift ~/.config/redshift.conf".into()));
+            }
+            Command::ImportRedshift(args[2].clone())
+        }
+        "--weather-provider" | "weather-provider" => {
+            if args.len() < 3 {
+                return Err(CliError::Usage(format!("--weather-provider requires one of: {}\n  Example: abraxas --weather-provider disabled", config::WeatherProvider::choices())));
+            }
+            Command::WeatherProvider(args[2].clone())
         }
-        other => {
-            eprintln!("Unknown command: {}", other);
-            print_usage();
-            process::exit(1);
+        "--use-location" | "use-location" => {
+            if args.len() < 3 {
+                return Err(CliError::Usage("--use-location requires a location name\n  Example: abraxas --use-location office".into()));
+            }
+            Command::UseLocation(args[2].clone())
         }
-    }
+        "--list-locations" | "list-locations" => Command::ListLocations,
+        "--use-profile" | "use-profile" => {
+            if args.len() < 3 {
+                return Err(CliError::Usage("--use-profile requires a profile name\n  Example: abraxas --use-profile office".into()));
+            }
+            Command::UseProfile(args[2].clone())
+        }
+        "--list-profiles" | "list-profiles" => Command::ListProfiles,
+        "--benchmark" | "benchmark" => Command::Benchmark,
+        "--check-backend" | "check-backend" => Command::CheckBackend,
+        "--diagnose" | "diagnose" => {
+            let mut json = false;
+            for arg in &args[2..] {
+                match arg.as_str() {
+                    "--json" => json = true,
+                    other => return Err(CliError::Usage(format!("Unexpected argument: {}", other))),
+                }
+            }
+            Command::Diagnose { json }
+        }
+        "--help" | "-h" | "help" => Command::Help,
+        other => return Err(CliError::UnknownCommand(other.to_string())),
+    })
 }
 
 fn main() {
-    let command = parse_args();
+    let code = match run_cli(std::env::args().collect()) {
+        Ok(code) => code,
+        Err(e) => {
+            e.report();
+            e.exit_code()
+        }
+    };
+    process::exit(code);
+}
+
+/// Run one command to completion and return its exit code. Nothing under
+/// here calls process::exit, so the daemon's cleanup always runs.
+fn run_cli(args: Vec<String>) -> Result<i32, CliError> {
+    let command = parse_args(args)?;
+    if let Command::Help = command {
+        print_usage();
+        return Ok(0);
+    }
 
     let paths = match config::Paths::init() {
         Ok(p) => p,
-        Err(e) => {
-            eprintln!("Failed to initialize paths: {e}");
-            process::exit(1);
-        }
+        Err(e) => return Err(CliError::Fatal(format!("Failed to initialize paths: {e}"))),
+    };
+
+    // Quick commands that read or write config files hold the config dir
+    // shared, so none of them lands while a daemon loads its startup state.
+    // Long-running ones (--fade, --watch, downloads) don't: a daemon start
+    // would wait on them.
+    let _config_lock = match command {
+        Command::Reset
+        | Command::Resume
+        | Command::Toggle
+        | Command::ExportConfig
+        | Command::ImportRedshift(_)
+        | Command::WeatherProvider(_)
+        | Command::UseLocation(_)
+        | Command::ListLocations
+        | Command::UseProfile(_)
+        | Command::ListProfiles
+        | Command::Set(_)
+        | Command::Status { .. }
+        | Command::Preview { .. } => match config::lock_config_dir_shared(&paths) {
+            Ok(lock) => Some(lock),
+            Err(e) => {
+                eprintln!("[warn] Cannot lock {}: {}", paths.lock_file.display(), e);
+                None
+            }
+        },
+        _ => None,
     };
 
     // Commands that don't need location
     match &command {
         Command::Reset => {
             cmd_reset(&paths);
-            return;
+            return Ok(0);
         }
         Command::Resume => {
             cmd_resume(&paths);
+            return Ok(0);
+        }
+        Command::Toggle => {
+            cmd_toggle(&paths);
+            return Ok(0);
+        }
+        Command::Stop => return Ok(cmd_stop(&paths)),
+        Command::Restart => return Ok(cmd_restart(&paths)),
+        Command::ExportConfig => {
+            cmd_export_config(&paths);
+            return Ok(0);
+        }
+        Command::ImportRedshift(path) => return Ok(cmd_import_redshift(path, &paths)),
+        Command::WeatherProvider(name) => return Ok(cmd_weather_provider(name, &paths)),
+        Command::UseLocation(name) => return Ok(cmd_use_location(name, &paths)),
+        Command::ListLocations => {
+            cmd_list_locations(&paths);
+            return Ok(0);
+        }
+        Command::UseProfile(name) => return Ok(cmd_use_profile(name, &paths)),
+        Command::ListProfiles => {
+            cmd_list_profiles(&paths);
+            return Ok(0);
+        }
+        Command::Benchmark => {
+            cmd_benchmark(&paths);
+            return Ok(0);
+        }
+        Command::CheckBackend => return Ok(cmd_check_backend(&paths)),
+        Command::Diagnose { json } => return Ok(cmd_diagnose(*json, &paths)),
+        Command::SetLocation { location, yes } => return Ok(cmd_set_location(location, *yes, &paths)),
+        Command::DownloadZipdb => return Ok(cmd_download_zipdb(&paths)),
+        Command::BuildZipdb(input) => return Ok(cmd_build_zipdb(input, &paths)),
+        Command::DownloadCitydb { min_population } => return Ok(cmd_download_citydb(*min_population, &paths)),
+        Command::BuildCitydb { cities, admin1, min_population } => return Ok(cmd_build_citydb(cities, admin1.as_deref(), *min_population, &paths)),
+        Command::Set(set) => return Ok(cmd_set_temp(set, &paths)),
+        Command::Fade { temp, duration_sec } => return Ok(cmd_fade(*temp, *duration_sec, &paths)),
+        _ => {}
+    }
+
+    // Remaining commands need location
+    let mut loc = match config::load_location(&paths) {
+        Some(loc) => loc,
+        None => {
+            return Err(CliError::Fatal(
+                concat!(
+                    "No location configured. Use --set-location first.\n",
+                    "  Example: abraxas --set-location 60614\n",
+                    "  Example: abraxas --set-location 41.88,-87.63",
+                )
+                .into(),
+            ))
+        }
+    };
+
+    // The daemon applies the active profile itself (and re-applies on change)
+    let profile = match command {
+        Command::Daemon { .. } => None,
+        _ => config::active_profile(&paths),
+    };
+    if let Some(ref p) = profile {
+        p.apply_location(&mut loc);
+    }
+
+    weather::init();
+
+    let result = match command {
+        Command::Status { bar: true, ref bar_format, .. } => {
+            cmd_status_bar(loc.lat, loc.lon, profile.as_ref(), bar_format.as_deref(), &paths);
+            0
+        }
+        Command::Status { debug, verbose, .. } => {
+            cmd_status(loc.lat, loc.lon, profile.as_ref(), debug, verbose, &paths);
+            0
+        }
+        Command::Watch { interval_sec } => cmd_watch(interval_sec, &paths),
+        Command::Refresh => cmd_refresh(loc.lat, loc.lon, &paths),
+        Command::ValidateLocation => cmd_validate_location(loc.lat, loc.lon),
+        Command::Oneshot { refresh } => cmd_oneshot(&loc, profile.as_ref(), refresh, &paths),
+        Command::Preview { date, json } => {
+            cmd_preview(&loc, profile.as_ref(), date.as_deref(), json, &paths)
+        }
+        Command::DebugSolar(when) => cmd_debug_solar(&loc, when.as_deref()),
+        Command::Set(ref set) => cmd_set_temp(set, &paths),
+        Command::Daemon { force_gnome, disable_night_light, dry_run } => {
+            if disable_night_light {
+                disable_gnome_night_light();
+            }
+            if dry_run {
+                // Still single-threaded; gamma::init_card picks this up
+                std::env::set_var("ABRAXAS_MOCK_GAMMA", "1");
+            }
+            match daemon::run(loc, &paths, force_gnome) {
+                Ok(()) => 0,
+                Err(e) => {
+                    weather::cleanup();
+                    return Err(e);
+                }
+            }
+        }
+        _ => unreachable!(),
+    };
+
+    weather::cleanup();
+    Ok(result)
+}
+
+/// Switch GNOME Night Light off so it doesn't fight the daemon over gamma.
+/// Best effort: a missing gsettings (non-GNOME desktop) is only a warning.
+fn disable_gnome_night_light() {
+    let result = process::Command::new("gsettings")
+        .args(["set", "org.gnome.settings-daemon.plugins.color", "night-light-enabled", "false"])
+        .status();
+    match result {
+        Ok(s) if s.success() => eprintln!("[abraxas] GNOME Night Light disabled"),
+        Ok(s) => eprintln!("[abraxas] gsettings failed ({}), Night Light left unchanged", s),
+        Err(e) => eprintln!("[abraxas] Cannot run gsettings: {}", e),
+    }
+}
+
+/// How close the nearest ZIP code centroid must be for --status to name
+/// it (outside the US there is none this close)
+const NEAR_ZIP_MAX_KM: f64 = 25.0;
+
+fn cmd_status(
+    lat: f64,
+    lon: f64,
+    profile: Option<&config::Profile>,
+    debug: bool,
+    verbose: bool,
+    paths: &config::Paths,
+) {
+    println!("ABRAXAS v8.4.0 [Rust]\n");
+    if let Some(p) = profile {
+        println!("Profile: {}", p.name);
+    }
+    let near_zip = zipdb::ZipDb::open(&paths.zipdb_file)
+        .and_then(|db| db.nearest(lat, lon))
+        .filter(|&(_, km)| km <= NEAR_ZIP_MAX_KM);
+    match near_zip {
+        Some((zip, _)) => println!("Location: {:.4}, {:.4} (near {})\n", lat, lon, String::from_utf8_lossy(&zip)),
+        None => println!("Location: {:.4}, {:.4}\n", lat, lon),
+    }
+
+    let now = chrono_now();
+    let st = solar::sunrise_sunset(now, lat, lon);
+    let sp = solar::position(now, lat, lon);
+
+    let local = local_time(now);
+    println!(
+        "Date: {:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        local.year, local.month, local.day, local.hour, local.min, local.sec
+    );
+
+    if let Some(ref times) = st {
+        let sr = local_time(times.sunrise);
+        let ss = local_time(times.sunset);
+        println!("Sunrise: {:02}:{:02}", sr.hour, sr.min);
+        println!("Sunset: {:02}:{:02}", ss.hour, ss.min);
+        println!("Solar noon: {}", hhmm(times.solar_noon));
+    } else {
+        let polar = solar::polar_label(now, lat, lon);
+        println!("Sunrise/Sunset: N/A ({})", polar);
+        println!("Solar noon: N/A ({})", polar);
+    }
+    match solar::day_length(now, lat, lon) {
+        Some(hours) => {
+            let day_min = (hours * 60.0).round() as i64;
+            println!("Day length: {}h {}m", day_min / 60, day_min % 60);
+        }
+        None => println!("Day length: N/A ({})", solar::polar_label(now, lat, lon)),
+    }
+    println!("Sun elevation: {:.1} degrees", sp.elevation);
+    println!("Sun azimuth: {:.1} degrees", sp.azimuth);
+    println!(
+        "Moon: {:.0}% illuminated ({})\n",
+        solar::moon_illumination(now) * 100.0,
+        solar::moon_phase_name(solar::moon_phase(now))
+    );
+
+    // Weather
+    let provider = config::load_settings(paths).network.weather_provider;
+    let weather = match provider {
+        config::WeatherProvider::Disabled => Some(weather::disabled()),
+        _ => config::load_weather_cache_locked(paths),
+    };
+    match weather {
+        _ if provider == config::WeatherProvider::Disabled => {
+            println!("Weather: Disabled ([network] weather_provider), assuming clear skies");
+        }
+        Some(ref w) => print_weather(w, now, provider),
+        None => println!("Weather: Not available"),
+    }
+    println!();
+
+    // Daemon-published state: what was actually applied
+    let daemon = if config::check_daemon_alive(paths) {
+        config::load_daemon_status(paths)
+    } else {
+        None
+    };
+    if let Some(ref d) = daemon {
+        let up = (now - d.started_at).max(0);
+        println!(
+            "Daemon: running (pid {}, {} backend, up {}h{:02}m, {} ticks)",
+            d.pid, d.backend, up / 3600, (up % 3600) / 60, d.tick_count
+        );
+        let lt = local_time(d.last_tick);
+        println!("Last tick: {:02}:{:02}:{:02}", lt.hour, lt.min, lt.sec);
+        if let Some(ref e) = d.last_error {
+            println!("Last gamma error: {}", e);
+        }
+        if debug {
+            println!("Ramp cache hits: {} (updates within [display] ramp_quantize_step)", d.ramp_cache_hits);
+            if let Some(ref e) = d.last_notify_error {
+                println!("Last notification error: {}", e);
+            }
+        }
+        println!();
+    }
+
+    #[cfg(feature = "gnome")]
+    if gamma::gnome::detect_night_light_active() {
+        println!("GNOME Night Light: active (see --disable-gnome-night-light)\n");
+    }
+
+    // Override status
+    let ovr = config::load_override(paths);
+    if let Some(ref o) = ovr {
+        if verbose && (o.active || o.paused) {
+            if let Ok(json) = serde_json::to_string_pretty(o) {
+                println!("Override state: {}\n", json);
+            }
+        }
+        if o.paused {
+            println!("Mode: PAUSED");
+            println!("Gamma adjustment paused (use --toggle to resume)");
+            return;
+        }
+        if o.is_pending(now) {
+            // Not yet in effect: report it, then the current mode below
+            let target = if o.is_relative {
+                format!("{:+}K (relative)", o.target_temp)
+            } else {
+                format!("{}K", o.target_temp)
+            };
+            let at = local_time(o.starts_at());
+            println!(
+                "Scheduled override: {} over {} at {:04}-{:02}-{:02} {:02}:{:02} (--resume cancels)\n",
+                target, format_duration(o.duration_secs), at.year, at.month, at.day, at.hour, at.min
+            );
+        } else if o.active {
+            println!("Mode: MANUAL OVERRIDE{}", if o.hold { " (held)" } else { "" });
+            if o.is_relative {
+                println!("Target: {:+}K (relative) over {}", o.target_temp, format_duration(o.duration_secs));
+            } else {
+                println!("Target: {}K over {}", o.target_temp, format_duration(o.duration_secs));
+            }
+
+            let it = local_time(o.issued_at);
+            println!(
+                "Issued: {:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+                it.year, it.month, it.day, it.hour, it.min, it.sec
+            );
+            if o.brightness < 1.0 {
+                println!("Brightness: {:.0}%", o.brightness * 100.0);
+            }
+            match o.hold_minutes {
+                Some(hold) => {
+                    let et = local_time(o.starts_at() + o.duration_secs + hold as i64 * 60);
+                    println!("Hold: {} min, expires at {:02}:{:02}", hold, et.hour, et.min);
+                }
+                None if o.hold => println!("Hold: until --resume"),
+                None => println!("Hold: until next dawn/dusk transition"),
+            }
+            if let Some(ref d) = daemon {
+                if d.applied_temp > 0 {
+                    println!("Applied temperature: {}K", d.applied_temp);
+                }
+            }
+            return;
+        }
+    }
+
+    let mut settings = config::load_settings(paths);
+    if let Some(p) = profile {
+        p.apply_settings(&mut settings);
+    }
+
+    if let Some(w) = daemon::active_window(&settings, now, &SystemClock) {
+        let end = w.end_hhmm % 1440;
+        println!("Mode: SCHEDULE WINDOW \"{}\" (until {:02}:{:02})", w.name, end / 60, end % 60);
+        println!("Target temperature: {}K", w.temp_k);
+        print_chromaticity(w.temp_k);
+        return;
+    }
+
+    if let Some(ref d) = daemon {
+        if (d.mode == "clear" || d.mode == "dark") && d.applied_temp > 0 {
+            println!("Mode: {}", d.mode.to_uppercase());
+            println!("Target temperature: {}K", d.applied_temp);
+            print_chromaticity(d.applied_temp);
+            if d.brightness < 1.0 {
+                println!("Brightness: {:.0}%", d.brightness * 100.0);
+            }
+            if verbose {
+                print_solar_curve(&config::Location { lat, lon }, &settings, weather.as_ref(), now);
+            }
+            return;
+        }
+    }
+
+    if let Some(ref sched) = settings.schedule {
+        let minute = daemon::minute_of_day(now, &SystemClock);
+        let temp = sigmoid::calculate_schedule_temp(minute, sched);
+        let next = sched.next_keyframe(minute);
+        println!("Mode: SCHEDULE ({} keyframes, sun ignored)", sched.keyframes.len());
+        println!("Target temperature: {}K", temp);
+        println!("Next keyframe: {:02}:{:02} -> {}K", next.minute / 60, next.minute % 60, next.temp);
+        print_chromaticity(temp);
+        return;
+    }
+
+    let is_dark = daemon::is_dark_weather(weather.as_ref(), &settings);
+    let temp = daemon::solar_temp_at(&config::Location { lat, lon }, &settings, weather.as_ref(), now, &SystemClock);
+
+    println!("Mode: {}", if is_dark { "DARK" } else { "CLEAR" });
+    if settings.daemon.mode == config::SolarMode::Elevation {
+        println!(
+            "Model: sun elevation ({} to {} degrees)",
+            settings.daemon.elevation_low, settings.daemon.elevation_high
+        );
+    }
+    println!("Target temperature: {}K", temp);
+    print_chromaticity(temp);
+    if verbose {
+        print_solar_curve(&config::Location { lat, lon }, &settings, weather.as_ref(), now);
+    }
+}
+
+/// --status --verbose: how the dawn/dusk curve reaches its temperature
+fn print_solar_curve(loc: &config::Location, settings: &config::Settings, weather: Option<&config::WeatherData>, now: i64) {
+    let curve = match daemon::solar_temp_traced(loc, settings, weather, now, &SystemClock) {
+        (_, Some(c)) => c,
+        (temp, None) => {
+            println!("\nCurve: not in use ({}K from golden hour, elevation mode or polar day/night)", temp);
             return;
         }
-        Command::Benchmark => {
-            cmd_benchmark(&paths);
-            return;
+    };
+    let t = &curve.trace;
+    let temps = &settings.temperatures;
+    println!();
+    println!("Minutes from sunrise: {:.1}", curve.minutes_from_sunrise);
+    println!("Minutes to sunset: {:.1}", curve.minutes_to_sunset);
+    println!("Curve: {} (sigmoid x = {:.3}, factor = {:.3})", t.phase.name(), t.x, t.factor);
+    println!(
+        "Weather: {}% cloud now, {}% in 3h (threshold {}%) -> day target {:.0}K (clear {}K, dark {}K)",
+        curve.cloud_cover, curve.cloud_cover_3h, temps.cloud_threshold, t.day_temp, temps.day_clear, temps.day_dark
+    );
+}
+
+/// --status --bar: one line from files only (state.json, or config and
+/// the weather cache without a daemon), fast enough for a bar to poll.
+fn cmd_status_bar(lat: f64, lon: f64, profile: Option<&config::Profile>, format: Option<&str>, paths: &config::Paths) {
+    let now = chrono_now();
+    let sun_elevation = solar::position(now, lat, lon).elevation;
+
+    let provider = config::load_settings(paths).network.weather_provider;
+    let weather = match provider {
+        config::WeatherProvider::Disabled => Some(weather::disabled()),
+        _ => config::load_weather_cache_locked(paths),
+    };
+    let cloud = weather.as_ref().filter(|w| !w.has_error).map(|w| w.cloud_cover);
+
+    // A live daemon's mode stands even before it has applied anything
+    // (paused from the start, or still initializing)
+    let daemon = if config::check_daemon_alive(paths) {
+        config::load_daemon_status(paths)
+    } else {
+        None
+    };
+    let (temp, mode) = match daemon {
+        Some(ref d) if d.applied_temp > 0 => (d.applied_temp, d.mode.clone()),
+        _ => {
+            // Nothing applied: show what the daemon would apply
+            let mut settings = config::load_settings(paths);
+            if let Some(p) = profile {
+                p.apply_settings(&mut settings);
+            }
+            let is_dark = daemon::is_dark_weather(weather.as_ref(), &settings);
+            let temp = daemon::solar_temp_at(&config::Location { lat, lon }, &settings, weather.as_ref(), now, &SystemClock);
+            let mode = match daemon {
+                Some(ref d) => d.mode.clone(),
+                None => if is_dark { "dark" } else { "clear" }.to_string(),
+            };
+            (temp, mode)
+        }
+    };
+
+    let state = statusbar::BarState {
+        temp,
+        mode: daemon::mode_with_night(&mode, sun_elevation).to_string(),
+        sun_elevation,
+        cloud,
+        daemon: daemon.is_some(),
+    };
+    match format {
+        Some(template) => println!("{}", statusbar::expand(template, &state)),
+        None => println!("{}", statusbar::waybar_json(&state)),
+    }
+}
+
+/// Default --watch refresh interval
+const WATCH_INTERVAL_SEC: u64 = 5;
+
+/// Put the cursor back and exit; the only way out of --watch.
+extern "C" fn watch_exit(_sig: libc::c_int) {
+    const SHOW_CURSOR: &[u8] = b"\x1b[?25h\n";
+    unsafe {
+        libc::write(1, SHOW_CURSOR.as_ptr() as *const libc::c_void, SHOW_CURSOR.len());
+        libc::_exit(0);
+    }
+}
+
+/// --status redrawn every `interval_sec` seconds. Location and profile are
+/// re-read each time, so --set-location and --use-profile show up live.
+fn cmd_watch(interval_sec: u64, paths: &config::Paths) -> i32 {
+    use std::io::Write;
+
+    unsafe {
+        let handler = watch_exit as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        libc::signal(libc::SIGINT, handler);
+        libc::signal(libc::SIGTERM, handler);
+    }
+    print!("\x1b[?25l");
+
+    loop {
+        print!("\x1b[2J\x1b[H");
+        match config::load_location(paths) {
+            Some(mut loc) => {
+                let profile = config::active_profile(paths);
+                if let Some(ref p) = profile {
+                    p.apply_location(&mut loc);
+                }
+                cmd_status(loc.lat, loc.lon, profile.as_ref(), false, false, paths);
+            }
+            None => println!("No location configured. Use --set-location first."),
+        }
+        println!("\n[Press Ctrl-C to exit]");
+        let _ = std::io::stdout().flush();
+        std::thread::sleep(std::time::Duration::from_secs(interval_sec));
+    }
+}
+
+/// Cached weather summary, with warnings when it can't be trusted.
+fn print_weather(w: &config::WeatherData, now: i64, provider: config::WeatherProvider) {
+    if w.has_error {
+        println!("Weather: Not available");
+        println!("Warning: Last weather fetch failed");
+        return;
+    }
+
+    println!("Weather: {}", w.forecast);
+    println!("Cloud cover: {}% (in 3h: {}%)", w.cloud_cover, w.cloud_cover_3h);
+
+    let ft = local_time(w.fetched_at);
+    println!(
+        "Last updated: {:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        ft.year, ft.month, ft.day, ft.hour, ft.min, ft.sec
+    );
+
+    let age_hours = (now - w.fetched_at) / 3600;
+    if age_hours > WEATHER_STALE_HOURS {
+        println!(
+            "Warning: Weather data is {}h old \u{2014} daemon may be unable to reach {}",
+            age_hours,
+            provider.host()
+        );
+    }
+}
+
+fn print_chromaticity(temp: i32) {
+    let (x, y) = gamma::colorramp::temp_to_xy(temp);
+    println!("Chromaticity: x={:.4}, y={:.4}", x, y);
+}
+
+/// Check that a location gives sane sunrise/sunset for today.
+fn check_location(lat: f64, lon: f64, now: i64) -> Result<(), String> {
+    if !config::valid_coordinates(lat, lon) {
+        return Err(format!("Coordinates out of range: {:.4}, {:.4}", lat, lon));
+    }
+
+    let polar_ok = lat.abs() > 65.0;
+    let times = match solar::sunrise_sunset(now, lat, lon) {
+        Some(t) => t,
+        None if polar_ok => return Ok(()), // midnight sun / polar night
+        None => {
+            return Err(format!(
+                "No sunrise/sunset today at latitude {:.4} (only expected above 65 degrees)",
+                lat
+            ))
+        }
+    };
+
+    const DAY: i64 = 86400;
+    if times.sunrise == 0 || times.sunset == 0
+        || (times.sunrise - now).abs() > DAY
+        || (times.sunset - now).abs() > DAY
+    {
+        return Err("Sunrise/sunset calculation is not within 24h of now".to_string());
+    }
+    if times.sunrise >= times.sunset {
+        return Err("Sunrise is not before sunset".to_string());
+    }
+
+    let noon = times.sunrise + (times.sunset - times.sunrise) / 2;
+    let elevation = solar::position(noon, lat, lon).elevation;
+    if !(-90.0..=90.0).contains(&elevation) {
+        return Err(format!("Solar noon elevation out of range: {:.1} degrees", elevation));
+    }
+
+    let day_hours = (times.sunset - times.sunrise) as f64 / 3600.0;
+    if !polar_ok && !(4.0..=22.0).contains(&day_hours) {
+        return Err(format!(
+            "Day length {:.1}h is implausible at latitude {:.4}",
+            day_hours, lat
+        ));
+    }
+    Ok(())
+}
+
+fn cmd_validate_location(lat: f64, lon: f64) -> i32 {
+    let now = now_epoch();
+    println!("Location: {:.4}, {:.4}", lat, lon);
+
+    match check_location(lat, lon, now) {
+        Ok(()) => {
+            println!("\u{2713} Location appears valid");
+            // Polar latitudes pass, but are also what a swapped lat/lon looks like
+            if lat.abs() > 65.0 && lon.abs() <= 65.0 {
+                println!("  Note: latitude {:.4} is polar. If that's unexpected, try:", lat);
+                println!("  abraxas --set-location {},{}", lon, lat);
+            }
+            0
+        }
+        Err(msg) => {
+            println!("\u{2717} {}", msg);
+            if check_location(lon, lat, now).is_ok() {
+                println!("  Latitude and longitude may be swapped. Try:");
+                println!("  abraxas --set-location {},{}", lon, lat);
+            }
+            1
+        }
+    }
+}
+
+/// --preview sample spacing (seconds)
+const PREVIEW_STEP_SEC: i64 = 600;
+
+/// Local midnight of a YYYY-MM-DD date, rejecting dates mktime would normalize.
+fn parse_date(s: &str) -> Option<i64> {
+    local_epoch(s, 0)
+}
+
+/// "YYYY-MM-DD HH:MM" in local time.
+fn parse_datetime(s: &str) -> Option<i64> {
+    let (date, time) = s.trim().split_once(char::is_whitespace)?;
+    local_epoch(date, config::parse_hhmm(time.trim())?)
+}
+
+/// `minute` minutes into a YYYY-MM-DD date, local time.
+fn local_epoch(date: &str, minute: i32) -> Option<i64> {
+    let parts: Vec<&str> = date.split('-').collect();
+    if parts.len() != 3 {
+        return None;
+    }
+    let year: i32 = parts[0].parse().ok()?;
+    let month: i32 = parts[1].parse().ok()?;
+    let day: i32 = parts[2].parse().ok()?;
+
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    tm.tm_year = year - 1900;
+    tm.tm_mon = month - 1;
+    tm.tm_mday = day;
+    tm.tm_hour = minute / 60;
+    tm.tm_min = minute % 60;
+    tm.tm_isdst = -1;
+    let t = unsafe { libc::mktime(&mut tm) };
+    if t == -1 || tm.tm_year != year - 1900 || tm.tm_mon != month - 1 || tm.tm_mday != day {
+        return None;
+    }
+    Some(t as i64)
+}
+
+/// Local midnight of the day containing `epoch`.
+fn day_start(epoch: i64) -> i64 {
+    let lt = local_time(epoch);
+    epoch - (lt.hour * 3600 + lt.min * 60 + lt.sec) as i64
+}
+
+/// Temperature the daemon would apply at each step of the day from `start`.
+fn preview_series(
+    loc: &config::Location,
+    settings: &config::Settings,
+    weather: Option<&config::WeatherData>,
+    start: i64,
+) -> Vec<(i64, i32)> {
+    (0..86400 / PREVIEW_STEP_SEC)
+        .map(|i| {
+            let t = start + i * PREVIEW_STEP_SEC;
+            (t, daemon::solar_temp_at(loc, settings, weather, t, &SystemClock))
+        })
+        .collect()
+}
+
+/// Dawn and dusk transition windows (start, end) around the sun times.
+fn transition_windows(times: &solar::SunTimes, tp: &config::TransitionParams) -> ((i64, i64), (i64, i64)) {
+    let dawn_mid = times.sunrise + (tp.dawn_offset_min * 60.0) as i64;
+    let dusk_mid = times.sunset - (tp.dusk_offset_min * 60.0) as i64;
+    let dawn_half = (tp.dawn_duration * 30.0) as i64;
+    let dusk_half = (tp.dusk_duration * 30.0) as i64;
+    ((dawn_mid - dawn_half, dawn_mid + dawn_half), (dusk_mid - dusk_half, dusk_mid + dusk_half))
+}
+
+fn hhmm(epoch: i64) -> String {
+    let lt = local_time(epoch);
+    format!("{:02}:{:02}", lt.hour, lt.min)
+}
+
+fn cmd_preview(
+    loc: &config::Location,
+    profile: Option<&config::Profile>,
+    date: Option<&str>,
+    json: bool,
+    paths: &config::Paths,
+) -> i32 {
+    let start = match date {
+        Some(d) => match parse_date(d) {
+            Some(t) => t,
+            None => {
+                eprintln!("Invalid date: {} (expected YYYY-MM-DD)", d);
+                return 1;
+            }
+        },
+        None => day_start(now_epoch()),
+    };
+
+    let mut settings = config::load_settings(paths);
+    if let Some(p) = profile {
+        p.apply_settings(&mut settings);
+    }
+    // Current cloud cover stands in for the whole day
+    let weather = config::load_weather_cache_locked(paths);
+    let is_dark = daemon::is_dark_weather(weather.as_ref(), &settings);
+
+    let series = preview_series(loc, &settings, weather.as_ref(), start);
+    let times = solar::sunrise_sunset(start + 43200, loc.lat, loc.lon);
+    let windows = times.as_ref().map(|t| transition_windows(t, &settings.transitions));
+    let lt = local_time(start);
+    let date_str = format!("{:04}-{:02}-{:02}", lt.year, lt.month, lt.day);
+
+    if json {
+        let out = serde_json::json!({
+            "date": date_str,
+            "lat": loc.lat,
+            "lon": loc.lon,
+            "dark": is_dark,
+            "sunrise": times.as_ref().map(|t| t.sunrise),
+            "sunset": times.as_ref().map(|t| t.sunset),
+            "dawn_window": windows.map(|(dawn, _)| [dawn.0, dawn.1]),
+            "dusk_window": windows.map(|(_, dusk)| [dusk.0, dusk.1]),
+            "series": series
+                .iter()
+                .map(|&(t, temp)| serde_json::json!({ "time": t, "local": hhmm(t), "temp": temp }))
+                .collect::<Vec<_>>(),
+        });
+        println!("{}", out);
+        return 0;
+    }
+
+    println!(
+        "Preview for {} at {:.4}, {:.4} ({})\n",
+        date_str, loc.lat, loc.lon,
+        if is_dark { "overcast, dark day target" } else { "clear sky" }
+    );
+    if settings.schedule.is_some() {
+        println!("[schedule] keyframes drive the curve; sun times shown for reference\n");
+    } else if settings.daemon.mode == config::SolarMode::Elevation {
+        println!(
+            "Elevation mode: night at or below {} degrees, day at or above {}; windows shown for reference\n",
+            settings.daemon.elevation_low, settings.daemon.elevation_high
+        );
+    }
+    match (&times, windows) {
+        (Some(t), Some((dawn, dusk))) => {
+            println!("Sunrise: {}   Sunset: {}", hhmm(t.sunrise), hhmm(t.sunset));
+            println!("Dawn transition: {} - {}", hhmm(dawn.0), hhmm(dawn.1));
+            println!("Dusk transition: {} - {}", hhmm(dusk.0), hhmm(dusk.1));
+        }
+        _ => {
+            println!("Sunrise/Sunset: N/A ({})", solar::polar_label(start + 43200, loc.lat, loc.lon));
+        }
+    }
+    println!();
+
+    // One row per hour: a sparkline of its 10-minute steps and the range
+    const BARS: [char; 8] = ['\u{2581}', '\u{2582}', '\u{2583}', '\u{2584}', '\u{2585}', '\u{2586}', '\u{2587}', '\u{2588}'];
+    let lo = series.iter().map(|&(_, k)| k).min().unwrap_or(0);
+    let hi = series.iter().map(|&(_, k)| k).max().unwrap_or(0);
+    let steps_per_hour = (3600 / PREVIEW_STEP_SEC) as usize;
+    for hour in series.chunks(steps_per_hour) {
+        let spark: String = hour
+            .iter()
+            .map(|&(_, k)| {
+                let level = if hi > lo { (k - lo) as usize * (BARS.len() - 1) / (hi - lo) as usize } else { 0 };
+                BARS[level]
+            })
+            .collect();
+        let first = hour[0].1;
+        let last = hour[hour.len() - 1].1;
+        if first == last {
+            println!("{}  {}  {}K", hhmm(hour[0].0), spark, first);
+        } else {
+            println!("{}  {}  {}K -> {}K", hhmm(hour[0].0), spark, first, last);
+        }
+    }
+    0
+}
+
+/// "HH:MM" for minutes after midnight (may fall outside 0..1440).
+fn format_minutes(min: f64) -> String {
+    let m = min.round() as i64;
+    let sign = if m < 0 { "-" } else { "" };
+    format!("{}{:02}:{:02}", sign, m.abs() / 60, m.abs() % 60)
+}
+
+/// Every intermediate value of the NOAA calculation, for checking it
+/// against the NOAA spreadsheet or another implementation.
+fn cmd_debug_solar(loc: &config::Location, when: Option<&str>) -> i32 {
+    let when = match when {
+        Some(s) => match parse_datetime(s) {
+            Some(t) => t,
+            None => {
+                eprintln!("Invalid date/time: {} (expected \"YYYY-MM-DD HH:MM\")", s);
+                return 1;
+            }
+        },
+        None => now_epoch(),
+    };
+
+    let lt = local_time(when);
+    println!(
+        "Solar calculation for {:04}-{:02}-{:02} {:02}:{:02}:{:02} local (epoch {}) at {:.4}, {:.4}\n",
+        lt.year, lt.month, lt.day, lt.hour, lt.min, lt.sec, when, loc.lat, loc.lon
+    );
+
+    let d = solar::position_detail(when, loc.lat, loc.lon);
+    let p = &d.params;
+    println!("Position:");
+    println!("  Julian Day:               {:.6}", d.julian_day);
+    println!("  Julian Century:           {:.10}", d.julian_century);
+    println!("  Mean longitude L0:        {:.6} deg", p.l0);
+    println!("  Mean anomaly M:           {:.6} deg", p.m);
+    println!("  Orbit eccentricity:       {:.9}", p.e);
+    println!("  Equation of center C:     {:.6} deg", p.c);
+    println!("  True longitude:           {:.6} deg", p.true_lon);
+    println!("  Apparent longitude:       {:.6} deg", p.apparent_lon);
+    println!("  Corrected obliquity:      {:.6} deg", p.obliq_corr);
+    println!("  Declination:              {:.6} deg", p.sun_declin);
+    println!("  Equation of time:         {:.4} min", p.eq_time);
+    println!("  Hour angle:               {:.6} deg", d.hour_angle);
+    println!("  Zenith:                   {:.6} deg", d.zenith);
+    println!("  Elevation:                {:.6} deg", 90.0 - d.zenith);
+    println!("  Azimuth:                  {:.6} deg", d.azimuth);
+
+    let x = solar::sunrise_sunset_detail(when, loc.lat, loc.lon);
+    println!("\nSunrise/sunset (zenith 90.833 deg, noon parameters):");
+    println!("  Julian Day (noon):        {:.6}", x.julian_day);
+    println!("  cos(HA):                  {:.9}", x.cos_ha);
+    println!("  Solar noon:               {:.2} min ({})", x.noon_min, format_minutes(x.noon_min));
+    match x.rise_set_min {
+        Some((rise, set)) => {
+            println!("  Sunrise:                  {:.2} min ({})", rise, format_minutes(rise));
+            println!("  Sunset:                   {:.2} min ({})", set, format_minutes(set));
+        }
+        None if x.cos_ha > 1.0 => println!("  No sunrise (polar night)"),
+        None => println!("  No sunset (midnight sun)"),
+    }
+    0
+}
+
+fn cmd_set_location(loc_str: &str, yes: bool, paths: &config::Paths) -> i32 {
+    if loc_str.trim().eq_ignore_ascii_case("auto") {
+        return set_location_auto(yes, paths);
+    }
+
+    // Anything not starting like a number or ZIP is a city name
+    if loc_str.trim_start().starts_with(|c: char| c.is_alphabetic()) {
+        return set_location_by_city(loc_str, paths);
+    }
+
+    if loc_str.contains(',') {
+        let (lat, lon) = match config::parse_coordinates(loc_str) {
+            Ok(c) => c,
+            Err(e) => {
+                eprintln!("{}", e);
+                return 1;
+            }
+        };
+
+        if let Err(e) = config::save_location(paths, lat, lon) {
+            config_write_failed(paths, &e);
+            return 1;
+        }
+        println!("Location set to: {:.4}, {:.4}", lat, lon);
+        print_sun_check(lat, lon);
+        return 0;
+    }
+
+    // ZIP code
+    if loc_str.len() != 5 || !loc_str.chars().all(|c| c.is_ascii_digit()) {
+        eprintln!("Invalid ZIP code. Must be 5 digits.");
+        return 1;
+    }
+
+    println!("Looking up ZIP code {}...", loc_str);
+    match zipdb::lookup(&paths.zipdb_file, loc_str) {
+        Some((lat, lon)) => {
+            println!("Found: {} -> {:.4}, {:.4}", loc_str, lat, lon);
+            if let Err(e) = config::save_location(paths, lat as f64, lon as f64) {
+                config_write_failed(paths, &e);
+                return 1;
+            }
+            println!("Location set to: {:.4}, {:.4}", lat, lon);
+            print_sun_check(lat as f64, lon as f64);
+            0
+        }
+        None => {
+            eprintln!("ZIP code {} not found in database.", loc_str);
+            if !paths.zipdb_file.exists() {
+                eprintln!("  Build it with: abraxas --download-zipdb");
+                eprintln!("  (or, offline, from a ZIP,LAT,LON file: abraxas --build-zipdb FILE.csv)");
+            }
+            1
+        }
+    }
+}
+
+/// GeoClue2 when compiled in and answering, else the public IP address.
+fn set_location_auto(yes: bool, paths: &config::Paths) -> i32 {
+    #[cfg(feature = "geoclue")]
+    {
+        println!("Asking GeoClue2 for the current location...");
+        match geoclue::locate(std::time::Duration::from_secs(GEOCLUE_TIMEOUT_SEC)) {
+            Ok(fix) => {
+                if let Err(e) = config::save_auto_location(paths, fix.lat, fix.lon) {
+                    config_write_failed(paths, &e);
+                    return 1;
+                }
+                println!("Location set to: {:.4}, {:.4} (within {:.1} km)", fix.lat, fix.lon, fix.accuracy_m / 1000.0);
+                println!("The daemon re-checks it every {} hours.", GEOCLUE_RECHECK_SEC / 3600);
+                print_sun_check(fix.lat, fix.lon);
+                return 0;
+            }
+            Err(e) => eprintln!("GeoClue2: {}; trying IP geolocation", e),
+        }
+    }
+    set_location_by_ip(yes, paths)
+}
+
+fn set_location_by_ip(yes: bool, paths: &config::Paths) -> i32 {
+    println!("Looking up this network's public IP address ({})...", ipgeo::IPGEO_URL);
+    let found = match ipgeo::lookup(IPGEO_TIMEOUT_SEC) {
+        Ok(f) => f,
+        Err(e) => {
+            eprintln!("IP geolocation failed: {}", e);
+            eprintln!("  Set the location by hand instead: abraxas --set-location 60614");
+            return 1;
+        }
+    };
+    println!("Found: {} -> {:.4}, {:.4}", found.place, found.lat, found.lon);
+    println!("  (approximate, and wrong behind a VPN)");
+
+    if !yes && !confirm("Use this location? [y/N] ") {
+        println!("Location not changed. (--yes skips this question)");
+        return 1;
+    }
+    // Not auto: the daemon never repeats an IP lookup
+    if let Err(e) = config::save_location(paths, found.lat, found.lon) {
+        config_write_failed(paths, &e);
+        return 1;
+    }
+    println!("Location set to: {:.4}, {:.4}", found.lat, found.lon);
+    print_sun_check(found.lat, found.lon);
+    0
+}
+
+/// Ask a yes/no question on stdin; anything but y/yes (or EOF) is no.
+fn confirm(prompt: &str) -> bool {
+    use std::io::Write;
+    print!("{}", prompt);
+    let _ = std::io::stdout().flush();
+    let mut answer = String::new();
+    match std::io::stdin().read_line(&mut answer) {
+        Ok(_) => matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes"),
+        Err(_) => false,
+    }
+}
+
+fn set_location_by_city(query: &str, paths: &config::Paths) -> i32 {
+    // No city database ships with ABRAXAS, so a first NAME lookup lands here
+    if !citydb::is_available(&paths.citydb_file) {
+        eprintln!("Cannot look up \"{}\": no city database at {}.", query.trim(), paths.citydb_file.display());
+        eprintln!("  Download it with: abraxas --download-citydb");
+        eprintln!("  (or --build-citydb on {} and", citydb::GEONAMES_CITIES_URL);
+        eprintln!("   {}, unpacked)", citydb::GEONAMES_ADMIN1_URL);
+        eprintln!("Or give the coordinates: abraxas --set-location LAT,LON");
+        return 1;
+    }
+    let matches = citydb::lookup(&paths.citydb_file, query);
+    match matches.as_slice() {
+        [] => {
+            eprintln!("No city matching \"{}\" in the city database.", query.trim());
+            eprintln!("  For every city of 100k+ people: abraxas --download-citydb");
+            1
+        }
+        [city] => {
+            let (lat, lon) = (city.lat as f64, city.lon as f64);
+            println!("Found: {} -> {:.4}, {:.4}", city.label(), lat, lon);
+            if let Err(e) = config::save_location(paths, lat, lon) {
+                config_write_failed(paths, &e);
+                return 1;
+            }
+            println!("Location set to: {:.4}, {:.4}", lat, lon);
+            print_sun_check(lat, lon);
+            0
+        }
+        _ => {
+            // Same-named cities are all in the lookup, so the hints are
+            // worked out against the unqualified name's matches
+            let name = query.split(',').next().unwrap_or(query);
+            let all = citydb::lookup(&paths.citydb_file, name);
+            eprintln!("{} matches for \"{}\":", matches.len(), query.trim());
+            for city in &matches {
+                eprintln!(
+                    "  {:<36} {:>8.4}, {:>9.4}  pass --set-location '{}'",
+                    city.label(),
+                    city.lat,
+                    city.lon,
+                    city.disambiguation(&all)
+                );
+            }
+            eprintln!("Or give the coordinates: abraxas --set-location LAT,LON");
+            1
+        }
+    }
+}
+
+/// Solar noon at `lon` minus clock noon in a zone `tz_hours` from UTC, in
+/// hours (ignoring the equation of time, at most ~16 minutes).
+fn noon_skew_hours(lon: f64, tz_hours: f64) -> f64 {
+    tz_hours - lon / 15.0
+}
+
+/// Solar noon further than this from clock noon suggests a wrong location
+const NOON_SKEW_WARN_HOURS: f64 = 3.0;
+
+/// Today's sun times for a just-saved location, so a typo shows up now
+/// rather than as transitions at the wrong hour days later.
+fn print_sun_check(lat: f64, lon: f64) {
+    let now = now_epoch();
+    match solar::sunrise_sunset(now, lat, lon) {
+        Some(t) => println!("Today: sunrise {}, sunset {}", hhmm(t.sunrise), hhmm(t.sunset)),
+        None => println!("Today: no sunrise/sunset ({})", solar::polar_label(now, lat, lon)),
+    }
+
+    let tz = solar::get_tz_offset_hours(now);
+    let skew = noon_skew_hours(lon, tz);
+    if skew.abs() <= NOON_SKEW_WARN_HOURS {
+        return;
+    }
+    eprintln!(
+        "Warning: solar noon is {:.1}h {} clock noon in this time zone (UTC{:+})",
+        skew.abs(),
+        if skew > 0.0 { "after" } else { "before" },
+        tz
+    );
+    if tz == 0.0 {
+        // Unset TZ is UTC, which says nothing about where the machine is
+        eprintln!("  Check the location, or set TZ if this machine isn't really on UTC");
+    } else if noon_skew_hours(lat, tz).abs() <= NOON_SKEW_WARN_HOURS && lon.abs() <= 90.0 {
+        eprintln!("  Latitude and longitude may be swapped: the order is LAT,LON");
+    } else if noon_skew_hours(-lon, tz).abs() <= NOON_SKEW_WARN_HOURS {
+        eprintln!("  The longitude sign looks wrong: west of Greenwich is negative, east positive");
+    } else {
+        eprintln!("  Check the location (or TZ, if this machine's time zone is wrong)");
+    }
+}
+
+/// Download a single-file ZIP archive next to `db_path` and return the
+/// file's text, reporting failures. The archive is removed either way.
+fn download_zip_member(url: &str, db_path: &std::path::Path) -> Option<String> {
+    use std::process::{Command, Stdio};
+
+    let mut zip_path = db_path.as_os_str().to_owned();
+    zip_path.push(".zip");
+    let zip_path = std::path::PathBuf::from(zip_path);
+
+    println!("  {}", url);
+    let status = Command::new("curl")
+        .args(["-fL", "--progress-bar", "--connect-timeout", "15", "-o"])
+        .arg(&zip_path)
+        .arg(url)
+        .status();
+    match status {
+        Ok(s) if s.success() => {}
+        Ok(s) => {
+            eprintln!("Download failed (curl {})", s);
+            let _ = std::fs::remove_file(&zip_path);
+            return None;
+        }
+        Err(e) => {
+            eprintln!("Cannot run curl: {}", e);
+            return None;
+        }
+    }
+
+    // unzip -p streams the single member to stdout
+    println!("Extracting...");
+    let output = Command::new("unzip")
+        .arg("-p")
+        .arg(&zip_path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .output();
+    let _ = std::fs::remove_file(&zip_path);
+    match output {
+        Ok(o) if o.status.success() => Some(String::from_utf8_lossy(&o.stdout).into_owned()),
+        Ok(o) => {
+            eprintln!("Extraction failed (unzip {})", o.status);
+            None
+        }
+        Err(e) => {
+            eprintln!("Cannot run unzip: {}", e);
+            None
+        }
+    }
+}
+
+fn cmd_download_zipdb(paths: &config::Paths) -> i32 {
+    println!("Downloading Census ZCTA gazetteer...");
+    let tsv = match download_zip_member(zipdb::GAZETTEER_URL, &paths.zipdb_file) {
+        Some(t) => t,
+        None => return 1,
+    };
+
+    let entries = match zipdb::parse_gazetteer(&tsv) {
+        Ok(e) => e,
+        Err(e) => {
+            eprintln!("Unexpected gazetteer format: {}", e);
+            return 1;
+        }
+    };
+
+    match write_zipdb(&entries, paths) {
+        Ok(size) => {
+            println!(
+                "Wrote {} ZIP codes to {} ({} KB)",
+                entries.len(),
+                paths.zipdb_file.display(),
+                size / 1024
+            );
+            0
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    }
+}
+
+fn cmd_build_zipdb(input: &str, paths: &config::Paths) -> i32 {
+    let text = match std::fs::read(input) {
+        Ok(data) => String::from_utf8_lossy(&data).into_owned(),
+        Err(e) => {
+            eprintln!("Cannot read {}: {}", input, e);
+            return 1;
+        }
+    };
+    let (entries, dropped) = match zipdb::parse_csv(&text) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("{}: {}", input, e);
+            eprintln!("  Nothing written; expected zip,lat,lon columns (any order, named in a header)");
+            return 1;
+        }
+    };
+
+    match write_zipdb(&entries, paths) {
+        Ok(size) => {
+            println!(
+                "Wrote {} ZIP codes to {} ({} KB), {} duplicate{} dropped",
+                entries.len(),
+                paths.zipdb_file.display(),
+                size / 1024,
+                dropped,
+                if dropped == 1 { "" } else { "s" }
+            );
+            0
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    }
+}
+
+/// Write sorted entries to the ZIP database, checking every one reads back
+/// through `ZipDb::lookup` before it replaces the old one. Returns the file
+/// size.
+fn write_zipdb(entries: &[zipdb::Entry], paths: &config::Paths) -> Result<usize, String> {
+    let data = zipdb::encode(entries);
+    let path = &paths.zipdb_file;
+    let verify = |tmp: &std::path::Path| {
+        let db = zipdb::ZipDb::open(tmp);
+        for (zip, lat, lon) in entries {
+            let zip = String::from_utf8_lossy(zip);
+            if db.as_ref().and_then(|db| db.lookup(&zip)) != Some((*lat, *lon)) {
+                return Err(std::io::Error::other(format!("verification failed at ZIP {}; kept the old database", zip)));
+            }
+        }
+        Ok(())
+    };
+    config::write_atomic_verified(path, &data, verify)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(data.len())
+}
+
+fn cmd_build_citydb(cities_path: &str, admin1_path: Option<&str>, min_population: u32, paths: &config::Paths) -> i32 {
+    let read = |path: &str| match std::fs::read(path) {
+        Ok(data) => Some(String::from_utf8_lossy(&data).into_owned()),
+        Err(e) => {
+            eprintln!("Cannot read {}: {}", path, e);
+            None
+        }
+    };
+    let cities = match read(cities_path) {
+        Some(c) => c,
+        None => return 1,
+    };
+    let admin1 = match admin1_path.map(read) {
+        Some(None) => return 1,
+        Some(a) => a,
+        None => None,
+    };
+    write_citydb(&cities, admin1.as_deref(), min_population, paths)
+}
+
+/// Timeout for the admin1 names download (a few hundred KB)
+const ADMIN1_TIMEOUT_SEC: u32 = 60;
+
+fn cmd_download_citydb(min_population: u32, paths: &config::Paths) -> i32 {
+    println!("Downloading GeoNames cities...");
+    let cities = match download_zip_member(citydb::GEONAMES_CITIES_URL, &paths.citydb_file) {
+        Some(c) => c,
+        None => return 1,
+    };
+
+    // Region names are a nicety: without them "Portland, OR" still works
+    println!("Downloading GeoNames region names...");
+    println!("  {}", citydb::GEONAMES_ADMIN1_URL);
+    let admin1 = match http::get(citydb::GEONAMES_ADMIN1_URL, ADMIN1_TIMEOUT_SEC, 15, &[]) {
+        Ok(r) if r.status == 200 => Some(r.body),
+        Ok(r) => {
+            eprintln!("  Skipped (HTTP {})", r.status);
+            None
+        }
+        Err(e) => {
+            eprintln!("  Skipped ({})", e);
+            None
+        }
+    };
+    write_citydb(&cities, admin1.as_deref(), min_population, paths)
+}
+
+/// Parse a GeoNames extract (plus admin1 names) and write cities.bin.
+fn write_citydb(cities: &str, admin1: Option<&str>, min_population: u32, paths: &config::Paths) -> i32 {
+    if citydb::is_zone_tab(cities) {
+        return write_zone_tab_citydb(cities, paths);
+    }
+    let entries = match citydb::parse_geonames(cities, admin1, min_population) {
+        Ok(e) => e,
+        Err(e) => {
+            eprintln!("Unexpected GeoNames format: {}", e);
+            return 1;
+        }
+    };
+
+    let data = citydb::encode(&entries);
+    if let Err(e) = config::write_atomic(&paths.citydb_file, &data) {
+        eprintln!("Failed to write {}: {}", paths.citydb_file.display(), e);
+        return 1;
+    }
+    println!(
+        "Wrote {} cities of {}+ people to {} ({} KB)",
+        entries.len(),
+        min_population,
+        paths.citydb_file.display(),
+        data.len() / 1024
+    );
+    if admin1.is_none() {
+        println!("  No admin1 file: regions only match by code (\"Portland, OR\", not \"Portland, Oregon\")");
+    }
+    0
+}
+
+/// The bundled database: the cities named in a tz database zone.tab.
+fn write_zone_tab_citydb(zone_tab: &str, paths: &config::Paths) -> i32 {
+    let entries = match citydb::parse_zone_tab(zone_tab) {
+        Ok(e) => e,
+        Err(e) => {
+            eprintln!("Unexpected zone.tab format: {}", e);
+            return 1;
+        }
+    };
+    let data = citydb::encode(&entries);
+    if let Err(e) = config::write_atomic(&paths.citydb_file, &data) {
+        eprintln!("Failed to write {}: {}", paths.citydb_file.display(), e);
+        return 1;
+    }
+    println!(
+        "Wrote {} time zone cities to {} ({} KB)",
+        entries.len(),
+        paths.citydb_file.display(),
+        data.len() / 1024
+    );
+    0
+}
+
+fn cmd_refresh(lat: f64, lon: f64, paths: &config::Paths) -> i32 {
+    let net = config::load_settings(paths).network;
+    if net.weather_provider == config::WeatherProvider::Disabled {
+        eprintln!("Weather is disabled ([network] weather_provider = disabled)");
+        eprintln!("  Enable it with: abraxas --weather-provider {}", config::WeatherProvider::default().name());
+        return 1;
+    }
+    println!("Fetching weather...");
+    let wd = weather::fetch(lat, lon, &net);
+
+    if wd.has_error {
+        eprintln!("Weather fetch failed");
+        // Exit 2: the daemon keeps using the old cache; 1: nothing to fall back on
+        return match config::load_weather_cache_locked(paths) {
+            Some(old) if !old.has_error => {
+                println!("Cached weather (still in use):");
+                print_weather(&old, now_epoch(), net.weather_provider);
+                2
+            }
+            _ => 1,
+        };
+    }
+
+    let _ = config::save_weather_cache_locked(paths, &wd);
+    println!("Weather: {}", wd.forecast);
+    println!("Cloud cover: {}% (in 3h: {}%)", wd.cloud_cover, wd.cloud_cover_3h);
+    0
+}
+
+/// Apply the solar temperature once and exit, leaving the ramp set.
+/// No sandbox: nothing long-running to protect.
+fn cmd_oneshot(
+    loc: &config::Location,
+    profile: Option<&config::Profile>,
+    refresh: bool,
+    paths: &config::Paths,
+) -> i32 {
+    let mut settings = config::load_settings(paths);
+    if let Some(p) = profile {
+        p.apply_settings(&mut settings);
+    }
+
+    let weather = if settings.network.weather_provider == config::WeatherProvider::Disabled {
+        Some(weather::disabled())
+    } else if refresh {
+        let wd = weather::fetch(loc.lat, loc.lon, &settings.network);
+        if wd.has_error {
+            eprintln!("[warn] Weather fetch failed, assuming clear sky");
+        } else {
+            let _ = config::save_weather_cache_locked(paths, &wd);
+        }
+        Some(wd)
+    } else {
+        config::load_weather_cache_locked(paths)
+    };
+
+    let temp = daemon::solar_temp_at(loc, &settings, weather.as_ref(), now_epoch(), &SystemClock);
+
+    let mut gamma = match gamma::init() {
+        Ok(g) => g,
+        Err(e) => {
+            eprintln!("No gamma backend: {}", e);
+            return 1;
+        }
+    };
+    if let Err(e) = gamma.set_temperature(temp, settings.display.brightness, settings.display.white_point()) {
+        eprintln!("Failed to apply {}K: {}", temp, e);
+        return 1;
+    }
+
+    println!("Applied {}K ({} backend)", temp, gamma.backend_name());
+    if gamma.backend_name() == "wayland" {
+        eprintln!("[warn] Wayland compositors reset gamma when abraxas exits; use --daemon");
+    }
+    if config::check_daemon_alive(paths) {
+        eprintln!("[warn] Daemon is running and will re-apply its own temperature");
+    }
+    gamma.leak();
+    0
+}
+
+/// Set by SIGINT/SIGTERM during --fade: stop where the fade has got to
+static FADE_INTERRUPTED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+extern "C" fn fade_interrupt(_sig: libc::c_int) {
+    FADE_INTERRUPTED.store(true, std::sync::atomic::Ordering::Relaxed);
+}
+
+/// Fade from what is on screen to `target` over `duration_sec`, one step a
+/// second along the sigmoid, then exit leaving the ramp applied. Works
+/// without the daemon; an interrupt leaves the intermediate temperature.
+fn cmd_fade(target: i32, duration_sec: u32, paths: &config::Paths) -> i32 {
+    use std::sync::atomic::Ordering;
+
+    let settings = config::load_settings(paths);
+    let limits = settings.limits;
+    if target < limits.min || target > limits.max {
+        eprintln!("Temperature must be between {}K and {}K.", limits.min, limits.max);
+        return 1;
+    }
+
+    let mut gamma = match gamma::init() {
+        Ok(g) => g,
+        Err(e) => {
+            eprintln!("No gamma backend: {}", e);
+            return 1;
+        }
+    };
+    gamma.set_ramp_quantize_step(settings.display.ramp_quantize_step);
+    let daemon_alive = config::check_daemon_alive(paths);
+    let start = match gamma.initial_temperature() {
+        Some(t) => t,
+        None => {
+            // The daemon's last applied temperature is the next best guess
+            let published = config::load_daemon_status(paths)
+                .filter(|s| daemon_alive && s.applied_temp > 0)
+                .map(|s| s.applied_temp);
+            let t = published.unwrap_or(TEMP_DAY_CLEAR);
+            eprintln!("[warn] The {} backend can't read the current ramp; fading from {}K", gamma.backend_name(), t);
+            t
+        }
+    };
+    if daemon_alive {
+        eprintln!("[warn] Daemon is running and will re-apply its own temperature");
+    }
+
+    unsafe {
+        let handler = fade_interrupt as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        libc::signal(libc::SIGINT, handler);
+        libc::signal(libc::SIGTERM, handler);
+    }
+
+    println!("Fading {}K -> {}K over {} ({} backend)", start, target, format_duration(duration_sec as i64),
+        gamma.backend_name());
+    let white_point = settings.display.white_point();
+    let mut temp = start;
+    let mut interrupted = false;
+    for second in 0..=duration_sec as i64 {
+        temp = sigmoid::calculate_manual_temp(start, target, 0, duration_sec as i64, config::Easing::Sigmoid, second);
+        if let Err(e) = gamma.set_temperature(temp, settings.display.brightness, white_point) {
+            eprintln!("Failed to apply {}K: {}", temp, e);
+            return 1;
+        }
+        if second == duration_sec as i64 {
+            break;
+        }
+        // sleep(3) returns early on a signal (std's sleep would resume)
+        unsafe { libc::sleep(1) };
+        if FADE_INTERRUPTED.load(Ordering::Relaxed) {
+            interrupted = true;
+            break;
+        }
+    }
+
+    if interrupted {
+        println!("Interrupted at {}K", temp);
+    } else {
+        println!("Applied {}K", temp);
+    }
+    if gamma.backend_name() == "wayland" {
+        eprintln!("[warn] Wayland compositors reset gamma when abraxas exits; use --daemon");
+    }
+    gamma.leak();
+    if interrupted { 130 } else { 0 }
+}
+
+fn cmd_set_temp(set: &SetArgs, paths: &config::Paths) -> i32 {
+    let SetArgs { temp: target_temp, duration: duration_sec, hold: hold_min, relative, brightness, easing, at, held } = *set;
+    let limits = config::load_settings(paths).limits;
+    if relative {
+        // Resolved and clamped by the daemon against its applied temperature
+        if target_temp.abs() > limits.max - limits.min {
+            eprintln!("Relative change must be within {}K.", limits.max - limits.min);
+            return 1;
+        }
+    } else if target_temp < limits.min || target_temp > limits.max {
+        eprintln!("Temperature must be between {}K and {}K.", limits.min, limits.max);
+        return 1;
+    }
+
+    let now = now_epoch();
+    let at = match at {
+        Some(t) if t <= now => {
+            eprintln!("[warn] Scheduled time {} has already passed; applying now.", hhmm(t));
+            None
+        }
+        other => other,
+    };
+
+    // Held until the new file is in place, so the sequence stays the newest
+    let lock = config::lock_override(paths);
+    let ovr = config::OverrideState {
+        active: true,
+        target_temp,
+        duration_secs: duration_sec,
+        issued_at: now,
+        start_temp: 0, // daemon fills this
+        hold_minutes: hold_min,
+        is_relative: relative,
+        brightness: brightness.unwrap_or(1.0),
+        easing: easing.unwrap_or_default(),
+        at,
+        hold: held,
+        sequence: config::next_override_sequence(paths),
+        ..Default::default()
+    };
+
+    if config::save_override(paths, &ovr).is_err() {
+        eprintln!("Failed to write override");
+        return 1;
+    }
+    drop(lock);
+
+    if relative && target_temp == 0 && brightness.is_some() {
+        println!("Override: current temperature (instant)");
+    } else if relative {
+        println!("Override: {:+}K from current over {} ({})", target_temp, format_duration(duration_sec), ovr.easing.name());
+    } else if duration_sec > 0 {
+        println!("Override: -> {}K over {} ({})", target_temp, format_duration(duration_sec), ovr.easing.name());
+    } else {
+        println!("Override: -> {}K (instant)", target_temp);
+    }
+    if let Some(b) = brightness {
+        println!("Brightness: {:.0}%", b * 100.0);
+    }
+    if let Some(hold) = hold_min {
+        println!("Holding for {} min, then resuming solar control.", hold);
+    }
+    if held {
+        println!("Holding through dawn/dusk until --resume.");
+    }
+    if let Some(t) = at {
+        println!("Scheduled for {} (cancel with --resume).", hhmm(t));
+    }
+
+    if config::check_daemon_alive(paths) {
+        if at.is_none() {
+            println!("Daemon will process on next tick (up to 60s).");
+        }
+    } else {
+        eprintln!("[warn] Daemon is not running. Override saved but won't apply until daemon starts.");
+    }
+    0
+}
+
+fn cmd_resume(paths: &config::Paths) {
+    let lock = config::lock_override(paths);
+    let ovr = config::OverrideState {
+        sequence: config::next_override_sequence(paths),
+        ..Default::default()
+    };
+    let _ = config::save_override(paths, &ovr);
+    drop(lock);
+
+    if config::check_daemon_alive(paths) {
+        println!("Resume sent. Daemon will return to solar control.");
+    } else {
+        eprintln!("[warn] Daemon is not running. Resume saved but won't apply until daemon starts.");
+    }
+}
+
+fn cmd_toggle(paths: &config::Paths) {
+    let lock = config::lock_override(paths);
+    let mut ovr = config::load_override(paths).unwrap_or_default();
+    ovr.paused = !ovr.paused;
+
+    if config::save_override(paths, &ovr).is_err() {
+        eprintln!("Failed to write override");
+        return;
+    }
+    drop(lock);
+
+    if ovr.paused {
+        println!("Paused. Gamma restored until next --toggle.");
+    } else {
+        println!("Unpaused. Resuming temperature control.");
+    }
+
+    if !config::check_daemon_alive(paths) {
+        eprintln!("[warn] Daemon is not running. Toggle saved but won't apply until daemon starts.");
+    }
+}
+
+fn cmd_reset(paths: &config::Paths) {
+    config::clear_override(paths);
+
+    if let Ok(mut state) = gamma::init() {
+        let _ = state.restore();
+    }
+
+    println!("Screen temperature reset.");
+}
+
+/// How long --stop waits for the daemon to remove its PID file
+const STOP_TIMEOUT_MS: u64 = 5000;
+
+/// Stop the daemon via SIGTERM.
+/// Exit codes: 0 stopped, 1 not running, 2 timed out.
+fn cmd_stop(paths: &config::Paths) -> i32 {
+    let pid = match config::read_pid(paths) {
+        Some(p) => p,
+        None => {
+            eprintln!("Daemon is not running.");
+            return 1;
+        }
+    };
+
+    // Guard against a stale PID file pointing at a reused PID
+    let comm = std::fs::read_to_string(format!("/proc/{}/comm", pid)).unwrap_or_default();
+    let comm = comm.trim();
+    if comm != "abraxas" {
+        if comm.is_empty() {
+            eprintln!("Daemon is not running (stale PID file for {}).", pid);
+        } else {
+            eprintln!("Daemon is not running (PID {} is now '{}', stale PID file).", pid, comm);
+        }
+        config::remove_pid(paths);
+        return 1;
+    }
+
+    if unsafe { libc::kill(pid, libc::SIGTERM) } != 0 {
+        eprintln!("Failed to signal daemon (PID {}): {}", pid, std::io::Error::last_os_error());
+        return 1;
+    }
+
+    // The daemon restores gamma and removes its PID file on the way out
+    let step = std::time::Duration::from_millis(100);
+    let mut waited = 0;
+    while waited < STOP_TIMEOUT_MS {
+        if !paths.pid_file.exists() {
+            println!("Daemon stopped (PID {}).", pid);
+            return 0;
+        }
+        std::thread::sleep(step);
+        waited += 100;
+    }
+
+    eprintln!("Timed out waiting for daemon (PID {}) to exit.", pid);
+    2
+}
+
+/// Stop the daemon, then replace this process with a fresh foreground daemon.
+fn cmd_restart(paths: &config::Paths) -> i32 {
+    use std::os::unix::process::CommandExt;
+
+    match cmd_stop(paths) {
+        0 => {}
+        1 => {
+            eprintln!("Start it with: abraxas --daemon");
+            return 1;
+        }
+        rc => return rc,
+    }
+
+    let exe = match std::env::current_exe() {
+        Ok(e) => e,
+        Err(e) => {
+            eprintln!("Cannot locate abraxas binary: {}", e);
+            eprintln!("Start it with: abraxas --daemon");
+            return 1;
+        }
+    };
+    println!("Starting daemon...");
+    let err = process::Command::new(exe).arg("--daemon").exec();
+    eprintln!("Failed to start daemon: {}", err);
+    1
+}
+
+fn cmd_use_location(name: &str, paths: &config::Paths) -> i32 {
+    let locations = config::load_locations(paths);
+    let (selected, loc) = if name == "none" {
+        match locations.default {
+            Some(ref l) => (None, l),
+            None => {
+                eprintln!("No [location] in {}. Use --set-location first.", paths.config_file.display());
+                return 1;
+            }
+        }
+    } else {
+        match locations.named.iter().find(|n| n.name == name) {
+            Some(config::NamedLocation { location: Some(l), .. }) => (Some(name), l),
+            Some(_) => {
+                eprintln!("[location \"{}\"] needs a valid latitude and longitude.", name);
+                return 1;
+            }
+            None => {
+                eprintln!("No location named '{}' in {}.", name, paths.config_file.display());
+                if !locations.named.is_empty() {
+                    let names: Vec<&str> = locations.named.iter().map(|n| n.name.as_str()).collect();
+                    eprintln!("Available: {}", names.join(", "));
+                }
+                return 1;
+            }
+        }
+    };
+
+    if let Err(e) = config::save_active_location(paths, selected) {
+        config_write_failed(paths, &e);
+        return 1;
+    }
+    match selected {
+        Some(name) => println!("Using location '{}': {:.4}, {:.4}", name, loc.lat, loc.lon),
+        None => println!("Using [location]: {:.4}, {:.4}", loc.lat, loc.lon),
+    }
+
+    if let Some(p) = config::active_profile(paths).filter(|p| p.location.is_some()) {
+        eprintln!("[warn] Profile '{}' sets its own location, which still takes precedence.", p.name);
+    }
+    if !config::check_daemon_alive(paths) {
+        eprintln!("[warn] Daemon is not running. Location saved but won't apply until daemon starts.");
+    }
+    0
+}
+
+fn cmd_list_locations(paths: &config::Paths) {
+    let locations = config::load_locations(paths);
+    if locations.named.is_empty() {
+        println!("No named locations. Add [location \"NAME\"] sections to {}.", paths.config_file.display());
+    }
+
+    let coords = |l: &Option<config::Location>| match l {
+        Some(l) => format!("{:.4}, {:.4}", l.lat, l.lon),
+        None => "(no valid latitude/longitude)".to_string(),
+    };
+    let active = locations.active.as_deref().filter(|a| locations.named.iter().any(|n| n.name == *a));
+    let width = locations.named.iter().map(|n| n.name.len()).max().unwrap_or(0).max("[location]".len());
+    let marker = if active.is_none() { "*" } else { " " };
+    println!("{} {:<width$}  {}", marker, "[location]", coords(&locations.default));
+    for n in &locations.named {
+        let marker = if active == Some(n.name.as_str()) { "*" } else { " " };
+        println!("{} {:<width$}  {}", marker, n.name, coords(&n.location));
+    }
+}
+
+fn cmd_use_profile(name: &str, paths: &config::Paths) -> i32 {
+    if name == "none" {
+        config::clear_active_profile(paths);
+        println!("Profile cleared. Using [location] and default temperatures.");
+    } else {
+        let profiles = config::load_profiles(paths);
+        let p = match profiles.iter().find(|p| p.name == name) {
+            Some(p) => p,
+            None => {
+                eprintln!("No profile named '{}' in {}.", name, paths.config_file.display());
+                if !profiles.is_empty() {
+                    let names: Vec<&str> = profiles.iter().map(|p| p.name.as_str()).collect();
+                    eprintln!("Available: {}", names.join(", "));
+                }
+                return 1;
+            }
+        };
+
+        if config::save_active_profile(paths, name).is_err() {
+            eprintln!("Failed to write active profile");
+            return 1;
+        }
+        println!("Using profile '{}'.", p.name);
+        if let Some(ref l) = p.location {
+            println!("  Location: {:.4}, {:.4}", l.lat, l.lon);
+        }
+    }
+
+    if !config::check_daemon_alive(paths) {
+        eprintln!("[warn] Daemon is not running. Profile saved but won't apply until daemon starts.");
+    }
+    0
+}
+
+fn cmd_list_profiles(paths: &config::Paths) {
+    let profiles = config::load_profiles(paths);
+    if profiles.is_empty() {
+        println!("No profiles defined. Add [profile.NAME] sections to {}.", paths.config_file.display());
+        return;
+    }
+
+    let active = config::active_profile(paths).map(|p| p.name);
+    for p in &profiles {
+        let marker = if active.as_deref() == Some(p.name.as_str()) { "*" } else { " " };
+        let mut line = format!("{} {}", marker, p.name);
+        if let Some(ref l) = p.location {
+            line.push_str(&format!("  {:.4}, {:.4}", l.lat, l.lon));
+        }
+        if let Some(t) = p.day_clear {
+            line.push_str(&format!("  day_clear={}K", t));
+        }
+        if let Some(t) = p.night {
+            line.push_str(&format!("  night={}K", t));
+        }
+        println!("{}", line);
+    }
+}
+
+fn cmd_weather_provider(name: &str, paths: &config::Paths) -> i32 {
+    let provider = match config::WeatherProvider::from_name(name) {
+        Some(p) if p.available() => p,
+        Some(p) => {
+            eprintln!("{} is not compiled into this build (cargo feature \"{}\")", p.name(), p.name());
+            eprintln!("  Available: {}", config::WeatherProvider::choices());
+            return 1;
+        }
+        None => {
+            eprintln!("Unknown weather provider: {}", name);
+            eprintln!("  Available: {}", config::WeatherProvider::choices());
+            return 1;
+        }
+    };
+
+    if let Err(e) = config::save_network_params(paths, provider) {
+        config_write_failed(paths, &e);
+        return 1;
+    }
+    match provider {
+        config::WeatherProvider::Disabled => println!("Weather disabled: daytime always counts as clear"),
+        p => println!("Weather provider set to {} ({})", p.name(), p.host()),
+    }
+    if config::check_daemon_alive(paths) {
+        println!("The running daemon switches after any fetch in progress");
+    }
+    0
+}
+
+fn cmd_import_redshift(path: &str, paths: &config::Paths) -> i32 {
+    let content = match std::fs::read_to_string(path) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("Cannot read {}: {}", path, e);
+            return 1;
+        }
+    };
+    let rs = config::parse_redshift(&content);
+
+    let valid_temp = |key: &str, temp: Option<i32>| match temp {
+        Some(t) if (TEMP_MIN..=TEMP_MAX).contains(&t) => Some(t),
+        Some(t) => {
+            eprintln!("[warn] {} = {} is outside {}-{}K, skipped", key, t, TEMP_MIN, TEMP_MAX);
+            None
+        }
+        None => None,
+    };
+    let day_clear = valid_temp("temp-day", rs.temp_day);
+    let night = valid_temp("temp-night", rs.temp_night);
+    let location = match (rs.lat, rs.lon) {
+        (Some(lat), Some(lon)) if config::valid_coordinates(lat, lon) => {
+            Some((lat, lon))
+        }
+        (Some(lat), Some(lon)) => {
+            eprintln!("[warn] [manual] lat/lon {}, {} out of range, skipped", lat, lon);
+            None
+        }
+        _ => None,
+    };
+
+    if rs.dawn_time.is_some() || rs.dusk_time.is_some() {
+        eprintln!("[warn] dawn-time/dusk-time ignored: abraxas calculates dawn and dusk from the sun's position");
+        eprintln!("       (a [schedule] section can pin fixed times instead)");
+    }
+    if rs.transition == Some(false) {
+        eprintln!("[warn] transition=0 ignored: abraxas always fades through dawn and dusk");
+    }
+    if location.is_none() && day_clear.is_none() && night.is_none() {
+        eprintln!("Nothing to import from {} (no [manual] lat/lon, temp-day or temp-night).", path);
+        return 1;
+    }
+
+    let before = std::fs::read_to_string(&paths.config_file).unwrap_or_default();
+    if let Some((lat, lon)) = location {
+        if let Err(e) = config::save_location(paths, lat, lon) {
+            config_write_failed(paths, &e);
+            return 1;
+        }
+    }
+    if day_clear.is_some() || night.is_some() {
+        if let Err(e) = config::save_temp_params(paths, day_clear, night) {
+            config_write_failed(paths, &e);
+            return 1;
+        }
+    }
+    let after = std::fs::read_to_string(&paths.config_file).unwrap_or_default();
+
+    println!("Imported {} into {}:", path, paths.config_file.display());
+    print_config_diff(&before, &after);
+    if location.is_none() {
+        println!("No [manual] location in the Redshift config; set one with --set-location.");
+    }
+    if config::check_daemon_alive(paths) {
+        println!("The running daemon picks up the new settings immediately.");
+    }
+    0
+}
+
+/// Report a failed config.ini write. In system mode the file is the
+/// administrator's: say so rather than leave a bare permission error.
+fn config_write_failed(paths: &config::Paths, e: &std::io::Error) {
+    eprintln!("Failed to write {}: {}", paths.config_file.display(), e);
+    if paths.mode == config::PathMode::System {
+        eprintln!("  System mode: {} is managed by root; edit it there (sudo) and", paths.config_file.display());
+        eprintln!("  the daemon reloads it, or drop --system to use your own config.");
+    }
+}
+
+/// Print the lines a config rewrite removed (-) and added (+), each under
+/// its section header.
+fn print_config_diff(before: &str, after: &str) {
+    let a: Vec<&str> = before.lines().collect();
+    let b: Vec<&str> = after.lines().collect();
+
+    // lcs[i][j]: longest common subsequence of a[i..] and b[j..]
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let (mut section, mut shown) = ("", "");
+    let mut changes = 0;
+    while i < a.len() || j < b.len() {
+        let (sign, line) = if i < a.len() && j < b.len() && a[i] == b[j] {
+            i += 1;
+            j += 1;
+            (' ', b[j - 1])
+        } else if i < a.len() && (j == b.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            i += 1;
+            ('-', a[i - 1])
+        } else {
+            j += 1;
+            ('+', b[j - 1])
+        };
+
+        let trimmed = line.trim();
+        let is_header = trimmed.starts_with('[') && trimmed.ends_with(']');
+        if is_header {
+            section = trimmed;
         }
-        Command::SetLocation(location) => {
-            process::exit(cmd_set_location(location, &paths));
+        if sign == ' ' {
+            continue;
         }
-        Command::Set { temp, duration } => {
-            process::exit(cmd_set_temp(*temp, *duration, &paths));
+        if is_header {
+            shown = section;
+        } else if section != shown {
+            println!("  {}", section);
+            shown = section;
         }
-        _ => {}
+        println!("{} {}", sign, line);
+        changes += 1;
     }
+    if changes == 0 {
+        println!("  (no changes)");
+    }
+}
 
-    // Remaining commands need location
-    let loc = match config::load_location(&paths) {
-        Some(loc) => loc,
-        None => {
-            eprintln!("No location configured. Use --set-location first.");
-            eprintln!("  Example: abraxas --set-location 60614");
-            eprintln!("  Example: abraxas --set-location 41.88,-87.63");
-            process::exit(1);
-        }
-    };
+fn cmd_export_config(paths: &config::Paths) {
+    let s = config::load_settings(paths);
+    let d = config::Settings::default();
 
-    weather::init();
+    println!("# ABRAXAS configuration");
+    println!("# Save to {}", paths.config_file.display());
+    println!();
 
-    let result = match command {
-        Command::Status => {
-            cmd_status(loc.lat, loc.lon, &paths);
-            0
+    println!("[location]");
+    match config::load_location(paths) {
+        Some(loc) => {
+            println!("# Latitude in decimal degrees (north positive)");
+            println!("latitude = {:.6}", loc.lat);
+            println!("# Longitude in decimal degrees (east positive)");
+            println!("longitude = {:.6}", loc.lon);
         }
-        Command::Refresh => cmd_refresh(loc.lat, loc.lon, &paths),
-        Command::Set { temp, duration } => cmd_set_temp(temp, duration, &paths),
-        Command::Daemon => {
-            daemon::run(loc, &paths);
-            0
+        None => {
+            println!("# Latitude in decimal degrees (north positive)");
+            println!("# latitude = 41.880000");
+            println!("# Longitude in decimal degrees (east positive)");
+            println!("# longitude = -87.630000");
         }
-        _ => unreachable!(),
-    };
+    }
+    if config::location_is_auto(paths) {
+        println!("# Set by --set-location auto; the daemon re-checks it daily via GeoClue2");
+        println!("auto = true");
+    }
+    println!();
 
-    weather::cleanup();
-    process::exit(result);
-}
+    let t = &s.temperatures;
+    println!("[temperatures]");
+    println!("# Daytime temperature under clear skies in Kelvin (default: {})", d.temperatures.day_clear);
+    println!("day_clear = {}", t.day_clear);
+    println!("# Daytime temperature when overcast in Kelvin (default: {})", d.temperatures.day_dark);
+    println!("day_dark = {}", t.day_dark);
+    println!("# Night temperature in Kelvin (default: {})", d.temperatures.night);
+    println!("night = {}", t.night);
+    println!("# Cloud cover percent that switches daytime to day_dark (default: {})", d.temperatures.cloud_threshold);
+    println!("cloud_threshold = {}", t.cloud_threshold);
+    println!("# Temperature during golden hour, sun -4 to 6 degrees; 0 = off (default: {})", d.temperatures.golden_hour_temp);
+    println!("golden_hour_temp = {}", t.golden_hour_temp);
+    println!("# Lower the night temperature up to {}K under a gibbous or full moon (default: {})", sigmoid::MOON_REDUCTION_MAX, d.temperatures.moon_brightness_reduction);
+    println!("moon_brightness_reduction = {}", t.moon_brightness_reduction);
+    println!();
 
-fn cmd_status(lat: f64, lon: f64, paths: &config::Paths) {
-    println!("ABRAXAS v8.4.0 [Rust]\n");
-    println!("Location: {:.4}, {:.4}\n", lat, lon);
+    println!("[transitions]");
+    println!("# Dawn transition duration in minutes (default: {})", d.transitions.dawn_duration);
+    println!("dawn_duration = {}", s.transitions.dawn_duration);
+    println!("# Dusk transition duration in minutes (default: {})", d.transitions.dusk_duration);
+    println!("dusk_duration = {}", s.transitions.dusk_duration);
+    println!("# Dawn midpoint in minutes after sunrise; negative shifts it earlier (default: {})", d.transitions.dawn_offset_min);
+    println!("dawn_offset = {}", s.transitions.dawn_offset_min);
+    println!("# Dusk midpoint in minutes before sunset, -120 to 120; negative shifts it later, past sunset (default: {})", d.transitions.dusk_offset_min);
+    println!("dusk_offset = {}", s.transitions.dusk_offset_min);
+    println!("# Sigmoid steepness of the dawn curve, 1 (gradual) to 20 (abrupt) (default: {})", d.transitions.steepness_dawn);
+    println!("steepness_dawn = {}", s.transitions.steepness_dawn);
+    println!("# Sigmoid steepness of the dusk curve, 1 (gradual) to 20 (abrupt) (default: {})", d.transitions.steepness_dusk);
+    println!("steepness_dusk = {}", s.transitions.steepness_dusk);
+    println!("# Seconds between updates inside a dawn/dusk window, 1-{} (default: {})", TEMP_UPDATE_SEC, d.transitions.transition_tick_sec);
+    println!("transition_tick_seconds = {}", s.transitions.transition_tick_sec);
+    println!("# Skip gamma updates smaller than this many Kelvin while not transitioning (default: {})", d.transitions.temp_change_threshold);
+    println!("temp_change_threshold = {}", s.transitions.temp_change_threshold);
+    println!();
 
-    let now = chrono_now();
-    let st = solar::sunrise_sunset(now, lat, lon);
-    let sp = solar::position(now, lat, lon);
+    println!("[daemon]");
+    println!("# time = sigmoid windows around sunrise/sunset, elevation = follow sun elevation (default: time)");
+    println!("mode = {}", if s.daemon.mode == config::SolarMode::Elevation { "elevation" } else { "time" });
+    println!("# Elevation mode: full day temperature at or above this sun elevation in degrees (default: {})", d.daemon.elevation_high);
+    println!("elevation_high = {}", s.daemon.elevation_high);
+    println!("# Elevation mode: night temperature at or below this sun elevation in degrees (default: {})", d.daemon.elevation_low);
+    println!("elevation_low = {}", s.daemon.elevation_low);
+    println!("# Desktop notification when dawn/dusk starts or an override ends (default: {})", d.daemon.notify);
+    println!("notify = {}", s.daemon.notify);
+    println!();
 
-    let local = local_time(now);
+    println!("[display]");
+    println!("# Brightness multiplier applied with every temperature, 0.1-1.0 (default: {})", d.display.brightness);
+    println!("brightness = {}", s.display.brightness);
+    println!("# Monitor's native white point (CIE x, y; default D65: {}, {})", d.display.monitor_white_x, d.display.monitor_white_y);
+    println!("monitor_white_x = {}", s.display.monitor_white_x);
+    println!("monitor_white_y = {}", s.display.monitor_white_y);
     println!(
-        "Date: {:04}-{:02}-{:02} {:02}:{:02}:{:02}",
-        local.year, local.month, local.day, local.hour, local.min, local.sec
+        "# Leave a gamma ramp alone when the temperature moved at most this many Kelvin, {}-{} (default: {})",
+        RAMP_QUANTIZE_RANGE.start(), RAMP_QUANTIZE_RANGE.end(), d.display.ramp_quantize_step
     );
+    println!("ramp_quantize_step = {}", s.display.ramp_quantize_step);
+    println!();
 
-    if let Some(ref times) = st {
-        let sr = local_time(times.sunrise);
-        let ss = local_time(times.sunset);
-        println!("Sunrise: {:02}:{:02}", sr.hour, sr.min);
-        println!("Sunset: {:02}:{:02}", ss.hour, ss.min);
+    println!("[network]");
+    println!("# Weather source: {} (default: {})", config::WeatherProvider::choices(), d.network.weather_provider.name());
+    println!("weather_provider = {}", s.network.weather_provider.name());
+    println!("# Minutes between weather refreshes (default: {})", d.network.weather_refresh_sec / 60);
+    println!("weather_refresh_minutes = {}", s.network.weather_refresh_sec / 60);
+    println!("# Seconds before a weather request gives up, 1-120 (default: {})", d.network.weather_timeout_sec);
+    println!("weather_timeout_seconds = {}", s.network.weather_timeout_sec);
+    println!("# Seconds allowed to connect to the weather API, 1-120 (default: {})", d.network.connect_timeout_sec);
+    println!("connect_timeout_seconds = {}", s.network.connect_timeout_sec);
+    println!();
+
+    println!("[temperature_limits]");
+    println!("# Lowest temperature accepted by --set in Kelvin (default: {})", d.limits.min);
+    println!("min = {}", s.limits.min);
+    println!("# Highest temperature accepted by --set in Kelvin (default: {})", d.limits.max);
+    println!("max = {}", s.limits.max);
+    println!();
+
+    println!("# Fixed daily schedule replacing the solar model: HH:MM = TEMP keyframes.");
+    println!("# Each temperature holds until the next keyframe, wrapping past midnight;");
+    println!("# changes blend over blend_minutes centered on the keyframe time.");
+    println!("# Named windows NAME_start / NAME_end / NAME_temp hold a temperature over");
+    println!("# either model between two times (no end = until midnight).");
+    if s.schedule.is_none() && s.schedule_windows.is_empty() {
+        println!("# [schedule]");
+        println!("# blend_minutes = {}", SCHEDULE_BLEND_MIN);
+        println!("# 07:00 = 6500");
+        println!("# 18:00 = 4500");
+        println!("# 21:00 = 2700");
+        println!("# focus_start = 09:00");
+        println!("# focus_end = 17:00");
+        println!("# focus_temp = 6000");
     } else {
-        println!("Sunrise/Sunset: N/A (polar region)");
+        println!("[schedule]");
+        if let Some(ref sched) = s.schedule {
+            println!("blend_minutes = {}", sched.blend_min);
+            for k in &sched.keyframes {
+                println!("{:02}:{:02} = {}", k.minute / 60, k.minute % 60, k.temp);
+            }
+        }
+        for w in &s.schedule_windows {
+            println!("{}_start = {:02}:{:02}", w.name, w.start_hhmm / 60, w.start_hhmm % 60);
+            if w.end_hhmm < 1440 {
+                println!("{}_end = {:02}:{:02}", w.name, w.end_hhmm / 60, w.end_hhmm % 60);
+            }
+            println!("{}_temp = {}", w.name, w.temp_k);
+        }
     }
-    println!("Sun elevation: {:.1} degrees\n", sp.elevation);
-
-    // Weather
-    let weather = config::load_weather_cache(paths);
-    if let Some(ref w) = weather {
-        if !w.has_error {
-            println!("Weather: {}", w.forecast);
-            println!("Cloud cover: {}%", w.cloud_cover);
 
-            let ft = local_time(w.fetched_at);
-            println!(
-                "Last updated: {:04}-{:02}-{:02} {:02}:{:02}:{:02}",
-                ft.year, ft.month, ft.day, ft.hour, ft.min, ft.sec
-            );
-        } else {
-            println!("Weather: Not available");
+    println!();
+    println!("# Named presets, selected with --use-profile NAME (all keys optional)");
+    let profiles = config::load_profiles(paths);
+    if profiles.is_empty() {
+        println!("# [profile.office]");
+        println!("# latitude = 41.880000");
+        println!("# longitude = -87.630000");
+        println!("# day_clear = 6000");
+        println!("# night = 3200");
+    }
+    for (i, p) in profiles.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        println!("[profile.{}]", p.name);
+        if let Some(ref l) = p.location {
+            println!("latitude = {:.6}", l.lat);
+            println!("longitude = {:.6}", l.lon);
+        }
+        if let Some(t) = p.day_clear {
+            println!("day_clear = {}", t);
+        }
+        if let Some(t) = p.night {
+            println!("night = {}", t);
         }
-    } else {
-        println!("Weather: Not available");
     }
+
+    let h = config::load_hook_params(paths);
     println!();
+    println!("[hooks]");
+    println!("# Scripts run with ABRAXAS_TEMP, ABRAXAS_MODE and ABRAXAS_BRIGHTNESS set");
+    println!("# (absolute paths; a new path needs a daemon restart to pass the sandbox)");
+    match h.on_change {
+        Some(ref p) => println!("on_change = {}", p.display()),
+        None => println!("# on_change = /home/me/bin/abraxas-changed"),
+    }
+    match h.on_mode_change {
+        Some(ref p) => println!("on_mode_change = {}", p.display()),
+        None => println!("# on_mode_change = /home/me/bin/abraxas-mode"),
+    }
+    println!("# Run on_change once the temperature moved this many Kelvin (default: {})", HOOK_CHANGE_DELTA);
+    println!("change_delta = {}", h.change_delta);
+    println!("# Never start a hook more often than this (default: {})", HOOK_MIN_INTERVAL_SEC);
+    println!("min_interval_seconds = {}", h.min_interval_sec);
+
+    #[cfg(feature = "thermal")]
+    {
+        let t = config::load_thermal_params(paths);
+        let td = config::ThermalParams::default();
+        println!();
+        println!("[thermal]");
+        println!("# Dim the display while the CPU runs hot (default: {})", td.enabled);
+        println!("enabled = {}", t.enabled);
+        println!("# Thermal zone in millidegrees Celsius, read at daemon start (default: {})", td.cpu_temp_file.display());
+        println!("cpu_temp_file = {}", t.cpu_temp_file.display());
+        println!("# Dim above this CPU temperature in Celsius (default: {})", td.temp_threshold_c);
+        println!("temp_threshold_c = {}", t.temp_threshold_c);
+        println!("# Fraction of brightness removed while hot, 0.0-0.9 (default: {})", td.brightness_reduction);
+        println!("brightness_reduction = {}", t.brightness_reduction);
+    }
+}
 
-    // Override status
-    let ovr = config::load_override(paths);
-    if let Some(ref o) = ovr {
-        if o.active {
-            println!("Mode: MANUAL OVERRIDE");
-            println!("Target: {}K over {} min", o.target_temp, o.duration_minutes);
+fn cmd_check_backend(paths: &config::Paths) -> i32 {
+    if config::check_daemon_alive(paths) {
+        println!("Note: the daemon is running; outputs it holds may report as busy.");
+    }
 
-            let it = local_time(o.issued_at);
-            println!(
-                "Issued: {:04}-{:02}-{:02} {:02}:{:02}:{:02}",
-                it.year, it.month, it.day, it.hour, it.min, it.sec
-            );
-            return;
+    let mut result = 0;
+    match gamma::init() {
+        Ok(mut g) => {
+            let name = g.backend_name().to_string();
+            let count = g.crtc_count();
+            println!("Backend: {} ({} CRTCs)", name, count);
+
+            let mut writable = 0;
+            let mut failed = 0;
+            for (i, name) in g.output_names().into_iter().enumerate() {
+                match g.check_crtc(i) {
+                    Ok(0) => println!("  {}: no gamma ramp, skipped", name),
+                    Ok(size) => {
+                        println!("  {}: {} entries, read/write OK", name, size);
+                        writable += 1;
+                    }
+                    Err(e) => {
+                        println!("  \u{2717} {}", e);
+                        failed += 1;
+                    }
+                }
+            }
+
+            if failed == 0 && writable > 0 {
+                println!("\u{2713} Backend: {}, {} outputs, all writable", name, writable);
+            } else {
+                println!(
+                    "\u{2717} Backend: {}, {} of {} outputs writable",
+                    name, writable, writable + failed
+                );
+                result = 1;
+            }
+        }
+        Err(e) => {
+            println!("\u{2717} Backend: {}", e);
+            result = 1;
         }
     }
 
-    let is_dark = weather
-        .as_ref()
-        .map(|w| !w.has_error && w.cloud_cover >= CLOUD_THRESHOLD)
-        .unwrap_or(false);
-
-    let (min_from_sunrise, min_to_sunset) = if let Some(ref times) = st {
-        (
-            (now - times.sunrise) as f64 / 60.0,
-            (times.sunset - now) as f64 / 60.0,
-        )
+    match landlock::abi_version() {
+        Some(abi) => println!("\u{2713} Landlock: supported (ABI v{})", abi),
+        None => println!("\u{2717} Landlock: not supported (kernel 5.13+ with CONFIG_SECURITY_LANDLOCK)"),
+    }
+    if seccomp::filter_supported() {
+        println!("\u{2713} seccomp: supported");
     } else {
-        (0.0, 0.0)
-    };
+        println!("\u{2717} seccomp: not supported (kernel lacks CONFIG_SECCOMP_FILTER)");
+    }
+    result
+}
 
-    let temp = sigmoid::calculate_solar_temp(min_from_sunrise, min_to_sunset, is_dark);
+/// What --diagnose found
+struct Diagnosis {
+    /// Session variables the backends go by
+    env: Vec<(&'static str, Option<String>)>,
+    daemon_running: bool,
+    /// Every compiled backend, in detection order
+    backends: Vec<(&'static str, Result<gamma::Probe, gamma::Error>)>,
+    dri_cards: Vec<DriCard>,
+    curl: Option<std::path::PathBuf>,
+    io_uring: Result<(), String>,
+    landlock_abi: Option<i32>,
+    seccomp: bool,
+}
 
-    println!("Mode: {}", if is_dark { "DARK" } else { "CLEAR" });
-    println!("Target temperature: {}K", temp);
+/// A /dev/dri/card* node and whether this user can open it
+struct DriCard {
+    path: String,
+    /// Owning group's name (the gid if it has none)
+    group: String,
+    in_group: bool,
+    /// Readable and writable (access(2))
+    accessible: bool,
 }
 
-fn cmd_set_location(loc_str: &str, paths: &config::Paths) -> i32 {
-    if loc_str.contains(',') {
-        let parts: Vec<&str> = loc_str.split(',').collect();
-        if parts.len() != 2 {
-            eprintln!("Invalid format. Use: LAT,LON (e.g., 41.88,-87.63)");
-            return 1;
+impl Diagnosis {
+    fn collect(paths: &config::Paths) -> Diagnosis {
+        let env = ["WAYLAND_DISPLAY", "DISPLAY", "XDG_SESSION_TYPE"]
+            .into_iter()
+            .map(|var| (var, std::env::var(var).ok().filter(|v| !v.is_empty())))
+            .collect();
+        Diagnosis {
+            env,
+            daemon_running: config::check_daemon_alive(paths),
+            backends: gamma::probe_all(0, false),
+            dri_cards: dri_cards(),
+            curl: find_in_path("curl"),
+            io_uring: io_uring_status(),
+            landlock_abi: landlock::abi_version(),
+            seccomp: seccomp::filter_supported(),
         }
-        let lat: f64 = match parts[0].parse() {
-            Ok(v) => v,
-            Err(_) => {
-                eprintln!("Invalid format. Use: LAT,LON (e.g., 41.88,-87.63)");
-                return 1;
-            }
-        };
-        let lon: f64 = match parts[1].parse() {
-            Ok(v) => v,
-            Err(_) => {
-                eprintln!("Invalid format. Use: LAT,LON (e.g., 41.88,-87.63)");
-                return 1;
-            }
-        };
+    }
 
-        if config::save_location(paths, lat, lon).is_err() {
-            eprintln!("Failed to save config");
-            return 1;
-        }
-        println!("Location set to: {:.4}, {:.4}", lat, lon);
-        return 0;
+    /// Backends with at least one output that took a ramp
+    fn usable(&self) -> Vec<&'static str> {
+        self.backends
+            .iter()
+            .filter(|(_, r)| matches!(r, Ok(p) if p.crtcs.iter().any(|c| matches!(c, Ok(size) if *size > 0))))
+            .map(|&(name, _)| name)
+            .collect()
     }
 
-    // ZIP code
-    if loc_str.len() != 5 || !loc_str.chars().all(|c| c.is_ascii_digit()) {
-        eprintln!("Invalid ZIP code. Must be 5 digits.");
-        return 1;
+    fn to_json(&self) -> serde_json::Value {
+        let err = |e: &gamma::Error| e.to_string();
+        let backends: Vec<_> = self
+            .backends
+            .iter()
+            .map(|(name, r)| match r {
+                Ok(p) => serde_json::json!({
+                    "backend": name,
+                    "connected": true,
+                    "crtcs": p.crtcs.iter().zip(&p.names).map(|(c, name)| match c {
+                        Ok(size) => serde_json::json!({ "name": name, "gamma_size": size }),
+                        Err(e) => serde_json::json!({ "name": name, "error": err(e) }),
+                    }).collect::<Vec<_>>(),
+                    "round_trip": p.round_trip.as_ref().map(|r| match r {
+                        Ok(()) => serde_json::json!({ "ok": true }),
+                        Err(e) => serde_json::json!({ "ok": false, "error": err(e) }),
+                    }),
+                }),
+                Err(e) => serde_json::json!({ "backend": name, "connected": false, "error": err(e) }),
+            })
+            .collect();
+        serde_json::json!({
+            "env": self.env.iter().map(|(k, v)| (k.to_string(), serde_json::json!(v))).collect::<serde_json::Map<_, _>>(),
+            "daemon_running": self.daemon_running,
+            "backends": backends,
+            "usable": self.usable(),
+            "dri_cards": self.dri_cards.iter().map(|c| serde_json::json!({
+                "path": c.path,
+                "group": c.group,
+                "in_group": c.in_group,
+                "accessible": c.accessible,
+            })).collect::<Vec<_>>(),
+            "curl": self.curl.as_ref().map(|p| p.display().to_string()),
+            "io_uring": match &self.io_uring {
+                Ok(()) => serde_json::json!({ "ok": true }),
+                Err(e) => serde_json::json!({ "ok": false, "error": e }),
+            },
+            "landlock_abi": self.landlock_abi,
+            "seccomp": self.seccomp,
+        })
     }
 
-    println!("Looking up ZIP code {}...", loc_str);
-    match zipdb::lookup(&paths.zipdb_file, loc_str) {
-        Some((lat, lon)) => {
-            println!("Found: {} -> {:.4}, {:.4}", loc_str, lat, lon);
-            if config::save_location(paths, lat as f64, lon as f64).is_err() {
-                eprintln!("Failed to save config");
-                return 1;
+    fn print(&self) {
+        println!("Environment:");
+        for (var, value) in &self.env {
+            println!("  {:<17} {}", var, value.as_deref().unwrap_or("(not set)"));
+        }
+        if self.daemon_running {
+            println!("Note: the daemon is running; outputs it holds may report as busy.");
+        }
+
+        println!("\nBackends (detection order):");
+        for (name, result) in &self.backends {
+            match result {
+                Ok(p) => {
+                    println!("  {}: connected, {} CRTCs", name, p.crtcs.len());
+                    for (crtc, name) in p.crtcs.iter().zip(&p.names) {
+                        match crtc {
+                            Ok(0) => println!("    {}: no gamma ramp", name),
+                            Ok(size) => println!("    {}: {} entries", name, size),
+                            Err(e) => println!("    \u{2717} {}: {}", name, e),
+                        }
+                    }
+                    match &p.round_trip {
+                        Some(Ok(())) => println!("    \u{2713} set + restore round trip"),
+                        Some(Err(e)) => println!("    \u{2717} set + restore: {}", e),
+                        None => println!("    - set + restore: no output to try it on"),
+                    }
+                }
+                Err(e) => println!("  {}: \u{2717} {}", name, e),
             }
-            println!("Location set to: {:.4}, {:.4}", lat, lon);
-            0
         }
-        None => {
-            eprintln!("ZIP code {} not found in database.", loc_str);
-            1
+
+        println!("\nDRM devices:");
+        if self.dri_cards.is_empty() {
+            println!("  none under /dev/dri");
+        }
+        for card in &self.dri_cards {
+            if card.accessible {
+                println!("  \u{2713} {}: read/write (group {})", card.path, card.group);
+            } else if card.in_group {
+                println!("  \u{2717} {}: no access (group {}, which you are in)", card.path, card.group);
+            } else {
+                println!("  \u{2717} {}: no access (group {}, which you are not in)", card.path, card.group);
+            }
         }
-    }
-}
 
-fn cmd_refresh(lat: f64, lon: f64, paths: &config::Paths) -> i32 {
-    println!("Fetching weather...");
-    let wd = weather::fetch(lat, lon);
+        println!("\nSystem:");
+        match &self.curl {
+            Some(path) => println!("  \u{2713} curl: {}", path.display()),
+            None => println!("  \u{2717} curl: not found in PATH (no weather or downloads)"),
+        }
+        match &self.io_uring {
+            Ok(()) => println!("  \u{2713} io_uring: available"),
+            Err(e) => println!("  \u{2717} io_uring: {}", e),
+        }
+        match self.landlock_abi {
+            Some(abi) => println!("  \u{2713} Landlock: supported (ABI v{})", abi),
+            None => println!("  \u{2717} Landlock: not supported (kernel 5.13+ with CONFIG_SECURITY_LANDLOCK)"),
+        }
+        if self.seccomp {
+            println!("  \u{2713} seccomp: supported");
+        } else {
+            println!("  \u{2717} seccomp: not supported (kernel lacks CONFIG_SECCOMP_FILTER)");
+        }
 
-    if wd.has_error {
-        eprintln!("Weather fetch failed");
-        return 1;
+        let usable = self.usable();
+        if usable.is_empty() {
+            println!("\n\u{2717} No backend can set gamma");
+        } else {
+            println!("\n\u{2713} Usable: {}", usable.join(", "));
+        }
     }
-
-    let _ = config::save_weather_cache(paths, &wd);
-    println!("Weather: {}", wd.forecast);
-    println!("Cloud cover: {}%", wd.cloud_cover);
-    0
 }
 
-fn cmd_set_temp(target_temp: i32, duration_min: i32, paths: &config::Paths) -> i32 {
-    if target_temp < TEMP_MIN || target_temp > TEMP_MAX {
-        eprintln!("Temperature must be between {}K and {}K.", TEMP_MIN, TEMP_MAX);
-        return 1;
-    }
+/// /dev/dri/card* nodes (not the cardN-connector entries), sorted
+fn dri_cards() -> Vec<DriCard> {
+    use std::os::unix::fs::MetadataExt;
 
-    let ovr = config::OverrideState {
-        active: true,
-        target_temp,
-        duration_minutes: duration_min,
-        issued_at: now_epoch(),
-        start_temp: 0, // daemon fills this
+    let entries = match std::fs::read_dir("/dev/dri") {
+        Ok(e) => e,
+        Err(_) => return Vec::new(),
     };
+    let mut paths: Vec<_> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| {
+            p.file_name()
+                .and_then(|n| n.to_str())
+                .and_then(|n| n.strip_prefix("card"))
+                .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+        })
+        .collect();
+    paths.sort();
+
+    let mut groups = vec![0 as libc::gid_t; 256];
+    let n = unsafe { libc::getgroups(groups.len() as i32, groups.as_mut_ptr()) };
+    groups.truncate(n.max(0) as usize);
+    groups.push(unsafe { libc::getegid() });
+
+    paths
+        .into_iter()
+        .filter_map(|path| {
+            let gid = std::fs::metadata(&path).ok()?.gid();
+            let c_path = std::ffi::CString::new(path.as_os_str().as_encoded_bytes()).ok()?;
+            let accessible = unsafe { libc::access(c_path.as_ptr(), libc::R_OK | libc::W_OK) } == 0;
+            Some(DriCard {
+                path: path.display().to_string(),
+                group: group_name(gid),
+                in_group: groups.contains(&gid),
+                accessible,
+            })
+        })
+        .collect()
+}
 
-    if config::save_override(paths, &ovr).is_err() {
-        eprintln!("Failed to write override");
-        return 1;
+fn group_name(gid: libc::gid_t) -> String {
+    let gr = unsafe { libc::getgrgid(gid) };
+    if gr.is_null() {
+        return gid.to_string();
     }
+    unsafe { std::ffi::CStr::from_ptr((*gr).gr_name) }.to_string_lossy().into_owned()
+}
 
-    if duration_min > 0 {
-        println!("Override: -> {}K over {} min (sigmoid)", target_temp, duration_min);
-    } else {
-        println!("Override: -> {}K (instant)", target_temp);
-    }
+/// First executable `name` on PATH
+fn find_in_path(name: &str) -> Option<std::path::PathBuf> {
+    use std::os::unix::fs::PermissionsExt;
 
-    if config::check_daemon_alive(paths) {
-        println!("Daemon will process on next tick (up to 60s).");
-    } else {
-        eprintln!("[warn] Daemon is not running. Override saved but won't apply until daemon starts.");
-    }
-    0
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .map(|dir| dir.join(name))
+        .find(|p| std::fs::metadata(p).is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0))
 }
 
-fn cmd_resume(paths: &config::Paths) {
-    let ovr = config::OverrideState {
-        active: false,
-        target_temp: 0,
-        duration_minutes: 0,
-        issued_at: 0,
-        start_temp: 0,
-    };
-    let _ = config::save_override(paths, &ovr);
-
-    if config::check_daemon_alive(paths) {
-        println!("Resume sent. Daemon will return to solar control.");
-    } else {
-        eprintln!("[warn] Daemon is not running. Resume saved but won't apply until daemon starts.");
+/// Whether a ring can be set up, and why not
+fn io_uring_status() -> Result<(), String> {
+    if uring::AbraxasRing::init(2).is_some() {
+        return Ok(());
+    }
+    let err = std::io::Error::last_os_error();
+    // kernel.io_uring_disabled (6.6+): 1 = privileged only, 2 = off
+    match std::fs::read_to_string("/proc/sys/kernel/io_uring_disabled") {
+        Ok(v) if v.trim() != "0" => Err(format!("disabled (kernel.io_uring_disabled = {}): {}", v.trim(), err)),
+        _ => Err(format!("setup failed: {}", err)),
     }
 }
 
-fn cmd_reset(paths: &config::Paths) {
-    config::clear_override(paths);
-
-    if let Ok(mut state) = gamma::init() {
-        let _ = state.restore();
+fn cmd_diagnose(json: bool, paths: &config::Paths) -> i32 {
+    let diagnosis = Diagnosis::collect(paths);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&diagnosis.to_json()).unwrap_or_default());
+    } else {
+        diagnosis.print();
+    }
+    if diagnosis.usable().is_empty() {
+        1
+    } else {
+        0
     }
-
-    println!("Screen temperature reset.");
 }
 
 fn cmd_benchmark(paths: &config::Paths) {
@@ -459,12 +3203,16 @@ fn cmd_benchmark(paths: &config::Paths) {
         elapsed / 1000, elapsed / N, N);
 
     // calculate_solar_temp
+    let settings = config::Settings::default();
     let start = bench_ns();
     for _ in 0..N {
         std::hint::black_box(sigmoid::calculate_solar_temp(
             std::hint::black_box(120.0),
             std::hint::black_box(300.0),
-            false,
+            0,
+            0,
+            &settings.temperatures,
+            &settings.transitions,
         ));
     }
     let elapsed = bench_ns() - start;
@@ -513,6 +3261,120 @@ fn cmd_benchmark(paths: &config::Paths) {
     } else {
         println!("  io_uring_setup()           UNAVAILABLE");
     }
+
+    #[cfg(all(feature = "splice-weather", any(feature = "noaa", feature = "met-norway")))]
+    bench_weather_splice(bench_ns);
+}
+
+/// --benchmark: a 256 KB response (about NOAA's hourly forecast) through a
+/// pipe and parsed, read() into a Vec as without splice-weather versus
+/// spliced into a memfd and parsed from its mapping.
+#[cfg(all(feature = "splice-weather", any(feature = "noaa", feature = "met-norway")))]
+fn bench_weather_splice(bench_ns: fn() -> u64) {
+    const CHUNK: usize = uring::PIPE_SPLICE_MAX as usize;
+    const RUNS: u64 = 100;
+
+    let period = r#"{"number":1,"shortForecast":"Partly Cloudy","temperature":68,"isDaytime":true},"#;
+    let mut json = String::from(r#"{"properties":{"periods":["#);
+    while json.len() < 4 * CHUNK - period.len() {
+        json.push_str(period);
+    }
+    json.pop();
+    json.push_str("]}}");
+    let body = json.as_bytes();
+
+    let mut fds = [0i32; 2];
+    let ring = uring::AbraxasRing::init(4);
+    let memfd = weather::splice::MemfdBuf::create();
+    let (mut ring, mut memfd) = match (ring, memfd) {
+        (Some(r), Some(m)) if unsafe { libc::pipe(fds.as_mut_ptr()) } == 0 => (r, m),
+        _ => {
+            println!("  weather body splice        UNAVAILABLE");
+            return;
+        }
+    };
+    let (rd, wr) = (fds[0], fds[1]);
+
+    // One chunk at a time, so each write fits in the pipe
+    let write_all = |mut chunk: &[u8]| {
+        while !chunk.is_empty() {
+            let n = unsafe { libc::write(wr, chunk.as_ptr() as *const libc::c_void, chunk.len()) };
+            if n <= 0 {
+                return false;
+            }
+            chunk = &chunk[n as usize..];
+        }
+        true
+    };
+
+    // Transfer and parse timed apart: parsing dominates either way
+    let (mut read_ns, mut read_parse_ns) = (0, 0);
+    for _ in 0..RUNS {
+        let start = bench_ns();
+        let mut buf = Vec::new();
+        let mut tmp = [0u8; 4096];
+        for chunk in body.chunks(CHUNK) {
+            write_all(chunk);
+            let mut left = chunk.len();
+            while left > 0 {
+                let n = unsafe { libc::read(rd, tmp.as_mut_ptr() as *mut libc::c_void, tmp.len()) };
+                if n <= 0 {
+                    break;
+                }
+                buf.extend_from_slice(&tmp[..n as usize]);
+                left -= n as usize;
+            }
+        }
+        let parse_start = bench_ns();
+        std::hint::black_box(serde_json::from_slice::<serde_json::Value>(&buf).is_ok());
+        read_parse_ns += bench_ns() - parse_start;
+        read_ns += parse_start - start;
+    }
+
+    let (mut splice_ns, mut splice_parse_ns) = (0, 0);
+    let mut ok = true;
+    for _ in 0..RUNS {
+        let start = bench_ns();
+        memfd.reset();
+        for chunk in body.chunks(CHUNK) {
+            write_all(chunk);
+            let mut left = chunk.len();
+            while ok && left > 0 {
+                ring.prep_splice(rd, memfd.fd(), uring::PIPE_SPLICE_MAX, uring::EV_SPLICE);
+                ring.submit_and_wait();
+                let res = loop {
+                    if let Some(cqe) = ring.peek_cqe() {
+                        break cqe.res;
+                    }
+                    ring.submit_and_wait();
+                };
+                ring.cqe_seen();
+                ok = res > 0;
+                if ok {
+                    memfd.add(res as usize);
+                    left -= res as usize;
+                }
+            }
+        }
+        let parse_start = bench_ns();
+        let parsed = memfd.map().map(|m| serde_json::from_slice::<serde_json::Value>(&m).is_ok());
+        splice_parse_ns += bench_ns() - parse_start;
+        splice_ns += parse_start - start;
+        ok = ok && parsed == Some(true) && memfd.len() == body.len();
+    }
+    unsafe {
+        libc::close(rd);
+        libc::close(wr);
+    }
+
+    println!();
+    println!("Weather body ({} KB through a pipe, then parsed; per fetch, {} fetches):", body.len() / 1024, RUNS);
+    println!("  read() into Vec            {:>8} ns  (+ {} ns parse)", read_ns / RUNS, read_parse_ns / RUNS);
+    if ok {
+        println!("  splice to memfd + mmap     {:>8} ns  (+ {} ns parse)", splice_ns / RUNS, splice_parse_ns / RUNS);
+    } else {
+        println!("  splice to memfd + mmap     FAILED (kernel without IORING_OP_SPLICE?)");
+    }
 }
 
 // Time helpers
@@ -547,3 +3409,205 @@ fn local_time(epoch: i64) -> LocalTime {
         sec: tm.tm_sec,
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    extern "C" {
+        fn tzset();
+    }
+
+    struct RestoreTz(Option<std::ffi::OsString>);
+
+    impl Drop for RestoreTz {
+        fn drop(&mut self) {
+            match self.0.take() {
+                Some(tz) => std::env::set_var("TZ", tz),
+                None => std::env::remove_var("TZ"),
+            }
+            unsafe { tzset() };
+        }
+    }
+
+    /// Run `f` with the process time zone set to `tz`. TZ is process-wide
+    /// and the solar math reads it through localtime_r, so callers are
+    /// serialized.
+    pub(crate) fn with_tz<R>(tz: &str, f: impl FnOnce() -> R) -> R {
+        static LOCK: Mutex<()> = Mutex::new(());
+        let _lock = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let _restore = RestoreTz(std::env::var_os("TZ"));
+        std::env::set_var("TZ", tz);
+        unsafe { tzset() };
+        f()
+    }
+
+    #[test]
+    fn parse_date_rejects_invalid() {
+        assert!(parse_date("2024-06-21").is_some());
+        assert!(parse_date("2024-02-30").is_none());
+        assert!(parse_date("2024-13-01").is_none());
+        assert!(parse_date("21/06/2024").is_none());
+    }
+
+    #[test]
+    fn noon_skew_flags_wrong_longitude() {
+        // Chicago: CDT (UTC-5) and CST (UTC-6) are both within an hour
+        assert!(noon_skew_hours(-87.63, -5.0).abs() < 1.0);
+        assert!(noon_skew_hours(-87.63, -6.0).abs() < 1.0);
+        // Dropped minus sign: almost 11 hours off
+        assert!(noon_skew_hours(87.63, -5.0) < -NOON_SKEW_WARN_HOURS);
+        // Western China keeps Beijing time (UTC+8), about 2.8h early
+        assert!(noon_skew_hours(87.6, 8.0).abs() <= NOON_SKEW_WARN_HOURS);
+    }
+
+    #[test]
+    fn parse_datetime_local() {
+        with_tz("UTC", || {
+            assert_eq!(parse_datetime("2024-06-21 12:52"), Some(1718974320));
+            assert_eq!(parse_datetime(" 2024-06-21  00:00 "), Some(1718928000));
+        });
+        assert!(parse_datetime("2024-06-21").is_none());
+        assert!(parse_datetime("2024-06-21 25:00").is_none());
+        assert!(parse_datetime("2024-02-30 12:00").is_none());
+    }
+
+    #[test]
+    fn parse_duration_units() {
+        assert_eq!(parse_duration("3"), Some(180));
+        assert_eq!(parse_duration("90s"), Some(90));
+        assert_eq!(parse_duration("2m"), Some(120));
+        assert_eq!(parse_duration("1h"), Some(3600));
+        assert_eq!(parse_duration("0"), Some(0));
+        assert_eq!(parse_duration("-5s"), None);
+        assert_eq!(parse_duration("2d"), None);
+        assert_eq!(parse_duration("s"), None);
+        assert_eq!(parse_duration("25h"), None);
+        assert_eq!(parse_duration("9223372036854775807h"), None);
+        assert_eq!(format_duration(90), "1 min 30 s");
+        assert_eq!(format_duration(120), "2 min");
+        assert_eq!(format_duration(45), "45 s");
+    }
+
+    #[test]
+    fn parse_at_times() {
+        with_tz("UTC", || {
+            let now = 1_750_000_000; // 2025-06-15 15:06:40 UTC
+            let midnight = now - now % 86400;
+            assert_eq!(parse_at("22:00", now), Some(midnight + 22 * 3600));
+            assert_eq!(parse_at("00:30", now), Some(midnight + 30 * 60));
+            assert_eq!(parse_at("1750003600", now), Some(1_750_003_600));
+            assert_eq!(parse_at("24:00", now), None);
+            assert_eq!(parse_at("tonight", now), None);
+            assert_eq!(parse_at("-5", now), None);
+        });
+    }
+
+    #[test]
+    fn preview_series_spans_day_and_night() {
+        let loc = config::Location { lat: 41.88, lon: -87.63 };
+        let settings = config::Settings::default();
+        let series = with_tz("America/Chicago", || {
+            preview_series(&loc, &settings, None, parse_date("2024-06-21").unwrap())
+        });
+
+        assert_eq!(series.len(), 144);
+        assert!(series.windows(2).all(|w| w[1].0 - w[0].0 == PREVIEW_STEP_SEC));
+        let temps: Vec<i32> = series.iter().map(|&(_, k)| k).collect();
+        let t = &settings.temperatures;
+        assert!(temps.iter().all(|&k| (t.night..=t.day_clear).contains(&k)));
+        assert!(temps.contains(&t.night));
+        assert!(temps.contains(&t.day_clear));
+    }
+
+    #[test]
+    fn transition_windows_follow_offsets() {
+        let times = solar::SunTimes { sunrise: 20_000, sunset: 70_000, solar_noon: 45_000 };
+        let tp = config::Settings::default().transitions;
+
+        let (dawn, dusk) = transition_windows(&times, &tp);
+
+        assert_eq!(dawn.1 - dawn.0, (tp.dawn_duration * 60.0) as i64);
+        assert_eq!((dawn.0 + dawn.1) / 2, times.sunrise + (tp.dawn_offset_min * 60.0) as i64);
+        assert_eq!(dusk.1 - dusk.0, (tp.dusk_duration * 60.0) as i64);
+        assert_eq!((dusk.0 + dusk.1) / 2, times.sunset - (tp.dusk_offset_min * 60.0) as i64);
+    }
+
+    fn exit_code(args: &[&str]) -> i32 {
+        let args = std::iter::once("abraxas").chain(args.iter().copied()).map(String::from).collect();
+        match run_cli(args) {
+            Ok(code) => code,
+            Err(e) => e.exit_code(),
+        }
+    }
+
+    #[test]
+    fn usage_errors_exit_1() {
+        assert_eq!(exit_code(&["bogus"]), 1);
+        assert_eq!(exit_code(&["--set"]), 1);
+        assert_eq!(exit_code(&["--set", "3500", "extra"]), 1);
+        assert_eq!(exit_code(&["--set", "3500", "--brightness", "2"]), 1);
+        assert_eq!(exit_code(&["--fade"]), 1);
+        assert_eq!(exit_code(&["--set-location"]), 1);
+        assert_eq!(exit_code(&["--help"]), 0);
+
+        let args = ["abraxas", "bogus"].map(String::from).to_vec();
+        assert!(matches!(parse_args(args), Err(CliError::UnknownCommand(c)) if c == "bogus"));
+        assert_eq!(CliError::Exit(101).exit_code(), 101);
+    }
+
+    #[test]
+    fn diagnose_json_reports_every_backend() {
+        use gamma::{Error, ErrorKind, Probe};
+
+        let busy = Error::with_errno(ErrorKind::Gamma, libc::EBUSY).on_crtc(1);
+        let diagnosis = Diagnosis {
+            env: vec![("WAYLAND_DISPLAY", None), ("DISPLAY", Some(":0".into()))],
+            daemon_running: false,
+            backends: vec![
+                ("drm", Err(Error::with_errno(ErrorKind::Permission, libc::EACCES).on_device("/dev/dri/card0"))),
+                ("x11", Ok(Probe {
+                    names: vec!["DP-1".into(), "HDMI-1".into(), "CRTC 2".into()],
+                    crtcs: vec![Ok(256), Err(busy), Ok(0)],
+                    round_trip: Some(Ok(())),
+                })),
+            ],
+            dri_cards: vec![DriCard { path: "/dev/dri/card0".into(), group: "video".into(), in_group: false, accessible: false }],
+            curl: None,
+            io_uring: Err("setup failed".into()),
+            landlock_abi: None,
+            seccomp: true,
+        };
+        assert_eq!(diagnosis.usable(), ["x11"]);
+
+        let json = diagnosis.to_json();
+        assert_eq!(json["env"]["DISPLAY"], ":0");
+        assert!(json["env"]["WAYLAND_DISPLAY"].is_null());
+        assert_eq!(json["backends"][0]["connected"], false);
+        assert!(json["backends"][0]["error"].as_str().unwrap().starts_with("/dev/dri/card0: Permission denied"));
+        assert_eq!(json["backends"][1]["crtcs"][0]["gamma_size"], 256);
+        assert_eq!(json["backends"][1]["crtcs"][1]["name"], "HDMI-1");
+        assert!(json["backends"][1]["crtcs"][1]["error"].as_str().unwrap().starts_with("CRTC 1:"));
+        assert_eq!(json["backends"][1]["round_trip"]["ok"], true);
+        assert_eq!(json["dri_cards"][0]["in_group"], false);
+        assert_eq!(json["io_uring"]["ok"], false);
+    }
+
+    #[test]
+    fn failed_zipdb_verification_keeps_old_database() {
+        let paths = config::tests::test_paths("zipdb-verify");
+        let good: Vec<zipdb::Entry> = vec![(*b"10001", 40.75, -73.99), (*b"60601", 41.88, -87.62)];
+        assert!(write_zipdb(&good, &paths).is_ok());
+        let before = std::fs::read(&paths.zipdb_file).unwrap();
+
+        // Out of order: ZipDb refuses it, so it never replaces the good one
+        let unsorted: Vec<zipdb::Entry> = good.iter().rev().copied().collect();
+        let err = write_zipdb(&unsorted, &paths).unwrap_err();
+        assert!(err.contains("kept the old database"), "{}", err);
+        assert_eq!(std::fs::read(&paths.zipdb_file).unwrap(), before);
+        let leftovers = std::fs::read_dir(&paths.cache_dir).unwrap().count();
+        assert_eq!(leftovers, 1);
+        let _ = std::fs::remove_dir_all(&paths.config_dir);
+    }
+}
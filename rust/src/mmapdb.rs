@@ -0,0 +1,89 @@
+//! Shared machinery for the sorted, mmap'd lookup files (zipdb, citydb).
+//!
+//! Both are a little-endian u32 count header (zipdb's now after a magic and
+//! version) followed by entries sorted by key; the file is mapped
+//! read-only and binary searched in place.
+
+use std::fs::File;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+
+/// Bytes of the u32 entry count at the start of every file
+pub const HEADER_SIZE: usize = 4;
+
+/// A read-only mmap of a whole file, unmapped on drop
+pub struct MappedFile {
+    ptr: *mut libc::c_void,
+    len: usize,
+}
+
+impl MappedFile {
+    /// None if the file is missing, empty or can't be mapped.
+    pub fn open(path: &Path) -> Option<Self> {
+        let file = File::open(path).ok()?;
+        let len = file.metadata().ok()?.len() as usize;
+        if len == 0 {
+            return None;
+        }
+
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return None;
+        }
+        Some(Self { ptr, len })
+    }
+
+    /// Entry count from the header (None if the file is too short for one).
+    pub fn count(&self) -> Option<usize> {
+        let header = self.get(..HEADER_SIZE)?;
+        Some(u32::from_le_bytes(header.try_into().ok()?) as usize)
+    }
+}
+
+impl std::ops::Deref for MappedFile {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.len) }
+    }
+}
+
+impl Drop for MappedFile {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.ptr, self.len) };
+    }
+}
+
+/// Little-endian u32/f32 at `offset` (None past the end)
+pub fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+pub fn read_f32(data: &[u8], offset: usize) -> Option<f32> {
+    read_u32(data, offset).map(f32::from_bits)
+}
+
+/// Binary search over entry indices 0..count: the first index for which
+/// `is_before` is false. Entries must be sorted so that every index
+/// `is_before` accepts comes first (as with `slice::partition_point`).
+pub fn partition_point(count: usize, mut is_before: impl FnMut(usize) -> bool) -> usize {
+    let (mut low, mut high) = (0, count);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if is_before(mid) {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+    low
+}
@@ -0,0 +1,325 @@
+//! Desktop notifications ([daemon] notify = true) when the dawn or dusk
+//! window starts and when a manual override ends on its own.
+//!
+//! The tick queues them; flush() sends them after gamma is applied, over
+//! org.freedesktop.Notifications (sd-bus, feature "notify"). A failure is
+//! logged once and published in state.json, and never touches gamma.
+
+use crate::sigmoid::SolarWindow;
+use crate::NOTIFY_COOLDOWN_SEC;
+
+/// One message for the notification server
+#[derive(Debug, PartialEq)]
+pub struct Notification {
+    pub summary: &'static str,
+    pub body: String,
+    /// Freedesktop icon name
+    pub icon: &'static str,
+}
+
+/// Kinds of notification, each with its own cooldown
+#[derive(Clone, Copy)]
+enum Event {
+    Dawn,
+    Dusk,
+    Resumed,
+}
+
+pub struct Notifier {
+    enabled: bool,
+    /// Window on the last tick; None until the first, so starting the
+    /// daemon mid-window stays quiet
+    window: Option<Option<SolarWindow>>,
+    /// When each Event was last queued (0 = never)
+    last_sent: [i64; 3],
+    pending: Vec<Notification>,
+    last_error: Option<String>,
+    #[cfg(feature = "notify")]
+    bus: Option<dbus::Bus>,
+    /// Server id of our last notification, so a new one replaces it
+    #[cfg(feature = "notify")]
+    replaces_id: u32,
+}
+
+impl Notifier {
+    pub fn new(enabled: bool) -> Self {
+        Notifier {
+            enabled,
+            window: None,
+            last_sent: [0; 3],
+            pending: Vec::new(),
+            last_error: None,
+            #[cfg(feature = "notify")]
+            bus: None,
+            #[cfg(feature = "notify")]
+            replaces_id: 0,
+        }
+    }
+
+    /// [daemon] notify from a config reload.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.pending.clear();
+            self.last_error = None;
+        }
+    }
+
+    /// Last delivery failure, cleared by the next success
+    pub fn last_error(&self) -> Option<&str> {
+        self.last_error.as_deref()
+    }
+
+    /// Queue `event` unless one like it went out within the cooldown.
+    fn queue(&mut self, event: Event, n: Notification, now: i64) {
+        let last = &mut self.last_sent[event as usize];
+        if self.enabled && (*last == 0 || now - *last >= NOTIFY_COOLDOWN_SEC) {
+            *last = now;
+            self.pending.push(n);
+        }
+    }
+
+    /// Follow the dawn/dusk window the solar curve is in (None outside
+    /// both, or while it isn't in charge), with its minutes left when
+    /// known. Entering one announces the shift to `target`.
+    pub fn transition(&mut self, window: Option<(SolarWindow, Option<f64>)>, target: i32, now: i64) {
+        let entered = window.map(|(w, _)| w);
+        let previous = self.window.replace(entered);
+        let (kind, left) = match (previous, window) {
+            (Some(prev), Some((kind, left))) if prev != Some(kind) => (kind, left),
+            _ => return,
+        };
+        let body = match left {
+            Some(min) => format!("Shifting to {}K over {} min", target, min.round() as i64),
+            None => format!("Shifting to {}K", target),
+        };
+        match kind {
+            SolarWindow::Dawn => {
+                let n = Notification { summary: "Dawn transition", body, icon: "weather-clear" };
+                self.queue(Event::Dawn, n, now);
+            }
+            SolarWindow::Dusk => {
+                let n = Notification { summary: "Dusk transition", body, icon: "weather-clear-night" };
+                self.queue(Event::Dusk, n, now);
+            }
+        }
+    }
+
+    /// A manual override ended by itself: back to `solar` K, faded over
+    /// `fade_min` minutes if given.
+    pub fn resumed(&mut self, solar: i32, fade_min: Option<i32>, now: i64) {
+        let body = match fade_min {
+            Some(min) => format!("Fading back to {}K over {} min", solar, min),
+            None => format!("Back to {}K", solar),
+        };
+        let n = Notification { summary: "Solar control resumed", body, icon: "preferences-desktop-display" };
+        self.queue(Event::Resumed, n, now);
+    }
+
+    /// Send what the tick queued.
+    pub fn flush(&mut self) {
+        for n in std::mem::take(&mut self.pending) {
+            match self.deliver(&n) {
+                Ok(()) => self.last_error = None,
+                Err(e) => {
+                    if self.last_error.as_deref() != Some(e.as_str()) {
+                        eprintln!("[notify] {}: {}", n.summary, e);
+                    }
+                    self.last_error = Some(e);
+                }
+            }
+        }
+    }
+
+    /// One connection, reopened after a failure (the session bus or the
+    /// notification server may have restarted).
+    #[cfg(feature = "notify")]
+    fn deliver(&mut self, n: &Notification) -> Result<(), String> {
+        let bus = match self.bus.take() {
+            Some(bus) => bus,
+            None => dbus::Bus::open()?,
+        };
+        self.replaces_id = dbus::notify(&bus, self.replaces_id, n)?;
+        self.bus = Some(bus);
+        Ok(())
+    }
+
+    #[cfg(not(feature = "notify"))]
+    fn deliver(&mut self, _n: &Notification) -> Result<(), String> {
+        Err("not compiled in (build with --features notify)".to_string())
+    }
+}
+
+#[cfg(feature = "notify")]
+mod dbus {
+    use super::Notification;
+    use crate::sdbus::*;
+    use std::ffi::{c_char, c_int, c_uint, CString};
+    use std::ptr;
+
+    const NOTIFY_NAME: &[u8] = b"org.freedesktop.Notifications\0";
+    const NOTIFY_PATH: &[u8] = b"/org/freedesktop/Notifications\0";
+    const APP_NAME: &[u8] = b"abraxas\0";
+    /// The event loop waits this long at most for the server (microseconds)
+    const CALL_TIMEOUT_USEC: u64 = 500_000;
+    /// Notify hint urgency: 0 = low
+    const URGENCY_LOW: c_int = 0;
+    /// expire_timeout: the server's default
+    const EXPIRE_DEFAULT: c_int = -1;
+
+    fn c(s: &[u8]) -> *const c_char {
+        s.as_ptr() as *const c_char
+    }
+
+    /// A session bus connection, unreferenced on drop
+    pub struct Bus(*mut SdBus);
+
+    impl Drop for Bus {
+        fn drop(&mut self) {
+            unsafe { sd_bus_unref(self.0) };
+        }
+    }
+
+    impl Bus {
+        pub fn open() -> Result<Bus, String> {
+            let mut raw: *mut SdBus = ptr::null_mut();
+            let r = unsafe { sd_bus_open_user(&mut raw) };
+            if r < 0 {
+                return Err(format!("no session bus: {}", std::io::Error::from_raw_os_error(-r)));
+            }
+            unsafe { sd_bus_set_method_call_timeout(raw, CALL_TIMEOUT_USEC) };
+            Ok(Bus(raw))
+        }
+    }
+
+    /// org.freedesktop.Notifications.Notify, replacing `replaces_id` (0 =
+    /// none). Returns the new notification's id.
+    pub fn notify(bus: &Bus, replaces_id: u32, n: &Notification) -> Result<u32, String> {
+        let text = |s: &str| CString::new(s).map_err(|_| "text contains a NUL byte".to_string());
+        let (summary, body, icon) = (text(n.summary)?, text(&n.body)?, text(n.icon)?);
+
+        let mut error = SdBusError::null();
+        let mut reply: *mut SdBusMessage = ptr::null_mut();
+        // susssasa{sv}i: app, replaces_id, icon, summary, body, no actions,
+        // hints {urgency: byte 0}, expire timeout
+        let r = unsafe {
+            sd_bus_call_method(
+                bus.0,
+                c(NOTIFY_NAME),
+                c(NOTIFY_PATH),
+                c(NOTIFY_NAME),
+                c(b"Notify\0"),
+                &mut error,
+                &mut reply,
+                c(b"susssasa{sv}i\0"),
+                c(APP_NAME),
+                replaces_id as c_uint,
+                icon.as_ptr(),
+                summary.as_ptr(),
+                body.as_ptr(),
+                0 as c_uint,
+                1 as c_uint,
+                c(b"urgency\0"),
+                c(b"y\0"),
+                URGENCY_LOW,
+                EXPIRE_DEFAULT,
+            )
+        };
+        let detail = error.describe();
+        unsafe { sd_bus_error_free(&mut error) };
+        if r < 0 {
+            return Err(match detail {
+                Some(d) => format!("Notify: {}", d),
+                None => format!("Notify: {}", std::io::Error::from_raw_os_error(-r)),
+            });
+        }
+
+        let mut id: c_uint = 0;
+        let r = unsafe { sd_bus_message_read(reply, c(b"u\0"), &mut id as *mut c_uint) };
+        unsafe { sd_bus_message_unref(reply) };
+        // The notification is shown either way; only replacing it is lost
+        Ok(if r < 0 { 0 } else { id })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DUSK: Option<(SolarWindow, Option<f64>)> = Some((SolarWindow::Dusk, Some(179.6)));
+
+    fn summaries(n: &mut Notifier) -> Vec<&'static str> {
+        std::mem::take(&mut n.pending).iter().map(|n| n.summary).collect()
+    }
+
+    #[test]
+    fn windows_announced_on_entry_only() {
+        let mut n = Notifier::new(true);
+        // Starting mid-window is quiet
+        n.transition(DUSK, 2900, 1000);
+        n.transition(None, 2900, 2000);
+        assert!(n.pending.is_empty());
+
+        n.transition(DUSK, 2900, 3000);
+        n.transition(DUSK, 2900, 3060);
+        assert_eq!(
+            std::mem::take(&mut n.pending),
+            [Notification {
+                summary: "Dusk transition",
+                body: "Shifting to 2900K over 180 min".to_string(),
+                icon: "weather-clear-night",
+            }]
+        );
+
+        // Elevation mode doesn't know the length
+        n.transition(None, 6500, 90_000);
+        n.transition(Some((SolarWindow::Dawn, None)), 6500, 90_060);
+        assert_eq!(n.pending[0].body, "Shifting to 6500K");
+    }
+
+    #[test]
+    fn cooldown_per_event_type() {
+        let mut n = Notifier::new(true);
+        n.transition(None, 2900, 0);
+        n.transition(DUSK, 2900, 1000);
+        n.resumed(4500, Some(3), 1010);
+        assert_eq!(summaries(&mut n), ["Dusk transition", "Solar control resumed"]);
+
+        // Flapping in and out of the window within the cooldown
+        n.transition(None, 2900, 1100);
+        n.transition(DUSK, 2900, 1200);
+        n.resumed(4500, None, 1300);
+        assert!(n.pending.is_empty());
+
+        n.transition(None, 2900, 1000 + NOTIFY_COOLDOWN_SEC);
+        n.transition(DUSK, 2900, 1000 + NOTIFY_COOLDOWN_SEC + 60);
+        n.resumed(4500, None, 1010 + NOTIFY_COOLDOWN_SEC);
+        assert_eq!(summaries(&mut n), ["Dusk transition", "Solar control resumed"]);
+    }
+
+    #[test]
+    fn disabled_queues_nothing_but_tracks_windows() {
+        let mut n = Notifier::new(false);
+        n.transition(None, 2900, 0);
+        n.transition(DUSK, 2900, 100);
+        n.resumed(4500, None, 100);
+        assert!(n.pending.is_empty());
+
+        // Enabled mid-window: nothing until the next window starts
+        n.set_enabled(true);
+        n.transition(DUSK, 2900, 200);
+        assert!(n.pending.is_empty());
+    }
+
+    #[cfg(not(feature = "notify"))]
+    #[test]
+    fn failures_kept_until_disabled() {
+        let mut n = Notifier::new(true);
+        n.resumed(4500, None, 100);
+        n.flush();
+        assert!(n.pending.is_empty());
+        assert!(n.last_error().unwrap().contains("--features notify"));
+        n.set_enabled(false);
+        assert_eq!(n.last_error(), None);
+    }
+}
@@ -0,0 +1,151 @@
+//! Raw sd-bus FFI (libsystemd), shared by the GNOME gamma backend, the
+//! GeoClue2 location lookup and desktop notifications. No async runtime,
+//! no zbus.
+
+// Each feature uses its own subset of the bindings
+#![allow(dead_code)]
+
+use std::ffi::{c_char, c_int, c_void, CStr};
+use std::ptr;
+
+#[repr(C)]
+pub struct SdBus {
+    _opaque: [u8; 0],
+}
+
+#[repr(C)]
+pub struct SdBusMessage {
+    _opaque: [u8; 0],
+}
+
+#[repr(C)]
+pub struct SdBusError {
+    pub name: *const c_char,
+    pub message: *const c_char,
+    _need_free: c_int,
+}
+
+impl SdBusError {
+    pub fn null() -> Self {
+        SdBusError {
+            name: ptr::null(),
+            message: ptr::null(),
+            _need_free: 0,
+        }
+    }
+
+    /// The error's message (or name), for logging. None if unset.
+    pub fn describe(&self) -> Option<String> {
+        let text = if self.message.is_null() { self.name } else { self.message };
+        if text.is_null() {
+            return None;
+        }
+        Some(unsafe { CStr::from_ptr(text) }.to_string_lossy().into_owned())
+    }
+}
+
+#[link(name = "systemd")]
+extern "C" {
+    pub fn sd_bus_open_user(bus: *mut *mut SdBus) -> c_int;
+    pub fn sd_bus_open_system(bus: *mut *mut SdBus) -> c_int;
+    pub fn sd_bus_unref(bus: *mut SdBus) -> *mut SdBus;
+    pub fn sd_bus_set_method_call_timeout(bus: *mut SdBus, usec: u64) -> c_int;
+
+    pub fn sd_bus_call_method(
+        bus: *mut SdBus,
+        destination: *const c_char,
+        path: *const c_char,
+        interface: *const c_char,
+        member: *const c_char,
+        error: *mut SdBusError,
+        reply: *mut *mut SdBusMessage,
+        types: *const c_char,
+        ...
+    ) -> c_int;
+
+    /// Leaves `reply` positioned inside the property's variant.
+    pub fn sd_bus_get_property(
+        bus: *mut SdBus,
+        destination: *const c_char,
+        path: *const c_char,
+        interface: *const c_char,
+        member: *const c_char,
+        error: *mut SdBusError,
+        reply: *mut *mut SdBusMessage,
+        type_: *const c_char,
+    ) -> c_int;
+
+    pub fn sd_bus_get_property_trivial(
+        bus: *mut SdBus,
+        destination: *const c_char,
+        path: *const c_char,
+        interface: *const c_char,
+        member: *const c_char,
+        error: *mut SdBusError,
+        type_: c_char,
+        ret: *mut c_void,
+    ) -> c_int;
+
+    pub fn sd_bus_set_property(
+        bus: *mut SdBus,
+        destination: *const c_char,
+        path: *const c_char,
+        interface: *const c_char,
+        member: *const c_char,
+        error: *mut SdBusError,
+        type_: *const c_char,
+        ...
+    ) -> c_int;
+
+    pub fn sd_bus_message_read(
+        msg: *mut SdBusMessage,
+        types: *const c_char,
+        ...
+    ) -> c_int;
+
+    pub fn sd_bus_message_enter_container(
+        msg: *mut SdBusMessage,
+        type_: c_char,
+        contents: *const c_char,
+    ) -> c_int;
+
+    pub fn sd_bus_message_exit_container(msg: *mut SdBusMessage) -> c_int;
+
+    pub fn sd_bus_message_skip(
+        msg: *mut SdBusMessage,
+        types: *const c_char,
+    ) -> c_int;
+
+    pub fn sd_bus_message_new_method_call(
+        bus: *mut SdBus,
+        msg: *mut *mut SdBusMessage,
+        destination: *const c_char,
+        path: *const c_char,
+        interface: *const c_char,
+        member: *const c_char,
+    ) -> c_int;
+
+    pub fn sd_bus_message_append(
+        msg: *mut SdBusMessage,
+        types: *const c_char,
+        ...
+    ) -> c_int;
+
+    pub fn sd_bus_message_append_array(
+        msg: *mut SdBusMessage,
+        type_: c_char,
+        ptr: *const c_void,
+        size: usize,
+    ) -> c_int;
+
+    pub fn sd_bus_call(
+        bus: *mut SdBus,
+        msg: *mut SdBusMessage,
+        usec: u64,
+        error: *mut SdBusError,
+        reply: *mut *mut SdBusMessage,
+    ) -> c_int;
+
+    pub fn sd_bus_message_unref(msg: *mut SdBusMessage) -> *mut SdBusMessage;
+    pub fn sd_bus_error_free(error: *mut SdBusError);
+}
@@ -3,7 +3,14 @@
 //! Restricts the process to only the syscalls needed for the event loop.
 //! Uses raw BPF instructions + prctl(PR_SET_SECCOMP). No libseccomp.
 //!
-//! SECCOMP_RET_KILL_PROCESS on any syscall not in the whitelist.
+//! SECCOMP_RET_KILL_PROCESS on any syscall not in the whitelist. With the
+//! `seccomp-log` feature (or `install_filter_permissive` in debug builds)
+//! violations are SECCOMP_RET_LOG instead: the kernel audit-logs the syscall
+//! number (`journalctl -k`) and lets it through.
+//!
+//! `build_filter` is architecture-neutral: each architecture has a table of
+//! its own syscall numbers (`nr_x86_64`, `nr_aarch64`) and its AUDIT_ARCH_*
+//! value, and the one for the build target is `nr`.
 
 // BPF instruction encoding
 const BPF_LD: u16 = 0x00;
@@ -16,18 +23,33 @@ const BPF_K: u16 = 0x00;
 
 // seccomp constants
 const SECCOMP_RET_KILL_PROCESS: u32 = 0x80000000;
+const SECCOMP_RET_LOG: u32 = 0x7ffc0000;
 const SECCOMP_RET_ALLOW: u32 = 0x7fff0000;
 const SECCOMP_MODE_FILTER: libc::c_int = 2;
 
 // Architecture
+#[cfg(any(target_arch = "x86_64", test))]
 const AUDIT_ARCH_X86_64: u32 = 0xc000003e;
+#[cfg(any(target_arch = "aarch64", test))]
+const AUDIT_ARCH_AARCH64: u32 = 0xc00000b7;
+
+#[cfg(target_arch = "x86_64")]
+use nr_x86_64 as nr;
+#[cfg(target_arch = "x86_64")]
+const AUDIT_ARCH: u32 = AUDIT_ARCH_X86_64;
+#[cfg(target_arch = "aarch64")]
+use nr_aarch64 as nr;
+#[cfg(target_arch = "aarch64")]
+const AUDIT_ARCH: u32 = AUDIT_ARCH_AARCH64;
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+compile_error!("seccomp.rs has syscall tables for x86_64 and aarch64 only");
 
 // seccomp_data offsets
 const OFFSET_ARCH: u32 = 4;
 const OFFSET_NR: u32 = 0;
 
 #[repr(C)]
-struct SockFilter {
+pub struct SockFilter {
     code: u16,
     jt: u8,
     jf: u8,
@@ -49,7 +71,8 @@ const fn bpf_jump(code: u16, k: u32, jt: u8, jf: u8) -> SockFilter {
 }
 
 /// Syscall numbers (x86_64) -- from asm/unistd_64.h
-mod nr {
+#[cfg(any(target_arch = "x86_64", test))]
+mod nr_x86_64 {
     pub const READ: u32 = 0;
     pub const WRITE: u32 = 1;
     pub const CLOSE: u32 = 3;
@@ -94,8 +117,12 @@ mod nr {
     pub const KILL: u32 = 62;
     pub const UNAME: u32 = 63;
     pub const FCNTL: u32 = 72;
+    pub const FLOCK: u32 = 73;
+    pub const FSYNC: u32 = 74;
+    pub const FTRUNCATE: u32 = 77;
     pub const GETCWD: u32 = 79;
     pub const MKDIR: u32 = 83;
+    pub const RENAME: u32 = 82;
     pub const UNLINK: u32 = 87;
     pub const READLINK: u32 = 89;
     pub const GETTIMEOFDAY: u32 = 96;
@@ -103,6 +130,7 @@ mod nr {
     pub const GETGID: u32 = 104;
     pub const GETEUID: u32 = 107;
     pub const GETEGID: u32 = 108;
+    pub const GETPPID: u32 = 110;
     pub const SIGALTSTACK: u32 = 131;
     pub const PRCTL: u32 = 157;
     pub const ARCH_PRCTL: u32 = 158;
@@ -118,9 +146,12 @@ mod nr {
     pub const MKDIRAT: u32 = 258;
     pub const NEWFSTATAT: u32 = 262;
     pub const UNLINKAT: u32 = 263;
+    pub const RENAMEAT: u32 = 264;
+    pub const LINKAT: u32 = 265;
     pub const READLINKAT: u32 = 267;
     pub const PPOLL: u32 = 271;
     pub const SET_ROBUST_LIST: u32 = 273;
+    pub const SPLICE: u32 = 275;
     pub const EPOLL_WAIT: u32 = 232;
     pub const EPOLL_CTL: u32 = 233;
     pub const SIGNALFD4: u32 = 289;
@@ -132,238 +163,423 @@ mod nr {
     pub const INOTIFY_INIT1: u32 = 294;
     pub const PRLIMIT64: u32 = 302;
     pub const GETRANDOM: u32 = 318;
+    pub const MEMFD_CREATE: u32 = 319;
     pub const STATX: u32 = 332;
+    pub const RENAMEAT2: u32 = 316;
     pub const RSEQ: u32 = 334;
     pub const IO_URING_SETUP: u32 = 425;
     pub const IO_URING_ENTER: u32 = 426;
     pub const IO_URING_REGISTER: u32 = 427;
     pub const CLONE3: u32 = 435;
     pub const FACCESSAT2: u32 = 439;
+
+    /// Everything the daemon (and its curl child, until exec) may call
+    pub const WHITELIST: [u32; 99] = [
+        // --- Core I/O ---
+        READ,
+        WRITE,
+        OPENAT,
+        CLOSE,
+        FSTAT,
+        NEWFSTATAT,
+        LSEEK,
+        PREAD64,
+
+        // --- Memory ---
+        MMAP,
+        MUNMAP,
+        MPROTECT,
+        BRK,
+        MREMAP,
+        MADVISE,
+        // Wayland gamma ramps travel in a sized memfd
+        MEMFD_CREATE,
+        FTRUNCATE,
+
+        // --- io_uring ---
+        IO_URING_SETUP,
+        IO_URING_ENTER,
+        IO_URING_REGISTER,
+
+        // --- Time ---
+        CLOCK_GETTIME,
+        CLOCK_NANOSLEEP,
+        NANOSLEEP,
+        GETTIMEOFDAY,
+
+        // --- ioctl (DRM gamma + inotify) ---
+        IOCTL,
+
+        // --- Process spawn (weather via curl, [hooks] scripts) ---
+        CLONE3,
+        CLONE,
+        EXECVE,
+        PIPE2,
+        DUP2,
+        DUP3,
+        WAIT4,
+        SET_ROBUST_LIST,
+        RSEQ,
+        PRLIMIT64,
+        ARCH_PRCTL,
+        SET_TID_ADDRESS,
+        // sh sets $PPID at startup
+        GETPPID,
+
+        // --- Signals ---
+        RT_SIGPROCMASK,
+        RT_SIGACTION,
+        RT_SIGRETURN,
+        SIGALTSTACK,
+
+        // --- File ops ---
+        UNLINK,
+        UNLINKAT,
+        RENAME,
+        RENAMEAT,
+        RENAMEAT2,
+        // override.json: O_TMPFILE written, fsynced, then linked in
+        LINKAT,
+        FSYNC,
+        MKDIR,
+        MKDIRAT,
+        ACCESS,
+        FACCESSAT2,
+        FCNTL,
+        // weather cache lock
+        FLOCK,
+        GETCWD,
+        READLINK,
+        READLINKAT,
+        STATX,
+        GETRANDOM,
+
+        // --- Process info ---
+        GETPID,
+        GETUID,
+        GETEUID,
+        GETGID,
+        GETEGID,
+        KILL,
+        PRCTL,
+        FUTEX,
+
+        // --- Exit ---
+        EXIT,
+        EXIT_GROUP,
+
+        // --- Event fds (inotify + signalfd) ---
+        SIGNALFD4,
+        INOTIFY_INIT1,
+        INOTIFY_ADD_WATCH,
+
+        // --- Socket I/O (X11/Wayland backend, curl child) ---
+        SOCKET,
+        CONNECT,
+        BIND,
+        SETSOCKOPT,
+        GETSOCKOPT,
+        SHUTDOWN,
+        SENDTO,
+        SENDMSG,
+        SENDMMSG,
+        RECVFROM,
+        RECVMSG,
+        RECVMMSG,
+        GETPEERNAME,
+        GETSOCKNAME,
+        POLL,
+        // also the event loop where io_uring is unavailable
+        PPOLL,
+        // splice-weather's pipe drain under that loop
+        SPLICE,
+        WRITEV,
+        UNAME,
+
+        // --- epoll + eventfd (curl child process) ---
+        EPOLL_CREATE1,
+        EPOLL_CTL,
+        EPOLL_WAIT,
+        EPOLL_PWAIT,
+        EVENTFD2,
+
+        // --- dlopen (backend loading) ---
+        GETDENTS64,
+
+        // --- Rust-specific (allocator, runtime) ---
+        SCHED_YIELD,
+        SCHED_GETAFFINITY,
+    ];
 }
 
-pub fn install_filter() -> bool {
-    // Each ALLOW_SYSCALL expands to 2 instructions: JEQ + RET_ALLOW
-    let filter: &[SockFilter] = &[
-        // Load architecture
-        bpf_stmt(BPF_LD | BPF_W | BPF_ABS, OFFSET_ARCH),
-        // Verify x86_64 -- kill if wrong arch
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, AUDIT_ARCH_X86_64, 1, 0),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_KILL_PROCESS),
-        // Load syscall number
-        bpf_stmt(BPF_LD | BPF_W | BPF_ABS, OFFSET_NR),
+/// Syscall numbers (aarch64) -- from asm-generic/unistd.h. The same
+/// whitelist without the legacy calls aarch64 never had (open-less
+/// access/unlink/rename/mkdir/readlink, poll, dup2, epoll_wait,
+/// arch_prctl): libc goes through the *at and p* versions instead.
+#[cfg(any(target_arch = "aarch64", test))]
+mod nr_aarch64 {
+    pub const GETCWD: u32 = 17;
+    pub const EVENTFD2: u32 = 19;
+    pub const EPOLL_CREATE1: u32 = 20;
+    pub const EPOLL_CTL: u32 = 21;
+    pub const EPOLL_PWAIT: u32 = 22;
+    pub const DUP3: u32 = 24;
+    pub const FCNTL: u32 = 25;
+    pub const INOTIFY_INIT1: u32 = 26;
+    pub const INOTIFY_ADD_WATCH: u32 = 27;
+    pub const IOCTL: u32 = 29;
+    pub const FLOCK: u32 = 32;
+    pub const MKDIRAT: u32 = 34;
+    pub const UNLINKAT: u32 = 35;
+    pub const LINKAT: u32 = 37;
+    pub const RENAMEAT: u32 = 38;
+    pub const FTRUNCATE: u32 = 46;
+    pub const FACCESSAT: u32 = 48;
+    pub const OPENAT: u32 = 56;
+    pub const CLOSE: u32 = 57;
+    pub const PIPE2: u32 = 59;
+    pub const GETDENTS64: u32 = 61;
+    pub const LSEEK: u32 = 62;
+    pub const READ: u32 = 63;
+    pub const WRITE: u32 = 64;
+    pub const WRITEV: u32 = 66;
+    pub const PREAD64: u32 = 67;
+    pub const PPOLL: u32 = 73;
+    pub const SIGNALFD4: u32 = 74;
+    pub const SPLICE: u32 = 76;
+    pub const READLINKAT: u32 = 78;
+    pub const NEWFSTATAT: u32 = 79;
+    pub const FSTAT: u32 = 80;
+    pub const FSYNC: u32 = 82;
+    pub const EXIT: u32 = 93;
+    pub const EXIT_GROUP: u32 = 94;
+    pub const SET_TID_ADDRESS: u32 = 96;
+    pub const FUTEX: u32 = 98;
+    pub const SET_ROBUST_LIST: u32 = 99;
+    pub const NANOSLEEP: u32 = 101;
+    pub const CLOCK_GETTIME: u32 = 113;
+    pub const CLOCK_NANOSLEEP: u32 = 115;
+    pub const SCHED_GETAFFINITY: u32 = 123;
+    pub const SCHED_YIELD: u32 = 124;
+    pub const KILL: u32 = 129;
+    pub const SIGALTSTACK: u32 = 132;
+    pub const RT_SIGACTION: u32 = 134;
+    pub const RT_SIGPROCMASK: u32 = 135;
+    pub const RT_SIGRETURN: u32 = 139;
+    pub const UNAME: u32 = 160;
+    pub const PRCTL: u32 = 167;
+    pub const GETTIMEOFDAY: u32 = 169;
+    pub const GETPID: u32 = 172;
+    pub const GETPPID: u32 = 173;
+    pub const GETUID: u32 = 174;
+    pub const GETEUID: u32 = 175;
+    pub const GETGID: u32 = 176;
+    pub const GETEGID: u32 = 177;
+    pub const SOCKET: u32 = 198;
+    pub const BIND: u32 = 200;
+    pub const CONNECT: u32 = 203;
+    pub const GETSOCKNAME: u32 = 204;
+    pub const GETPEERNAME: u32 = 205;
+    pub const SENDTO: u32 = 206;
+    pub const RECVFROM: u32 = 207;
+    pub const SETSOCKOPT: u32 = 208;
+    pub const GETSOCKOPT: u32 = 209;
+    pub const SHUTDOWN: u32 = 210;
+    pub const SENDMSG: u32 = 211;
+    pub const RECVMSG: u32 = 212;
+    pub const BRK: u32 = 214;
+    pub const MUNMAP: u32 = 215;
+    pub const MREMAP: u32 = 216;
+    pub const CLONE: u32 = 220;
+    pub const EXECVE: u32 = 221;
+    pub const MMAP: u32 = 222;
+    pub const MPROTECT: u32 = 226;
+    pub const MADVISE: u32 = 233;
+    pub const RECVMMSG: u32 = 243;
+    pub const WAIT4: u32 = 260;
+    pub const PRLIMIT64: u32 = 261;
+    pub const SENDMMSG: u32 = 269;
+    pub const RENAMEAT2: u32 = 276;
+    pub const GETRANDOM: u32 = 278;
+    pub const MEMFD_CREATE: u32 = 279;
+    pub const STATX: u32 = 291;
+    pub const RSEQ: u32 = 293;
+    pub const IO_URING_SETUP: u32 = 425;
+    pub const IO_URING_ENTER: u32 = 426;
+    pub const IO_URING_REGISTER: u32 = 427;
+    pub const CLONE3: u32 = 435;
+    pub const FACCESSAT2: u32 = 439;
 
+    /// Everything the daemon (and its curl child, until exec) may call;
+    /// grouped as in nr_x86_64
+    pub const WHITELIST: [u32; 91] = [
         // --- Core I/O ---
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::READ, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::WRITE, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::OPENAT, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::CLOSE, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::FSTAT, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::NEWFSTATAT, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::LSEEK, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::PREAD64, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
+        READ,
+        WRITE,
+        OPENAT,
+        CLOSE,
+        FSTAT,
+        NEWFSTATAT,
+        LSEEK,
+        PREAD64,
 
         // --- Memory ---
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::MMAP, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::MUNMAP, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::MPROTECT, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::BRK, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::MREMAP, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::MADVISE, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
+        MMAP,
+        MUNMAP,
+        MPROTECT,
+        BRK,
+        MREMAP,
+        MADVISE,
+        MEMFD_CREATE,
+        FTRUNCATE,
 
         // --- io_uring ---
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::IO_URING_SETUP, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::IO_URING_ENTER, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::IO_URING_REGISTER, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
+        IO_URING_SETUP,
+        IO_URING_ENTER,
+        IO_URING_REGISTER,
 
         // --- Time ---
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::CLOCK_GETTIME, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::CLOCK_NANOSLEEP, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::NANOSLEEP, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::GETTIMEOFDAY, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
+        CLOCK_GETTIME,
+        CLOCK_NANOSLEEP,
+        NANOSLEEP,
+        GETTIMEOFDAY,
 
         // --- ioctl (DRM gamma + inotify) ---
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::IOCTL, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-
-        // --- Process spawn (weather via curl) ---
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::CLONE3, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::CLONE, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::EXECVE, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::PIPE2, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::DUP2, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::DUP3, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::WAIT4, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::SET_ROBUST_LIST, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::RSEQ, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::PRLIMIT64, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::ARCH_PRCTL, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::SET_TID_ADDRESS, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
+        IOCTL,
+
+        // --- Process spawn (weather via curl, [hooks] scripts) ---
+        CLONE3,
+        CLONE,
+        EXECVE,
+        PIPE2,
+        DUP3,
+        WAIT4,
+        SET_ROBUST_LIST,
+        RSEQ,
+        PRLIMIT64,
+        SET_TID_ADDRESS,
+        GETPPID,
 
         // --- Signals ---
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::RT_SIGPROCMASK, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::RT_SIGACTION, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::RT_SIGRETURN, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::SIGALTSTACK, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
+        RT_SIGPROCMASK,
+        RT_SIGACTION,
+        RT_SIGRETURN,
+        SIGALTSTACK,
 
         // --- File ops ---
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::UNLINK, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::UNLINKAT, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::MKDIR, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::MKDIRAT, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::ACCESS, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::FACCESSAT2, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::FCNTL, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::GETCWD, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::READLINK, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::READLINKAT, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::STATX, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::GETRANDOM, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
+        UNLINKAT,
+        RENAMEAT,
+        RENAMEAT2,
+        LINKAT,
+        FSYNC,
+        MKDIRAT,
+        FACCESSAT,
+        FACCESSAT2,
+        FCNTL,
+        FLOCK,
+        GETCWD,
+        READLINKAT,
+        STATX,
+        GETRANDOM,
 
         // --- Process info ---
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::GETPID, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::GETUID, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::GETEUID, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::GETGID, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::GETEGID, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::KILL, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::PRCTL, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::FUTEX, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
+        GETPID,
+        GETUID,
+        GETEUID,
+        GETGID,
+        GETEGID,
+        KILL,
+        PRCTL,
+        FUTEX,
 
         // --- Exit ---
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::EXIT, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::EXIT_GROUP, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
+        EXIT,
+        EXIT_GROUP,
 
         // --- Event fds (inotify + signalfd) ---
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::SIGNALFD4, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::INOTIFY_INIT1, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::INOTIFY_ADD_WATCH, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
+        SIGNALFD4,
+        INOTIFY_INIT1,
+        INOTIFY_ADD_WATCH,
 
         // --- Socket I/O (X11/Wayland backend, curl child) ---
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::SOCKET, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::CONNECT, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::BIND, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::SETSOCKOPT, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::GETSOCKOPT, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::SHUTDOWN, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::SENDTO, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::SENDMSG, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::SENDMMSG, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::RECVFROM, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::RECVMSG, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::RECVMMSG, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::GETPEERNAME, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::GETSOCKNAME, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::POLL, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::PPOLL, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::WRITEV, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::UNAME, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
+        SOCKET,
+        CONNECT,
+        BIND,
+        SETSOCKOPT,
+        GETSOCKOPT,
+        SHUTDOWN,
+        SENDTO,
+        SENDMSG,
+        SENDMMSG,
+        RECVFROM,
+        RECVMSG,
+        RECVMMSG,
+        GETPEERNAME,
+        GETSOCKNAME,
+        PPOLL,
+        SPLICE,
+        WRITEV,
+        UNAME,
 
         // --- epoll + eventfd (curl child process) ---
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::EPOLL_CREATE1, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::EPOLL_CTL, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::EPOLL_WAIT, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::EPOLL_PWAIT, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::EVENTFD2, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
+        EPOLL_CREATE1,
+        EPOLL_CTL,
+        EPOLL_PWAIT,
+        EVENTFD2,
 
         // --- dlopen (backend loading) ---
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::GETDENTS64, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
+        GETDENTS64,
 
         // --- Rust-specific (allocator, runtime) ---
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::SCHED_YIELD, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, nr::SCHED_GETAFFINITY, 0, 1),
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
-
-        // Default: KILL
-        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_KILL_PROCESS),
+        SCHED_YIELD,
+        SCHED_GETAFFINITY,
     ];
+}
+
+/// Most instructions the kernel accepts in one filter program
+const BPF_MAXINSNS: usize = 4096;
+
+/// Instructions besides the allow pairs: load arch, check it, kill on a
+/// mismatch, load the syscall number, default action
+const FILTER_OVERHEAD: usize = 5;
 
+// Each allowed syscall costs a JEQ + RET_ALLOW pair
+const _: () = assert!(FILTER_OVERHEAD + 2 * nr::WHITELIST.len() <= BPF_MAXINSNS);
+
+/// Build the whitelist program for `arch_id` (an AUDIT_ARCH_* value):
+/// another ABI is killed outright, `syscalls` (numbered for that ABI) are
+/// allowed and everything else is killed.
+pub fn build_filter(arch_id: u32, syscalls: &[u32]) -> Vec<SockFilter> {
+    let mut filter = Vec::with_capacity(FILTER_OVERHEAD + 2 * syscalls.len());
+    // Load architecture -- kill if wrong arch
+    filter.push(bpf_stmt(BPF_LD | BPF_W | BPF_ABS, OFFSET_ARCH));
+    filter.push(bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, arch_id, 1, 0));
+    filter.push(bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_KILL_PROCESS));
+    // Load syscall number
+    filter.push(bpf_stmt(BPF_LD | BPF_W | BPF_ABS, OFFSET_NR));
+    for &syscall in syscalls {
+        filter.push(bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, syscall, 0, 1));
+        filter.push(bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW));
+    }
+    // Default: KILL
+    filter.push(bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_KILL_PROCESS));
+    filter
+}
+
+/// The whitelist for the running architecture. Unlisted syscalls are
+/// killed when `kill_on_violation`, otherwise logged and allowed.
+fn native_filter(kill_on_violation: bool) -> Vec<SockFilter> {
+    let mut filter = build_filter(AUDIT_ARCH, &nr::WHITELIST);
+    if !kill_on_violation {
+        // The default action is the last instruction (the wrong-arch kill stays)
+        if let Some(default) = filter.last_mut() {
+            default.k = SECCOMP_RET_LOG;
+        }
+    }
+    filter
+}
+
+fn load_filter(filter: &[SockFilter]) -> bool {
     let prog = SockFprog {
         len: filter.len() as u16,
         filter: filter.as_ptr(),
@@ -377,3 +593,134 @@ pub fn install_filter() -> bool {
         ) == 0
     }
 }
+
+/// Syscalls the whitelist for this architecture allows
+pub fn whitelist_len() -> usize {
+    nr::WHITELIST.len()
+}
+
+/// Whether the kernel has seccomp filter mode: a NULL program fails with
+/// EFAULT when it does and EINVAL when it does not.
+pub fn filter_supported() -> bool {
+    let ret = unsafe {
+        libc::prctl(
+            libc::PR_SET_SECCOMP,
+            SECCOMP_MODE_FILTER,
+            std::ptr::null::<SockFprog>(),
+        )
+    };
+    ret < 0 && std::io::Error::last_os_error().raw_os_error() != Some(libc::EINVAL)
+}
+
+/// Install the syscall whitelist. Kills on violation unless built with the
+/// `seccomp-log` feature.
+pub fn install_filter() -> bool {
+    load_filter(&native_filter(!cfg!(feature = "seccomp-log")))
+}
+
+/// Install the whitelist in log-only mode, for iterating on the allowlist.
+#[cfg(debug_assertions)]
+pub fn install_filter_permissive() -> bool {
+    load_filter(&native_filter(false))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filter_layout() {
+        let filter = build_filter(AUDIT_ARCH_X86_64, &[nr::READ, nr::WRITE]);
+        assert_eq!(filter.len(), FILTER_OVERHEAD + 4);
+        assert_eq!((filter[1].k, filter[1].jt, filter[1].jf), (AUDIT_ARCH_X86_64, 1, 0));
+        assert_eq!(filter[2].k, SECCOMP_RET_KILL_PROCESS);
+        assert_eq!(filter[3].k, OFFSET_NR);
+        assert_eq!((filter[4].k, filter[4].jt, filter[4].jf), (nr::READ, 0, 1));
+        assert_eq!(filter[5].k, SECCOMP_RET_ALLOW);
+        assert_eq!(filter[6].k, nr::WRITE);
+        assert_eq!(filter.last().unwrap().k, SECCOMP_RET_KILL_PROCESS);
+
+        // Log mode only relaxes the default, never the architecture check
+        let permissive = native_filter(false);
+        assert_eq!(permissive.len(), FILTER_OVERHEAD + 2 * nr::WHITELIST.len());
+        assert_eq!(permissive[2].k, SECCOMP_RET_KILL_PROCESS);
+        assert_eq!(permissive.last().unwrap().k, SECCOMP_RET_LOG);
+        assert_eq!(native_filter(true).last().unwrap().k, SECCOMP_RET_KILL_PROCESS);
+
+        assert_eq!(permissive[1].k, AUDIT_ARCH);
+    }
+
+    #[test]
+    fn whitelists_have_no_duplicates() {
+        for (arch, whitelist) in [("x86_64", &nr_x86_64::WHITELIST[..]), ("aarch64", &nr_aarch64::WHITELIST[..])] {
+            let mut sorted = whitelist.to_vec();
+            sorted.sort_unstable();
+            sorted.dedup();
+            assert_eq!(sorted.len(), whitelist.len(), "duplicate syscall in the {} WHITELIST", arch);
+            assert!(FILTER_OVERHEAD + 2 * whitelist.len() <= BPF_MAXINSNS);
+        }
+        let filter = build_filter(AUDIT_ARCH_AARCH64, &nr_aarch64::WHITELIST);
+        assert_eq!((filter[1].k, filter[4].k), (AUDIT_ARCH_AARCH64, nr_aarch64::READ));
+    }
+
+    #[test]
+    fn hook_runs_under_kill_filter() {
+        use crate::config::HookParams;
+        use crate::hooks::HookRunner;
+        use std::os::unix::fs::PermissionsExt;
+
+        if !filter_supported() {
+            return;
+        }
+        let dir = std::env::temp_dir().join(format!("abraxas-seccomp-hook-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("log");
+        let script = dir.join("hook.sh");
+        std::fs::write(&script, format!("#!/bin/sh\necho \"$ABRAXAS_TEMP $ABRAXAS_MODE\" > {}\n", log.display())).unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let mut hooks = HookRunner::new(HookParams {
+            on_change: Some(script),
+            on_mode_change: None,
+            change_delta: 100,
+            min_interval_sec: 0,
+        });
+        let filter = native_filter(true);
+
+        // The filter can't be taken off again, so it goes on in a child;
+        // an unlisted syscall in the spawn or the script kills with SIGSYS
+        let pid = unsafe { libc::fork() };
+        assert!(pid >= 0);
+        if pid == 0 {
+            unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) };
+            let code = if load_filter(&filter) {
+                hooks.notify(Some(3400), "night", 1.0, 1000);
+                hooks.wait();
+                0
+            } else {
+                2
+            };
+            unsafe { libc::_exit(code) };
+        }
+
+        let mut status = 0;
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+        while unsafe { libc::waitpid(pid, &mut status, libc::WNOHANG) } == 0 {
+            if std::time::Instant::now() > deadline {
+                unsafe { libc::kill(pid, libc::SIGKILL) };
+                unsafe { libc::waitpid(pid, &mut status, 0) };
+                panic!("hook child hung");
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert!(
+            libc::WIFEXITED(status) && libc::WEXITSTATUS(status) == 0,
+            "hook child died: status {:#x} (SIGSYS = {})",
+            status,
+            libc::SIGSYS
+        );
+        // The script itself runs under the filter too
+        let out = std::fs::read_to_string(&log).unwrap_or_default();
+        assert_eq!(out, "3400 night\n", "hook script was killed or failed under the filter");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
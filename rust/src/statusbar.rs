@@ -0,0 +1,145 @@
+//! One-line status for waybar, i3status and friends (--status --bar).
+//!
+//! Bars poll every couple of seconds, so this only reads files: the
+//! daemon's state.json when it runs, else config.ini and the weather
+//! cache. No gamma backend and no network.
+
+/// What the bar shows
+pub struct BarState {
+    /// Applied temperature, or the computed target when nothing is applied
+    pub temp: i32,
+    /// clear, dark, night, manual or paused
+    pub mode: String,
+    pub sun_elevation: f64,
+    /// Cloud cover percent (None: no weather)
+    pub cloud: Option<i32>,
+    /// The daemon is running, so `temp` is what the screen shows
+    pub daemon: bool,
+}
+
+fn icon(mode: &str) -> &'static str {
+    match mode {
+        "clear" => "\u{2600}",  // ☀
+        "dark" => "\u{2601}",   // ☁
+        "night" => "\u{263e}",  // ☾
+        "manual" => "\u{270b}", // ✋
+        _ => "\u{23f8}",        // ⏸
+    }
+}
+
+fn placeholder(name: &str, s: &BarState) -> Option<String> {
+    Some(match name {
+        "temp" => s.temp.to_string(),
+        "mode" => s.mode.clone(),
+        "icon" => icon(&s.mode).to_string(),
+        "sun_elevation" => format!("{:.1}", s.sun_elevation),
+        "cloud" => s.cloud.map_or_else(|| "-".to_string(), |c| c.to_string()),
+        _ => return None,
+    })
+}
+
+/// Fill `{temp}`, `{mode}`, `{icon}`, `{sun_elevation}` and `{cloud}` in a
+/// --bar-format template. Anything else in braces is left as written.
+pub fn expand(template: &str, s: &BarState) -> String {
+    let mut out = String::with_capacity(template.len() + 8);
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        out.push_str(&rest[..open]);
+        rest = &rest[open..];
+        let filled = rest
+            .find('}')
+            .and_then(|close| placeholder(&rest[1..close], s).map(|value| (value, close)));
+        match filled {
+            Some((value, close)) => {
+                out.push_str(&value);
+                rest = &rest[close + 1..];
+            }
+            None => {
+                out.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Waybar custom module JSON: text, tooltip, and the mode as class (for
+/// CSS) and alt (for format-icons). A paused daemon has restored the
+/// original gamma, so no temperature is shown for it.
+pub fn waybar_json(s: &BarState) -> String {
+    let paused = s.daemon && s.mode == "paused";
+    let text = if paused { "paused".to_string() } else { format!("{}K", s.temp) };
+    let mut tooltip = if paused {
+        format!("ABRAXAS: paused\nSun: {:.1}\u{b0}", s.sun_elevation)
+    } else {
+        format!("ABRAXAS: {}K, {}\nSun: {:.1}\u{b0}", s.temp, s.mode, s.sun_elevation)
+    };
+    if let Some(c) = s.cloud {
+        tooltip.push_str(&format!("\nClouds: {}%", c));
+    }
+    if !s.daemon {
+        tooltip.push_str("\nDaemon not running");
+    }
+    serde_json::json!({
+        "text": text,
+        "tooltip": tooltip,
+        "class": s.mode,
+        "alt": s.mode,
+    })
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn night() -> BarState {
+        BarState {
+            temp: 3400,
+            mode: "night".to_string(),
+            sun_elevation: -12.34,
+            cloud: Some(40),
+            daemon: true,
+        }
+    }
+
+    #[test]
+    fn bar_format_placeholders_expanded() {
+        let s = night();
+        assert_eq!(expand("{temp}K {icon}", &s), "3400K \u{263e}");
+        assert_eq!(expand("{mode} {sun_elevation}\u{b0} {cloud}%", &s), "night -12.3\u{b0} 40%");
+        assert_eq!(expand("no placeholders", &s), "no placeholders");
+        assert_eq!(expand("", &s), "");
+
+        // Unknown names and stray braces pass through
+        assert_eq!(expand("{temp} {nope} {", &s), "3400 {nope} {");
+        assert_eq!(expand("}{{temp}}", &s), "}{3400}");
+        assert_eq!(expand("{temp", &s), "{temp");
+
+        let s = BarState { cloud: None, mode: "paused".to_string(), ..night() };
+        assert_eq!(expand("{cloud} {icon}", &s), "- \u{23f8}");
+    }
+
+    #[test]
+    fn waybar_json_fields() {
+        let v: serde_json::Value = serde_json::from_str(&waybar_json(&night())).unwrap();
+        assert_eq!(v["text"], "3400K");
+        assert_eq!(v["class"], "night");
+        assert_eq!(v["alt"], "night");
+        assert_eq!(v["tooltip"], "ABRAXAS: 3400K, night\nSun: -12.3\u{b0}\nClouds: 40%");
+
+        let s = BarState { cloud: None, daemon: false, ..night() };
+        let v: serde_json::Value = serde_json::from_str(&waybar_json(&s)).unwrap();
+        assert_eq!(v["tooltip"], "ABRAXAS: 3400K, night\nSun: -12.3\u{b0}\nDaemon not running");
+    }
+
+    #[test]
+    fn paused_daemon_shown_as_paused() {
+        let s = BarState { mode: "paused".to_string(), ..night() };
+        let v: serde_json::Value = serde_json::from_str(&waybar_json(&s)).unwrap();
+        assert_eq!(v["text"], "paused");
+        assert_eq!(v["class"], "paused");
+        assert_eq!(v["tooltip"], "ABRAXAS: paused\nSun: -12.3\u{b0}\nClouds: 40%");
+    }
+}
@@ -0,0 +1,92 @@
+//! Thermal-aware brightness dimming.
+//!
+//! Reads a sysfs thermal zone (millidegrees Celsius) and reduces output
+//! brightness while the CPU is above a threshold. The temp file is opened
+//! once at daemon startup, before landlock hides /sys; each tick is a
+//! single pread on the held fd.
+
+use std::fs::File;
+use std::os::unix::fs::FileExt;
+
+use crate::config::ThermalParams;
+
+pub struct ThermalMonitor {
+    file: File,
+    threshold_c: f64,
+    reduction: f32,
+    dimming: bool,
+}
+
+impl ThermalMonitor {
+    /// Open the configured temp file. Returns None when disabled or unreadable.
+    pub fn open(params: &ThermalParams) -> Option<Self> {
+        if !params.enabled {
+            return None;
+        }
+        match File::open(&params.cpu_temp_file) {
+            Ok(file) => Some(Self {
+                file,
+                threshold_c: params.temp_threshold_c,
+                reduction: params.brightness_reduction,
+                dimming: false,
+            }),
+            Err(e) => {
+                eprintln!(
+                    "[thermal] Cannot open {}: {}",
+                    params.cpu_temp_file.display(),
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    /// Pick up new threshold/reduction after a config reload. The temp file
+    /// itself stays open; changing cpu_temp_file needs a daemon restart.
+    pub fn update(&mut self, params: &ThermalParams) {
+        self.threshold_c = params.temp_threshold_c;
+        self.reduction = params.brightness_reduction;
+    }
+
+    /// Current temperature in degrees Celsius.
+    fn read_celsius(&self) -> Option<f64> {
+        let mut buf = [0u8; 32];
+        let n = self.file.read_at(&mut buf, 0).ok()?;
+        let text = std::str::from_utf8(&buf[..n]).ok()?;
+        let milli: i64 = text.trim().parse().ok()?;
+        Some(milli as f64 / 1000.0)
+    }
+
+    /// Brightness to apply given the configured brightness and current CPU
+    /// temperature. Logs when dimming starts or stops.
+    pub fn adjust_brightness(&mut self, brightness: f32) -> f32 {
+        let temp_c = match self.read_celsius() {
+            Some(t) => t,
+            None => return brightness,
+        };
+
+        let hot = temp_c > self.threshold_c;
+        let dimmed = (brightness * (1.0 - self.reduction)).max(0.1);
+
+        if hot && !self.dimming {
+            eprintln!(
+                "[thermal] Temp: {:.0}°C, dimming to {:.0}%",
+                temp_c,
+                dimmed * 100.0
+            );
+        } else if !hot && self.dimming {
+            eprintln!(
+                "[thermal] Temp: {:.0}°C, restoring {:.0}%",
+                temp_c,
+                brightness * 100.0
+            );
+        }
+        self.dimming = hot;
+
+        if hot {
+            dimmed
+        } else {
+            brightness
+        }
+    }
+}
@@ -8,9 +8,11 @@
 //! Memory ordering: acquire/release fences around shared ring indices.
 //! The kernel writes cq_tail and reads sq_tail; userspace does the inverse.
 
+use std::cell::Cell;
 use std::sync::atomic::{fence, Ordering};
 
-// Syscall numbers (x86_64)
+// Syscall numbers: the same on every architecture (x86_64, aarch64, ...),
+// as for every syscall added since Linux 5.1 (424 on)
 const NR_IO_URING_SETUP: libc::c_long = 425;
 const NR_IO_URING_ENTER: libc::c_long = 426;
 
@@ -26,10 +28,21 @@ const IORING_ENTER_GETEVENTS: u32 = 1;
 const IORING_OP_POLL_ADD: u8 = 6;
 const IORING_OP_TIMEOUT: u8 = 11;
 const IORING_OP_ASYNC_CANCEL: u8 = 14;
+const IORING_OP_READ: u8 = 22;
+#[cfg(feature = "splice-weather")]
+const IORING_OP_SPLICE: u8 = 30;
+
+// io_uring_params.features: kernel buffers CQEs instead of dropping them
+// when the CQ ring is full (Linux 5.5+)
+const IORING_FEAT_NODROP: u32 = 1 << 1;
 
 // Multi-shot poll (Linux 5.13+) -- sqe.len flag
 const IORING_POLL_ADD_MULTI: u32 = 1 << 0;
 
+// Multi-shot timeout (Linux 5.19+) -- sqe.timeout_flags flag; older
+// kernels reject the SQE with -EINVAL
+const IORING_TIMEOUT_MULTISHOT: u32 = 1 << 6;
+
 // CQE flags
 pub const IORING_CQE_F_MORE: u32 = 1 << 1;
 
@@ -39,6 +52,21 @@ pub const EV_SIGNAL: u64 = 2;
 pub const EV_TIMEOUT: u64 = 3;
 pub const EV_CANCEL: u64 = 4;
 pub const EV_WEATHER: u64 = 5;
+#[cfg(feature = "splice-weather")]
+pub const EV_SPLICE: u64 = 6;
+
+/// EV_SPLICE with a fetch generation in the upper 32 bits
+#[cfg(feature = "splice-weather")]
+pub fn splice_tag(generation: u32) -> u64 {
+    EV_SPLICE | (generation as u64) << 32
+}
+
+/// Most one splice from a pipe can move: the default pipe capacity
+#[cfg(feature = "splice-weather")]
+pub const PIPE_SPLICE_MAX: u32 = 64 * 1024;
+pub const EV_WAYLAND: u64 = 10;
+pub const EV_TIMEOUT_FAST: u64 = 11;
+pub const EV_OVERRIDE_READ: u64 = 12;
 
 /// Kernel struct io_sqring_offsets (40 bytes)
 #[repr(C)]
@@ -140,7 +168,12 @@ pub struct AbraxasRing {
     cq_head: *mut u32,
     cq_tail: *mut u32,
     cq_mask: *mut u32,
+    cq_overflow: *mut u32,
     cqes: *mut IoUringCqe,
+    // cq_overflow value at the last peek_cqe, to log each new loss once
+    seen_overflow: Cell<u32>,
+    // io_uring_enter calls so far
+    enters: u64,
 }
 
 impl AbraxasRing {
@@ -154,6 +187,10 @@ impl AbraxasRing {
             return None;
         }
 
+        if params.features & IORING_FEAT_NODROP == 0 {
+            eprintln!("[uring] Kernel lacks IORING_FEAT_NODROP, CQ overflow would drop events");
+        }
+
         // Map SQ ring
         let sq_ring_size =
             params.sq_off.array as usize + params.sq_entries as usize * std::mem::size_of::<u32>();
@@ -232,20 +269,24 @@ impl AbraxasRing {
             cq_head: unsafe { cq.add(params.cq_off.head as usize) as *mut u32 },
             cq_tail: unsafe { cq.add(params.cq_off.tail as usize) as *mut u32 },
             cq_mask: unsafe { cq.add(params.cq_off.ring_mask as usize) as *mut u32 },
+            cq_overflow: unsafe { cq.add(params.cq_off.overflow as usize) as *mut u32 },
             cqes: unsafe { cq.add(params.cq_off.cqes as usize) as *mut IoUringCqe },
+            seen_overflow: Cell::new(0),
+            enters: 0,
         })
     }
 
-    /// Get next SQE slot, zeroed.
+    /// Get next SQE slot, zeroed. A full SQ ring is flushed to the kernel
+    /// first; None only if the kernel took none of it.
     fn get_sqe(&mut self) -> Option<*mut IoUringSqe> {
+        if self.sq_free() == 0 {
+            self.flush();
+            if self.sq_free() == 0 {
+                return None;
+            }
+        }
         unsafe {
             let tail = *self.sq_tail;
-            let head = *self.sq_head;
-
-            if tail - head >= self.sq_entries {
-                return None; // Ring full
-            }
-
             let idx = tail & *self.sq_mask;
             *self.sq_array.add(idx as usize) = idx;
 
@@ -255,6 +296,33 @@ impl AbraxasRing {
         }
     }
 
+    /// SQ slots not yet handed to the kernel
+    fn sq_free(&self) -> u32 {
+        unsafe {
+            let tail = *self.sq_tail;
+            fence(Ordering::Acquire);
+            let head = *self.sq_head;
+            self.sq_entries - tail.wrapping_sub(head)
+        }
+    }
+
+    /// Submit queued SQEs without waiting for any completion.
+    fn flush(&mut self) {
+        unsafe {
+            let to_submit = (*self.sq_tail).wrapping_sub(*self.sq_head);
+            self.enters += 1;
+            libc::syscall(
+                NR_IO_URING_ENTER,
+                self.ring_fd,
+                to_submit,
+                0u32, // min_complete
+                0u32,
+                std::ptr::null::<libc::c_void>(),
+                0usize,
+            );
+        }
+    }
+
     /// Commit one SQE by advancing sq_tail.
     fn commit_sqe(&mut self) {
         fence(Ordering::Release);
@@ -262,42 +330,114 @@ impl AbraxasRing {
     }
 
     /// Multi-shot POLL_ADD: fd stays monitored until closed or cancelled.
-    pub fn prep_poll(&mut self, fd: i32, user_data: u64) {
-        if let Some(sqe) = self.get_sqe() {
-            unsafe {
-                (*sqe).opcode = IORING_OP_POLL_ADD;
-                (*sqe).fd = fd;
-                (*sqe).len = IORING_POLL_ADD_MULTI;
-                (*sqe).rw_flags = libc::POLLIN as u32;
-                (*sqe).user_data = user_data;
-            }
-            self.commit_sqe();
+    /// Like every prep_*, false if no SQ slot frees up even after a flush.
+    pub fn prep_poll(&mut self, fd: i32, user_data: u64) -> bool {
+        let sqe = match self.get_sqe() {
+            Some(sqe) => sqe,
+            None => return false,
+        };
+        unsafe {
+            (*sqe).opcode = IORING_OP_POLL_ADD;
+            (*sqe).fd = fd;
+            (*sqe).len = IORING_POLL_ADD_MULTI;
+            (*sqe).rw_flags = libc::POLLIN as u32;
+            (*sqe).user_data = user_data;
         }
+        self.commit_sqe();
+        true
     }
 
-    pub fn prep_timeout(&mut self, ts: &KernelTimespec, user_data: u64) {
-        if let Some(sqe) = self.get_sqe() {
-            unsafe {
-                (*sqe).opcode = IORING_OP_TIMEOUT;
-                (*sqe).fd = -1;
-                (*sqe).addr = ts as *const KernelTimespec as u64;
-                (*sqe).len = 1; // 1 timespec entry; event count is sqe.off (0 = pure timeout)
-                (*sqe).user_data = user_data;
-            }
-            self.commit_sqe();
+    pub fn prep_timeout(&mut self, ts: &KernelTimespec, user_data: u64) -> bool {
+        let sqe = match self.get_sqe() {
+            Some(sqe) => sqe,
+            None => return false,
+        };
+        unsafe {
+            (*sqe).opcode = IORING_OP_TIMEOUT;
+            (*sqe).fd = -1;
+            (*sqe).addr = ts as *const KernelTimespec as u64;
+            (*sqe).len = 1; // 1 timespec entry; event count is sqe.off (0 = pure timeout)
+            (*sqe).user_data = user_data;
         }
+        self.commit_sqe();
+        true
     }
 
-    pub fn prep_cancel(&mut self, target_user_data: u64, user_data: u64) {
-        if let Some(sqe) = self.get_sqe() {
-            unsafe {
-                (*sqe).opcode = IORING_OP_ASYNC_CANCEL;
-                (*sqe).fd = -1;
-                (*sqe).addr = target_user_data;
-                (*sqe).user_data = user_data;
-            }
-            self.commit_sqe();
+    /// Timeout the kernel re-arms after every expiry until cancelled: each
+    /// expiry posts -ETIME with IORING_CQE_F_MORE set.
+    pub fn prep_timeout_multishot(&mut self, ts: &KernelTimespec, user_data: u64) -> bool {
+        let sqe = match self.get_sqe() {
+            Some(sqe) => sqe,
+            None => return false,
+        };
+        unsafe {
+            (*sqe).opcode = IORING_OP_TIMEOUT;
+            (*sqe).fd = -1;
+            (*sqe).addr = ts as *const KernelTimespec as u64;
+            (*sqe).len = 1;
+            (*sqe).off = 0; // repeat count, 0 = until cancelled
+            (*sqe).rw_flags = IORING_TIMEOUT_MULTISHOT;
+            (*sqe).user_data = user_data;
         }
+        self.commit_sqe();
+        true
+    }
+
+    pub fn prep_cancel(&mut self, target_user_data: u64, user_data: u64) -> bool {
+        let sqe = match self.get_sqe() {
+            Some(sqe) => sqe,
+            None => return false,
+        };
+        unsafe {
+            (*sqe).opcode = IORING_OP_ASYNC_CANCEL;
+            (*sqe).fd = -1;
+            (*sqe).addr = target_user_data;
+            (*sqe).user_data = user_data;
+        }
+        self.commit_sqe();
+        true
+    }
+
+    /// Read up to `len` bytes from `fd` at `offset` into the buffer at
+    /// `buf_addr` (Linux 5.6+; older kernels complete it with -EINVAL).
+    /// The buffer must stay put until the completion is reaped.
+    pub fn prep_read(&mut self, fd: i32, buf_addr: u64, len: u32, offset: u64, user_data: u64) -> bool {
+        let sqe = match self.get_sqe() {
+            Some(sqe) => sqe,
+            None => return false,
+        };
+        unsafe {
+            (*sqe).opcode = IORING_OP_READ;
+            (*sqe).fd = fd;
+            (*sqe).addr = buf_addr;
+            (*sqe).len = len;
+            (*sqe).off = offset;
+            (*sqe).user_data = user_data;
+        }
+        self.commit_sqe();
+        true
+    }
+
+    /// Splice up to `len` bytes from a pipe into `dst_fd` at its file
+    /// position (Linux 5.7+). Blocks in an io-wq worker, not in the caller,
+    /// until data or EOF arrives on a blocking pipe.
+    #[cfg(feature = "splice-weather")]
+    pub fn prep_splice(&mut self, src_fd: i32, dst_fd: i32, len: u32, user_data: u64) -> bool {
+        let sqe = match self.get_sqe() {
+            Some(sqe) => sqe,
+            None => return false,
+        };
+        unsafe {
+            (*sqe).opcode = IORING_OP_SPLICE;
+            (*sqe).fd = dst_fd;
+            (*sqe).off = u64::MAX;  // off_out -1: use and advance the file position
+            (*sqe).addr = u64::MAX; // off_in -1: pipes have no offset
+            (*sqe).splice_fd_in = src_fd;
+            (*sqe).len = len;
+            (*sqe).user_data = user_data;
+        }
+        self.commit_sqe();
+        true
     }
 
     pub fn submit_and_wait(&mut self) -> i32 {
@@ -306,11 +446,10 @@ impl AbraxasRing {
             fence(Ordering::Acquire);
             let head = *self.sq_head;
 
+            // Nothing new to submit still waits (on multi-shot requests)
             let to_submit = tail - head;
-            if to_submit == 0 {
-                return 0;
-            }
 
+            self.enters += 1;
             let ret = libc::syscall(
                 NR_IO_URING_ENTER,
                 self.ring_fd,
@@ -329,6 +468,12 @@ impl AbraxasRing {
     }
 
     pub fn peek_cqe(&self) -> Option<&IoUringCqe> {
+        let overflow = self.ring_overflow_count();
+        if overflow != self.seen_overflow.get() {
+            eprintln!("[uring] CQ overflow detected, events lost");
+            self.seen_overflow.set(overflow);
+        }
+
         unsafe {
             let head = *self.cq_head;
             fence(Ordering::Acquire);
@@ -343,6 +488,18 @@ impl AbraxasRing {
         }
     }
 
+    /// io_uring_enter calls made through this ring
+    #[cfg(test)]
+    pub fn enter_count(&self) -> u64 {
+        self.enters
+    }
+
+    /// CQEs the kernel has dropped because the CQ ring was full.
+    pub fn ring_overflow_count(&self) -> u32 {
+        fence(Ordering::Acquire);
+        unsafe { *self.cq_overflow }
+    }
+
     pub fn cqe_seen(&mut self) {
         fence(Ordering::Release);
         unsafe { *self.cq_head += 1 };
@@ -367,3 +524,99 @@ impl Drop for AbraxasRing {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::fd::AsRawFd;
+
+    #[test]
+    fn multishot_timeout_rearms_until_cancelled() {
+        let mut ring = match AbraxasRing::init(4) {
+            Some(r) => r,
+            None => return, // io_uring disabled (e.g. kernel.io_uring_disabled)
+        };
+        let ts = KernelTimespec { tv_sec: 0, tv_nsec: 1_000_000 };
+        ring.prep_timeout_multishot(&ts, EV_TIMEOUT);
+
+        let next = |ring: &mut AbraxasRing| loop {
+            if let Some(cqe) = ring.peek_cqe() {
+                let (user_data, res, flags) = (cqe.user_data, cqe.res, cqe.flags);
+                ring.cqe_seen();
+                return (user_data, res, flags);
+            }
+            ring.submit_and_wait();
+        };
+        let (user_data, res, flags) = next(&mut ring);
+        if res == -libc::EINVAL {
+            return; // pre-5.19 kernel
+        }
+        // Expiries keep coming with F_MORE, without another SQE
+        assert_eq!((user_data, res), (EV_TIMEOUT, -libc::ETIME));
+        assert_ne!(flags & IORING_CQE_F_MORE, 0);
+        assert_eq!(next(&mut ring).1, -libc::ETIME);
+
+        ring.prep_cancel(EV_TIMEOUT, EV_CANCEL);
+        let mut cancelled = false;
+        while !cancelled {
+            let (user_data, res, flags) = next(&mut ring);
+            if user_data == EV_TIMEOUT && res == -libc::ECANCELED {
+                assert_eq!(flags & IORING_CQE_F_MORE, 0);
+                cancelled = true;
+            }
+        }
+    }
+
+    #[test]
+    fn full_sq_ring_is_flushed_not_dropped() {
+        let mut ring = match AbraxasRing::init(4) {
+            Some(r) => r,
+            None => return,
+        };
+        // Twice the SQ ring, still within the CQ ring (2x entries)
+        let ts = KernelTimespec { tv_sec: 0, tv_nsec: 1_000_000 };
+        let count = ring.sq_entries as u64 * 2;
+        for user_data in 0..count {
+            assert!(ring.prep_timeout(&ts, 100 + user_data), "SQE {} dropped", user_data);
+        }
+
+        let mut expired = Vec::new();
+        while expired.len() < count as usize {
+            ring.submit_and_wait();
+            while let Some(cqe) = ring.peek_cqe() {
+                assert_eq!(cqe.res, -libc::ETIME);
+                expired.push(cqe.user_data);
+                ring.cqe_seen();
+            }
+        }
+        expired.sort();
+        assert_eq!(expired, (100..100 + count).collect::<Vec<_>>());
+        assert_eq!(ring.ring_overflow_count(), 0);
+    }
+
+    #[test]
+    fn read_fills_buffer_at_offset() {
+        let mut ring = match AbraxasRing::init(4) {
+            Some(r) => r,
+            None => return,
+        };
+        let path = std::env::temp_dir().join(format!("abraxas-uring-read-{}", std::process::id()));
+        std::fs::write(&path, b"0123456789").unwrap();
+        let file = std::fs::File::open(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        let mut buf = [0u8; 16];
+        ring.prep_read(file.as_raw_fd(), buf.as_mut_ptr() as u64, buf.len() as u32, 4, EV_OVERRIDE_READ);
+        ring.submit_and_wait();
+        let (user_data, res) = match ring.peek_cqe() {
+            Some(cqe) => (cqe.user_data, cqe.res),
+            None => panic!("no completion"),
+        };
+        ring.cqe_seen();
+        if res == -libc::EINVAL {
+            return; // pre-5.6 kernel
+        }
+        assert_eq!((user_data, res), (EV_OVERRIDE_READ, 6));
+        assert_eq!(&buf[..6], b"456789");
+    }
+}
@@ -0,0 +1,293 @@
+//! MET Norway (Yr.no) Locationforecast 2.0 provider.
+//!
+//! Single-step API, no registration:
+//!   GET https://api.met.no/weatherapi/locationforecast/2.0/compact?lat={}&lon={}
+//!   -> properties.timeseries[0].data.instant.details.cloud_area_fraction
+//!   -> properties.timeseries[0].data.next_1_hours.summary.symbol_code
+//!   -> properties.timeseries[2] likewise, for the look-ahead cloud cover
+//!
+//! MET's terms require an identifying User-Agent; anonymous requests get 403.
+
+use super::{Body, CurlFetch, ReadResult, WeatherFetcher};
+use crate::config::{NetworkParams, WeatherData, WeatherProvider};
+use crate::now_epoch;
+
+/// curl header value sent with every request
+pub const USER_AGENT: &str = "User-Agent: abraxas/7.0 contact@example.com";
+
+/// curl -H arguments sent with every request
+const HTTP_HEADERS: [&str; 4] = ["-H", USER_AGENT, "-H", "Accept: application/json"];
+
+/// Compact forecast URL. MET asks for at most 4 decimals (cache friendliness).
+pub fn forecast_url(lat: f64, lon: f64) -> String {
+    format!(
+        "https://api.met.no/weatherapi/locationforecast/2.0/compact?lat={:.4}&lon={:.4}",
+        lat, lon
+    )
+}
+
+/// Blocking fetch of the compact forecast.
+pub fn fetch(lat: f64, lon: f64, net: &NetworkParams) -> Result<WeatherData, Box<dyn std::error::Error>> {
+    parse_compact(&super::http_get(&forecast_url(lat, lon), net, &HTTP_HEADERS)?)
+}
+
+/// Parse a compact forecast response: first timeseries entry, plus the
+/// cloud cover of the third (hourly steps near the start) for the dusk
+/// look-ahead.
+pub fn parse_compact(body: &str) -> Result<WeatherData, Box<dyn std::error::Error>> {
+    let resp: serde_json::Value = serde_json::from_str(body)?;
+
+    let series = &resp["properties"]["timeseries"];
+    let data = &series[0]["data"];
+    if data.is_null() {
+        return Err("No forecast timeseries".into());
+    }
+
+    let details = &data["instant"]["details"];
+    let symbol = symbol_code(data);
+    let cloud_cover = entry_cloud_cover(data);
+    let cloud_cover_3h = match &series[2]["data"] {
+        serde_json::Value::Null => cloud_cover,
+        later => entry_cloud_cover(later),
+    };
+
+    // Celsius from MET; cached as Fahrenheit like NOAA
+    let temperature = details["air_temperature"]
+        .as_f64()
+        .map(|c| c * 9.0 / 5.0 + 32.0)
+        .unwrap_or(0.0);
+
+    Ok(WeatherData {
+        cloud_cover,
+        cloud_cover_3h,
+        forecast: forecast_from_symbol(symbol),
+        temperature,
+        is_day: !symbol.ends_with("_night"),
+        fetched_at: now_epoch(),
+        has_error: false,
+    })
+}
+
+/// next_1_hours symbol code of a timeseries entry's data ("" if absent).
+fn symbol_code(data: &serde_json::Value) -> &str {
+    data["next_1_hours"]["summary"]["symbol_code"]
+        .as_str()
+        .unwrap_or("")
+}
+
+/// Measured fraction when present, otherwise estimate from the symbol.
+fn entry_cloud_cover(data: &serde_json::Value) -> i32 {
+    match data["instant"]["details"]["cloud_area_fraction"].as_f64() {
+        Some(f) => f.round().clamp(0.0, 100.0) as i32,
+        None => cloud_cover_from_symbol(symbol_code(data)),
+    }
+}
+
+/// Symbol code without its _day/_night/_polartwilight variant suffix.
+fn symbol_base(symbol: &str) -> &str {
+    symbol.split('_').next().unwrap_or(symbol)
+}
+
+/// Approximate cloud cover (%) for a MET symbol code such as
+/// "partlycloudy_day" or "lightrainshowers_night".
+pub fn cloud_cover_from_symbol(symbol: &str) -> i32 {
+    let base = symbol_base(symbol);
+
+    // Showers break up between cells (before general precipitation)
+    if base.contains("showers") && !base.contains("thunder") {
+        return 85;
+    }
+
+    // Precipitation always means heavy cloud
+    if base.contains("rain")
+        || base.contains("sleet")
+        || base.contains("snow")
+        || base.contains("thunder")
+    {
+        return 95;
+    }
+
+    match base {
+        "clearsky" => 0,
+        "fair" => 25,
+        "partlycloudy" => 50,
+        "cloudy" | "fog" => 90,
+        _ => 0,
+    }
+}
+
+/// Human-readable forecast from a symbol code:
+/// "lightrainshowersandthunder_day" -> "Light Rain Showers And Thunder".
+fn forecast_from_symbol(symbol: &str) -> String {
+    const WORDS: [&str; 14] = [
+        "clear", "sky", "fair", "partly", "cloudy", "fog", "light", "heavy",
+        "rain", "sleet", "snow", "showers", "and", "thunder",
+    ];
+
+    let mut rest = symbol_base(symbol);
+    let mut words: Vec<String> = Vec::new();
+    while !rest.is_empty() {
+        match WORDS.iter().find(|w| rest.starts_with(*w)) {
+            Some(w) => {
+                words.push(w[..1].to_uppercase() + &w[1..]);
+                rest = &rest[w.len()..];
+            }
+            None => return "Unknown".to_string(),
+        }
+    }
+
+    if words.is_empty() {
+        "Unknown".to_string()
+    } else {
+        words.join(" ")
+    }
+}
+
+/// A single request, so a fetch is running exactly while curl is
+pub struct MetNorwayFetcher {
+    curl: CurlFetch,
+    active: bool,
+}
+
+impl MetNorwayFetcher {
+    pub fn new(net: &NetworkParams) -> Self {
+        Self { curl: CurlFetch::new(net, &HTTP_HEADERS), active: false }
+    }
+
+    fn response(&mut self, body: Option<Result<Body, Box<dyn std::error::Error>>>) -> ReadResult {
+        let body = match body {
+            None => return ReadResult::Pending,
+            Some(b) => b,
+        };
+        self.active = false;
+        ReadResult::Done(body.and_then(|b| match std::str::from_utf8(&b) {
+            Ok(text) => parse_compact(text),
+            Err(_) => Err("invalid utf8".into()),
+        }))
+    }
+}
+
+impl WeatherFetcher for MetNorwayFetcher {
+    fn provider(&self) -> WeatherProvider {
+        WeatherProvider::MetNorway
+    }
+
+    fn set_timeouts(&mut self, net: &NetworkParams) {
+        self.curl.set_timeouts(net);
+    }
+
+    fn is_idle(&self) -> bool {
+        !self.active
+    }
+
+    fn pipe_fd(&self) -> i32 {
+        self.curl.pipe_fd
+    }
+
+    #[cfg(feature = "splice-weather")]
+    fn splice_fd(&self) -> i32 {
+        self.curl.splice_fd()
+    }
+
+    #[cfg(feature = "splice-weather")]
+    fn splice_generation(&self) -> u32 {
+        self.curl.generation
+    }
+
+    fn start(&mut self, lat: f64, lon: f64) -> bool {
+        if self.active {
+            return false;
+        }
+        match self.curl.spawn(&forecast_url(lat, lon)) {
+            Ok(()) => {
+                self.active = true;
+                true
+            }
+            Err(e) => {
+                eprintln!("  spawn_curl failed: {}", e);
+                false
+            }
+        }
+    }
+
+    #[cfg(not(feature = "splice-weather"))]
+    fn read_response(&mut self) -> ReadResult {
+        let body = self.curl.read();
+        self.response(body)
+    }
+
+    #[cfg(feature = "splice-weather")]
+    fn spliced(&mut self, res: i32) -> ReadResult {
+        let body = self.curl.spliced(res);
+        self.response(body)
+    }
+
+    fn abort(&mut self) {
+        self.curl.abort();
+        self.active = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn symbol_cloud_cover() {
+        let cases = [
+            ("clearsky_day", 0),
+            ("fair_night", 25),
+            ("partlycloudy_polartwilight", 50),
+            ("cloudy", 90),
+            ("fog", 90),
+            ("lightrainshowers_day", 85),
+            ("heavysnow", 95),
+            ("rainandthunder", 95),
+            ("lightsleetshowersandthunder_day", 95),
+            ("", 0),
+        ];
+        for (symbol, expected) in cases {
+            assert_eq!(cloud_cover_from_symbol(symbol), expected, "{}", symbol);
+        }
+    }
+
+    #[test]
+    fn symbol_forecast_text() {
+        assert_eq!(forecast_from_symbol("partlycloudy_day"), "Partly Cloudy");
+        assert_eq!(
+            forecast_from_symbol("lightrainshowersandthunder_night"),
+            "Light Rain Showers And Thunder"
+        );
+        assert_eq!(forecast_from_symbol("nonsense"), "Unknown");
+        assert_eq!(forecast_from_symbol(""), "Unknown");
+    }
+
+    #[test]
+    fn parse_compact_first_entry() {
+        let body = r#"{"properties":{"timeseries":[{"time":"2025-06-21T12:00:00Z","data":{
+            "instant":{"details":{"air_temperature":20.0,"cloud_area_fraction":62.7}},
+            "next_1_hours":{"summary":{"symbol_code":"partlycloudy_night"}}}}]}}"#;
+        let wd = parse_compact(body).unwrap();
+        assert_eq!(wd.cloud_cover, 63);
+        assert_eq!(wd.cloud_cover_3h, 63); // no later entries
+        assert_eq!(wd.forecast, "Partly Cloudy");
+        assert_eq!(wd.temperature, 68.0);
+        assert!(!wd.is_day);
+        assert!(!wd.has_error);
+
+        // No measured fraction: fall back to the symbol
+        let body = r#"{"properties":{"timeseries":[{"data":{"instant":{"details":{}},
+            "next_1_hours":{"summary":{"symbol_code":"cloudy"}}}}]}}"#;
+        assert_eq!(parse_compact(body).unwrap().cloud_cover, 90);
+
+        assert!(parse_compact(r#"{"properties":{"timeseries":[]}}"#).is_err());
+
+        // Third entry supplies the look-ahead cover
+        let body = r#"{"properties":{"timeseries":[
+            {"data":{"instant":{"details":{"cloud_area_fraction":10.0}}}},
+            {"data":{"instant":{"details":{"cloud_area_fraction":40.0}}}},
+            {"data":{"instant":{"details":{}},"next_1_hours":{"summary":{"symbol_code":"rain"}}}}]}}"#;
+        let wd = parse_compact(body).unwrap();
+        assert_eq!((wd.cloud_cover, wd.cloud_cover_3h), (10, 95));
+    }
+}
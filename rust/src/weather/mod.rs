@@ -0,0 +1,517 @@
+//! Weather API client.
+//!
+//! Providers, picked at runtime by [network] weather_provider from those
+//! compiled in:
+//!   noaa        (feature "noaa", US only, see noaa.rs): two-step API
+//!   met-norway  (feature "met-norway", worldwide, see met_norway.rs)
+//!   disabled    no fetches, always clear skies
+//!
+//! Uses curl(1) child process for HTTP -- zero TLS dependencies. The daemon
+//! drives a `WeatherFetcher` from its io_uring loop; one-shot commands use
+//! the blocking `fetch` (through http.rs).
+
+#[cfg(feature = "met-norway")]
+pub mod met_norway;
+#[cfg(feature = "noaa")]
+pub mod noaa;
+#[cfg(all(feature = "splice-weather", any(feature = "noaa", feature = "met-norway")))]
+pub mod splice;
+
+use crate::config::{NetworkParams, WeatherData, WeatherProvider};
+use crate::now_epoch;
+#[cfg(any(feature = "noaa", feature = "met-norway"))]
+use crate::http;
+
+pub fn init() {}
+
+pub fn cleanup() {}
+
+/// Fetch now, blocking. A failed fetch comes back with has_error set.
+#[cfg_attr(not(any(feature = "noaa", feature = "met-norway")), allow(unused_variables))]
+pub fn fetch(lat: f64, lon: f64, net: &NetworkParams) -> WeatherData {
+    match net.weather_provider {
+        #[cfg(feature = "noaa")]
+        WeatherProvider::Noaa => noaa::fetch(lat, lon, net).unwrap_or_else(|_| fetch_failed()),
+        #[cfg(feature = "met-norway")]
+        WeatherProvider::MetNorway => met_norway::fetch(lat, lon, net).unwrap_or_else(|_| fetch_failed()),
+        // Disabled, or not compiled in (config only selects available ones)
+        _ => disabled(),
+    }
+}
+
+#[cfg(any(feature = "noaa", feature = "met-norway"))]
+fn fetch_failed() -> WeatherData {
+    WeatherData {
+        cloud_cover: 0,
+        cloud_cover_3h: 0,
+        forecast: "Unknown".to_string(),
+        temperature: 0.0,
+        is_day: true,
+        fetched_at: now_epoch(),
+        has_error: true,
+    }
+}
+
+/// Weather for weather_provider = disabled: clear skies, never stale
+/// enough to matter since nothing refreshes it.
+pub fn disabled() -> WeatherData {
+    WeatherData {
+        cloud_cover: 0,
+        cloud_cover_3h: 0,
+        forecast: "Disabled".to_string(),
+        temperature: 0.0,
+        is_day: true,
+        fetched_at: now_epoch(),
+        has_error: false,
+    }
+}
+
+/// Blocking GET with the provider's curl -H arguments; anything but a 200
+/// is an error.
+#[cfg(any(feature = "noaa", feature = "met-norway"))]
+fn http_get(url: &str, net: &NetworkParams, headers: &[&str]) -> Result<String, Box<dyn std::error::Error>> {
+    let resp = http::get(url, net.weather_timeout_sec, net.connect_timeout_sec, headers)?;
+    if resp.status != 200 {
+        return Err(format!("HTTP {}", resp.status).into());
+    }
+    Ok(resp.body)
+}
+
+// --- WMO weather codes (provider-agnostic) ---
+//
+// WMO 4677 "present weather" codes, 0-99, as used by Open-Meteo, MET Norway
+// and most non-NOAA providers.
+
+/// Approximate cloud cover (%) for a WMO weather code.
+#[allow(dead_code)] // used by WMO-code weather providers
+pub fn cloud_cover_from_wmo_code(code: i32) -> i32 {
+    match code {
+        // Cloud development (oktas-style): clear, mainly clear, partly, overcast
+        0 => 0,
+        1 => 25,
+        2 => 50,
+        3 => 75,
+        // Haze, smoke, dust
+        4..=9 => 25,
+        // Mist, shallow fog
+        10 => 50,
+        11..=12 => 75,
+        // Lightning, squalls, funnel clouds
+        13..=19 => 85,
+        // Precipitation/fog in the preceding hour, not at observation time
+        20..=29 => 50,
+        // Duststorms, blowing snow
+        30..=39 => 75,
+        // Fog (45 fog, 48 depositing rime fog)
+        40..=49 => 90,
+        // Drizzle, rain, freezing rain
+        50..=69 => 90,
+        // Snow, snow grains, ice pellets
+        70..=79 => 95,
+        // Rain showers
+        80..=82 => 85,
+        // Mixed, snow and hail showers
+        83..=90 => 90,
+        // Thunderstorms
+        91..=99 => 95,
+        _ => 0,
+    }
+}
+
+/// Human-readable description for a WMO weather code.
+#[allow(dead_code)] // used by WMO-code weather providers
+pub fn forecast_from_wmo_code(code: i32) -> &'static str {
+    match code {
+        0 => "Clear",
+        1 => "Mainly Clear",
+        2 => "Partly Cloudy",
+        3 => "Overcast",
+        4..=9 => "Haze",
+        10 => "Mist",
+        11..=12 => "Shallow Fog",
+        13..=19 => "Squalls",
+        20..=29 => "Recent Precipitation",
+        30..=39 => "Blowing Dust or Snow",
+        45 => "Fog",
+        48 => "Depositing Rime Fog",
+        40..=49 => "Fog",
+        51 => "Light Drizzle",
+        53 => "Drizzle",
+        55 => "Dense Drizzle",
+        56..=57 => "Freezing Drizzle",
+        50..=59 => "Drizzle",
+        61 => "Light Rain",
+        63 => "Rain",
+        65 => "Heavy Rain",
+        66..=67 => "Freezing Rain",
+        60..=69 => "Rain",
+        71 => "Light Snow",
+        73 => "Snow",
+        75 => "Heavy Snow",
+        77 => "Snow Grains",
+        70..=79 => "Snow",
+        80 => "Light Rain Showers",
+        81 => "Rain Showers",
+        82 => "Violent Rain Showers",
+        85 => "Light Snow Showers",
+        86 => "Heavy Snow Showers",
+        83..=90 => "Showers",
+        95 => "Thunderstorm",
+        96 | 99 => "Thunderstorm with Hail",
+        91..=99 => "Thunderstorm",
+        _ => "Unknown",
+    }
+}
+
+// --- Async weather fetch (non-blocking, io_uring integrated) ---
+
+pub enum ReadResult {
+    #[cfg_attr(not(any(feature = "noaa", feature = "met-norway")), allow(dead_code))] // HTTP providers only
+    Pending,
+    #[cfg_attr(not(feature = "noaa"), allow(dead_code))] // NOAA's second request
+    NewPipe,
+    Done(Result<WeatherData, Box<dyn std::error::Error>>),
+}
+
+/// One provider's fetch, driven by the daemon's event loop: `start` spawns
+/// curl, the loop waits on `pipe_fd` and hands each wakeup (with
+/// splice-weather, each completed splice) back until the result is Done.
+pub trait WeatherFetcher {
+    fn provider(&self) -> WeatherProvider;
+
+    /// Take the (possibly reloaded) [network] timeouts for the next fetch
+    fn set_timeouts(&mut self, net: &NetworkParams);
+
+    /// No fetch in progress
+    fn is_idle(&self) -> bool;
+
+    /// curl's stdout while a response is arriving, else -1
+    fn pipe_fd(&self) -> i32;
+
+    fn needs_poll(&self) -> bool {
+        self.pipe_fd() >= 0
+    }
+
+    /// memfd the daemon splices the pipe into (-1 before the first start())
+    #[cfg(feature = "splice-weather")]
+    fn splice_fd(&self) -> i32;
+
+    /// Changes with every request and abort. The daemon tags its splices
+    /// with it, so one still in flight from an aborted fetch can't be taken
+    /// for the next fetch's.
+    #[cfg(feature = "splice-weather")]
+    fn splice_generation(&self) -> u32;
+
+    /// Begin a fetch; false if one is running or curl couldn't start.
+    fn start(&mut self, lat: f64, lon: f64) -> bool;
+
+    /// The pipe is readable: drain it, finishing at EOF.
+    #[cfg(not(feature = "splice-weather"))]
+    fn read_response(&mut self) -> ReadResult;
+
+    /// Account for a completed splice (`res` from its CQE): bytes moved,
+    /// 0 at EOF, or -errno.
+    #[cfg(feature = "splice-weather")]
+    fn spliced(&mut self, res: i32) -> ReadResult;
+
+    /// Kill any running curl and go idle.
+    fn abort(&mut self);
+}
+
+/// Fetcher for the configured provider.
+pub fn fetcher(net: &NetworkParams) -> Box<dyn WeatherFetcher> {
+    match net.weather_provider {
+        #[cfg(feature = "noaa")]
+        WeatherProvider::Noaa => Box::new(noaa::NoaaFetcher::new(net)),
+        #[cfg(feature = "met-norway")]
+        WeatherProvider::MetNorway => Box::new(met_norway::MetNorwayFetcher::new(net)),
+        _ => Box::new(DisabledFetcher),
+    }
+}
+
+/// weather_provider = disabled (and builds without a provider): never fetches
+pub struct DisabledFetcher;
+
+impl WeatherFetcher for DisabledFetcher {
+    fn provider(&self) -> WeatherProvider {
+        WeatherProvider::Disabled
+    }
+    fn set_timeouts(&mut self, _net: &NetworkParams) {}
+    fn is_idle(&self) -> bool {
+        true
+    }
+    fn pipe_fd(&self) -> i32 {
+        -1
+    }
+    #[cfg(feature = "splice-weather")]
+    fn splice_fd(&self) -> i32 {
+        -1
+    }
+    #[cfg(feature = "splice-weather")]
+    fn splice_generation(&self) -> u32 {
+        0
+    }
+    fn start(&mut self, _lat: f64, _lon: f64) -> bool {
+        false
+    }
+    #[cfg(not(feature = "splice-weather"))]
+    fn read_response(&mut self) -> ReadResult {
+        ReadResult::Done(Ok(disabled()))
+    }
+    #[cfg(feature = "splice-weather")]
+    fn spliced(&mut self, _res: i32) -> ReadResult {
+        ReadResult::Done(Ok(disabled()))
+    }
+    fn abort(&mut self) {}
+}
+
+/// A response body: the memfd mapping when spliced, else the bytes read
+#[cfg(all(feature = "splice-weather", any(feature = "noaa", feature = "met-norway")))]
+type Body = splice::Mapping;
+#[cfg(all(not(feature = "splice-weather"), any(feature = "noaa", feature = "met-norway")))]
+type Body = Vec<u8>;
+
+/// The curl child and its stdout pipe, shared by the HTTP providers: one
+/// request at a time, each response collected whole.
+#[cfg(any(feature = "noaa", feature = "met-norway"))]
+struct CurlFetch {
+    child: Option<std::process::Child>,
+    pipe_fd: i32,
+    #[cfg(not(feature = "splice-weather"))]
+    buf: Vec<u8>,
+    /// Splice target for the response body (created by the first spawn())
+    #[cfg(feature = "splice-weather")]
+    memfd: Option<splice::MemfdBuf>,
+    /// Bumped by each spawn() and abort() (WeatherFetcher::splice_generation)
+    #[cfg(feature = "splice-weather")]
+    generation: u32,
+    /// curl -H arguments for the provider
+    headers: &'static [&'static str],
+    /// curl --max-time per request ([network] weather_timeout_seconds)
+    timeout_ms: u32,
+    /// curl --connect-timeout ([network] connect_timeout_seconds)
+    connect_timeout_ms: u32,
+}
+
+#[cfg(any(feature = "noaa", feature = "met-norway"))]
+impl CurlFetch {
+    fn new(net: &NetworkParams, headers: &'static [&'static str]) -> Self {
+        let mut cf = Self {
+            child: None,
+            pipe_fd: -1,
+            #[cfg(not(feature = "splice-weather"))]
+            buf: Vec::new(),
+            #[cfg(feature = "splice-weather")]
+            memfd: None,
+            #[cfg(feature = "splice-weather")]
+            generation: 0,
+            headers,
+            timeout_ms: 0,
+            connect_timeout_ms: 0,
+        };
+        cf.set_timeouts(net);
+        cf
+    }
+
+    fn set_timeouts(&mut self, net: &NetworkParams) {
+        self.timeout_ms = net.weather_timeout_sec * 1000;
+        self.connect_timeout_ms = net.connect_timeout_sec * 1000;
+    }
+
+    #[cfg(feature = "splice-weather")]
+    fn splice_fd(&self) -> i32 {
+        self.memfd.as_ref().map(|m| m.fd()).unwrap_or(-1)
+    }
+
+    /// Request `url`, discarding the previous response (any mapping of it
+    /// must be dropped first).
+    fn spawn(&mut self, url: &str) -> Result<(), Box<dyn std::error::Error>> {
+        use std::os::unix::io::AsRawFd;
+        use std::process::Stdio;
+
+        #[cfg(not(feature = "splice-weather"))]
+        self.buf.clear();
+        #[cfg(feature = "splice-weather")]
+        {
+            self.generation = self.generation.wrapping_add(1);
+            if self.memfd.is_none() {
+                self.memfd = splice::MemfdBuf::create();
+            }
+            if !self.memfd.as_mut().map(|m| m.reset()).unwrap_or(false) {
+                return Err("memfd for weather splice unavailable".into());
+            }
+        }
+
+        let child = std::process::Command::new("curl")
+            .args(["-s", "-f", "-L"])
+            .args(http::curl_timeout_args(self.timeout_ms, self.connect_timeout_ms))
+            .args(self.headers)
+            .arg(url)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()?;
+
+        let fd = child.stdout.as_ref()
+            .ok_or("no stdout")?
+            .as_raw_fd();
+
+        // Spliced pipes stay blocking: the splice waits in an io-wq worker
+        #[cfg(not(feature = "splice-weather"))]
+        {
+            let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+            if flags < 0 {
+                return Err("fcntl F_GETFL failed".into());
+            }
+            if unsafe { libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) } < 0 {
+                return Err("fcntl O_NONBLOCK failed".into());
+            }
+        }
+
+        self.child = Some(child);
+        self.pipe_fd = fd;
+        Ok(())
+    }
+
+    /// Non-blocking drain. Returns Ok(true) for EOF, Ok(false) for EAGAIN.
+    #[cfg(not(feature = "splice-weather"))]
+    fn drain_pipe(&mut self) -> Result<bool, ()> {
+        let mut chunk = [0u8; 4096];
+        loop {
+            let n = unsafe {
+                libc::read(
+                    self.pipe_fd,
+                    chunk.as_mut_ptr() as *mut libc::c_void,
+                    chunk.len(),
+                )
+            };
+            if n > 0 {
+                self.buf.extend_from_slice(&chunk[..n as usize]);
+                continue;
+            }
+            if n == 0 {
+                return Ok(true); // EOF
+            }
+            let err = unsafe { *libc::__errno_location() };
+            if err == libc::EAGAIN || err == libc::EWOULDBLOCK {
+                return Ok(false);
+            }
+            return Err(());
+        }
+    }
+
+    /// Drain the readable pipe: None until the response is complete.
+    #[cfg(not(feature = "splice-weather"))]
+    fn read(&mut self) -> Option<Result<Body, Box<dyn std::error::Error>>> {
+        match self.drain_pipe() {
+            Ok(false) => None,
+            Err(()) => {
+                self.abort();
+                Some(Err("pipe read error".into()))
+            }
+            Ok(true) => Some(self.finish()), // EOF
+        }
+    }
+
+    /// Count a completed splice: None until the response is complete.
+    #[cfg(feature = "splice-weather")]
+    fn spliced(&mut self, res: i32) -> Option<Result<Body, Box<dyn std::error::Error>>> {
+        if res < 0 {
+            self.abort();
+            let err = std::io::Error::from_raw_os_error(-res);
+            return Some(Err(format!("splice failed: {}", err).into()));
+        }
+        if res > 0 {
+            if let Some(m) = self.memfd.as_mut() {
+                m.add(res as usize);
+            }
+            return None;
+        }
+        Some(self.finish())
+    }
+
+    /// The pipe is at EOF: reap curl and hand over the body.
+    fn finish(&mut self) -> Result<Body, Box<dyn std::error::Error>> {
+        self.pipe_fd = -1;
+        let status = match self.child.take() {
+            Some(mut c) => c.wait(),
+            None => return Err("no child".into()),
+        };
+
+        // Body bytes: parsed in place from the memfd mapping when spliced
+        #[cfg(feature = "splice-weather")]
+        let body = self.memfd.as_ref().and_then(|m| m.map());
+        #[cfg(not(feature = "splice-weather"))]
+        let body = Some(std::mem::take(&mut self.buf)).filter(|b| !b.is_empty());
+
+        match (status, body) {
+            (Ok(s), Some(body)) if s.success() => Ok(body),
+            _ => Err("curl failed".into()),
+        }
+    }
+
+    fn abort(&mut self) {
+        if let Some(ref mut child) = self.child {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        self.child = None;
+        self.pipe_fd = -1;
+        #[cfg(not(feature = "splice-weather"))]
+        self.buf.clear();
+        // A splice still in flight keeps writing into the old memfd, not
+        // the next fetch's
+        #[cfg(feature = "splice-weather")]
+        {
+            self.generation = self.generation.wrapping_add(1);
+            self.memfd = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wmo_cloud_cover_categories() {
+        let cases = [
+            (0, 0), (1, 25), (2, 50), (3, 75),
+            (4, 25), (5, 25), (9, 25),
+            (10, 50), (11, 75), (12, 75),
+            (13, 85), (17, 85), (19, 85),
+            (20, 50), (25, 50), (29, 50),
+            (30, 75), (36, 75), (39, 75),
+            (40, 90), (45, 90), (48, 90), (49, 90),
+            (51, 90), (53, 90), (55, 90), (56, 90), (57, 90),
+            (61, 90), (63, 90), (65, 90), (66, 90), (67, 90),
+            (71, 95), (73, 95), (75, 95), (77, 95),
+            (80, 85), (81, 85), (82, 85),
+            (85, 90), (86, 90),
+            (95, 95), (96, 95), (99, 95),
+        ];
+        for (code, expected) in cases {
+            assert_eq!(cloud_cover_from_wmo_code(code), expected, "WMO code {}", code);
+        }
+    }
+
+    #[test]
+    fn wmo_out_of_range() {
+        assert_eq!(cloud_cover_from_wmo_code(-1), 0);
+        assert_eq!(cloud_cover_from_wmo_code(100), 0);
+        assert_eq!(forecast_from_wmo_code(-1), "Unknown");
+        assert_eq!(forecast_from_wmo_code(100), "Unknown");
+    }
+
+    #[test]
+    fn wmo_forecast_strings() {
+        assert_eq!(forecast_from_wmo_code(0), "Clear");
+        assert_eq!(forecast_from_wmo_code(3), "Overcast");
+        assert_eq!(forecast_from_wmo_code(45), "Fog");
+        assert_eq!(forecast_from_wmo_code(65), "Heavy Rain");
+        assert_eq!(forecast_from_wmo_code(75), "Heavy Snow");
+        assert_eq!(forecast_from_wmo_code(95), "Thunderstorm");
+        for code in 0..=99 {
+            assert_ne!(forecast_from_wmo_code(code), "Unknown", "WMO code {}", code);
+        }
+    }
+}
@@ -0,0 +1,236 @@
+//! NOAA (api.weather.gov) provider, US only.
+//!
+//! Two-step API:
+//!   1. GET https://api.weather.gov/points/{lat},{lon}
+//!      -> extract properties.forecastHourly URL
+//!   2. GET that URL
+//!      -> extract first period's shortForecast, temperature, isDaytime
+//!      -> and the third period's shortForecast for the look-ahead cover
+
+use super::{Body, CurlFetch, ReadResult, WeatherFetcher};
+use crate::config::{NetworkParams, WeatherData, WeatherProvider};
+use crate::now_epoch;
+
+/// curl -H arguments sent with every request
+const HTTP_HEADERS: [&str; 4] = [
+    "-H", "User-Agent: abraxas/7.0 (weather color temp daemon)",
+    "-H", "Accept: application/geo+json",
+];
+
+/// Step 1: the grid point for a location
+fn points_url(lat: f64, lon: f64) -> String {
+    format!("https://api.weather.gov/points/{:.4},{:.4}", lat, lon)
+}
+
+/// The hourly forecast URL from a points response.
+fn forecast_url(points: &[u8]) -> Result<String, Box<dyn std::error::Error>> {
+    let resp: serde_json::Value = serde_json::from_slice(points)?;
+    match resp["properties"]["forecastHourly"].as_str() {
+        Some(u) => Ok(u.to_string()),
+        None => Err("no forecastHourly URL".into()),
+    }
+}
+
+/// Blocking fetch of both steps.
+pub fn fetch(lat: f64, lon: f64, net: &NetworkParams) -> Result<WeatherData, Box<dyn std::error::Error>> {
+    let body = super::http_get(&points_url(lat, lon), net, &HTTP_HEADERS)?;
+    let url = forecast_url(body.as_bytes())?;
+    let body = super::http_get(&url, net, &HTTP_HEADERS)?;
+    parse_forecast(&body)
+}
+
+/// Parse the NOAA hourly forecast: first period, plus the cloud cover
+/// of the third for the dusk look-ahead.
+fn parse_forecast(body: &str) -> Result<WeatherData, Box<dyn std::error::Error>> {
+    let resp: serde_json::Value = serde_json::from_str(body)?;
+
+    let period = &resp["properties"]["periods"][0];
+    if period.is_null() {
+        return Err("No forecast periods".into());
+    }
+
+    let short_forecast = period["shortForecast"]
+        .as_str()
+        .unwrap_or("Unknown")
+        .to_string();
+    let temperature = period["temperature"].as_f64().unwrap_or(0.0);
+    let is_day = period["isDaytime"].as_bool().unwrap_or(true);
+
+    let cloud_cover = cloud_cover_from_forecast(&short_forecast);
+    let cloud_cover_3h = resp["properties"]["periods"][2]["shortForecast"]
+        .as_str()
+        .map(cloud_cover_from_forecast)
+        .unwrap_or(cloud_cover);
+
+    Ok(WeatherData {
+        cloud_cover,
+        cloud_cover_3h,
+        forecast: short_forecast,
+        temperature,
+        is_day,
+        fetched_at: now_epoch(),
+        has_error: false,
+    })
+}
+
+fn cloud_cover_from_forecast(forecast: &str) -> i32 {
+    let lower = forecast.to_lowercase();
+
+    // Precipitation always means heavy cloud
+    if lower.contains("rain")
+        || lower.contains("storm")
+        || lower.contains("snow")
+        || lower.contains("drizzle")
+        || lower.contains("showers")
+    {
+        return 95;
+    }
+
+    if lower.contains("overcast") {
+        return 90;
+    }
+
+    // Mostly cloudy (before general "cloudy" check)
+    if lower.contains("mostly cloudy") {
+        return 75;
+    }
+
+    if lower.contains("cloudy") {
+        return 90;
+    }
+
+    if lower.contains("partly") {
+        return 50;
+    }
+
+    // Mostly sunny/clear (before general "sunny"/"clear")
+    if lower.contains("mostly sunny") || lower.contains("mostly clear") {
+        return 25;
+    }
+
+    if lower.contains("sunny") || lower.contains("clear") {
+        return 10;
+    }
+
+    0
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Step {
+    Idle,
+    Points,
+    Forecast,
+}
+
+pub struct NoaaFetcher {
+    curl: CurlFetch,
+    step: Step,
+}
+
+impl NoaaFetcher {
+    pub fn new(net: &NetworkParams) -> Self {
+        Self { curl: CurlFetch::new(net, &HTTP_HEADERS), step: Step::Idle }
+    }
+
+    /// Move on from a completed response: points -> forecast request,
+    /// forecast -> parsed weather.
+    fn response(&mut self, body: Option<Result<Body, Box<dyn std::error::Error>>>) -> ReadResult {
+        let body = match body {
+            None => return ReadResult::Pending,
+            Some(Ok(b)) => b,
+            Some(Err(e)) => {
+                self.step = Step::Idle;
+                return ReadResult::Done(Err(e));
+            }
+        };
+
+        match self.step {
+            Step::Points => {
+                let url = forecast_url(&body);
+                drop(body); // unmap before the memfd is reused
+                let spawned = url.and_then(|u| {
+                    self.curl.spawn(&u).inspect_err(|e| eprintln!("  spawn_curl (forecast) failed: {}", e))
+                });
+                match spawned {
+                    Ok(()) => {
+                        self.step = Step::Forecast;
+                        ReadResult::NewPipe
+                    }
+                    Err(e) => {
+                        self.step = Step::Idle;
+                        ReadResult::Done(Err(e))
+                    }
+                }
+            }
+            Step::Forecast => {
+                self.step = Step::Idle;
+                match std::str::from_utf8(&body) {
+                    Ok(text) => ReadResult::Done(parse_forecast(text)),
+                    Err(_) => ReadResult::Done(Err("invalid utf8".into())),
+                }
+            }
+            Step::Idle => ReadResult::Done(Err("unexpected idle".into())),
+        }
+    }
+}
+
+impl WeatherFetcher for NoaaFetcher {
+    fn provider(&self) -> WeatherProvider {
+        WeatherProvider::Noaa
+    }
+
+    fn set_timeouts(&mut self, net: &NetworkParams) {
+        self.curl.set_timeouts(net);
+    }
+
+    fn is_idle(&self) -> bool {
+        self.step == Step::Idle
+    }
+
+    fn pipe_fd(&self) -> i32 {
+        self.curl.pipe_fd
+    }
+
+    #[cfg(feature = "splice-weather")]
+    fn splice_fd(&self) -> i32 {
+        self.curl.splice_fd()
+    }
+
+    #[cfg(feature = "splice-weather")]
+    fn splice_generation(&self) -> u32 {
+        self.curl.generation
+    }
+
+    fn start(&mut self, lat: f64, lon: f64) -> bool {
+        if self.step != Step::Idle {
+            return false;
+        }
+        match self.curl.spawn(&points_url(lat, lon)) {
+            Ok(()) => {
+                self.step = Step::Points;
+                true
+            }
+            Err(e) => {
+                eprintln!("  spawn_curl failed: {}", e);
+                false
+            }
+        }
+    }
+
+    #[cfg(not(feature = "splice-weather"))]
+    fn read_response(&mut self) -> ReadResult {
+        let body = self.curl.read();
+        self.response(body)
+    }
+
+    #[cfg(feature = "splice-weather")]
+    fn spliced(&mut self, res: i32) -> ReadResult {
+        let body = self.curl.spliced(res);
+        self.response(body)
+    }
+
+    fn abort(&mut self) {
+        self.curl.abort();
+        self.step = Step::Idle;
+    }
+}
@@ -0,0 +1,120 @@
+//! memfd sink for spliced weather responses (feature "splice-weather").
+//!
+//! The daemon splices curl's stdout pipe into an anonymous memfd with
+//! IORING_OP_SPLICE, so the kernel moves the pages and the body never
+//! passes through a userspace read buffer. At EOF the memfd is mapped
+//! read-only and the JSON parsed straight out of the mapping.
+
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+/// Size the memfd starts at (and is trimmed back to between fetches);
+/// longer responses grow it as splices land
+const MEMFD_SIZE: libc::off_t = 64 * 1024;
+
+pub struct MemfdBuf {
+    fd: OwnedFd,
+    /// Bytes spliced in since the last reset
+    len: usize,
+}
+
+impl MemfdBuf {
+    pub fn create() -> Option<Self> {
+        let fd = unsafe { libc::memfd_create(c"abraxas-weather".as_ptr(), libc::MFD_CLOEXEC) };
+        if fd < 0 {
+            return None;
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        if unsafe { libc::ftruncate(fd.as_raw_fd(), MEMFD_SIZE) } < 0 {
+            return None;
+        }
+        Some(Self { fd, len: 0 })
+    }
+
+    pub fn fd(&self) -> i32 {
+        self.fd.as_raw_fd()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Count a completed splice (splices write at the file position).
+    pub fn add(&mut self, n: usize) {
+        self.len += n;
+    }
+
+    /// Empty it for the next response: rewind, and drop pages a long
+    /// response grew it by.
+    pub fn reset(&mut self) -> bool {
+        self.len = 0;
+        unsafe {
+            libc::ftruncate(self.fd(), MEMFD_SIZE) == 0
+                && libc::lseek(self.fd(), 0, libc::SEEK_SET) == 0
+        }
+    }
+
+    /// Read-only view of the spliced bytes (None when empty).
+    pub fn map(&self) -> Option<Mapping> {
+        if self.len == 0 {
+            return None;
+        }
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                self.len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                self.fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return None;
+        }
+        Some(Mapping { ptr, len: self.len })
+    }
+}
+
+/// An mmap of a MemfdBuf, unmapped on drop
+pub struct Mapping {
+    ptr: *mut libc::c_void,
+    len: usize,
+}
+
+impl std::ops::Deref for Mapping {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.len) }
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.ptr, self.len) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memfd_maps_what_was_written_since_reset() {
+        let mut m = MemfdBuf::create().unwrap();
+        assert!(m.map().is_none());
+
+        let write = |m: &mut MemfdBuf, data: &[u8]| {
+            let n = unsafe { libc::write(m.fd(), data.as_ptr() as *const libc::c_void, data.len()) };
+            m.add(n as usize);
+        };
+        write(&mut m, b"{\"stale\":");
+        assert!(m.reset());
+        write(&mut m, b"{\"a\":");
+        write(&mut m, &[b'1'; 70 * 1024]); // past the initial size
+        write(&mut m, b"}");
+        let map = m.map().unwrap();
+        assert_eq!(map.len(), 5 + 70 * 1024 + 1);
+        assert!(map.starts_with(b"{\"a\":1") && map.ends_with(b"1}"));
+    }
+}
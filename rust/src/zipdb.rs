@@ -1,94 +1,442 @@
 //! ZIP code database lookup.
 //!
-//! mmap'd binary search on us_zipcodes.bin.
+//! mmap'd binary search on us_zipcodes.bin (see mmapdb.rs).
 //! Entry format: 5 bytes ASCII ZIP + 4 bytes f32 lat + 4 bytes f32 lon.
-//! File header: 4 bytes u32 count (little-endian).
+//! File header: "ABZP" magic, u32 format version, u32 count (little-endian).
+//! The legacy header, a bare u32 count, is still read: it is the format
+//! shipped in the repository and read by the C23 build.
+//!
+//! Built from the Census Bureau ZCTA gazetteer by `--download-zipdb`, or
+//! from a local ZIP,lat,lon file by `--build-zipdb`.
 
-use std::fs::File;
-use std::os::unix::io::AsRawFd;
+use crate::mmapdb::{self, MappedFile};
+use crate::{LAT_RANGE, LON_RANGE};
+use std::io;
 use std::path::Path;
 
 const ENTRY_SIZE: usize = 13; // 5 + 4 + 4
-const HEADER_SIZE: usize = 4; // u32 count
 
-pub fn lookup(db_path: &Path, zipcode: &str) -> Option<(f32, f32)> {
-    // Normalize to exactly 5 digits
-    let mut zip5 = [b'0'; 5];
-    let bytes = zipcode.as_bytes();
-    let len = bytes.len().min(5);
-    zip5[5 - len..].copy_from_slice(&bytes[..len]);
-
-    let file = File::open(db_path).ok()?;
-    let file_size = file.metadata().ok()?.len() as usize;
-
-    if file_size < HEADER_SIZE {
-        return None;
-    }
-
-    // mmap the file
-    let data = unsafe {
-        let ptr = libc::mmap(
-            std::ptr::null_mut(),
-            file_size,
-            libc::PROT_READ,
-            libc::MAP_PRIVATE,
-            file.as_raw_fd(),
-            0,
-        );
-        if ptr == libc::MAP_FAILED {
-            return None;
+const MAGIC: &[u8; 4] = b"ABZP";
+pub const FORMAT_VERSION: u32 = 1;
+const HEADER_SIZE: usize = 12; // magic + version + count
+
+/// Entries spot-checked for order before a file is trusted
+const SORT_PROBES: usize = 16;
+
+/// Census 2023 ZCTA gazetteer (zip containing one tab-separated file)
+pub const GAZETTEER_URL: &str =
+    "https://www2.census.gov/geo/docs/maps-data/data/gazetteer/2023_Gazetteer/2023_Gaz_zcta_national.zip";
+
+/// One database entry: ASCII ZIP, latitude, longitude
+pub type Entry = ([u8; 5], f32, f32);
+
+/// Parse the gazetteer TSV (GEOID, INTPTLAT, INTPTLONG columns, located by
+/// header name). Malformed rows are skipped. Returned sorted by ZIP.
+pub fn parse_gazetteer(tsv: &str) -> Result<Vec<Entry>, String> {
+    let mut lines = tsv.lines();
+    let header: Vec<&str> = lines
+        .next()
+        .ok_or("empty gazetteer file")?
+        .split('\t')
+        .map(str::trim)
+        .collect();
+    let column = |name: &str| {
+        header
+            .iter()
+            .position(|h| *h == name)
+            .ok_or_else(|| format!("gazetteer header has no {} column", name))
+    };
+    let geoid = column("GEOID")?;
+    let lat_col = column("INTPTLAT")?;
+    let lon_col = column("INTPTLONG")?;
+
+    let mut entries = Vec::new();
+    for line in lines {
+        let fields: Vec<&str> = line.split('\t').map(str::trim).collect();
+        let (zip, lat, lon) = match (fields.get(geoid), fields.get(lat_col), fields.get(lon_col)) {
+            (Some(z), Some(la), Some(lo)) => (*z, la.parse::<f32>(), lo.parse::<f32>()),
+            _ => continue,
+        };
+        if zip.len() != 5 || !zip.bytes().all(|b| b.is_ascii_digit()) {
+            continue;
         }
-        std::slice::from_raw_parts(ptr as *const u8, file_size)
+        let (lat, lon) = match (lat, lon) {
+            (Ok(la), Ok(lo)) => (la, lo),
+            _ => continue,
+        };
+        let mut zip5 = [0u8; 5];
+        zip5.copy_from_slice(zip.as_bytes());
+        entries.push((zip5, lat, lon));
+    }
+
+    if entries.is_empty() {
+        return Err("no ZIP code rows in gazetteer file".into());
+    }
+    entries.sort_by_key(|e| e.0);
+    entries.dedup_by(|a, b| a.0 == b.0);
+    Ok(entries)
+}
+
+/// ZIP column names accepted in a --build-zipdb header (lowercased)
+const CSV_ZIP_COLUMNS: [&str; 5] = ["zip", "zipcode", "zip_code", "zcta", "geoid"];
+const CSV_LAT_COLUMNS: [&str; 3] = ["lat", "latitude", "intptlat"];
+const CSV_LON_COLUMNS: [&str; 5] = ["lon", "lng", "long", "longitude", "intptlong"];
+
+/// Parse a ZIP -> lat/lon file for --build-zipdb: comma- or tab-separated,
+/// with a header naming the columns (zip/lat/lon and common variants),
+/// or headerless as either ZIP,LAT,LON or a GeoNames postal code dump
+/// (US.txt: country, ZIP, place, ..., lat and lon in columns 10 and 11).
+/// ZIPs that lost their leading zeros in a spreadsheet are padded back.
+/// Any malformed row is an error, naming its line. Returned sorted by ZIP
+/// with the number of repeated ZIPs dropped (the first row for each wins).
+pub fn parse_csv(text: &str) -> Result<(Vec<Entry>, usize), String> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let first = text.lines().next().ok_or("empty file")?;
+    let sep = if first.contains('\t') { '\t' } else { ',' };
+    let split = |line: &str| -> Vec<String> {
+        line.split(sep)
+            .map(|f| f.trim().trim_matches('"').trim().to_string())
+            .collect()
     };
 
-    // Read entry count (little-endian u32)
-    let count = u32::from_le_bytes([data[0], data[1], data[2], data[3]]) as usize;
+    let head = split(first);
+    let find = |names: &[&str]| head.iter().position(|h| names.contains(&h.to_ascii_lowercase().as_str()));
+    // GeoNames postal codes start with the 2-letter country code
+    let geonames = head.len() >= 11 && head[0].len() == 2 && head[0].bytes().all(|b| b.is_ascii_uppercase());
+    let (zip_col, lat_col, lon_col, skip) = match (find(&CSV_ZIP_COLUMNS), find(&CSV_LAT_COLUMNS), find(&CSV_LON_COLUMNS)) {
+        (Some(z), Some(la), Some(lo)) => (z, la, lo, 1),
+        _ if geonames => (1, 9, 10, 0),
+        _ if head.get(1).is_some_and(|f| f.parse::<f32>().is_ok()) => (0, 1, 2, 0),
+        _ => return Err(format!("line 1: header needs zip, lat and lon columns, found \"{}\"", first.trim())),
+    };
 
-    // Binary search
-    let mut low: usize = 0;
-    let mut high = count.wrapping_sub(1);
-    let mut result = None;
+    let mut entries: Vec<Entry> = Vec::new();
+    for (n, line) in text.lines().enumerate().skip(skip) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let line_no = n + 1;
+        let fields = split(line);
+        let field = |col: usize| fields.get(col).map(String::as_str).unwrap_or("");
+        let zip = field(zip_col);
+        if zip.is_empty() || zip.len() > 5 || !zip.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(format!("line {}: bad ZIP code \"{}\"", line_no, zip));
+        }
+        let (lat, lon) = match (field(lat_col).parse::<f32>(), field(lon_col).parse::<f32>()) {
+            (Ok(la), Ok(lo)) if LAT_RANGE.contains(&(la as f64)) && LON_RANGE.contains(&(lo as f64)) => (la, lo),
+            _ => {
+                return Err(format!(
+                    "line {}: bad coordinates \"{}\", \"{}\" for ZIP {}",
+                    line_no,
+                    field(lat_col),
+                    field(lon_col),
+                    zip
+                ));
+            }
+        };
+        let mut zip5 = [b'0'; 5];
+        zip5[5 - zip.len()..].copy_from_slice(zip.as_bytes());
+        entries.push((zip5, lat, lon));
+    }
 
-    while low <= high && high < count {
-        let mid = low + (high - low) / 2;
-        let offset = HEADER_SIZE + mid * ENTRY_SIZE;
+    if entries.is_empty() {
+        return Err("no ZIP code rows".into());
+    }
+    // Stable, so the first row of a repeated ZIP survives the dedup
+    entries.sort_by_key(|e| e.0);
+    let total = entries.len();
+    entries.dedup_by(|a, b| a.0 == b.0);
+    let dropped = total - entries.len();
+    Ok((entries, dropped))
+}
 
-        if offset + ENTRY_SIZE > file_size {
-            break;
+/// Serialize sorted entries in the on-disk format read by `lookup`.
+pub fn encode(entries: &[Entry]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(HEADER_SIZE + entries.len() * ENTRY_SIZE);
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    out.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+    for (zip, lat, lon) in entries {
+        out.extend_from_slice(zip);
+        out.extend_from_slice(&lat.to_le_bytes());
+        out.extend_from_slice(&lon.to_le_bytes());
+    }
+    out
+}
+
+/// Where the entries start and how many there are, after checking the
+/// file can hold them and spot-checking that they are sorted ZIPs.
+fn validate(data: &[u8]) -> Result<(usize, usize), String> {
+    let (header, count) = if data.starts_with(MAGIC) {
+        match mmapdb::read_u32(data, 4) {
+            Some(FORMAT_VERSION) => (HEADER_SIZE, mmapdb::read_u32(data, 8)),
+            Some(v) => return Err(format!("format version {} (this build reads {})", v, FORMAT_VERSION)),
+            None => return Err("truncated header".into()),
         }
+    } else {
+        (mmapdb::HEADER_SIZE, mmapdb::read_u32(data, 0))
+    };
+    let count = match count {
+        Some(n) => n as usize,
+        None => return Err("truncated header".into()),
+    };
+    let held = data.len().saturating_sub(header) / ENTRY_SIZE;
+    if count > held {
+        return Err(format!("header claims {} entries but the file holds {}", count, held));
+    }
 
-        let entry_zip = &data[offset..offset + 5];
-        match entry_zip.cmp(&zip5) {
-            std::cmp::Ordering::Equal => {
-                let lat = f32::from_le_bytes([
-                    data[offset + 5],
-                    data[offset + 6],
-                    data[offset + 7],
-                    data[offset + 8],
-                ]);
-                let lon = f32::from_le_bytes([
-                    data[offset + 9],
-                    data[offset + 10],
-                    data[offset + 11],
-                    data[offset + 12],
-                ]);
-                result = Some((lat, lon));
-                break;
-            }
-            std::cmp::Ordering::Less => low = mid + 1,
-            std::cmp::Ordering::Greater => {
-                if mid == 0 {
-                    break;
-                }
-                high = mid - 1;
+    let zip_at = |i: usize| &data[header + i * ENTRY_SIZE..header + i * ENTRY_SIZE + 5];
+    let probes = count.min(SORT_PROBES);
+    let mut prev: Option<&[u8]> = None;
+    for p in 0..probes {
+        // Evenly spaced, always including the first and last entries
+        let i = if probes == 1 { 0 } else { p * (count - 1) / (probes - 1) };
+        let zip = zip_at(i);
+        if !zip.iter().all(u8::is_ascii_digit) {
+            return Err(format!("entry {} is not a ZIP code", i));
+        }
+        if prev.is_some_and(|z| z >= zip) {
+            return Err(format!("entries are not sorted by ZIP code (at entry {})", i));
+        }
+        prev = Some(zip);
+    }
+    Ok((header, count))
+}
+
+/// A ZIP database mapped once for any number of lookups (unmapped on drop).
+pub struct ZipDb {
+    data: MappedFile,
+    /// Offset of the first entry (depends on the format version)
+    header: usize,
+    count: usize,
+}
+
+impl ZipDb {
+    /// None if the file is missing, or unusable (with a warning saying so).
+    pub fn open(path: &Path) -> Option<Self> {
+        let data = MappedFile::open(path)?;
+        match validate(&data) {
+            Ok((header, count)) => Some(Self { data, header, count }),
+            Err(e) => {
+                eprintln!("[warn] Cannot use {}: {}", path.display(), e);
+                eprintln!("  Regenerate it with: abraxas --download-zipdb (or --build-zipdb FILE.csv)");
+                None
             }
         }
     }
 
-    // munmap
-    unsafe {
-        libc::munmap(data.as_ptr() as *mut libc::c_void, file_size);
+    fn zip_at(&self, i: usize) -> &[u8] {
+        let offset = self.header + i * ENTRY_SIZE;
+        &self.data[offset..offset + 5]
+    }
+
+    fn entry_at(&self, i: usize) -> Entry {
+        let offset = self.header + i * ENTRY_SIZE;
+        let mut zip = [0u8; 5];
+        zip.copy_from_slice(self.zip_at(i));
+        // In bounds: validate() checked the file holds `count` entries
+        let coord = |at: usize| mmapdb::read_f32(&self.data, offset + at).unwrap_or(f32::NAN);
+        (zip, coord(5), coord(9))
+    }
+
+    /// Coordinates of a ZIP code (shorter codes are zero-padded: 601 is 00601).
+    pub fn lookup(&self, zipcode: &str) -> Option<(f32, f32)> {
+        let mut zip5 = [b'0'; 5];
+        let bytes = zipcode.as_bytes();
+        let len = bytes.len().min(5);
+        zip5[5 - len..].copy_from_slice(&bytes[..len]);
+
+        let i = mmapdb::partition_point(self.count, |i| self.zip_at(i) < &zip5[..]);
+        if i == self.count || self.zip_at(i) != zip5 {
+            return None;
+        }
+        let (_, lat, lon) = self.entry_at(i);
+        Some((lat, lon))
+    }
+
+    /// Every entry, in ZIP order.
+    pub fn entries(&self) -> impl Iterator<Item = Entry> + '_ {
+        (0..self.count).map(|i| self.entry_at(i))
+    }
+
+    /// The ZIP whose centroid is closest to a point, and how far away it
+    /// is in km. A linear scan: fine for one-off display, not a hot path.
+    pub fn nearest(&self, lat: f64, lon: f64) -> Option<([u8; 5], f64)> {
+        self.entries()
+            .map(|(zip, zlat, zlon)| (zip, distance_km(lat, lon, zlat as f64, zlon as f64)))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
     }
+}
+
+/// Great-circle (haversine) distance between two points.
+fn distance_km(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    const EARTH_RADIUS_KM: f64 = 6371.0;
+    let (dlat, dlon) = ((lat2 - lat1).to_radians(), (lon2 - lon1).to_radians());
+    let a = (dlat / 2.0).sin().powi(2) + lat1.to_radians().cos() * lat2.to_radians().cos() * (dlon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+}
+
+/// One-off lookup: opens and maps the database for a single ZIP code.
+pub fn lookup(db_path: &Path, zipcode: &str) -> Option<(f32, f32)> {
+    ZipDb::open(db_path)?.lookup(zipcode)
+}
+
+/// Rewrite a valid legacy-format (bare count header) database in the
+/// current format. Ok(false) if it is missing, current, or corrupt (left
+/// for `lookup` to report).
+pub fn upgrade_file(path: &Path) -> io::Result<bool> {
+    let data = match MappedFile::open(path) {
+        Some(d) => d,
+        None => return Ok(false),
+    };
+    if data.starts_with(MAGIC) {
+        return Ok(false);
+    }
+    let count = match validate(&data) {
+        Ok((_, count)) => count,
+        Err(_) => return Ok(false),
+    };
+
+    let mut out = Vec::with_capacity(HEADER_SIZE + count * ENTRY_SIZE);
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    out.extend_from_slice(&data[..mmapdb::HEADER_SIZE]);
+    out.extend_from_slice(&data[mmapdb::HEADER_SIZE..mmapdb::HEADER_SIZE + count * ENTRY_SIZE]);
+    crate::config::write_atomic(path, out)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gazetteer_round_trip() {
+        let tsv = "GEOID\tALAND\tAWATER\tALAND_SQMI\tAWATER_SQMI\tINTPTLAT\tINTPTLONG                                                                                                               \n\
+                   60614\t8350000\t0\t3.224\t0.000\t41.922939\t-87.652892          \n\
+                   00601\t166847909\t799292\t64.420\t0.309\t18.180555\t-66.749961          \n\
+                   bogus\t0\t0\t0\t0\t1.0\t2.0\n\
+                   10001\t1\t0\t0\t0\tnot-a-number\t-73.99\n";
+        let entries = parse_gazetteer(tsv).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(&entries[0].0, b"00601");
+        assert_eq!(&entries[1].0, b"60614");
+
+        let path = std::env::temp_dir().join(format!("abraxas-zipdb-{}.bin", std::process::id()));
+        std::fs::write(&path, encode(&entries)).unwrap();
+        assert_eq!(lookup(&path, "60614"), Some((41.92294, -87.65289)));
+        assert_eq!(lookup(&path, "601"), Some((18.180555, -66.74996)));
+        assert_eq!(lookup(&path, "10001"), None);
+        let _ = std::fs::remove_file(&path);
 
-    result
+        assert!(parse_gazetteer("ZIP\tLAT\tLON\n").is_err());
+    }
+
+    #[test]
+    fn csv_parsed_strictly() {
+        let csv = "\u{feff}\"zip\",\"city\",\"latitude\",\"longitude\"\n\
+                   60614,Chicago,41.922939,-87.652892\n\
+                   601,Adjuntas,18.180555,-66.749961\n\
+                   \n\
+                   60614,Chicago (again),0,0\n";
+        let (entries, dropped) = parse_csv(csv).unwrap();
+        assert_eq!(dropped, 1);
+        assert_eq!(entries, [(*b"00601", 18.180555, -66.74996), (*b"60614", 41.92294, -87.65289)]);
+
+        // Headerless, plain and GeoNames layouts
+        let (entries, _) = parse_csv("97201\t45.5\t-122.69\n").unwrap();
+        assert_eq!(entries[0], (*b"97201", 45.5, -122.69));
+        let geonames = "US\t97201\tPortland\tOregon\tOR\tMultnomah\t051\t\t\t45.5079\t-122.6908\t4\n";
+        assert_eq!(parse_csv(geonames).unwrap().0[0], (*b"97201", 45.5079, -122.6908));
+
+        let err = |csv: &str| parse_csv(csv).unwrap_err();
+        assert!(err("zip,lat,lon\n60614,41.9,-87.6\n6O614,41.9,-87.6\n").starts_with("line 3: bad ZIP"));
+        assert!(err("zip,lat,lon\n60614,41.9\n").starts_with("line 2: bad coordinates"));
+        assert!(err("zip,lat,lon\n60614,-87.6,241.9\n").starts_with("line 2: bad coordinates"));
+        assert!(err("postcode,y,x\n60614,41.9,-87.6\n").starts_with("line 1: header"));
+        assert!(err("zip,lat,lon\n").contains("no ZIP code rows"));
+        assert!(err("").contains("empty"));
+    }
+
+    #[test]
+    fn corrupt_files_rejected() {
+        let entries: Vec<Entry> = (0..40u32)
+            .map(|n| {
+                let mut zip = [0u8; 5];
+                zip.copy_from_slice(format!("{:05}", 10000 + n * 7).as_bytes());
+                (zip, 40.0 + n as f32 / 100.0, -80.0)
+            })
+            .collect();
+        let good = encode(&entries);
+        assert_eq!(validate(&good), Ok((HEADER_SIZE, 40)));
+
+        let truncated = &good[..good.len() - 1];
+        assert!(validate(truncated).unwrap_err().contains("claims 40 entries but the file holds 39"));
+        assert!(validate(&good[..6]).unwrap_err().contains("truncated header"));
+
+        let mut oversized = good.clone();
+        oversized[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(validate(&oversized).unwrap_err().contains("claims 4294967295 entries"));
+
+        let mut unsorted = good.clone();
+        let last = HEADER_SIZE + 39 * ENTRY_SIZE;
+        unsorted[last..last + 5].copy_from_slice(b"00001");
+        assert!(validate(&unsorted).unwrap_err().contains("not sorted"));
+
+        let mut future = good.clone();
+        future[4..8].copy_from_slice(&2u32.to_le_bytes());
+        assert!(validate(&future).unwrap_err().contains("format version 2"));
+
+        let path = std::env::temp_dir().join(format!("abraxas-zipdb-bad-{}.bin", std::process::id()));
+        std::fs::write(&path, &unsorted).unwrap();
+        assert_eq!(lookup(&path, "10000"), None);
+        std::fs::write(&path, &good).unwrap();
+        assert_eq!(lookup(&path, "10000"), Some((40.0, -80.0)));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn legacy_format_read_and_upgraded() {
+        let entries = [(*b"00601", 18.180555, -66.74996), (*b"60614", 41.92294, -87.65289)];
+        // Bare count header, as shipped and as the C23 build reads it
+        let legacy = &encode(&entries)[8..];
+        assert_eq!(validate(legacy), Ok((4, 2)));
+        assert!(validate(&legacy[..legacy.len() - 4]).is_err());
+
+        let path = std::env::temp_dir().join(format!("abraxas-zipdb-legacy-{}.bin", std::process::id()));
+        std::fs::write(&path, legacy).unwrap();
+        assert_eq!(lookup(&path, "60614"), Some((41.92294, -87.65289)));
+
+        assert!(upgrade_file(&path).unwrap());
+        assert_eq!(std::fs::read(&path).unwrap(), encode(&entries));
+        assert!(!upgrade_file(&path).unwrap());
+        assert_eq!(lookup(&path, "601"), Some((18.180555, -66.74996)));
+        let _ = std::fs::remove_file(&path);
+        assert!(!upgrade_file(&path).unwrap());
+    }
+
+    #[test]
+    fn zipdb_kept_open_for_lookups() {
+        let entries = [
+            (*b"00601", 18.180555, -66.74996),
+            (*b"60614", 41.92294, -87.65289),
+            (*b"60657", 41.94031, -87.65332),
+            (*b"97201", 45.507856, -122.690794),
+        ];
+        let path = std::env::temp_dir().join(format!("abraxas-zipdb-open-{}.bin", std::process::id()));
+        std::fs::write(&path, encode(&entries)).unwrap();
+        let db = ZipDb::open(&path).unwrap();
+        let _ = std::fs::remove_file(&path); // the mapping outlives the name
+
+        assert!(db.entries().eq(entries.iter().copied()));
+        assert_eq!(db.lookup("60657"), Some((41.94031, -87.65332)));
+        assert_eq!(db.lookup("99999"), None);
+
+        // Lincoln Park, Chicago: 60614 is about 1.6 km away
+        let (zip, km) = db.nearest(41.9214, -87.6336).unwrap();
+        assert_eq!(&zip, b"60614");
+        assert!((1.0..2.5).contains(&km), "{}", km);
+        assert_eq!(&db.nearest(45.52, -122.68).unwrap().0, b"97201");
+        assert!((distance_km(0.0, 0.0, 0.0, 180.0) - 20015.1).abs() < 0.1);
+    }
 }